@@ -0,0 +1,52 @@
+#[macro_use]
+extern crate criterion;
+extern crate fuzzy_logic;
+
+use criterion::Criterion;
+use fuzzy_logic::functions::{DefuzzFactory, MembershipFactory};
+use fuzzy_logic::inference::{InferenceMachine, InferenceOptions};
+use fuzzy_logic::rules::{is, Rule, RuleSet};
+use fuzzy_logic::set::{Set, UniversalSet};
+use std::collections::HashMap;
+
+/// Calculates center of mass of output sets with 1k, 10k and 100k points.
+fn center_of_mass(c: &mut Criterion) {
+    for &points in &[1000, 10000, 100000] {
+        let mem = MembershipFactory::triangular(0.0, points as f32 / 2.0, points as f32);
+        let set = Set::new_with_mem("Output", mem);
+        for i in 0..points {
+            set.check(i as f32);
+        }
+        let defuzz = DefuzzFactory::center_of_mass();
+        c.bench_function(&format!("center_of_mass {}", points), move |b| b.iter(|| defuzz(&set)));
+    }
+}
+
+/// Computes two rules over the output universe of 10k points
+/// with center of mass and with the weighted average, which doesn't compute the rules' results.
+fn height_method(c: &mut Criterion) {
+    let mut temp = UniversalSet::new("temp".to_string());
+    temp.create_set("cold".to_string(), MembershipFactory::triangular(-20.0, 0.0, 25.0)).unwrap();
+    temp.create_set("hot".to_string(), MembershipFactory::triangular(15.0, 40.0, 60.0)).unwrap();
+    let mut fan = UniversalSet::new("fan".to_string());
+    fan.set_domain((0..10000).map(|x| x as f32 / 1000.0).collect());
+    fan.create_set("slow".to_string(), MembershipFactory::triangular(-5.0, 0.0, 6.0)).unwrap();
+    fan.create_set("fast".to_string(), MembershipFactory::triangular(4.0, 10.0, 15.0)).unwrap();
+    let mut universes = HashMap::new();
+    universes.insert("temp".to_string(), temp);
+    universes.insert("fan".to_string(), fan);
+    let rules = vec![Rule::new(Box::new(is("temp", "cold")), "fan".to_string(), "slow".to_string()),
+                     Rule::new(Box::new(is("temp", "hot")), "fan".to_string(), "fast".to_string())];
+    let rules = RuleSet::new(rules).unwrap();
+    let mut machine = InferenceMachine::new(rules, universes, InferenceOptions::mamdani());
+    machine.set_value("temp", 20.0).unwrap();
+    machine.preallocate();
+    let defuzz = DefuzzFactory::weighted_average();
+    c.bench_function("inference center_of_mass",
+                     |b| b.iter(|| machine.compute_for("fan").unwrap()));
+    c.bench_function("inference weighted_average",
+                     |b| b.iter(|| machine.compute_weighted(&*defuzz).unwrap()));
+}
+
+criterion_group!(benches, center_of_mass, height_method);
+criterion_main!(benches);
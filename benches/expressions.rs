@@ -0,0 +1,98 @@
+#[macro_use]
+extern crate criterion;
+extern crate fuzzy_logic;
+
+use criterion::Criterion;
+use fuzzy_logic::arena::{ExprArena, ExprId};
+use fuzzy_logic::functions::MembershipFactory;
+use fuzzy_logic::inference::{InferenceContext, InferenceError, InferenceOptions};
+use fuzzy_logic::program::Program;
+use fuzzy_logic::rules::{And, Expression, Is, Or};
+use fuzzy_logic::set::UniversalSet;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Boxed expression, lets the generic connectives build trees of any shape.
+struct Node(Box<Expression>);
+
+impl Expression for Node {
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        self.0.eval(context)
+    }
+    fn to_string(&self) -> String {
+        self.0.to_string()
+    }
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        self.0.compile(program)
+    }
+}
+
+/// Builds a balanced tree of alternating 'and' and 'or' over `2^depth` leaves.
+fn tree(depth: usize, index: &mut usize) -> Node {
+    if depth == 0 {
+        let variable = format!("x{}", *index % 8);
+        let set = if index.is_multiple_of(3) { "low" } else { "high" };
+        *index += 1;
+        return Node(Box::new(Is::new(variable, set.to_string())));
+    }
+    let left = tree(depth - 1, index);
+    let right = tree(depth - 1, index);
+    if depth.is_multiple_of(2) {
+        Node(Box::new(And::new(left, right)))
+    } else {
+        Node(Box::new(Or::new(left, right)))
+    }
+}
+
+/// Builds the same tree as `tree` in the arena.
+fn arena_tree(arena: &mut ExprArena, depth: usize, index: &mut usize) -> ExprId {
+    if depth == 0 {
+        let variable = format!("x{}", *index % 8);
+        let set = if index.is_multiple_of(3) { "low" } else { "high" };
+        *index += 1;
+        return arena.is(&variable, set);
+    }
+    let left = arena_tree(arena, depth - 1, index);
+    let right = arena_tree(arena, depth - 1, index);
+    if depth.is_multiple_of(2) {
+        arena.and(left, right)
+    } else {
+        arena.or(left, right)
+    }
+}
+
+/// Evaluates a 256-leaf expression as a boxed tree, an arena tree and a compiled program.
+fn deep_expression(c: &mut Criterion) {
+    let mut universes = HashMap::new();
+    let mut values = HashMap::new();
+    for i in 0..8 {
+        let name = format!("x{}", i);
+        let mut universe = UniversalSet::new(name.clone());
+        universe.create_set("low".to_string(),
+                            MembershipFactory::triangular(-1.0, 0.0, 0.6)).unwrap();
+        universe.create_set("high".to_string(),
+                            MembershipFactory::triangular(0.4, 1.0, 2.0)).unwrap();
+        universes.insert(name.clone(), Rc::new(universe));
+        values.insert(name, i as f32 / 8.0);
+    }
+    let options = InferenceOptions::mamdani();
+    let tree = tree(8, &mut 0);
+    let program = Program::compile(&tree).unwrap();
+    let mut arena = ExprArena::new();
+    let root = arena_tree(&mut arena, 8, &mut 0);
+    let bindings = HashMap::new();
+    let fuzzy_values = HashMap::new();
+    let context = InferenceContext {
+        values: &values,
+        universes: &universes,
+        bindings: &bindings,
+        fuzzy_values: &fuzzy_values,
+        options: &options,
+    };
+    c.bench_function("expression tree", |b| b.iter(|| tree.eval(&context)));
+    c.bench_function("expression arena", |b| b.iter(|| arena.eval(root, &context)));
+    c.bench_function("expression program", |b| b.iter(|| program.eval(&context)));
+}
+
+criterion_group!(benches, deep_expression);
+criterion_main!(benches);
@@ -0,0 +1,49 @@
+#[macro_use]
+extern crate criterion;
+extern crate fuzzy_logic;
+
+use criterion::{black_box, Criterion};
+use fuzzy_logic::functions::MembershipFactory;
+use fuzzy_logic::set::UniversalSet;
+
+/// Precomputes a gaussian and a sigmoidal set over the domain.
+fn precompute(c: &mut Criterion) {
+    let domain: Vec<f32> = (0..10000).map(|x| x as f32 / 100.0).collect();
+    c.bench_function("precompute exp", move |b| {
+        b.iter(|| {
+            let mut universe = UniversalSet::new("x".to_string());
+            universe.set_domain(black_box(domain.clone()));
+            universe.create_set("gaussian".to_string(),
+                                MembershipFactory::gaussian(1.0, 50.0, 10.0)).unwrap();
+            universe.create_set("sigmoidal".to_string(),
+                                MembershipFactory::sigmoidal(0.5, 50.0)).unwrap();
+            universe
+        })
+    });
+}
+
+/// Evaluates the membership functions over a batch of points.
+fn batch(c: &mut Criterion) {
+    let points: Vec<f32> = (0..10000).map(|x| x as f32 / 100.0).collect();
+    let gaussian = MembershipFactory::gaussian(1.0, 50.0, 10.0);
+    let sigmoidal = MembershipFactory::sigmoidal(0.5, 50.0);
+    c.bench_function("batch exp", move |b| {
+        b.iter(|| points.iter().fold(0.0, |acc, &x| acc + gaussian(x) + sigmoidal(x)))
+    });
+}
+
+#[cfg(feature = "fast_math")]
+fn batch_fast(c: &mut Criterion) {
+    let points: Vec<f32> = (0..10000).map(|x| x as f32 / 100.0).collect();
+    let gaussian = MembershipFactory::gaussian_fast(1.0, 50.0, 10.0);
+    let sigmoidal = MembershipFactory::sigmoidal_fast(0.5, 50.0);
+    c.bench_function("batch fast_exp", move |b| {
+        b.iter(|| points.iter().fold(0.0, |acc, &x| acc + gaussian(x) + sigmoidal(x)))
+    });
+}
+
+#[cfg(feature = "fast_math")]
+criterion_group!(benches, precompute, batch, batch_fast);
+#[cfg(not(feature = "fast_math"))]
+criterion_group!(benches, precompute, batch);
+criterion_main!(benches);
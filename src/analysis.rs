@@ -0,0 +1,421 @@
+//! This module contains the static analysis of rule bases.
+//!
+//! `analyze` samples the input space on a grid and evaluates every rule's condition
+//! at each point, giving firing profiles of the rules. The profiles reveal
+//! points no rule covers, rules covered by others and rules contradicting each other.
+//! Conditions are compared structurally by compiling them into `Program`s.
+use inference::{InferenceContext, InferenceError, InferenceOptions};
+use program::Program;
+use rules::RuleSet;
+use set::UniversalSet;
+use std::collections::HashMap;
+use std::f32;
+use std::fmt;
+use std::rc::Rc;
+
+/// Settings of the analysis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisConfig {
+    /// Number of grid points along each input variable.
+    pub resolution: usize,
+    /// Points where the highest firing strength is below this are gaps.
+    pub completeness: f32,
+    /// Rules with different results, whose firing profiles are at least this similar,
+    /// contradict each other.
+    pub similarity: f32,
+}
+
+impl Default for AnalysisConfig {
+    fn default() -> AnalysisConfig {
+        AnalysisConfig {
+            resolution: 11,
+            completeness: 0.1,
+            similarity: 0.8,
+        }
+    }
+}
+
+/// Point of the input space, where no rule fires strongly enough.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gap {
+    /// Values of the input variables, sorted by name.
+    pub point: Vec<(String, f32)>,
+    /// Highest firing strength at the point.
+    pub strength: f32,
+}
+
+/// Rule whose results are all given by another rule wherever it fires.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redundancy {
+    /// Index of the redundant rule.
+    pub rule: usize,
+    /// Index of the rule covering it.
+    pub covered_by: usize,
+    /// Whether the conditions are identical, elsewise the redundant one is subsumed.
+    pub identical: bool,
+}
+
+/// Rules with similar conditions and different results in the same universe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contradiction {
+    /// Indices of the rules.
+    pub rules: (usize, usize),
+    /// Similarity of their firing profiles.
+    pub similarity: f32,
+}
+
+/// Findings of the analysis. Rules are referred to by their indices in `RuleSet`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisReport {
+    /// Number of the sampled points.
+    pub points: usize,
+    /// Points where no rule fires strongly enough.
+    pub gaps: Vec<Gap>,
+    /// Redundant rules.
+    pub redundancies: Vec<Redundancy>,
+    /// Potentially contradicting rules.
+    pub contradictions: Vec<Contradiction>,
+    /// Rules whose conditions can't be compiled, they aren't analyzed.
+    pub skipped: Vec<usize>,
+}
+
+impl AnalysisReport {
+    /// Returns `true` if nothing was found.
+    pub fn is_clean(&self) -> bool {
+        self.gaps.is_empty() && self.redundancies.is_empty() && self.contradictions.is_empty()
+    }
+}
+
+impl fmt::Display for Gap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let point = self.point
+                        .iter()
+                        .map(|&(ref variable, value)| format!("{} = {}", variable, value))
+                        .collect::<Vec<_>>();
+        write!(f, "{}: {}", point.join(", "), self.strength)
+    }
+}
+
+impl fmt::Display for Redundancy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "rule {} is {} rule {}",
+               self.rule,
+               if self.identical { "identical to" } else { "subsumed by" },
+               self.covered_by)
+    }
+}
+
+impl fmt::Display for Contradiction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "rules {} and {} are {} similar",
+               self.rules.0,
+               self.rules.1,
+               self.similarity)
+    }
+}
+
+impl fmt::Display for AnalysisReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "(AnalysisReport")?;
+        writeln!(f, "gaps: {} of {} points", self.gaps.len(), self.points)?;
+        for gap in &self.gaps {
+            writeln!(f, "\t{}", gap)?;
+        }
+        writeln!(f, "redundancies:")?;
+        for redundancy in &self.redundancies {
+            writeln!(f, "\t{}", redundancy)?;
+        }
+        writeln!(f, "contradictions:")?;
+        for contradiction in &self.contradictions {
+            writeln!(f, "\t{}", contradiction)?;
+        }
+        if !self.skipped.is_empty() {
+            writeln!(f, "skipped: {:?}", self.skipped)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Analyzes the rules with the default settings, see `analyze_with`.
+pub fn analyze(rules: &RuleSet,
+               universes: &HashMap<String, Rc<UniversalSet>>)
+               -> Result<AnalysisReport, InferenceError> {
+    analyze_with(rules, universes, &AnalysisConfig::default())
+}
+
+/// Analyzes the rules.
+///
+/// Input variables are those of the conditions, each is in the universe named after it and
+/// is sampled at `resolution` points over the universe's domain, so the grid has
+/// `resolution ^ inputs` points. Conditions are evaluated with Zadeh logic operations.
+///
+/// Rule is redundant if another rule giving all of its consequents has the identical
+/// condition, or fires at least as strong at every point. Rules with different sets in any
+/// common universe contradict if the Jaccard similarity of their firing profiles is high enough.
+pub fn analyze_with(rules: &RuleSet,
+                    universes: &HashMap<String, Rc<UniversalSet>>,
+                    config: &AnalysisConfig)
+                    -> Result<AnalysisReport, InferenceError> {
+    let mut sources = Vec::with_capacity(rules.len());
+    let mut skipped = Vec::new();
+    let mut variables = Vec::new();
+    for (index, rule) in rules.rules().iter().enumerate() {
+        match Program::compile(rule.condition()) {
+            Ok(program) => {
+                for term in program.terms() {
+                    if !variables.contains(&term.variable) {
+                        variables.push(term.variable.clone());
+                    }
+                }
+                sources.push(Some(rule.condition().to_string()));
+            }
+            Err(_) => {
+                skipped.push(index);
+                sources.push(None);
+            }
+        }
+    }
+    variables.sort();
+    let mut axes = Vec::with_capacity(variables.len());
+    for variable in &variables {
+        let universe = universes.get(variable)
+                                .ok_or_else(|| InferenceError::UnboundVariable(variable.clone()))?;
+        let domain = universe.domain();
+        if domain.is_empty() {
+            return Err(InferenceError::EmptyDomain(variable.clone()));
+        }
+        let min = domain.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = domain.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let steps = config.resolution.max(2) - 1;
+        axes.push((0..steps + 1)
+                      .map(|i| min + (max - min) * i as f32 / steps as f32)
+                      .collect::<Vec<_>>());
+    }
+
+    // Firing profiles: strengths of each rule at each point of the grid.
+    let options = InferenceOptions::mamdani();
+    let bindings = HashMap::new();
+    let fuzzy_values = HashMap::new();
+    let mut values = HashMap::new();
+    let mut profiles = vec![Vec::new(); rules.len()];
+    let mut gaps = Vec::new();
+    let mut indices = vec![0; axes.len()];
+    let mut points = 0;
+    loop {
+        for (variable, (axis, &i)) in variables.iter().zip(axes.iter().zip(&indices)) {
+            values.insert(variable.clone(), axis[i]);
+        }
+        let context = InferenceContext {
+            values: &values,
+            universes: universes,
+            bindings: &bindings,
+            fuzzy_values: &fuzzy_values,
+            options: &options,
+        };
+        let mut highest: f32 = 0.0;
+        for (index, rule) in rules.rules().iter().enumerate() {
+            if sources[index].is_some() {
+                let strength = rule.condition().eval(&context)?;
+                highest = highest.max(strength);
+                profiles[index].push(strength);
+            }
+        }
+        if highest < config.completeness {
+            gaps.push(Gap {
+                point: variables.iter()
+                                .map(|variable| (variable.clone(), values[variable]))
+                                .collect(),
+                strength: highest,
+            });
+        }
+        points += 1;
+        if !next_point(&mut indices, &axes) {
+            break;
+        }
+    }
+
+    let mut redundancies = Vec::new();
+    let mut contradictions = Vec::new();
+    let rules = rules.rules();
+    for i in 0..rules.len() {
+        for j in 0..rules.len() {
+            let (source_i, source_j) = match (&sources[i], &sources[j]) {
+                (&Some(ref source_i), &Some(ref source_j)) if i != j => (source_i, source_j),
+                _ => continue,
+            };
+            let (consequents_i, consequents_j) = (rules[i].consequents(), rules[j].consequents());
+            let conflicting = consequents_i.iter().any(|&(universe, set)| {
+                consequents_j.iter()
+                             .any(|&(other, other_set)| other == universe && other_set != set)
+            });
+            if consequents_i.iter().all(|consequent| consequents_j.contains(consequent)) {
+                let identical = source_i == source_j;
+                // Of identical rules with the same consequents the later one is redundant.
+                if identical && i < j &&
+                   consequents_j.iter().all(|consequent| consequents_i.contains(consequent)) {
+                    continue;
+                }
+                let covered = profiles[i].iter().zip(&profiles[j]).all(|(a, b)| a <= b);
+                if identical || covered && !redundancies.iter().any(|r: &Redundancy| {
+                    r.rule == j && r.covered_by == i
+                }) {
+                    redundancies.push(Redundancy {
+                        rule: i,
+                        covered_by: j,
+                        identical: identical,
+                    });
+                }
+            } else if conflicting && i < j {
+                let similarity = similarity(&profiles[i], &profiles[j]);
+                if similarity >= config.similarity {
+                    contradictions.push(Contradiction {
+                        rules: (i, j),
+                        similarity: similarity,
+                    });
+                }
+            }
+        }
+    }
+    Ok(AnalysisReport {
+        points: points,
+        gaps: gaps,
+        redundancies: redundancies,
+        contradictions: contradictions,
+        skipped: skipped,
+    })
+}
+
+/// Advances the indices to the next point of the grid, returns `false` after the last one.
+fn next_point(indices: &mut [usize], axes: &[Vec<f32>]) -> bool {
+    for (index, axis) in indices.iter_mut().zip(axes) {
+        *index += 1;
+        if *index < axis.len() {
+            return true;
+        }
+        *index = 0;
+    }
+    false
+}
+
+/// Returns the Jaccard similarity of the profiles, 0 if neither rule fires.
+fn similarity(a: &[f32], b: &[f32]) -> f32 {
+    let (intersection, union) = a.iter()
+                                 .zip(b)
+                                 .fold((0.0, 0.0), |(intersection, union), (&a, &b)| {
+                                     (intersection + a.min(b), union + a.max(b))
+                                 });
+    if union > 0.0 { intersection / union } else { 0.0 }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use functions::MembershipFactory;
+    use rules::{is, not, Rule, RuleSet};
+
+    fn universes() -> HashMap<String, Rc<UniversalSet>> {
+        let mut temp = UniversalSet::new("temp".to_string());
+        temp.set_domain((0..41).map(|x| x as f32).collect());
+        temp.create_set("cold".to_string(), MembershipFactory::triangular(-10.0, 0.0, 10.0))
+            .unwrap();
+        temp.create_set("warm".to_string(), MembershipFactory::triangular(0.0, 10.0, 20.0))
+            .unwrap();
+        let mut humidity = UniversalSet::new("humidity".to_string());
+        humidity.set_domain((0..101).map(|x| x as f32).collect());
+        humidity.create_set("dry".to_string(),
+                            MembershipFactory::triangular(-100.0, 0.0, 100.0))
+                .unwrap();
+        let mut fan = UniversalSet::new("fan".to_string());
+        fan.set_domain((0..11).map(|x| x as f32).collect());
+        for set in &["slow", "fast"] {
+            fan.create_set(set.to_string(), MembershipFactory::triangular(0.0, 5.0, 10.0))
+               .unwrap();
+        }
+        let mut universes = HashMap::new();
+        for universe in [temp, humidity, fan] {
+            universes.insert(universe.name().to_string(), Rc::new(universe));
+        }
+        universes
+    }
+
+    #[test]
+    fn seeded_defects() {
+        let mut rules = fuzzy_rules! {
+            if temp is cold => fan is slow;
+            if temp is cold && humidity is dry => fan is slow;
+            if temp is warm => fan is fast;
+        };
+        let warm = Rule::new(Box::new(not(not(is("temp", "warm")))),
+                             "fan".to_string(),
+                             "slow".to_string());
+        rules.push(warm);
+        let report = analyze(&RuleSet::new(rules).unwrap(), &universes()).unwrap();
+
+        // Nothing covers temperatures above 20.
+        assert_eq!(report.points, 11 * 11);
+        assert!(!report.gaps.is_empty());
+        assert!(report.gaps.iter().all(|gap| gap.point[1].0 == "temp" && gap.point[1].1 >= 20.0));
+        assert!(report.gaps.iter().any(|gap| gap.point[1].1 == 40.0));
+        assert_eq!(report.redundancies,
+                   vec![Redundancy {
+                            rule: 1,
+                            covered_by: 0,
+                            identical: false,
+                        }]);
+        assert_eq!(report.contradictions.len(), 1);
+        assert_eq!(report.contradictions[0].rules, (2, 3));
+        assert!(report.contradictions[0].similarity > 0.999);
+        assert!(!report.is_clean());
+        assert!(format!("{}", report).contains("rule 1 is subsumed by rule 0"));
+    }
+
+    #[test]
+    fn multiple_consequents() {
+        let mut valve = UniversalSet::new("valve".to_string());
+        valve.set_domain((0..11).map(|x| x as f32).collect());
+        for set in &["open", "closed"] {
+            valve.create_set(set.to_string(), MembershipFactory::triangular(0.0, 5.0, 10.0))
+                 .unwrap();
+        }
+        let mut universes = universes();
+        universes.insert("valve".to_string(), Rc::new(valve));
+        let condition = || Box::new(is("temp", "cold"));
+        let rules = vec![Rule::new(condition(), "fan".to_string(), "slow".to_string())
+                             .with_consequent("valve".to_string(), "open".to_string()),
+                         Rule::new(condition(), "fan".to_string(), "slow".to_string())
+                             .with_consequent("valve".to_string(), "closed".to_string()),
+                         Rule::new(condition(), "valve".to_string(), "open".to_string()),
+                         Rule::new(condition(), "fan".to_string(), "fast".to_string())];
+        let report = analyze(&RuleSet::new(rules).unwrap(), &universes).unwrap();
+        // Only the rule giving a subset of the other's consequents is redundant.
+        assert_eq!(report.redundancies,
+                   vec![Redundancy {
+                            rule: 2,
+                            covered_by: 0,
+                            identical: true,
+                        }]);
+        let contradictions = report.contradictions
+                                   .iter()
+                                   .map(|contradiction| contradiction.rules)
+                                   .collect::<Vec<_>>();
+        assert_eq!(contradictions, vec![(0, 1), (0, 3), (1, 2), (1, 3)]);
+    }
+
+    #[test]
+    fn identical_rules() {
+        let rules = fuzzy_rules! {
+            if temp is cold || temp is warm => fan is slow;
+            if temp is cold || temp is warm => fan is slow;
+        };
+        let report = analyze(&RuleSet::new(rules).unwrap(), &universes()).unwrap();
+        assert_eq!(report.redundancies,
+                   vec![Redundancy {
+                            rule: 1,
+                            covered_by: 0,
+                            identical: true,
+                        }]);
+        assert!(report.contradictions.is_empty());
+    }
+}
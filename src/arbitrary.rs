@@ -0,0 +1,406 @@
+//! This module contains generators for property tests, available with the `quickcheck` feature.
+//!
+//! `Set` implements `quickcheck::Arbitrary` directly. Membership functions, expressions and
+//! machines can't be printed or cloned, so they are generated as specifications:
+//! `MembershipParams`, `ExprSpec` and `MachineSpec`, which build the real values.
+//! Shrinking keeps specifications valid: parameters stay ordered and machines keep a rule.
+extern crate ordered_float;
+extern crate quickcheck;
+
+use functions::{MembershipFactory, MembershipFunction};
+use inference::{InferenceMachine, InferenceOptions};
+use rules::{And, Expression, Is, Not, Or, Rule, RuleSet};
+use set::{Set, UniversalSet};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+
+use self::ordered_float::OrderedFloat;
+use self::quickcheck::{Arbitrary, Gen};
+
+/// Variables of generated expressions and machines, with their sets and ranges.
+pub const VOCABULARY: &'static [(&'static str, &'static [&'static str], f32, f32)] =
+    &[("temp", &["cold", "warm", "hot"], 0.0, 40.0), ("humidity", &["dry", "wet"], 0.0, 100.0)];
+
+/// Output universe of generated machines, with its sets and range.
+pub const OUTPUT: (&'static str, &'static [&'static str], f32, f32) =
+    ("fan", &["slow", "medium", "fast"], 0.0, 10.0);
+
+/// Maximum depth of generated expressions.
+pub const MAX_DEPTH: usize = 4;
+
+/// Maximum number of points of generated sets.
+pub const MAX_POINTS: usize = 32;
+
+/// Minimum width of generated gaussian functions.
+pub const MIN_SIGMA: f32 = 1e-3;
+
+/// Returns a number in `[min, max]`.
+fn float(g: &mut Gen, min: f32, max: f32) -> f32 {
+    let step = u32::arbitrary(g) % 10_001;
+    min + (max - min) * step as f32 / 10_000.0
+}
+
+/// Returns a number in `[0, bound)`.
+fn below(g: &mut Gen, bound: usize) -> usize {
+    usize::arbitrary(g) % bound.max(1)
+}
+
+/// Parameters of a membership function.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MembershipParams {
+    /// Triangular function with `a <= b <= c`.
+    Triangular(f32, f32, f32),
+    /// Trapezoidal function with `a <= b <= c <= d`.
+    Trapezoidal(f32, f32, f32, f32),
+    /// Gaussian function of height 1 with the center and width of at least `MIN_SIGMA`.
+    Gaussian(f32, f32),
+}
+
+impl MembershipParams {
+    /// Generates parameters of a function over `[min, max]`.
+    pub fn generate(g: &mut Gen, min: f32, max: f32) -> MembershipParams {
+        let mut points = (0..4).map(|_| float(g, min, max)).collect::<Vec<_>>();
+        points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        match below(g, 3) {
+            0 => MembershipParams::Triangular(points[0], points[1], points[3]),
+            1 => MembershipParams::Trapezoidal(points[0], points[1], points[2], points[3]),
+            _ => MembershipParams::Gaussian(points[1], (points[3] - points[0]).max(MIN_SIGMA)),
+        }
+    }
+
+    /// Returns the membership function.
+    pub fn function(&self) -> Box<MembershipFunction> {
+        match *self {
+            MembershipParams::Triangular(a, b, c) => MembershipFactory::triangular(a, b, c),
+            MembershipParams::Trapezoidal(a, b, c, d) => {
+                MembershipFactory::trapezoidal(a, b, c, d)
+            }
+            MembershipParams::Gaussian(center, sigma) => {
+                MembershipFactory::gaussian(1.0, center, sigma)
+            }
+        }
+    }
+
+    /// Returns the parameters with the ranges halved towards the middle of the support.
+    fn narrowed(&self) -> MembershipParams {
+        match *self {
+            MembershipParams::Triangular(a, b, c) => {
+                MembershipParams::Triangular((a + b) / 2.0, b, (b + c) / 2.0)
+            }
+            MembershipParams::Trapezoidal(a, b, c, d) => {
+                let middle = (b + c) / 2.0;
+                MembershipParams::Trapezoidal((a + b) / 2.0,
+                                              (b + middle) / 2.0,
+                                              (c + middle) / 2.0,
+                                              (c + d) / 2.0)
+            }
+            MembershipParams::Gaussian(center, sigma) => {
+                MembershipParams::Gaussian(center, sigma / 2.0)
+            }
+        }
+    }
+}
+
+impl Arbitrary for MembershipParams {
+    /// Generates parameters of a function over `[-100, 100]`.
+    fn arbitrary(g: &mut Gen) -> MembershipParams {
+        MembershipParams::generate(g, -100.0, 100.0)
+    }
+
+    /// Trapezoids shrink into triangles, every function shrinks to a narrower one.
+    fn shrink(&self) -> Box<Iterator<Item = MembershipParams>> {
+        let mut shrunk = Vec::new();
+        if let MembershipParams::Trapezoidal(a, b, _, d) = *self {
+            shrunk.push(MembershipParams::Triangular(a, b, d));
+        }
+        let narrowed = self.narrowed();
+        let valid = match narrowed {
+            MembershipParams::Gaussian(_, sigma) => sigma >= MIN_SIGMA,
+            _ => true,
+        };
+        if valid && narrowed != *self {
+            shrunk.push(narrowed);
+        }
+        Box::new(shrunk.into_iter())
+    }
+}
+
+/// Sets have up to `MAX_POINTS` cached points in `[-100, 100]` with memberships in `[0, 1]`.
+impl Arbitrary for Set {
+    fn arbitrary(g: &mut Gen) -> Set {
+        let count = below(g, MAX_POINTS + 1);
+        let points = (0..count)
+                         .map(|_| (float(g, -100.0, 100.0), float(g, 0.0, 1.0)))
+                         .collect::<Vec<_>>();
+        set_of(points)
+    }
+
+    /// Shrinks by removing points.
+    fn shrink(&self) -> Box<Iterator<Item = Set>> {
+        let mut points = self.cache
+                             .borrow()
+                             .iter()
+                             .map(|(x, &mem)| (x.0, mem))
+                             .collect::<Vec<_>>();
+        points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        Box::new((0..points.len()).map(move |i| {
+            let mut points = points.clone();
+            points.remove(i);
+            set_of(points)
+        }))
+    }
+}
+
+/// Returns the set with given cached points.
+fn set_of(points: Vec<(f32, f32)>) -> Set {
+    let cache = points.into_iter().map(|(x, mem)| (OrderedFloat(x), mem)).collect();
+    Set::new_with_domain("arbitrary", RefCell::new(cache))
+}
+
+/// Specification of an expression tree.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprSpec {
+    /// `variable is set`.
+    Is(String, String),
+    /// Conjunction.
+    And(Box<ExprSpec>, Box<ExprSpec>),
+    /// Disjunction.
+    Or(Box<ExprSpec>, Box<ExprSpec>),
+    /// Negation.
+    Not(Box<ExprSpec>),
+}
+
+impl ExprSpec {
+    /// Generates the tree up to `depth` levels deep with terms of the vocabulary.
+    ///
+    /// The vocabulary lists variables with their sets.
+    pub fn generate(g: &mut Gen, vocabulary: &[(&str, &[&str])], depth: usize) -> ExprSpec {
+        if depth <= 1 || below(g, 3) == 0 {
+            let &(variable, sets) = &vocabulary[below(g, vocabulary.len())];
+            return ExprSpec::Is(variable.to_string(), sets[below(g, sets.len())].to_string());
+        }
+        let child = |g: &mut Gen| Box::new(ExprSpec::generate(g, vocabulary, depth - 1));
+        match below(g, 3) {
+            0 => ExprSpec::And(child(g), child(g)),
+            1 => ExprSpec::Or(child(g), child(g)),
+            _ => ExprSpec::Not(child(g)),
+        }
+    }
+
+    /// Returns the expression.
+    pub fn expression(&self) -> Box<Expression> {
+        match *self {
+            ExprSpec::Is(ref variable, ref set) => {
+                Box::new(Is::new(variable.clone(), set.clone()))
+            }
+            ExprSpec::And(ref left, ref right) => {
+                Box::new(And::new(left.expression(), right.expression()))
+            }
+            ExprSpec::Or(ref left, ref right) => {
+                Box::new(Or::new(left.expression(), right.expression()))
+            }
+            ExprSpec::Not(ref expression) => Box::new(Not::new(expression.expression())),
+        }
+    }
+
+    /// Returns the depth of the tree.
+    pub fn depth(&self) -> usize {
+        match *self {
+            ExprSpec::Is(..) => 1,
+            ExprSpec::And(ref left, ref right) |
+            ExprSpec::Or(ref left, ref right) => 1 + left.depth().max(right.depth()),
+            ExprSpec::Not(ref expression) => 1 + expression.depth(),
+        }
+    }
+}
+
+/// Returns the vocabulary of `VOCABULARY` without ranges.
+fn vocabulary() -> Vec<(&'static str, &'static [&'static str])> {
+    VOCABULARY.iter().map(|&(variable, sets, _, _)| (variable, sets)).collect()
+}
+
+impl Arbitrary for ExprSpec {
+    /// Generates the tree up to `MAX_DEPTH` levels deep over `VOCABULARY`.
+    fn arbitrary(g: &mut Gen) -> ExprSpec {
+        ExprSpec::generate(g, &vocabulary(), MAX_DEPTH)
+    }
+
+    /// Shrinks into the subtrees.
+    fn shrink(&self) -> Box<Iterator<Item = ExprSpec>> {
+        let shrunk = match *self {
+            ExprSpec::Is(..) => Vec::new(),
+            ExprSpec::And(ref left, ref right) |
+            ExprSpec::Or(ref left, ref right) => vec![(**left).clone(), (**right).clone()],
+            ExprSpec::Not(ref expression) => vec![(**expression).clone()],
+        };
+        Box::new(shrunk.into_iter())
+    }
+}
+
+/// Specification of a machine over `VOCABULARY` and `OUTPUT`.
+#[derive(Clone, PartialEq)]
+pub struct MachineSpec {
+    /// Membership functions of every set, by universe and set names.
+    pub sets: Vec<(String, String, MembershipParams)>,
+    /// Rules: conditions and output sets, there is at least one.
+    pub rules: Vec<(ExprSpec, String)>,
+}
+
+impl MachineSpec {
+    /// Returns the machine with Mamdani options.
+    ///
+    /// Universes have 101 points over their ranges, values aren't set.
+    pub fn build(&self) -> InferenceMachine {
+        let mut universes = HashMap::new();
+        for &(name, _, min, max) in VOCABULARY.iter().chain(Some(&OUTPUT)) {
+            let mut universe = UniversalSet::new(name.to_string());
+            universe.set_domain((0..101).map(|i| min + (max - min) * i as f32 / 100.0).collect());
+            universes.insert(name.to_string(), universe);
+        }
+        for &(ref universe, ref set, ref params) in &self.sets {
+            universes.get_mut(universe)
+                     .unwrap()
+                     .replace_set(set.clone(), params.function());
+        }
+        let rules = self.rules
+                        .iter()
+                        .map(|&(ref condition, ref set)| {
+                            Rule::new(condition.expression(), OUTPUT.0.to_string(), set.clone())
+                        })
+                        .collect();
+        InferenceMachine::new(RuleSet::new(rules).unwrap(),
+                              universes,
+                              InferenceOptions::mamdani())
+    }
+}
+
+impl fmt::Debug for MachineSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "(MachineSpec")?;
+        for &(ref universe, ref set, ref params) in &self.sets {
+            writeln!(f, "\t{}:{} {:?}", universe, set, params)?;
+        }
+        for &(ref condition, ref set) in &self.rules {
+            writeln!(f, "\t{:?} => {}", condition, set)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl Arbitrary for MachineSpec {
+    /// Generates functions of every set within its universe's range and 1 to 4 rules.
+    fn arbitrary(g: &mut Gen) -> MachineSpec {
+        let mut sets = Vec::new();
+        for &(universe, names, min, max) in VOCABULARY.iter().chain(Some(&OUTPUT)) {
+            for name in names {
+                sets.push((universe.to_string(),
+                           name.to_string(),
+                           MembershipParams::generate(g, min, max)));
+            }
+        }
+        let rules = (0..1 + below(g, 4))
+                        .map(|_| {
+                            (ExprSpec::generate(g, &vocabulary(), MAX_DEPTH),
+                             OUTPUT.1[below(g, OUTPUT.1.len())].to_string())
+                        })
+                        .collect();
+        MachineSpec {
+            sets: sets,
+            rules: rules,
+        }
+    }
+
+    /// Shrinks by removing rules, simplifying conditions and narrowing functions.
+    fn shrink(&self) -> Box<Iterator<Item = MachineSpec>> {
+        let mut shrunk = Vec::new();
+        if self.rules.len() > 1 {
+            for i in 0..self.rules.len() {
+                let mut spec = self.clone();
+                spec.rules.remove(i);
+                shrunk.push(spec);
+            }
+        }
+        for (i, &(ref condition, _)) in self.rules.iter().enumerate() {
+            for condition in condition.shrink() {
+                let mut spec = self.clone();
+                spec.rules[i].0 = condition;
+                shrunk.push(spec);
+            }
+        }
+        for (i, &(_, _, ref params)) in self.sets.iter().enumerate() {
+            for params in params.shrink() {
+                let mut spec = self.clone();
+                spec.sets[i].2 = params;
+                shrunk.push(spec);
+            }
+        }
+        Box::new(shrunk.into_iter())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::quickcheck::{quickcheck, TestResult};
+    use inference::InferenceError;
+    use ops::{MinMaxOps, SetOps};
+
+    #[test]
+    fn valid_params() {
+        fn prop(params: MembershipParams) -> bool {
+            let valid = match params {
+                MembershipParams::Triangular(a, b, c) => a <= b && b <= c,
+                MembershipParams::Trapezoidal(a, b, c, d) => a <= b && b <= c && c <= d,
+                MembershipParams::Gaussian(_, sigma) => sigma >= MIN_SIGMA,
+            };
+            valid && params.shrink().all(prop)
+        }
+        quickcheck(prop as fn(MembershipParams) -> bool);
+    }
+
+    #[test]
+    fn bounded_sets() {
+        fn prop(set: Set) -> bool {
+            let cache = set.cache.borrow();
+            cache.len() <= MAX_POINTS &&
+            cache.iter().all(|(x, &mem)| x.0.abs() <= 100.0 && mem >= 0.0 && mem <= 1.0)
+        }
+        quickcheck(prop as fn(Set) -> bool);
+    }
+
+    #[test]
+    fn bounded_expressions() {
+        fn prop(expression: ExprSpec) -> bool {
+            expression.depth() <= MAX_DEPTH
+        }
+        quickcheck(prop as fn(ExprSpec) -> bool);
+    }
+
+    #[test]
+    fn union_is_commutative() {
+        fn prop(a: Set, b: Set) -> bool {
+            let ops = MinMaxOps {};
+            let ab = ops.union(&mut a.clone(), &mut b.clone());
+            let ba = ops.union(&mut b.clone(), &mut a.clone());
+            let equal = *ab.cache.borrow() == *ba.cache.borrow();
+            equal
+        }
+        quickcheck(prop as fn(Set, Set) -> bool);
+    }
+
+    #[test]
+    fn output_within_domain() {
+        fn prop(spec: MachineSpec, temp: u8, humidity: u8) -> TestResult {
+            let mut machine = spec.build();
+            machine.set_value("temp", temp as f32 % 41.0).unwrap();
+            machine.set_value("humidity", humidity as f32 % 101.0).unwrap();
+            match machine.compute_for("fan") {
+                Ok(result) => TestResult::from_bool(0.0 <= result.value && result.value <= 10.0),
+                Err(InferenceError::NoRuleFired) => TestResult::discard(),
+                Err(_) => TestResult::failed(),
+            }
+        }
+        quickcheck(prop as fn(MachineSpec, u8, u8) -> TestResult);
+    }
+}
@@ -0,0 +1,245 @@
+//! This module contains arena storage for expression trees.
+//!
+//! `ExprArena` owns all the nodes in one vector and children are referenced by `ExprId` indices.
+//! It avoids an allocation per node, keeps nodes close in memory and makes trees cheap to clone.
+//!
+//! # Usage
+//!
+//! ```rust
+//! use fuzzy_logic::arena::ExprArena;
+//! use std::rc::Rc;
+//!
+//! let mut arena = ExprArena::new();
+//! let hot = arena.is("temp", "hot");
+//! let low = arena.is("humidity", "low");
+//! let root = arena.and(hot, low);
+//! assert_eq!(arena.to_string(root), "(and (is temp hot) (is humidity low))");
+//! let condition = ExprArena::expression(Rc::new(arena), root);
+//! ```
+use inference::{InferenceContext, InferenceError};
+use program::{Op, Program};
+use rules::Expression;
+
+use std::rc::Rc;
+
+/// Index of the node in `ExprArena`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(u32);
+
+/// Node of the expression tree.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    /// Membership of the variable in the set.
+    Is {
+        /// Name of the variable.
+        variable: String,
+        /// Name of the set.
+        set: String,
+    },
+    /// Fuzzy 'and' of two nodes.
+    And(ExprId, ExprId),
+    /// Fuzzy 'or' of two nodes.
+    Or(ExprId, ExprId),
+    /// Fuzzy 'not' of the node.
+    Not(ExprId),
+}
+
+/// Owns nodes of expression trees.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExprArena {
+    /// All nodes. Children are always stored before their parents.
+    nodes: Vec<Node>,
+}
+
+impl ExprArena {
+    /// Constructs the empty arena.
+    pub fn new() -> ExprArena {
+        ExprArena { nodes: Vec::new() }
+    }
+
+    /// Adds the node, returns its index.
+    fn add(&mut self, node: Node) -> ExprId {
+        self.nodes.push(node);
+        ExprId((self.nodes.len() - 1) as u32)
+    }
+
+    /// Adds 'is' node.
+    pub fn is(&mut self, variable: &str, set: &str) -> ExprId {
+        self.add(Node::Is {
+            variable: variable.to_string(),
+            set: set.to_string(),
+        })
+    }
+
+    /// Adds 'and' node.
+    pub fn and(&mut self, left: ExprId, right: ExprId) -> ExprId {
+        self.add(Node::And(left, right))
+    }
+
+    /// Adds 'or' node.
+    pub fn or(&mut self, left: ExprId, right: ExprId) -> ExprId {
+        self.add(Node::Or(left, right))
+    }
+
+    /// Adds 'not' node.
+    pub fn not(&mut self, expression: ExprId) -> ExprId {
+        self.add(Node::Not(expression))
+    }
+
+    /// Returns the node with given index.
+    pub fn node(&self, id: ExprId) -> &Node {
+        &self.nodes[id.0 as usize]
+    }
+
+    /// Returns the number of nodes.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if the arena has no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Evaluates the tree with given root.
+    pub fn eval(&self, root: ExprId, context: &InferenceContext) -> Result<f32, InferenceError> {
+        let ops = &*context.options.logic_ops;
+        Ok(match *self.node(root) {
+            Node::Is { ref variable, ref set } => context.membership(variable, set)?,
+            Node::And(left, right) => {
+                ops.and(self.eval(left, context)?, self.eval(right, context)?)
+            }
+            Node::Or(left, right) => ops.or(self.eval(left, context)?, self.eval(right, context)?),
+            Node::Not(expression) => ops.not(self.eval(expression, context)?),
+        })
+    }
+
+    /// String representation of the tree with given root.
+    ///
+    /// Same as the representation of the equal tree of `rules` expressions.
+    pub fn to_string(&self, root: ExprId) -> String {
+        match *self.node(root) {
+            Node::Is { ref variable, ref set } => format!("(is {} {})", variable, set),
+            Node::And(left, right) => format!("(and {} {})", self.to_string(left), self.to_string(right)),
+            Node::Or(left, right) => format!("(or {} {})", self.to_string(left), self.to_string(right)),
+            Node::Not(expression) => format!("(not {})", self.to_string(expression)),
+        }
+    }
+
+    /// Appends the postfix instructions of the tree with given root to `program`.
+    pub fn compile(&self, root: ExprId, program: &mut Program) {
+        match *self.node(root) {
+            Node::Is { ref variable, ref set } => program.push_membership(variable, set),
+            Node::And(left, right) => {
+                self.compile(left, program);
+                self.compile(right, program);
+                program.push(Op::And);
+            }
+            Node::Or(left, right) => {
+                self.compile(left, program);
+                self.compile(right, program);
+                program.push(Op::Or);
+            }
+            Node::Not(expression) => {
+                self.compile(expression, program);
+                program.push(Op::Not);
+            }
+        }
+    }
+
+    /// Constructs the `Expression` evaluating the tree with given root.
+    pub fn expression(arena: Rc<ExprArena>, root: ExprId) -> ArenaExpression {
+        ArenaExpression {
+            arena: arena,
+            root: root,
+        }
+    }
+}
+
+/// Tree of the shared `ExprArena` used as an `Expression`.
+#[derive(Debug, Clone)]
+pub struct ArenaExpression {
+    /// Arena with the nodes.
+    arena: Rc<ExprArena>,
+    /// Root of the tree.
+    root: ExprId,
+}
+
+impl Expression for ArenaExpression {
+    /// Evaluates the tree in the arena.
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        self.arena.eval(self.root, context)
+    }
+
+    /// String representation of the tree.
+    fn to_string(&self) -> String {
+        self.arena.to_string(self.root)
+    }
+
+    /// Compiles the tree.
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        self.arena.compile(self.root, program);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use functions::MembershipFactory;
+    use inference::InferenceOptions;
+    use rules::{And, Is, Or, Rule};
+    use set::UniversalSet;
+    use std::collections::HashMap;
+
+    fn is(variable: &str, set: &str) -> Is {
+        Is::new(variable.to_string(), set.to_string())
+    }
+
+    #[test]
+    fn arena_matches_tree() {
+        let mut universes = HashMap::new();
+        for name in &["a", "b"] {
+            let mut universe = UniversalSet::new(name.to_string());
+            universe.create_set("low".to_string(),
+                                MembershipFactory::triangular(-1.0, 0.0, 0.6)).unwrap();
+            universe.create_set("high".to_string(),
+                                MembershipFactory::triangular(0.4, 1.0, 2.0)).unwrap();
+            universes.insert(name.to_string(), Rc::new(universe));
+        }
+        universes.insert("c".to_string(), Rc::new(UniversalSet::new("c".to_string())));
+        let options = InferenceOptions::mamdani();
+
+        let tree = Or::new(And::new(is("a", "low"), is("b", "high")), is("b", "low"));
+        let mut arena = ExprArena::new();
+        let a_low = arena.is("a", "low");
+        let b_high = arena.is("b", "high");
+        let and = arena.and(a_low, b_high);
+        let b_low = arena.is("b", "low");
+        let root = arena.or(and, b_low);
+        let expression = ExprArena::expression(Rc::new(arena.clone()), root);
+        assert_eq!(expression.to_string(), tree.to_string());
+        assert_eq!(arena.len(), 5);
+
+        let rule = Rule::new_with_arena(Rc::new(arena), root, "c".to_string(), "off".to_string());
+        assert_eq!(format!("{}", rule), format!("(Rule c:off if:{})", tree.to_string()));
+
+        for i in 0..20 {
+            let mut values = HashMap::new();
+            values.insert("a".to_string(), i as f32 / 20.0);
+            values.insert("b".to_string(), 1.0 - i as f32 / 20.0);
+            let bindings = HashMap::new();
+            let fuzzy_values = HashMap::new();
+            let context = InferenceContext {
+                values: &values,
+                universes: &universes,
+                bindings: &bindings,
+                fuzzy_values: &fuzzy_values,
+                options: &options,
+            };
+            assert_eq!(expression.eval(&context).unwrap(), tree.eval(&context).unwrap());
+            let program = Program::compile(&expression).unwrap();
+            assert_eq!(program.eval(&context).unwrap(), tree.eval(&context).unwrap());
+        }
+    }
+}
@@ -0,0 +1,448 @@
+//! This module contains the builder of the whole inference system.
+//!
+//! `SystemBuilder` collects input and output universes, rules and options,
+//! then checks that every name referenced by the rules exists and constructs `InferenceMachine`.
+//!
+//! # Usage
+//!
+//! ```rust
+//! use fuzzy_logic::builder::SystemBuilder;
+//! use fuzzy_logic::functions::MembershipFactory;
+//! use fuzzy_logic::inference::InferenceOptions;
+//! use fuzzy_logic::rules::is;
+//!
+//! let mut machine = SystemBuilder::new()
+//!     .input("temp", |u| {
+//!         u.range(0.0, 40.0, 81)
+//!          .term("cold", MembershipFactory::triangular(-20.0, 0.0, 25.0))
+//!          .term("hot", MembershipFactory::triangular(15.0, 40.0, 60.0))
+//!     })
+//!     .output("fan", |u| {
+//!         u.range(0.0, 10.0, 101)
+//!          .term("slow", MembershipFactory::triangular(-5.0, 0.0, 6.0))
+//!          .term("fast", MembershipFactory::triangular(4.0, 10.0, 15.0))
+//!     })
+//!     .rule(|r| r.when(is("temp", "cold")).then("fan", "slow"))
+//!     .rule(|r| r.when(is("temp", "hot")).then("fan", "fast"))
+//!     .options(InferenceOptions::mamdani())
+//!     .build()
+//!     .unwrap();
+//! machine.set_value("temp", 35.0).unwrap();
+//! assert!(machine.compute_for("fan").unwrap().value > 5.0);
+//! ```
+use functions::MembershipFunction;
+use inference::{InferenceContext, InferenceError, InferenceMachine, InferenceOptions};
+use rules::{Expression, Rule, RuleSet};
+use set::{SetError, UniversalSet};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+
+/// Error of the system validation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    /// There are no rules.
+    NoRules,
+    /// Domain of the universe with given name is not set.
+    NoDomain(String),
+    /// Two universes have the same name.
+    DuplicateUniverse(String),
+    /// Set is defined twice in the universe.
+    DuplicateSet {
+        /// Name of the universe.
+        universe: String,
+        /// Name of the set.
+        set: String,
+    },
+    /// Set can't be created in the universe.
+    InvalidSet {
+        /// Name of the universe.
+        universe: String,
+        /// Error of the set's creation.
+        error: SetError,
+    },
+    /// Rule with given index has no condition or no result.
+    IncompleteRule(usize),
+    /// Variable of the rule's condition is not an input.
+    UnknownInput(String),
+    /// Universe of the rule's result is not an output.
+    UnknownOutput(String),
+    /// Set referenced by the rule doesn't exist in the universe.
+    UnknownSet {
+        /// Name of the universe.
+        universe: String,
+        /// Name of the set.
+        set: String,
+    },
+    /// Rules can't be combined into `RuleSet`.
+    InvalidRules(String),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BuildError::NoRules => write!(f, "system has no rules"),
+            BuildError::NoDomain(ref universe) => write!(f, "domain of {} is not set", universe),
+            BuildError::DuplicateUniverse(ref universe) => {
+                write!(f, "universe {} is defined twice", universe)
+            }
+            BuildError::DuplicateSet { ref universe, ref set } => {
+                write!(f, "set {} is defined twice in universe {}", set, universe)
+            }
+            BuildError::InvalidSet { ref universe, ref error } => {
+                write!(f, "invalid set in universe {}: {}", universe, error)
+            }
+            BuildError::IncompleteRule(index) => {
+                write!(f, "rule {} has no condition or no result", index)
+            }
+            BuildError::UnknownInput(ref variable) => write!(f, "input {} doesn't exist", variable),
+            BuildError::UnknownOutput(ref universe) => {
+                write!(f, "output {} doesn't exist", universe)
+            }
+            BuildError::UnknownSet { ref universe, ref set } => {
+                write!(f, "set {} doesn't exist in universe {}", set, universe)
+            }
+            BuildError::InvalidRules(ref message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for BuildError {
+    fn description(&self) -> &str {
+        match *self {
+            BuildError::NoRules => "system has no rules",
+            BuildError::NoDomain(_) => "domain is not set",
+            BuildError::DuplicateUniverse(_) => "universe is defined twice",
+            BuildError::DuplicateSet { .. } => "set is defined twice",
+            BuildError::InvalidSet { .. } => "set can't be created",
+            BuildError::IncompleteRule(_) => "rule has no condition or no result",
+            BuildError::UnknownInput(_) => "input doesn't exist",
+            BuildError::UnknownOutput(_) => "output doesn't exist",
+            BuildError::UnknownSet { .. } => "set doesn't exist",
+            BuildError::InvalidRules(_) => "rules can't be combined",
+        }
+    }
+}
+
+/// Collects the domain and the sets of a universe.
+pub struct UniverseBuilder {
+    /// Name of the universe.
+    name: String,
+    /// Domain of the universe.
+    domain: Vec<f32>,
+    /// Sets with their membership functions.
+    terms: Vec<(String, Box<MembershipFunction>)>,
+}
+
+impl UniverseBuilder {
+    /// Constructs the builder of the universe with given name.
+    fn new(name: &str) -> UniverseBuilder {
+        UniverseBuilder {
+            name: name.to_string(),
+            domain: Vec::new(),
+            terms: Vec::new(),
+        }
+    }
+
+    /// Sets the domain to `points` evenly spaced points from `min` to `max` inclusive.
+    pub fn range(mut self, min: f32, max: f32, points: usize) -> UniverseBuilder {
+        self.domain = match points {
+            0 => Vec::new(),
+            1 => vec![min],
+            _ => {
+                let step = (max - min) / (points - 1) as f32;
+                (0..points).map(|i| min + step * i as f32).collect()
+            }
+        };
+        self
+    }
+
+    /// Sets the domain.
+    pub fn domain(mut self, domain: Vec<f32>) -> UniverseBuilder {
+        self.domain = domain;
+        self
+    }
+
+    /// Adds the set with given membership function.
+    pub fn term(mut self, name: &str, membership: Box<MembershipFunction>) -> UniverseBuilder {
+        self.terms.push((name.to_string(), membership));
+        self
+    }
+
+    /// Constructs the universe.
+    fn build(self) -> Result<UniversalSet, BuildError> {
+        if self.domain.is_empty() {
+            return Err(BuildError::NoDomain(self.name));
+        }
+        let mut universe = UniversalSet::new(self.name.clone());
+        universe.set_domain(self.domain);
+        for (set, membership) in self.terms {
+            match universe.create_set(set, membership) {
+                Ok(()) => {}
+                Err(SetError::DuplicateSet(set)) => {
+                    return Err(BuildError::DuplicateSet {
+                        universe: self.name,
+                        set: set,
+                    })
+                }
+                Err(error) => {
+                    return Err(BuildError::InvalidSet {
+                        universe: self.name,
+                        error: error,
+                    })
+                }
+            }
+        }
+        Ok(universe)
+    }
+}
+
+/// Collects the condition and the result of a rule.
+#[derive(Default)]
+pub struct RuleBuilder {
+    /// Condition of the rule.
+    condition: Option<Box<Expression>>,
+    /// Universe and set of the rule's result.
+    result: Option<(String, String)>,
+}
+
+impl RuleBuilder {
+    /// Sets the condition of the rule.
+    pub fn when<E: 'static + Expression>(mut self, condition: E) -> RuleBuilder {
+        self.condition = Some(Box::new(condition));
+        self
+    }
+
+    /// Sets the result of the rule.
+    pub fn then(mut self, universe: &str, set: &str) -> RuleBuilder {
+        self.result = Some((universe.to_string(), set.to_string()));
+        self
+    }
+}
+
+/// Builder of `InferenceMachine`.
+///
+/// Inputs are bound to the variables with the same name.
+#[derive(Default)]
+pub struct SystemBuilder {
+    /// Universes of the input variables.
+    inputs: Vec<UniverseBuilder>,
+    /// Universes of the rules' results.
+    outputs: Vec<UniverseBuilder>,
+    /// Rules.
+    rules: Vec<RuleBuilder>,
+    /// Options, `InferenceOptions::mamdani()` if not set.
+    options: Option<InferenceOptions>,
+}
+
+impl SystemBuilder {
+    /// Constructs the empty builder.
+    pub fn new() -> SystemBuilder {
+        SystemBuilder::default()
+    }
+
+    /// Adds the input universe, built by `build`.
+    pub fn input<F>(mut self, name: &str, build: F) -> SystemBuilder
+        where F: FnOnce(UniverseBuilder) -> UniverseBuilder
+    {
+        self.inputs.push(build(UniverseBuilder::new(name)));
+        self
+    }
+
+    /// Adds the output universe, built by `build`.
+    pub fn output<F>(mut self, name: &str, build: F) -> SystemBuilder
+        where F: FnOnce(UniverseBuilder) -> UniverseBuilder
+    {
+        self.outputs.push(build(UniverseBuilder::new(name)));
+        self
+    }
+
+    /// Adds the rule, built by `build`.
+    pub fn rule<F>(mut self, build: F) -> SystemBuilder
+        where F: FnOnce(RuleBuilder) -> RuleBuilder
+    {
+        self.rules.push(build(RuleBuilder::default()));
+        self
+    }
+
+    /// Sets the inference options.
+    pub fn options(mut self, options: InferenceOptions) -> SystemBuilder {
+        self.options = Some(options);
+        self
+    }
+
+    /// Validates the system and constructs `InferenceMachine`.
+    ///
+    /// Every universe must have a domain, there must be at least one rule,
+    /// and every input, output and set referenced by the rules must exist.
+    /// Names in conditions are checked by evaluating them at the first point
+    /// of every input's domain.
+    pub fn build(self) -> Result<InferenceMachine, BuildError> {
+        let mut inputs = HashMap::new();
+        for input in self.inputs {
+            let name = input.name.clone();
+            if inputs.contains_key(&name) {
+                return Err(BuildError::DuplicateUniverse(name));
+            }
+            inputs.insert(name, Rc::new(input.build()?));
+        }
+        let mut outputs = HashMap::new();
+        for output in self.outputs {
+            let name = output.name.clone();
+            if inputs.contains_key(&name) || outputs.contains_key(&name) {
+                return Err(BuildError::DuplicateUniverse(name));
+            }
+            outputs.insert(name, Rc::new(output.build()?));
+        }
+        if self.rules.is_empty() {
+            return Err(BuildError::NoRules);
+        }
+
+        let options = self.options.unwrap_or_else(InferenceOptions::mamdani);
+        let mut rules = Vec::new();
+        {
+            // Every input has a value, so a condition fails only on unknown names.
+            let values = inputs.iter()
+                               .map(|(name, input)| (name.clone(), input.domain()[0]))
+                               .collect();
+            let bindings = HashMap::new();
+            let fuzzy_values = HashMap::new();
+            let context = InferenceContext {
+                values: &values,
+                universes: &inputs,
+                bindings: &bindings,
+                fuzzy_values: &fuzzy_values,
+                options: &options,
+            };
+            for (index, rule) in self.rules.into_iter().enumerate() {
+                let (condition, (universe, set)) = match (rule.condition, rule.result) {
+                    (Some(condition), Some(result)) => (condition, result),
+                    _ => return Err(BuildError::IncompleteRule(index)),
+                };
+                match condition.eval(&context) {
+                    Err(InferenceError::MissingValue(variable)) |
+                    Err(InferenceError::UnboundVariable(variable)) => {
+                        return Err(BuildError::UnknownInput(variable))
+                    }
+                    Err(InferenceError::UnknownSet { universe, set }) => {
+                        return Err(BuildError::UnknownSet {
+                            universe: universe,
+                            set: set,
+                        })
+                    }
+                    _ => {}
+                }
+                let output = outputs.get(&universe)
+                                    .ok_or_else(|| BuildError::UnknownOutput(universe.clone()))?;
+                if !output.sets.contains_key(&set) {
+                    return Err(BuildError::UnknownSet {
+                        universe: universe,
+                        set: set,
+                    });
+                }
+                rules.push(Rule::new(condition, universe, set));
+            }
+        }
+        let rules = RuleSet::new(rules).map_err(BuildError::InvalidRules)?;
+
+        let mut universes = inputs;
+        universes.extend(outputs);
+        Ok(InferenceMachine::new(rules, universes, options))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use functions::MembershipFactory;
+    use rules::{is, And, GreaterThan};
+
+    fn builder() -> SystemBuilder {
+        SystemBuilder::new()
+            .input("temp", |u| {
+                u.range(0.0, 40.0, 41)
+                 .term("cold", MembershipFactory::triangular(-20.0, 0.0, 25.0))
+                 .term("hot", MembershipFactory::triangular(15.0, 40.0, 60.0))
+            })
+            .input("humidity", |u| {
+                u.range(0.0, 1.0, 11)
+                 .term("low", MembershipFactory::triangular(-1.0, 0.0, 1.0))
+            })
+            .output("fan", |u| {
+                u.range(0.0, 10.0, 101)
+                 .term("slow", MembershipFactory::triangular(-5.0, 0.0, 6.0))
+                 .term("fast", MembershipFactory::triangular(4.0, 10.0, 15.0))
+            })
+            .rule(|r| r.when(is("temp", "cold")).then("fan", "slow"))
+    }
+
+    #[test]
+    fn build() {
+        let mut machine = builder()
+                              .rule(|r| {
+                                  r.when(And::new(is("temp", "hot"), is("humidity", "low")))
+                                   .then("fan", "fast")
+                              })
+                              .build()
+                              .unwrap();
+        assert_eq!(machine.rules.len(), 2);
+        assert_eq!(machine.universes["temp"].domain().len(), 41);
+        assert_eq!(machine.universes["fan"].domain()[100], 10.0);
+        machine.set_value("temp", 35.0).unwrap();
+        machine.set_value("humidity", 0.2).unwrap();
+        let result = machine.compute_for("fan").unwrap();
+        assert_eq!(result.dominant_rule, 1);
+        assert!(result.value > 5.0);
+    }
+
+    #[test]
+    fn unknown_names() {
+        let error = builder().rule(|r| r.when(is("pressure", "high")).then("fan", "fast")).build();
+        assert_eq!(error.err(), Some(BuildError::UnknownInput("pressure".to_string())));
+
+        let error = builder().rule(|r| r.when(is("temp", "warm")).then("fan", "fast")).build();
+        assert_eq!(error.err(),
+                   Some(BuildError::UnknownSet {
+                       universe: "temp".to_string(),
+                       set: "warm".to_string(),
+                   }));
+
+        let error = builder().rule(|r| r.when(is("temp", "hot")).then("pump", "fast")).build();
+        assert_eq!(error.err(), Some(BuildError::UnknownOutput("pump".to_string())));
+
+        // Conditions which can't be compiled are checked too.
+        let above = |variable: &str| GreaterThan::new(variable.to_string(), 30.0);
+        let error = builder().rule(|r| r.when(above("pressure")).then("fan", "fast")).build();
+        assert_eq!(error.err(), Some(BuildError::UnknownInput("pressure".to_string())));
+        let error = builder()
+                        .rule(|r| {
+                            r.when(And::new(above("temp"), is("humidity", "high")))
+                             .then("fan", "fast")
+                        })
+                        .build();
+        assert_eq!(error.err(),
+                   Some(BuildError::UnknownSet {
+                       universe: "humidity".to_string(),
+                       set: "high".to_string(),
+                   }));
+    }
+
+    #[test]
+    fn no_domain() {
+        let error = builder()
+                        .input("pressure",
+                               |u| u.term("high", MembershipFactory::sigmoidal(1.0, 0.0)))
+                        .build();
+        assert_eq!(error.err(), Some(BuildError::NoDomain("pressure".to_string())));
+    }
+
+    #[test]
+    fn no_rules() {
+        let error = SystemBuilder::new().input("temp", |u| u.range(0.0, 1.0, 2)).build();
+        assert_eq!(error.err(), Some(BuildError::NoRules));
+        let error = builder().rule(|r| r.then("fan", "fast")).build();
+        assert_eq!(error.err(), Some(BuildError::IncompleteRule(1)));
+    }
+}
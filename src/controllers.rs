@@ -0,0 +1,271 @@
+//! This module contains prebuilt fuzzy controllers.
+//!
+//! `FuzzyController` is the classic controller of the error and its rate of change:
+//! inputs `error` and `delta_error` and output `control` are partitioned symmetrically,
+//! and the rule matrix maps every pair of input terms to an output term.
+use builder::{BuildError, SystemBuilder, UniverseBuilder};
+use functions::MembershipFactory;
+use inference::InferenceMachine;
+use rules::{is, And};
+
+/// Settings of `FuzzyController`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControllerConfig {
+    /// Errors are partitioned over `[-error_range, error_range]`.
+    pub error_range: f32,
+    /// Rates of change of the error are partitioned over `[-delta_range, delta_range]`.
+    pub delta_range: f32,
+    /// Control is partitioned over `[-control_range, control_range]`.
+    pub control_range: f32,
+    /// Number of the error's terms.
+    pub error_terms: usize,
+    /// Number of terms of the error's rate of change.
+    pub delta_terms: usize,
+    /// Number of the control's terms.
+    pub control_terms: usize,
+    /// Number of points in the domain of the control.
+    pub points: usize,
+    /// Rule matrix: `rules[i][j]` is the index of the control's term when the error is in
+    /// its `i`-th term and its rate of change is in its `j`-th term. Terms are counted from
+    /// the most negative one.
+    ///
+    /// By default offsets of the input terms from the middle ones, relative to the numbers of
+    /// terms, are summed into the offset of the control's term, clamped to the outermost terms.
+    pub rules: Option<Vec<Vec<usize>>>,
+    /// If set, the inference gives the rate of change of the control, which is integrated.
+    ///
+    /// Incremental controllers act like PI controllers, removing the steady state error.
+    pub incremental: bool,
+}
+
+impl Default for ControllerConfig {
+    fn default() -> ControllerConfig {
+        ControllerConfig {
+            error_range: 1.0,
+            delta_range: 1.0,
+            control_range: 1.0,
+            error_terms: 5,
+            delta_terms: 5,
+            control_terms: 5,
+            points: 101,
+            rules: None,
+            incremental: false,
+        }
+    }
+}
+
+/// Controller of the error and its rate of change.
+pub struct FuzzyController {
+    machine: InferenceMachine,
+    error_range: f32,
+    delta_range: f32,
+    control_range: f32,
+    incremental: bool,
+    previous_error: Option<f32>,
+    control: f32,
+}
+
+impl FuzzyController {
+    /// Constructs the controller.
+    ///
+    /// Fails with `BuildError::InvalidRules` if a variable has less than two terms,
+    /// or the rule matrix doesn't match the numbers of terms.
+    pub fn new(config: ControllerConfig) -> Result<FuzzyController, BuildError> {
+        let (ne, nd, nc) = (config.error_terms, config.delta_terms, config.control_terms);
+        if ne < 2 || nd < 2 || nc < 2 {
+            return Err(BuildError::InvalidRules("variables must have at least two terms"
+                                                    .to_string()));
+        }
+        let matrix = match config.rules {
+            Some(ref rules) => {
+                if rules.len() != ne || rules.iter().any(|row| row.len() != nd) {
+                    return Err(BuildError::InvalidRules(format!("rule matrix must be {}x{}",
+                                                                ne,
+                                                                nd)));
+                }
+                if rules.iter().any(|row| row.iter().any(|&term| term >= nc)) {
+                    return Err(BuildError::InvalidRules(format!("control has {} terms", nc)));
+                }
+                rules.clone()
+            }
+            None => {
+                let middle = |terms: usize| (terms - 1) as f32 / 2.0;
+                let (me, md, mc) = (middle(ne), middle(nd), middle(nc));
+                (0..ne)
+                    .map(|i| {
+                        (0..nd)
+                            .map(|j| {
+                                let offset = (i as f32 - me) / me + (j as f32 - md) / md;
+                                (mc + offset * mc).round().max(0.0).min(2.0 * mc) as usize
+                            })
+                            .collect()
+                    })
+                    .collect()
+            }
+        };
+
+        let points = config.points;
+        let mut builder = SystemBuilder::new()
+                              .input("error",
+                                     |u| partition(u, config.error_range, ne, points))
+                              .input("delta_error",
+                                     |u| partition(u, config.delta_range, nd, points))
+                              .output("control",
+                                      |u| partition(u, config.control_range, nc, points));
+        for (i, row) in matrix.iter().enumerate() {
+            for (j, &term) in row.iter().enumerate() {
+                builder = builder.rule(|r| {
+                    r.when(And::new(is("error", &label(i)), is("delta_error", &label(j))))
+                     .then("control", &label(term))
+                });
+            }
+        }
+        Ok(FuzzyController {
+            machine: builder.build()?,
+            error_range: config.error_range,
+            delta_range: config.delta_range,
+            control_range: config.control_range,
+            incremental: config.incremental,
+            previous_error: None,
+            control: 0.0,
+        })
+    }
+
+    /// Computes the control for the measurement after `dt` since the previous step.
+    ///
+    /// Inputs are clamped into their ranges. The error's rate of change is 0 on the first step.
+    /// Gives 0 if the inference fails. The step is rejected, giving 0 and keeping the state,
+    /// if `dt` is NaN, infinite or not positive.
+    pub fn step(&mut self, setpoint: f32, measurement: f32, dt: f32) -> f32 {
+        if !dt.is_finite() || dt <= 0.0 {
+            return 0.0;
+        }
+        let error = setpoint - measurement;
+        let delta = match self.previous_error {
+            Some(previous) => (error - previous) / dt,
+            None => 0.0,
+        };
+        self.previous_error = Some(error);
+        let inputs = [("error", error, self.error_range), ("delta_error", delta, self.delta_range)];
+        for &(variable, value, range) in &inputs {
+            if self.machine.set_value(variable, value.max(-range).min(range)).is_err() {
+                return 0.0;
+            }
+        }
+        let output = self.machine.compute_for("control").map(|result| result.value).unwrap_or(0.0);
+        if self.incremental {
+            self.control = (self.control + output * dt).max(-self.control_range)
+                                                       .min(self.control_range);
+        } else {
+            self.control = output;
+        }
+        self.control
+    }
+
+    /// Forgets the previous error and resets the integrated control.
+    pub fn reset(&mut self) {
+        self.previous_error = None;
+        self.control = 0.0;
+    }
+
+    /// Returns the inference machine of the controller.
+    pub fn machine(&self) -> &InferenceMachine {
+        &self.machine
+    }
+}
+
+/// Returns the label of the term with given index.
+fn label(index: usize) -> String {
+    format!("t{}", index)
+}
+
+/// Partitions `[-range, range]` into `terms` triangular sets with feet at neighbouring peaks.
+/// Outermost sets are shoulders.
+fn partition(universe: UniverseBuilder,
+             range: f32,
+             terms: usize,
+             points: usize)
+             -> UniverseBuilder {
+    let step = 2.0 * range / (terms - 1) as f32;
+    let mut universe = universe.range(-range, range, points);
+    for i in 0..terms {
+        let peak = -range + step * i as f32;
+        let membership = if i == 0 {
+            MembershipFactory::trapezoidal(peak - 2.0 * step, peak - step, peak, peak + step)
+        } else if i == terms - 1 {
+            MembershipFactory::trapezoidal(peak - step, peak, peak + step, peak + 2.0 * step)
+        } else {
+            MembershipFactory::triangular(peak - step, peak, peak + step)
+        };
+        universe = universe.term(&label(i), membership);
+    }
+    universe
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Simulates the first-order plant `tau * dy/dt = gain * u - y` for `steps` steps.
+    fn close_loop(controller: &mut FuzzyController, setpoint: f32, gain: f32, steps: usize) -> f32 {
+        let (tau, dt) = (1.0, 0.05);
+        let mut y = 0.0;
+        for _ in 0..steps {
+            let u = controller.step(setpoint, y, dt);
+            y += dt * (gain * u - y) / tau;
+        }
+        y
+    }
+
+    #[test]
+    fn incremental_controller_converges() {
+        let mut controller = FuzzyController::new(ControllerConfig {
+                                 error_range: 2.0,
+                                 delta_range: 4.0,
+                                 control_range: 3.0,
+                                 incremental: true,
+                                 ..ControllerConfig::default()
+                             })
+                                 .unwrap();
+        assert!((close_loop(&mut controller, 1.0, 1.0, 300) - 1.0).abs() < 0.02);
+        controller.reset();
+        assert!((close_loop(&mut controller, -0.5, 2.0, 300) + 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn custom_rules() {
+        // Sign of the control follows the error only.
+        let rules = vec![vec![0, 0, 0], vec![1, 1, 1], vec![2, 2, 2]];
+        let mut controller = FuzzyController::new(ControllerConfig {
+                                 error_terms: 3,
+                                 delta_terms: 3,
+                                 control_terms: 3,
+                                 rules: Some(rules),
+                                 ..ControllerConfig::default()
+                             })
+                                 .unwrap();
+        assert!(controller.step(1.0, 0.0, 0.1) > 0.5);
+        assert!(controller.step(-1.0, 0.0, 0.1) < -0.5);
+        assert!(controller.step(0.0, 0.0, 0.1).abs() < 1e-5);
+
+        let config = ControllerConfig {
+            rules: Some(vec![vec![0; 5]; 4]),
+            ..ControllerConfig::default()
+        };
+        assert!(FuzzyController::new(config).is_err());
+    }
+
+    #[test]
+    fn invalid_dt_is_rejected() {
+        let mut controller = FuzzyController::new(ControllerConfig {
+                                 incremental: true,
+                                 ..ControllerConfig::default()
+                             })
+                                 .unwrap();
+        let control = controller.step(1.0, 0.0, 0.1);
+        for &dt in &[f32::NAN, f32::INFINITY, 0.0, -0.1] {
+            assert_eq!(controller.step(1.0, 0.0, dt), 0.0);
+        }
+        assert!(controller.step(1.0, 0.0, 0.1) > control);
+    }
+}
@@ -0,0 +1,148 @@
+//! This module contains evaluation of the inference machine against recorded data.
+use inference::InferenceMachine;
+use std::collections::HashMap;
+use std::f32;
+use std::fmt;
+
+/// Errors of the machine's outputs against the targets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalReport {
+    /// Output minus target for each record, `None` where the computation failed.
+    pub residuals: Vec<Option<f32>>,
+    /// Number of records with failed computation, they aren't in the metrics.
+    pub failed: usize,
+    /// Root mean squared error.
+    pub rmse: f32,
+    /// Mean absolute error.
+    pub mae: f32,
+    /// Maximum absolute error.
+    pub max_error: f32,
+}
+
+impl fmt::Display for EvalReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "records: {}, failed: {}, rmse: {:.4}, mae: {:.4}, max error: {:.4}",
+               self.residuals.len(),
+               self.failed,
+               self.rmse,
+               self.mae,
+               self.max_error)
+    }
+}
+
+/// Computes the machine's outputs for the `(inputs, target)` records and their errors.
+///
+/// Records are computed with `InferenceMachine::compute_batch` over the variables of all
+/// records, so a record lacking some of them fails. Values of the machine are restored.
+/// Metrics are NaN if every record failed.
+pub fn evaluate(machine: &mut InferenceMachine,
+                data: &[(HashMap<String, f32>, f32)])
+                -> EvalReport {
+    let mut variables = data.iter()
+                            .flat_map(|record| record.0.keys())
+                            .map(|variable| variable.as_str())
+                            .collect::<Vec<_>>();
+    variables.sort();
+    variables.dedup();
+    let rows = data.iter()
+                   .map(|&(ref inputs, _)| {
+                       variables.iter()
+                                .map(|variable| *inputs.get(*variable).unwrap_or(&f32::NAN))
+                                .collect()
+                   })
+                   .collect::<Vec<_>>();
+    let residuals = machine.compute_batch(&variables, &rows)
+                           .into_iter()
+                           .zip(data)
+                           .map(|(result, &(_, target))| result.ok().map(|r| r.value - target))
+                           .collect::<Vec<_>>();
+
+    let errors = residuals.iter()
+                          .filter_map(|residual| residual.map(f32::abs))
+                          .collect::<Vec<_>>();
+    let count = errors.len() as f32;
+    let squares = errors.iter().fold(0.0, |sum, error| sum + error * error);
+    let sum = errors.iter().fold(0.0, |sum, error| sum + error);
+    EvalReport {
+        failed: residuals.len() - errors.len(),
+        residuals: residuals,
+        rmse: (squares / count).sqrt(),
+        mae: sum / count,
+        max_error: errors.iter()
+                         .cloned()
+                         .fold(if count > 0.0 { 0.0 } else { f32::NAN }, f32::max),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use functions::MembershipFactory;
+    use inference::InferenceOptions;
+    use rules::RuleSet;
+    use set::UniversalSet;
+
+    /// Machine computing the fan speed from the temperature and the records of its outputs.
+    fn machine() -> (InferenceMachine, Vec<(HashMap<String, f32>, f32)>) {
+        let mut temp = UniversalSet::new("temp".to_string());
+        temp.set_domain((0..41).map(|x| x as f32).collect());
+        temp.create_set("cold".to_string(), MembershipFactory::triangular(-40.0, 0.0, 40.0))
+            .unwrap();
+        temp.create_set("hot".to_string(), MembershipFactory::triangular(0.0, 40.0, 80.0))
+            .unwrap();
+        let mut fan = UniversalSet::new("fan".to_string());
+        fan.set_domain((0..101).map(|x| x as f32 / 10.0).collect());
+        fan.create_set("slow".to_string(), MembershipFactory::triangular(-5.0, 0.0, 6.0))
+           .unwrap();
+        fan.create_set("fast".to_string(), MembershipFactory::triangular(4.0, 10.0, 15.0))
+           .unwrap();
+        let mut universes = HashMap::new();
+        universes.insert("temp".to_string(), temp);
+        universes.insert("fan".to_string(), fan);
+        let rules = fuzzy_rules! {
+            if temp is cold => fan is slow;
+            if temp is hot => fan is fast;
+        };
+        let mut machine = InferenceMachine::new(RuleSet::new(rules).unwrap(),
+                                                universes,
+                                                InferenceOptions::mamdani());
+        let data = (0..9)
+                       .map(|i| {
+                           let mut values = HashMap::new();
+                           values.insert("temp".to_string(), i as f32 * 5.0);
+                           machine.update(&values).unwrap();
+                           (values, machine.compute_for("fan").unwrap().value)
+                       })
+                       .collect();
+        machine.values.clear();
+        (machine, data)
+    }
+
+    #[test]
+    fn exact_machine() {
+        let (mut machine, data) = machine();
+        let report = evaluate(&mut machine, &data);
+        assert_eq!(report.failed, 0);
+        assert_eq!(report.residuals.len(), data.len());
+        assert!(report.rmse < 1e-5 && report.mae < 1e-5 && report.max_error < 1e-5);
+        assert!(machine.values.is_empty());
+    }
+
+    #[test]
+    fn biased_machine() {
+        let (mut machine, mut data) = machine();
+        for record in &mut data {
+            record.1 -= 0.5;
+        }
+        data.push((HashMap::new(), 1.0));
+        let report = evaluate(&mut machine, &data);
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.residuals[data.len() - 1], None);
+        assert!((report.mae - 0.5).abs() < 1e-5);
+        assert!((report.rmse - 0.5).abs() < 1e-5);
+        assert!((report.max_error - 0.5).abs() < 1e-5);
+        assert_eq!(format!("{}", report),
+                   "records: 10, failed: 1, rmse: 0.5000, mae: 0.5000, max error: 0.5000");
+    }
+}
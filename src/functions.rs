@@ -2,16 +2,169 @@
 //!
 //! Module contains implementation of membership functions and defuzzification functions.
 //! Also contains factory methods to create most used functions.
+#[cfg(feature = "parallel")]
+extern crate rayon;
 
 use set::Set;
 
+use std::error::Error;
+use std::f32;
+use std::fmt;
+
 /// Used to calculate the membership of the given item.
 /// All membership functions must be this type.
 pub type MembershipFunction = Fn(f32) -> f32;
 
 /// Used to defuzzificate the fuzzy logic inference result.
 /// All defuzzification functions must be this type.
-pub type DefuzzFunc = Fn(&Set) -> f32;
+pub type DefuzzFunc = Fn(&Set) -> Result<f32, DefuzzError>;
+
+/// Used to defuzzificate the results of the rules taken separately, before their union.
+pub type SumsDefuzzFunc = Fn(&[Set]) -> Result<f32, DefuzzError>;
+
+/// Used to defuzzificate the pairs of representative points of the rules' consequents
+/// and firing strengths of the rules, without computing the rules' results.
+pub type WeightedDefuzzFunc = Fn(&[(f32, f32)]) -> Result<f32, DefuzzError>;
+
+/// Error of the defuzzification.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DefuzzError {
+    /// The set has no points with non-zero membership, so it has no crisp value.
+    EmptyOutput,
+}
+
+impl fmt::Display for DefuzzError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DefuzzError::EmptyOutput => write!(f, "set has no points with non-zero membership"),
+        }
+    }
+}
+
+impl Error for DefuzzError {
+    fn description(&self) -> &str {
+        "set has no points with non-zero membership"
+    }
+}
+
+/// Error of the membership function's parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MembershipError {
+    /// Parameter with given name is NaN or infinite.
+    NotFinite(&'static str),
+    /// Parameter `first` must be less than parameter `second` (or equal, if allowed), but it isn't.
+    Unordered {
+        /// Name of the lesser parameter.
+        first: &'static str,
+        /// Name of the greater parameter.
+        second: &'static str,
+    },
+    /// Parameter with given name must be greater than zero.
+    NonPositive(&'static str),
+    /// Breakpoints are less than two, so there is nothing to interpolate.
+    TooFewPoints(usize),
+    /// Breakpoint with given index is not to the right of the previous one.
+    NotIncreasing(usize),
+    /// Membership of the breakpoint with given index is not in [0, 1].
+    OutOfUnitInterval(usize),
+}
+
+impl fmt::Display for MembershipError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MembershipError::NotFinite(name) => {
+                write!(f, "parameter {} is not a finite number", name)
+            }
+            MembershipError::Unordered { first, second } => {
+                write!(f, "parameter {} is not less than {}", first, second)
+            }
+            MembershipError::NonPositive(name) => {
+                write!(f, "parameter {} is not greater than zero", name)
+            }
+            MembershipError::TooFewPoints(count) => {
+                write!(f, "at least two breakpoints are required, got {}", count)
+            }
+            MembershipError::NotIncreasing(index) => {
+                write!(f, "breakpoint {} is not to the right of the previous one", index)
+            }
+            MembershipError::OutOfUnitInterval(index) => {
+                write!(f, "membership of breakpoint {} is not in [0, 1]", index)
+            }
+        }
+    }
+}
+
+impl Error for MembershipError {
+    fn description(&self) -> &str {
+        match *self {
+            MembershipError::NotFinite(_) => "parameter is not a finite number",
+            MembershipError::Unordered { .. } => "parameters are not in order",
+            MembershipError::NonPositive(_) => "parameter is not greater than zero",
+            MembershipError::TooFewPoints(_) => "at least two breakpoints are required",
+            MembershipError::NotIncreasing(_) => "breakpoints are not increasing",
+            MembershipError::OutOfUnitInterval(_) => "membership of breakpoint is not in [0, 1]",
+        }
+    }
+}
+
+/// Checks that the named parameter is finite.
+fn check_finite(name: &'static str, value: f32) -> Result<(), MembershipError> {
+    if !value.is_finite() {
+        return Err(MembershipError::NotFinite(name));
+    }
+    Ok(())
+}
+
+/// Checks that named parameters are finite and non-decreasing.
+fn check_order(parameters: &[(&'static str, f32)]) -> Result<(), MembershipError> {
+    for &(name, value) in parameters {
+        check_finite(name, value)?;
+    }
+    for pair in parameters.windows(2) {
+        if pair[0].1 > pair[1].1 {
+            return Err(MembershipError::Unordered {
+                first: pair[0].0,
+                second: pair[1].0,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Whether the boundary belongs to the interval of `MembershipFactory::rectangular_with`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Boundary {
+    /// The boundary belongs to the interval.
+    Closed,
+    /// The boundary doesn't belong to the interval.
+    Open,
+}
+
+/// Default tolerance of `MembershipFactory::singleton`.
+pub const SINGLETON_EPSILON: f32 = 1e-6;
+
+/// Checks that the named parameter is finite and greater than zero.
+fn check_positive(name: &'static str, value: f32) -> Result<(), MembershipError> {
+    check_finite(name, value)?;
+    if value <= 0.0 {
+        return Err(MembershipError::NonPositive(name));
+    }
+    Ok(())
+}
+
+/// Checks that named parameters are finite and strictly increasing.
+fn check_strict_order(parameters: &[(&'static str, f32)]) -> Result<(), MembershipError> {
+    check_order(parameters)?;
+    for pair in parameters.windows(2) {
+        if pair[0].1 == pair[1].1 {
+            return Err(MembershipError::Unordered {
+                first: pair[0].0,
+                second: pair[1].0,
+            });
+        }
+    }
+    Ok(())
+}
 
 /// Defines methods to create most used membership functions.
 ///
@@ -27,12 +180,15 @@ pub struct MembershipFactory;
 
 impl MembershipFactory {
     /// Creates triangular function.
+    ///
+    /// Degenerate edges are vertical: `triangular(a, a, c)` and `triangular(a, c, c)` are 1
+    /// at the shared point, `triangular(a, a, a)` is the singleton at `a`.
     pub fn triangular(a: f32, b: f32, c: f32) -> Box<MembershipFunction> {
         Box::new(move |x: f32| {
             if a <= x && x <= b {
-                1.0 - (b - x) / (b - a)
+                if a == b { 1.0 } else { 1.0 - (b - x) / (b - a) }
             } else if b <= x && x <= c {
-                1.0 - (x - b) / (c - b)
+                if b == c { 1.0 } else { 1.0 - (x - b) / (c - b) }
             } else {
                 0.0
             }
@@ -58,13 +214,347 @@ impl MembershipFactory {
 
     /// Creates sigmoidal function.
     pub fn sigmoidal(a: f32, c: f32) -> Box<MembershipFunction> {
-        Box::new(move |x: f32| 1.0 / (1.0 + (-1.0 * a * (x - c)).exp()))
+        Box::new(move |x: f32| sigmoid(a, c, x))
+    }
+
+    /// Creates difference of sigmoidal functions: `sigmoidal(a1, c1) - sigmoidal(a2, c2)`.
+    ///
+    /// Gives asymmetric humps, e.g. with positive `a1` and `a2` and `c1 < c2`.
+    /// The difference is clamped into [0, 1].
+    pub fn dsigmoid(a1: f32, c1: f32, a2: f32, c2: f32) -> Box<MembershipFunction> {
+        Box::new(move |x: f32| clamp_height(sigmoid(a1, c1, x) - sigmoid(a2, c2, x)))
     }
 
     /// Creates gaussian function.
+    ///
+    /// `a` is the height of the curve, `b` is its center and `c` is its width.
+    /// Memberships can't exceed 1.0, so `a` is clamped into [0, 1].
     pub fn gaussian(a: f32, b: f32, c: f32) -> Box<MembershipFunction> {
+        let a = clamp_height(a);
         Box::new(move |x: f32| a * (-1.0 * ((x - b).powi(2) / (2.0 * c.powi(2)))).exp())
     }
+
+    /// Same as `triangular`, but fails if parameters are not finite or not in order a ≤ b ≤ c.
+    pub fn try_triangular(a: f32,
+                          b: f32,
+                          c: f32)
+                          -> Result<Box<MembershipFunction>, MembershipError> {
+        check_order(&[("a", a), ("b", b), ("c", c)])?;
+        Ok(MembershipFactory::triangular(a, b, c))
+    }
+
+    /// Same as `trapezoidal`, but fails if parameters are not finite
+    /// or not in order a < b ≤ c < d.
+    pub fn try_trapezoidal(a: f32,
+                           b: f32,
+                           c: f32,
+                           d: f32)
+                           -> Result<Box<MembershipFunction>, MembershipError> {
+        check_strict_order(&[("a", a), ("b", b)])?;
+        check_order(&[("b", b), ("c", c)])?;
+        check_strict_order(&[("c", c), ("d", d)])?;
+        Ok(MembershipFactory::trapezoidal(a, b, c, d))
+    }
+
+    /// Same as `sigmoidal`, but fails if parameters are not finite.
+    pub fn try_sigmoidal(a: f32, c: f32) -> Result<Box<MembershipFunction>, MembershipError> {
+        check_finite("a", a)?;
+        check_finite("c", c)?;
+        Ok(MembershipFactory::sigmoidal(a, c))
+    }
+
+    /// Same as `gaussian`, but fails if parameters are not finite or the width `c` is not positive.
+    pub fn try_gaussian(a: f32,
+                        b: f32,
+                        c: f32)
+                        -> Result<Box<MembershipFunction>, MembershipError> {
+        check_finite("a", a)?;
+        check_finite("b", b)?;
+        check_positive("c", c)?;
+        Ok(MembershipFactory::gaussian(a, b, c))
+    }
+
+    /// Creates generalized bell function: 1 / (1 + |(x - c) / a|^(2b)).
+    ///
+    /// `a` is the half width (memberships at c ± a are 0.5), `b` is the slope and `c` is the center.
+    /// Zero `a` gives the singleton at `c`. Negative `b` is clamped to zero,
+    /// which gives 0.5 everywhere.
+    pub fn bell(a: f32, b: f32, c: f32) -> Box<MembershipFunction> {
+        let b = b.max(0.0);
+        Box::new(move |x: f32| {
+            if a == 0.0 {
+                if x == c { 1.0 } else { 0.0 }
+            } else {
+                1.0 / (1.0 + ((x - c) / a).abs().powf(2.0 * b))
+            }
+        })
+    }
+
+    /// Creates S-shaped function: the smooth spline rising from 0 at `a` to 1 at `b`.
+    ///
+    /// Memberships are 0 up to `a`, 0.5 at the midpoint and 1 from `b`.
+    /// `a == b` gives the step from 0 to 1 at `a`.
+    pub fn s_shaped(a: f32, b: f32) -> Box<MembershipFunction> {
+        Box::new(move |x: f32| s_spline(a, b, x))
+    }
+
+    /// Creates Z-shaped function: the smooth spline falling from 1 at `a` to 0 at `b`.
+    ///
+    /// Mirrors `s_shaped`: `z_shaped(a, b)(x) == 1 - s_shaped(a, b)(x)`.
+    pub fn z_shaped(a: f32, b: f32) -> Box<MembershipFunction> {
+        Box::new(move |x: f32| 1.0 - s_spline(a, b, x))
+    }
+
+    /// Creates pi-shaped function: the smooth analogue of `trapezoidal`.
+    ///
+    /// Rises as `s_shaped(a, b)`, is 1 on [b, c] and falls as `z_shaped(c, d)`.
+    /// Fails if parameters are not finite or not in order a ≤ b ≤ c ≤ d.
+    pub fn pi_shaped(a: f32,
+                     b: f32,
+                     c: f32,
+                     d: f32)
+                     -> Result<Box<MembershipFunction>, MembershipError> {
+        check_order(&[("a", a), ("b", b), ("c", c), ("d", d)])?;
+        Ok(Box::new(move |x: f32| {
+            if x <= b {
+                s_spline(a, b, x)
+            } else if x <= c {
+                1.0
+            } else {
+                1.0 - s_spline(c, d, x)
+            }
+        }))
+    }
+
+    /// Creates singleton function: 1 at `x0` and 0 elsewhere.
+    ///
+    /// Items closer to `x0` than `SINGLETON_EPSILON` are considered equal to it.
+    pub fn singleton(x0: f32) -> Box<MembershipFunction> {
+        MembershipFactory::singleton_with_epsilon(x0, SINGLETON_EPSILON)
+    }
+
+    /// Same as `singleton`, but items closer to `x0` than `epsilon` are considered equal to it.
+    pub fn singleton_with_epsilon(x0: f32, epsilon: f32) -> Box<MembershipFunction> {
+        Box::new(move |x: f32| if (x - x0).abs() <= epsilon { 1.0 } else { 0.0 })
+    }
+
+    /// Creates function linearly interpolating between the breakpoints `(x, membership)`.
+    ///
+    /// Memberships left of the first breakpoint and right of the last one are clamped to theirs.
+    /// Evaluation does the binary search over the breakpoints. Membership of NaN is NaN.
+    /// Fails if there are less than two breakpoints, `x` is not finite or not strictly increasing,
+    /// or membership is not in [0, 1].
+    pub fn piecewise_linear(points: Vec<(f32, f32)>)
+                            -> Result<Box<MembershipFunction>, MembershipError> {
+        if points.len() < 2 {
+            return Err(MembershipError::TooFewPoints(points.len()));
+        }
+        for (index, &(x, mem)) in points.iter().enumerate() {
+            if !x.is_finite() {
+                return Err(MembershipError::NotFinite("points"));
+            }
+            if index > 0 && x <= points[index - 1].0 {
+                return Err(MembershipError::NotIncreasing(index));
+            }
+            if !(0.0 <= mem && mem <= 1.0) {
+                return Err(MembershipError::OutOfUnitInterval(index));
+            }
+        }
+        Ok(Box::new(move |x: f32| {
+            if x.is_nan() {
+                return x;
+            }
+            // Index of the first breakpoint to the right of `x`.
+            let right = match points.binary_search_by(|point| point.0.partial_cmp(&x).unwrap()) {
+                Ok(index) => return points[index].1,
+                Err(index) => index,
+            };
+            if right == 0 {
+                points[0].1
+            } else if right == points.len() {
+                points[right - 1].1
+            } else {
+                let (x0, m0) = points[right - 1];
+                let (x1, m1) = points[right];
+                m0 + (m1 - m0) * (x - x0) / (x1 - x0)
+            }
+        }))
+    }
+
+    /// Creates two-sided gaussian function.
+    ///
+    /// Rises as the gaussian with width `sigma_left` up to `c_left`, is 1 on [c_left, c_right]
+    /// and falls as the gaussian with width `sigma_right` after `c_right`.
+    /// Fails if parameters are not finite, widths are not positive or `c_left > c_right`.
+    pub fn gaussian2(sigma_left: f32,
+                     c_left: f32,
+                     sigma_right: f32,
+                     c_right: f32)
+                     -> Result<Box<MembershipFunction>, MembershipError> {
+        check_positive("sigma_left", sigma_left)?;
+        check_positive("sigma_right", sigma_right)?;
+        check_order(&[("c_left", c_left), ("c_right", c_right)])?;
+        Ok(Box::new(move |x: f32| {
+            if x < c_left {
+                (-1.0 * ((x - c_left).powi(2) / (2.0 * sigma_left.powi(2)))).exp()
+            } else if x <= c_right {
+                1.0
+            } else {
+                (-1.0 * ((x - c_right).powi(2) / (2.0 * sigma_right.powi(2)))).exp()
+            }
+        }))
+    }
+
+    /// Creates raised cosine function: 0.5 * (1 + cos(pi * (x - center) / width))
+    /// on [center - width, center + width] and 0 outside.
+    ///
+    /// Fails if parameters are not finite or `width` is not positive.
+    pub fn cosine(center: f32, width: f32) -> Result<Box<MembershipFunction>, MembershipError> {
+        check_finite("center", center)?;
+        check_positive("width", width)?;
+        Ok(Box::new(move |x: f32| {
+            let offset = (x - center).abs();
+            if offset >= width {
+                0.0
+            } else {
+                0.5 * (1.0 + (f32::consts::PI * offset / width).cos())
+            }
+        }))
+    }
+
+    /// Creates left shoulder function: 1 up to `a`, falls linearly to 0 at `b` and stays 0.
+    ///
+    /// Fails if parameters are not finite or `a` is not less than `b`.
+    pub fn left_shoulder(a: f32, b: f32) -> Result<Box<MembershipFunction>, MembershipError> {
+        check_strict_order(&[("a", a), ("b", b)])?;
+        Ok(Box::new(move |x: f32| {
+            if x <= a {
+                1.0
+            } else if x < b {
+                (b - x) / (b - a)
+            } else {
+                0.0
+            }
+        }))
+    }
+
+    /// Creates right shoulder function: 0 up to `a`, rises linearly to 1 at `b` and stays 1.
+    ///
+    /// Fails if parameters are not finite or `a` is not less than `b`.
+    pub fn right_shoulder(a: f32, b: f32) -> Result<Box<MembershipFunction>, MembershipError> {
+        check_strict_order(&[("a", a), ("b", b)])?;
+        Ok(Box::new(move |x: f32| {
+            if x <= a {
+                0.0
+            } else if x < b {
+                (x - a) / (b - a)
+            } else {
+                1.0
+            }
+        }))
+    }
+
+    /// Creates crisp rectangular function: 1 on [low, high] and 0 outside.
+    ///
+    /// Fails if parameters are not finite or `low > high`.
+    pub fn rectangular(low: f32, high: f32) -> Result<Box<MembershipFunction>, MembershipError> {
+        MembershipFactory::rectangular_with(low, Boundary::Closed, high, Boundary::Closed)
+    }
+
+    /// Same as `rectangular`, but boundaries are either closed or open.
+    ///
+    /// Sets of crisp terms usually should cache zeros too, see `ZeroPolicy::Keep`.
+    pub fn rectangular_with(low: f32,
+                            low_boundary: Boundary,
+                            high: f32,
+                            high_boundary: Boundary)
+                            -> Result<Box<MembershipFunction>, MembershipError> {
+        check_order(&[("low", low), ("high", high)])?;
+        Ok(Box::new(move |x: f32| {
+            let above = match low_boundary {
+                Boundary::Closed => x >= low,
+                Boundary::Open => x > low,
+            };
+            let below = match high_boundary {
+                Boundary::Closed => x <= high,
+                Boundary::Open => x < high,
+            };
+            if above && below { 1.0 } else { 0.0 }
+        }))
+    }
+
+    /// Creates sigmoidal function which uses the approximated `exp`.
+    ///
+    /// See `fast_exp` for the accuracy of the approximation.
+    #[cfg(feature = "fast_math")]
+    pub fn sigmoidal_fast(a: f32, c: f32) -> Box<MembershipFunction> {
+        Box::new(move |x: f32| 1.0 / (1.0 + fast_exp(-1.0 * a * (x - c))))
+    }
+
+    /// Creates gaussian function which uses the approximated `exp`.
+    ///
+    /// See `fast_exp` for the accuracy of the approximation.
+    /// Parameters are the same as in `gaussian`.
+    #[cfg(feature = "fast_math")]
+    pub fn gaussian_fast(a: f32, b: f32, c: f32) -> Box<MembershipFunction> {
+        let a = clamp_height(a);
+        Box::new(move |x: f32| a * fast_exp(-1.0 * ((x - b).powi(2) / (2.0 * c.powi(2)))))
+    }
+}
+
+/// Value of the S-shaped spline from 0 at `a` to 1 at `b` in `x`.
+fn s_spline(a: f32, b: f32, x: f32) -> f32 {
+    if x <= a {
+        if x < a || a < b { 0.0 } else { 1.0 }
+    } else if x >= b {
+        1.0
+    } else if x <= (a + b) / 2.0 {
+        2.0 * ((x - a) / (b - a)).powi(2)
+    } else {
+        1.0 - 2.0 * ((b - x) / (b - a)).powi(2)
+    }
+}
+
+/// Value of the sigmoidal function with slope `a` and midpoint `c` in `x`.
+fn sigmoid(a: f32, c: f32, x: f32) -> f32 {
+    1.0 / (1.0 + (-1.0 * a * (x - c)).exp())
+}
+
+/// Clamps the height of a membership function into [0, 1].
+fn clamp_height(height: f32) -> f32 {
+    height.max(0.0).min(1.0)
+}
+
+/// Approximates `x.exp()`.
+///
+/// The argument is split as `x * log2(e) = n + f` with integer `n` and `|f| <= 0.5`,
+/// `2^f` is evaluated with a degree 5 polynomial and `2^n` is put into the exponent bits.
+/// Maximum relative error is below `1e-5` for `x` in `[-87, 88]`.
+/// Smaller arguments give `0.0`, larger ones give infinity.
+///
+/// Pays off on targets with a slow software `exp`. Where the platform's libm is already
+/// tuned (e.g. glibc on x86_64) `f32::exp` can be as fast or faster, so measure with
+/// `cargo bench --features fast_math` first.
+#[cfg(feature = "fast_math")]
+pub fn fast_exp(x: f32) -> f32 {
+    use std::f32;
+
+    if x < -87.0 {
+        return 0.0;
+    }
+    if x > 88.0 {
+        return f32::INFINITY;
+    }
+    let t = x * f32::consts::LOG2_E;
+    // Rounds to the nearest integer with a plain conversion, `round` is a library call.
+    let n = (t + 0.5f32.copysign(t)) as i32;
+    let f = t - n as f32;
+    let p = 1.0 +
+            f *
+            (f32::consts::LN_2 +
+             f * (0.240_226_5 + f * (0.055_504_11 + f * (0.009_618_129 + f * 0.001_333_355))));
+    let scale = f32::from_bits(((n + 127) as u32) << 23);
+    p * scale
 }
 
 /// Defines methods to create most used defuzzification functions.
@@ -76,29 +566,443 @@ impl MembershipFactory {
 /// use fuzzy_logic::functions::{DefuzzFactory, MembershipFactory};
 /// use fuzzy_logic::set::Set;
 ///
-/// let mem = MembershipFactory::triangular(-15.0, -15.0, 22.0);
+/// let mem = MembershipFactory::triangular(-15.0, 0.0, 15.0);
 /// let df = DefuzzFactory::center_of_mass();
 /// let set = Set::new_with_mem("Test".to_string(), mem);
-/// df(&set);
+/// for x in -15..16 {
+///     set.check(x as f32);
+/// }
+/// assert!(df(&set).unwrap().abs() < 1e-6);
 /// ```
 pub struct DefuzzFactory;
 
 impl DefuzzFactory {
     /// Creates function which calculates center of mass.
+    ///
+    /// Fails with `DefuzzError::EmptyOutput` if memberships of all points are zero.
+    ///
+    /// With the `parallel` feature, sets of `PARALLEL_THRESHOLD` points and more
+    /// are integrated on the rayon thread pool. The result is the same as the serial one.
     pub fn center_of_mass() -> Box<DefuzzFunc> {
         Box::new(|s: &Set| {
-            let sum = s.cache.borrow().iter().fold(0.0, |acc, (_, &v)| acc + v);
-            let prod_sum = s.cache.borrow().iter().fold(0.0, |acc, (&k, &v)| acc + k.into_inner() * v);
-            prod_sum / sum
+            let (area, moment) = moments(s);
+            if area > 0.0 {
+                Ok(moment / area)
+            } else {
+                Err(DefuzzError::EmptyOutput)
+            }
+        })
+    }
+
+    /// Creates function which calculates center of mass over `steps` evenly spaced points
+    /// of [min, max], so the result doesn't depend on which items are cached.
+    ///
+    /// Memberships are computed from the membership function with `Set::membership_at`.
+    /// Sets without membership function, e.g. rules' results, are interpolated linearly
+    /// between the nearest cached items, and are zero outside of the cached items.
+    /// Fails with `DefuzzError::EmptyOutput` if memberships of all points are zero.
+    pub fn centroid_sampled(min: f32, max: f32, steps: usize) -> Box<DefuzzFunc> {
+        let points = (0..steps)
+                         .map(|i| {
+                             if steps > 1 {
+                                 min + (max - min) * i as f32 / (steps - 1) as f32
+                             } else {
+                                 min
+                             }
+                         })
+                         .collect::<Vec<_>>();
+        Box::new(move |s: &Set| {
+            let memberships = match s.membership {
+                Some(_) => points.iter().map(|&x| s.membership_at(x)).collect::<Vec<_>>(),
+                None => interpolate(s, &points),
+            };
+            let (area, moment) = block_moments(&points, &memberships);
+            if area > 0.0 {
+                Ok((moment / area) as f32)
+            } else {
+                Err(DefuzzError::EmptyOutput)
+            }
+        })
+    }
+
+    /// Creates function which calculates center of sums of the rules' results.
+    ///
+    /// Same as `center_of_mass` of the sum of the sets,
+    /// so areas where sets overlap are counted several times.
+    /// Fails with `DefuzzError::EmptyOutput` if memberships of all points are zero.
+    pub fn center_of_sums() -> Box<SumsDefuzzFunc> {
+        Box::new(|sets: &[Set]| {
+            let (area, moment) = sets.iter().fold((0.0, 0.0), |(area, moment), set| {
+                let (set_area, set_moment) = moments(set);
+                (area + set_area, moment + set_moment)
+            });
+            if area > 0.0 {
+                Ok(moment / area)
+            } else {
+                Err(DefuzzError::EmptyOutput)
+            }
+        })
+    }
+
+    /// Creates function which calculates the weighted average (height method):
+    /// the mean of the points weighted by the firing strengths.
+    ///
+    /// Fails with `DefuzzError::EmptyOutput` if all strengths are zero.
+    pub fn weighted_average() -> Box<WeightedDefuzzFunc> {
+        Box::new(|points: &[(f32, f32)]| {
+            let (weight, moment) = points.iter().fold((0.0, 0.0), |(weight, moment), &(x, w)| {
+                (weight + w, moment + x * w)
+            });
+            if weight > 0.0 {
+                Ok(moment / weight)
+            } else {
+                Err(DefuzzError::EmptyOutput)
+            }
+        })
+    }
+
+    /// Creates function which calculates the mean of the points with the highest membership.
+    ///
+    /// Memberships closer to the highest than `MAXIMA_EPSILON` are considered equal to it.
+    /// Fails with `DefuzzError::EmptyOutput` if memberships of all points are zero.
+    pub fn mean_of_maxima() -> Box<DefuzzFunc> {
+        Box::new(|s: &Set| {
+            let maxima = maxima(s)?;
+            Ok(maxima.iter().sum::<f32>() / maxima.len() as f32)
+        })
+    }
+
+    /// Creates function which returns the smallest of the points with the highest membership.
+    ///
+    /// Fails like `mean_of_maxima`.
+    pub fn smallest_of_maxima() -> Box<DefuzzFunc> {
+        Box::new(|s: &Set| maxima(s).map(|maxima| maxima[0]))
+    }
+
+    /// Creates function which returns the largest of the points with the highest membership.
+    ///
+    /// Fails like `mean_of_maxima`.
+    pub fn largest_of_maxima() -> Box<DefuzzFunc> {
+        Box::new(|s: &Set| maxima(s).map(|maxima| maxima[maxima.len() - 1]))
+    }
+
+    /// Creates function which returns the point splitting the area of the set into equal halves.
+    ///
+    /// Memberships are discrete weights centered at their points,
+    /// the crossing of the half of the area is interpolated between neighbouring points.
+    /// Fails with `DefuzzError::EmptyOutput` if memberships of all points are zero.
+    pub fn bisector() -> Box<DefuzzFunc> {
+        Box::new(|s: &Set| {
+            let mut points = s.cache
+                              .borrow()
+                              .iter()
+                              .filter(|&(_, &mem)| mem > 0.0)
+                              .map(|(x, &mem)| (x.into_inner(), mem))
+                              .collect::<Vec<_>>();
+            points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+            let half = points.iter().map(|&(_, mem)| mem).sum::<f32>() / 2.0;
+            if half <= 0.0 {
+                return Err(DefuzzError::EmptyOutput);
+            }
+            // Area left of the current point plus the half of its own weight.
+            let mut area = 0.0;
+            let mut previous: Option<(f32, f32)> = None;
+            for &(x, mem) in &points {
+                let position = area + mem / 2.0;
+                if position >= half {
+                    return Ok(match previous {
+                        Some((previous_x, previous_position)) => {
+                            let t = (half - previous_position) / (position - previous_position);
+                            previous_x + t * (x - previous_x)
+                        }
+                        None => x,
+                    });
+                }
+                previous = Some((x, position));
+                area += mem;
+            }
+            Ok(points[points.len() - 1].0)
         })
     }
 }
 
+/// Interpolates cached memberships of the set linearly at `points`.
+///
+/// Memberships outside of the cached items are zero.
+fn interpolate(set: &Set, points: &[f32]) -> Vec<f32> {
+    let mut cached = set.cache
+                        .borrow()
+                        .iter()
+                        .map(|(x, &mem)| (x.into_inner(), mem))
+                        .collect::<Vec<_>>();
+    cached.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    points.iter()
+          .map(|&x| {
+              match cached.binary_search_by(|item| item.0.partial_cmp(&x).unwrap()) {
+                  Ok(index) => cached[index].1,
+                  Err(index) if index == 0 || index == cached.len() => 0.0,
+                  Err(index) => {
+                      let (x0, m0) = cached[index - 1];
+                      let (x1, m1) = cached[index];
+                      m0 + (m1 - m0) * (x - x0) / (x1 - x0)
+                  }
+              }
+          })
+          .collect()
+}
+
+/// Tolerance of the highest membership in `DefuzzFactory::mean_of_maxima` and similar.
+pub const MAXIMA_EPSILON: f32 = 1e-6;
+
+/// Returns the sorted points with the highest membership.
+///
+/// Fails with `DefuzzError::EmptyOutput` if memberships of all points are zero.
+fn maxima(set: &Set) -> Result<Vec<f32>, DefuzzError> {
+    let cache = set.cache.borrow();
+    let height = cache.values().fold(0.0, |height: f32, &mem| height.max(mem));
+    if height <= 0.0 {
+        return Err(DefuzzError::EmptyOutput);
+    }
+    let mut maxima = cache.iter()
+                          .filter(|&(_, &mem)| mem >= height - MAXIMA_EPSILON)
+                          .map(|(x, _)| x.into_inner())
+                          .collect::<Vec<_>>();
+    maxima.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    Ok(maxima)
+}
+
+/// Number of points integrated as one block.
+const BLOCK: usize = 256;
+
+/// Number of points from which the integration is done in parallel.
+#[cfg(feature = "parallel")]
+pub const PARALLEL_THRESHOLD: usize = 16384;
+
+/// Compensated (Kahan) sum in double precision.
+///
+/// The error doesn't grow with the number of terms and is far below the precision of `f32`,
+/// so the rounded result doesn't depend on the order of the terms in practice.
+#[derive(Clone, Copy, Default)]
+struct KahanSum {
+    sum: f64,
+    /// Low-order bits lost by the last additions, negated.
+    compensation: f64,
+}
+
+impl KahanSum {
+    fn add(&mut self, value: f64) {
+        let y = value - self.compensation;
+        let t = self.sum + y;
+        self.compensation = (t - self.sum) - y;
+        self.sum = t;
+    }
+
+    fn value(&self) -> f64 {
+        self.sum - self.compensation
+    }
+}
+
+/// Returns the sum of memberships and the sum of moments of the block.
+///
+/// Accumulates `LANES` independent sums so the loop can be vectorized.
+fn block_moments(keys: &[f32], values: &[f32]) -> (f64, f64) {
+    const LANES: usize = 8;
+    let mut area = [KahanSum::default(); LANES];
+    let mut moment = [KahanSum::default(); LANES];
+    let mut key_chunks = keys.chunks_exact(LANES);
+    let mut value_chunks = values.chunks_exact(LANES);
+    for (k, v) in (&mut key_chunks).zip(&mut value_chunks) {
+        for i in 0..LANES {
+            area[i].add(v[i] as f64);
+            moment[i].add(k[i] as f64 * v[i] as f64);
+        }
+    }
+    let mut area_sum = KahanSum::default();
+    let mut moment_sum = KahanSum::default();
+    for i in 0..LANES {
+        area_sum.add(area[i].value());
+        moment_sum.add(moment[i].value());
+    }
+    for (&k, &v) in key_chunks.remainder().iter().zip(value_chunks.remainder()) {
+        area_sum.add(v as f64);
+        moment_sum.add(k as f64 * v as f64);
+    }
+    (area_sum.value(), moment_sum.value())
+}
+
+/// Returns the area and the moment of the set's cache.
+fn moments(set: &Set) -> (f32, f32) {
+    #[cfg(feature = "parallel")]
+    {
+        if set.cache.borrow().len() >= PARALLEL_THRESHOLD {
+            return moments_parallel(set);
+        }
+    }
+    moments_serial(set)
+}
+
+/// Integrates the cache block by block, buffering each block on the stack.
+fn moments_serial(set: &Set) -> (f32, f32) {
+    let mut keys = [0.0f32; BLOCK];
+    let mut values = [0.0f32; BLOCK];
+    let mut len = 0;
+    let mut area = KahanSum::default();
+    let mut moment = KahanSum::default();
+    for (k, &v) in set.cache.borrow().iter() {
+        keys[len] = k.into_inner();
+        values[len] = v;
+        len += 1;
+        if len == BLOCK {
+            let (a, m) = block_moments(&keys, &values);
+            area.add(a);
+            moment.add(m);
+            len = 0;
+        }
+    }
+    let (a, m) = block_moments(&keys[..len], &values[..len]);
+    area.add(a);
+    moment.add(m);
+    (area.value() as f32, moment.value() as f32)
+}
+
+/// Integrates the blocks in parallel.
+///
+/// Blocks and their partial sums are combined in the same order as in `moments_serial`,
+/// so both give the same result.
+#[cfg(feature = "parallel")]
+fn moments_parallel(set: &Set) -> (f32, f32) {
+    use self::rayon::prelude::*;
+
+    let (keys, values): (Vec<f32>, Vec<f32>) = set.cache
+                                                  .borrow()
+                                                  .iter()
+                                                  .map(|(k, &v)| (k.into_inner(), v))
+                                                  .unzip();
+    let blocks = keys.par_chunks(BLOCK)
+                     .zip(values.par_chunks(BLOCK))
+                     .map(|(k, v)| block_moments(k, v))
+                     .collect::<Vec<_>>();
+    let mut area = KahanSum::default();
+    let mut moment = KahanSum::default();
+    for &(a, m) in &blocks {
+        area.add(a);
+        moment.add(m);
+    }
+    (area.value() as f32, moment.value() as f32)
+}
+
 #[cfg(test)]
 mod test {
+    extern crate ordered_float;
+
     use std::f32;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use self::ordered_float::OrderedFloat;
+    use set::ZeroPolicy;
     use super::*;
 
+    #[cfg(feature = "fast_math")]
+    #[test]
+    fn fast_exp_accuracy() {
+        for i in -8700..8800 {
+            let x = i as f32 / 100.0;
+            let precise = x.exp();
+            let relative = (fast_exp(x) - precise).abs() / precise;
+            assert!(relative < 1e-5, "exp({}) relative error {}", x, relative);
+        }
+    }
+
+    #[cfg(feature = "fast_math")]
+    #[test]
+    fn fast_factories() {
+        let gaussian = MembershipFactory::gaussian(1.0, 5.0, 2.0);
+        let gaussian_fast = MembershipFactory::gaussian_fast(1.0, 5.0, 2.0);
+        let sigmoidal = MembershipFactory::sigmoidal(2.0, 5.0);
+        let sigmoidal_fast = MembershipFactory::sigmoidal_fast(2.0, 5.0);
+        for i in -1000..1000 {
+            let x = i as f32 / 50.0;
+            assert!((gaussian(x) - gaussian_fast(x)).abs() <= 1e-5);
+            assert!((sigmoidal(x) - sigmoidal_fast(x)).abs() <= 1e-5);
+        }
+    }
+
+    fn wide_set(points: usize) -> Set {
+        let mem = MembershipFactory::gaussian(1.0, points as f32 / 3.0, points as f32 / 10.0);
+        let set = Set::new_with_mem("Test", mem);
+        for i in 1..points + 1 {
+            set.check(i as f32);
+        }
+        set
+    }
+
+    #[test]
+    fn center_of_mass_blocks() {
+        for &points in &[1, 7, BLOCK, BLOCK + 1, 10000] {
+            let set = wide_set(points);
+            let naive = set.cache.borrow().iter().fold((0.0f64, 0.0f64), |(a, m), (&k, &v)| {
+                (a + v as f64, m + k.into_inner() as f64 * v as f64)
+            });
+            let expected = (naive.1 / naive.0) as f32;
+            let actual = DefuzzFactory::center_of_mass()(&set).unwrap();
+            assert!(((actual - expected) / expected).abs() <= 1e-5);
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn center_of_mass_parallel() {
+        for &points in &[PARALLEL_THRESHOLD, PARALLEL_THRESHOLD + 3, 100000] {
+            let set = wide_set(points);
+            assert_eq!(moments_parallel(&set), moments_serial(&set));
+        }
+    }
+
+    #[test]
+    fn center_of_mass_empty() {
+        let df = DefuzzFactory::center_of_mass();
+        let empty = Set::new_with_domain("Empty", RefCell::new(HashMap::new()));
+        assert_eq!(df(&empty), Err(DefuzzError::EmptyOutput));
+        let zeros = (0..10).map(|x| (OrderedFloat(x as f32), 0.0)).collect();
+        let zeros = Set::new_with_domain("Zeros", RefCell::new(zeros));
+        assert_eq!(df(&zeros), Err(DefuzzError::EmptyOutput));
+    }
+
+    #[test]
+    fn gaussian_height() {
+        let f = MembershipFactory::gaussian(2.0, 0.0, 1.0);
+        assert_eq!(f(0.0), 1.0);
+        let f = MembershipFactory::gaussian(-1.0, 0.0, 1.0);
+        assert_eq!(f(0.0), 0.0);
+        let f = MembershipFactory::gaussian(0.5, 0.0, 1.0);
+        assert_eq!(f(0.0), 0.5);
+    }
+
+    #[test]
+    fn factories_in_unit_interval() {
+        let functions = vec![MembershipFactory::triangular(-1.0, 0.0, 1.0),
+                             MembershipFactory::trapezoidal(-2.0, -1.0, 1.0, 2.0),
+                             MembershipFactory::sigmoidal(5.0, 0.0),
+                             MembershipFactory::sigmoidal(-5.0, 0.0),
+                             MembershipFactory::gaussian(2.0, 0.0, 1.0),
+                             MembershipFactory::gaussian(1.0, 0.0, 0.01),
+                             MembershipFactory::bell(2.0, 3.0, 0.0),
+                             MembershipFactory::bell(0.0, 3.0, 0.0),
+                             MembershipFactory::s_shaped(-1.0, 3.0),
+                             MembershipFactory::z_shaped(-1.0, 3.0),
+                             MembershipFactory::pi_shaped(-3.0, -1.0, 1.0, 3.0).unwrap(),
+                             MembershipFactory::dsigmoid(5.0, -1.0, 5.0, 1.0),
+                             MembershipFactory::dsigmoid(5.0, 1.0, 5.0, -1.0)];
+        let mut points = (-1000..1000).map(|i| i as f32 / 100.0).collect::<Vec<_>>();
+        points.extend_from_slice(&[f32::MIN, -1e30, 1e30, f32::MAX]);
+        for f in &functions {
+            for &x in &points {
+                let mem = f(x);
+                assert!(0.0 <= mem && mem <= 1.0, "membership {} at {}", mem, x);
+            }
+        }
+    }
+
     #[test]
     fn sigmoidal() {
         let steepness = 2.0;
@@ -109,4 +1013,405 @@ mod test {
             assert!(diff <= f32::EPSILON);
         }
     }
+
+    #[test]
+    fn bell() {
+        let f = MembershipFactory::bell(2.0, 3.0, 5.0);
+        assert_eq!(f(5.0), 1.0);
+        assert_eq!(f(3.0), 0.5);
+        assert_eq!(f(7.0), 0.5);
+        let centered = MembershipFactory::bell(2.0, 3.0, 0.0);
+        for i in 0..100 {
+            let x = i as f32 / 10.0;
+            assert_eq!(centered(-x), centered(x));
+        }
+        assert!(f(6.9) > 0.5 && f(7.1) < 0.5);
+
+        let singleton = MembershipFactory::bell(0.0, 3.0, 5.0);
+        assert_eq!(singleton(5.0), 1.0);
+        assert_eq!(singleton(5.1), 0.0);
+        let flat = MembershipFactory::bell(2.0, -1.0, 5.0);
+        assert_eq!(flat(5.0), 0.5);
+        assert_eq!(flat(100.0), 0.5);
+    }
+
+    #[test]
+    fn s_and_z_shaped() {
+        let s = MembershipFactory::s_shaped(1.0, 5.0);
+        let z = MembershipFactory::z_shaped(1.0, 5.0);
+        assert_eq!(s(3.0), 0.5);
+        assert_eq!(z(3.0), 0.5);
+        assert_eq!((s(1.0), s(5.0)), (0.0, 1.0));
+        assert_eq!((z(1.0), z(5.0)), (1.0, 0.0));
+        assert_eq!((s(-10.0), s(10.0)), (0.0, 1.0));
+        assert_eq!((z(-10.0), z(10.0)), (1.0, 0.0));
+        let mut previous = 0.0;
+        for i in 0..41 {
+            let x = 1.0 + i as f32 / 10.0;
+            assert!(s(x) >= previous);
+            assert!((s(x) + z(x) - 1.0).abs() <= f32::EPSILON);
+            previous = s(x);
+        }
+
+        let step = MembershipFactory::s_shaped(2.0, 2.0);
+        assert_eq!((step(1.9), step(2.0), step(2.1)), (0.0, 1.0, 1.0));
+        let step = MembershipFactory::z_shaped(2.0, 2.0);
+        assert_eq!((step(1.9), step(2.0), step(2.1)), (1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn pi_shaped() {
+        let f = MembershipFactory::pi_shaped(0.0, 2.0, 4.0, 8.0).unwrap();
+        let s = MembershipFactory::s_shaped(0.0, 2.0);
+        let z = MembershipFactory::z_shaped(4.0, 8.0);
+        for i in -20..101 {
+            let x = i as f32 / 10.0;
+            let mem = f(x);
+            assert!(0.0 <= mem && mem <= 1.0, "membership {} at {}", mem, x);
+            let expected = if x <= 2.0 {
+                s(x)
+            } else if x <= 4.0 {
+                1.0
+            } else {
+                z(x)
+            };
+            assert_eq!(mem, expected);
+        }
+        assert_eq!((f(0.0), f(1.0), f(3.0), f(6.0), f(8.0)), (0.0, 0.5, 1.0, 0.5, 0.0));
+
+        let peak = MembershipFactory::pi_shaped(0.0, 2.0, 2.0, 4.0).unwrap();
+        assert_eq!((peak(1.0), peak(2.0), peak(3.0)), (0.5, 1.0, 0.5));
+
+        assert_eq!(MembershipFactory::pi_shaped(0.0, 3.0, 2.0, 4.0).err(),
+                   Some(MembershipError::Unordered {
+                       first: "b",
+                       second: "c",
+                   }));
+        assert_eq!(MembershipFactory::pi_shaped(0.0, 2.0, 3.0, f32::NAN).err(),
+                   Some(MembershipError::NotFinite("d")));
+    }
+
+    #[test]
+    fn singleton() {
+        let f = MembershipFactory::singleton(2.5);
+        assert_eq!(f(2.5), 1.0);
+        assert_eq!(f(2.5 + SINGLETON_EPSILON / 2.0), 1.0);
+        assert_eq!(f(2.6), 0.0);
+        let f = MembershipFactory::singleton_with_epsilon(2.5, 0.2);
+        assert_eq!(f(2.6), 1.0);
+        assert_eq!(f(2.8), 0.0);
+
+        let set = Set::new_with_mem("Singleton", MembershipFactory::singleton(2.5));
+        for i in 0..101 {
+            set.check(i as f32 / 10.0);
+        }
+        // Zero memberships are not cached, so only the singleton itself is.
+        assert_eq!(set.cache.borrow().len(), 1);
+        assert_eq!(DefuzzFactory::center_of_mass()(&set), Ok(2.5));
+    }
+
+    #[test]
+    fn piecewise_linear() {
+        let points = vec![(0.0, 0.2), (1.0, 1.0), (3.0, 0.0), (4.0, 0.5)];
+        let f = MembershipFactory::piecewise_linear(points.clone()).unwrap();
+        for &(x, mem) in &points {
+            assert_eq!(f(x), mem);
+        }
+        assert!((f(0.5) - 0.6).abs() < 1e-6);
+        assert!((f(2.5) - 0.25).abs() < 1e-6);
+        assert!((f(3.5) - 0.25).abs() < 1e-6);
+        assert_eq!((f(-10.0), f(10.0)), (0.2, 0.5));
+        assert!(f(f32::NAN).is_nan());
+
+        let many = (0..500).map(|i| (i as f32, (i % 2) as f32)).collect();
+        let f = MembershipFactory::piecewise_linear(many).unwrap();
+        assert_eq!((f(250.0), f(250.25), f(251.0)), (0.0, 0.25, 1.0));
+
+        assert_eq!(MembershipFactory::piecewise_linear(vec![]).err(),
+                   Some(MembershipError::TooFewPoints(0)));
+        assert_eq!(MembershipFactory::piecewise_linear(vec![(1.0, 1.0)]).err(),
+                   Some(MembershipError::TooFewPoints(1)));
+        assert_eq!(MembershipFactory::piecewise_linear(vec![(0.0, 0.0), (0.0, 1.0)]).err(),
+                   Some(MembershipError::NotIncreasing(1)));
+        assert_eq!(MembershipFactory::piecewise_linear(vec![(0.0, 0.0), (1.0, 1.5)]).err(),
+                   Some(MembershipError::OutOfUnitInterval(1)));
+        assert_eq!(MembershipFactory::piecewise_linear(vec![(0.0, f32::NAN), (1.0, 1.0)]).err(),
+                   Some(MembershipError::OutOfUnitInterval(0)));
+        assert_eq!(MembershipFactory::piecewise_linear(vec![(0.0, 0.0), (f32::INFINITY, 1.0)])
+                       .err(),
+                   Some(MembershipError::NotFinite("points")));
+    }
+
+    #[test]
+    fn dsigmoid() {
+        let f = MembershipFactory::dsigmoid(4.0, 2.0, 4.0, 6.0);
+        let peak = (0..801).map(|i| i as f32 / 100.0)
+                           .max_by(|&a, &b| f(a).partial_cmp(&f(b)).unwrap())
+                           .unwrap();
+        assert_eq!(peak, 4.0);
+        assert!(f(4.0) < 1.0 && f(4.0) > 0.99);
+        assert!((f(2.0) - 0.5).abs() < 1e-3 && (f(6.0) - 0.5).abs() < 1e-3);
+
+        let skewed = MembershipFactory::dsigmoid(8.0, 2.0, 1.0, 6.0);
+        assert!(skewed(1.0) < skewed(7.0));
+        // The raw difference is negative here.
+        let inverted = MembershipFactory::dsigmoid(1.0, 6.0, 1.0, 2.0);
+        assert_eq!(inverted(4.0), 0.0);
+    }
+
+    #[test]
+    fn gaussian2() {
+        let f = MembershipFactory::gaussian2(1.0, 2.0, 3.0, 4.0).unwrap();
+        assert_eq!((f(2.0), f(3.0), f(4.0)), (1.0, 1.0, 1.0));
+        let left = MembershipFactory::gaussian(1.0, 2.0, 1.0);
+        let right = MembershipFactory::gaussian(1.0, 4.0, 3.0);
+        for i in 0..50 {
+            let offset = i as f32 / 10.0;
+            assert_eq!(f(2.0 - offset), left(2.0 - offset));
+            assert_eq!(f(4.0 + offset), right(4.0 + offset));
+        }
+        assert!(f(0.0) < f(6.0));
+
+        assert_eq!(MembershipFactory::gaussian2(0.0, 2.0, 3.0, 4.0).err(),
+                   Some(MembershipError::NonPositive("sigma_left")));
+        assert_eq!(MembershipFactory::gaussian2(1.0, 2.0, -3.0, 4.0).err(),
+                   Some(MembershipError::NonPositive("sigma_right")));
+        assert_eq!(MembershipFactory::gaussian2(1.0, 5.0, 3.0, 4.0).err(),
+                   Some(MembershipError::Unordered {
+                       first: "c_left",
+                       second: "c_right",
+                   }));
+        assert_eq!(MembershipFactory::gaussian2(1.0, f32::INFINITY, 3.0, 4.0).err(),
+                   Some(MembershipError::NotFinite("c_left")));
+    }
+
+    #[test]
+    fn cosine() {
+        let f = MembershipFactory::cosine(0.0, 2.0).unwrap();
+        assert_eq!(f(0.0), 1.0);
+        assert_eq!((f(-2.0), f(2.0), f(-5.0), f(5.0)), (0.0, 0.0, 0.0, 0.0));
+        assert!((f(1.0) - 0.5).abs() < 1e-6);
+        for i in 0..30 {
+            let x = i as f32 / 10.0;
+            assert_eq!(f(-x), f(x));
+            assert!(0.0 <= f(x) && f(x) <= 1.0);
+        }
+        assert_eq!(MembershipFactory::cosine(0.0, 0.0).err(),
+                   Some(MembershipError::NonPositive("width")));
+        assert_eq!(MembershipFactory::cosine(0.0, -1.0).err(),
+                   Some(MembershipError::NonPositive("width")));
+    }
+
+    #[test]
+    fn shoulders() {
+        let left = MembershipFactory::left_shoulder(1.0, 3.0).unwrap();
+        let right = MembershipFactory::right_shoulder(1.0, 3.0).unwrap();
+        assert_eq!((left(f32::MIN), left(-1e30), left(1.0)), (1.0, 1.0, 1.0));
+        assert_eq!((left(2.0), left(2.5)), (0.5, 0.25));
+        assert_eq!((left(3.0), left(1e30), left(f32::MAX)), (0.0, 0.0, 0.0));
+        assert_eq!((right(f32::MIN), right(1.0)), (0.0, 0.0));
+        assert_eq!((right(2.0), right(2.5)), (0.5, 0.75));
+        assert_eq!((right(3.0), right(f32::MAX)), (1.0, 1.0));
+
+        for &(a, b) in &[(1.0, 1.0), (3.0, 1.0)] {
+            let expected = Some(MembershipError::Unordered {
+                first: "a",
+                second: "b",
+            });
+            assert_eq!(MembershipFactory::left_shoulder(a, b).err(), expected);
+            assert_eq!(MembershipFactory::right_shoulder(a, b).err(), expected);
+        }
+        assert_eq!(MembershipFactory::right_shoulder(f32::NAN, 1.0).err(),
+                   Some(MembershipError::NotFinite("a")));
+    }
+
+    #[test]
+    fn rectangular() {
+        let f = MembershipFactory::rectangular(100.0, 200.0).unwrap();
+        assert_eq!((f(99.9), f(100.0), f(150.0), f(200.0), f(200.1)), (0.0, 1.0, 1.0, 1.0, 0.0));
+        let f = MembershipFactory::rectangular_with(100.0, Boundary::Open, 200.0, Boundary::Closed)
+                    .unwrap();
+        assert_eq!((f(100.0), f(100.1), f(200.0)), (0.0, 1.0, 1.0));
+        let f = MembershipFactory::rectangular_with(100.0, Boundary::Closed, 200.0, Boundary::Open)
+                    .unwrap();
+        assert_eq!((f(100.0), f(199.9), f(200.0)), (1.0, 1.0, 0.0));
+        let point = MembershipFactory::rectangular(5.0, 5.0).unwrap();
+        assert_eq!((point(4.9), point(5.0), point(5.1)), (0.0, 1.0, 0.0));
+        assert_eq!(MembershipFactory::rectangular(2.0, 1.0).err(),
+                   Some(MembershipError::Unordered {
+                       first: "low",
+                       second: "high",
+                   }));
+
+        let set = Set::new_with_mem("Open", MembershipFactory::rectangular(100.0, 200.0).unwrap())
+                      .with_zero_policy(ZeroPolicy::Keep);
+        for _ in 0..5 {
+            assert_eq!(set.check(300.0), 0.0);
+            assert_eq!(set.check(150.0), 1.0);
+        }
+        let stats = set.cache_stats();
+        assert_eq!((stats.misses, stats.hits), (2, 8));
+    }
+
+    #[test]
+    fn validated_factories() {
+        let unordered = |first, second| {
+            Some(MembershipError::Unordered {
+                first: first,
+                second: second,
+            })
+        };
+        assert_eq!(MembershipFactory::try_triangular(5.0, 1.0, 3.0).err(), unordered("a", "b"));
+        assert_eq!(MembershipFactory::try_triangular(1.0, 5.0, 3.0).err(), unordered("b", "c"));
+        assert_eq!(MembershipFactory::try_triangular(1.0, f32::NAN, 3.0).err(),
+                   Some(MembershipError::NotFinite("b")));
+        assert_eq!(MembershipFactory::try_trapezoidal(1.0, 1.0, 2.0, 3.0).err(),
+                   unordered("a", "b"));
+        assert_eq!(MembershipFactory::try_trapezoidal(0.0, 3.0, 2.0, 4.0).err(),
+                   unordered("b", "c"));
+        assert_eq!(MembershipFactory::try_trapezoidal(0.0, 1.0, 2.0, 2.0).err(),
+                   unordered("c", "d"));
+        assert_eq!(MembershipFactory::try_trapezoidal(0.0, 1.0, 2.0, f32::INFINITY).err(),
+                   Some(MembershipError::NotFinite("d")));
+        assert_eq!(MembershipFactory::try_sigmoidal(f32::NAN, 0.0).err(),
+                   Some(MembershipError::NotFinite("a")));
+        assert_eq!(MembershipFactory::try_gaussian(1.0, 0.0, 0.0).err(),
+                   Some(MembershipError::NonPositive("c")));
+        assert_eq!(MembershipFactory::try_gaussian(1.0, f32::NEG_INFINITY, 1.0).err(),
+                   Some(MembershipError::NotFinite("b")));
+
+        let pairs = vec![(MembershipFactory::try_triangular(-1.0, 0.0, 2.0).unwrap(),
+                          MembershipFactory::triangular(-1.0, 0.0, 2.0)),
+                         (MembershipFactory::try_trapezoidal(-2.0, -1.0, -1.0, 2.0).unwrap(),
+                          MembershipFactory::trapezoidal(-2.0, -1.0, -1.0, 2.0)),
+                         (MembershipFactory::try_sigmoidal(2.0, 0.5).unwrap(),
+                          MembershipFactory::sigmoidal(2.0, 0.5)),
+                         (MembershipFactory::try_gaussian(0.8, 0.0, 1.5).unwrap(),
+                          MembershipFactory::gaussian(0.8, 0.0, 1.5))];
+        for (checked, unchecked) in pairs {
+            for i in -30..31 {
+                let x = i as f32 / 10.0;
+                assert_eq!(checked(x), unchecked(x));
+                assert!(!checked(x).is_nan());
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate_triangular() {
+        let f = MembershipFactory::triangular(-15.0, -15.0, 22.0);
+        assert_eq!((f(-15.1), f(-15.0), f(3.5), f(22.0)), (0.0, 1.0, 0.5, 0.0));
+        let f = MembershipFactory::triangular(-1.0, 3.0, 3.0);
+        assert_eq!((f(-1.0), f(1.0), f(3.0), f(3.1)), (0.0, 0.5, 1.0, 0.0));
+        let f = MembershipFactory::triangular(2.0, 2.0, 2.0);
+        assert_eq!((f(1.9), f(2.0), f(2.1)), (0.0, 1.0, 0.0));
+        assert!(MembershipFactory::try_triangular(2.0, 2.0, 2.0).is_ok());
+
+        let set = Set::new_with_mem("Right", MembershipFactory::triangular(0.0, 10.0, 10.0));
+        for x in 0..21 {
+            assert!(!set.check(x as f32).is_nan());
+        }
+        let value = DefuzzFactory::center_of_mass()(&set).unwrap();
+        // Memberships x / 10 at 1..10: sum of x^2 / sum of x.
+        assert!((value - 7.0).abs() < 1e-5);
+    }
+
+    fn set_of(points: &[(f32, f32)]) -> Set {
+        let cache = points.iter().map(|&(x, mem)| (OrderedFloat(x), mem)).collect();
+        Set::new_with_domain("Test", RefCell::new(cache))
+    }
+
+    #[test]
+    fn mean_of_maxima() {
+        let df = DefuzzFactory::mean_of_maxima();
+        let bimodal = set_of(&[(-3.0, 0.2), (-2.0, 0.8), (-1.0, 0.3), (0.0, 0.1), (1.0, 0.5),
+                               (2.0, 0.8 + MAXIMA_EPSILON / 2.0), (3.0, 0.4)]);
+        assert_eq!(df(&bimodal), Ok(0.0));
+        let plateau = set_of(&[(1.0, 0.5), (2.0, 1.0), (3.0, 1.0), (4.0, 1.0), (10.0, 0.9)]);
+        assert_eq!(df(&plateau), Ok(3.0));
+        assert_eq!(df(&set_of(&[(5.0, 0.3)])), Ok(5.0));
+        assert_eq!(df(&set_of(&[])), Err(DefuzzError::EmptyOutput));
+        assert_eq!(df(&set_of(&[(1.0, 0.0), (2.0, 0.0)])), Err(DefuzzError::EmptyOutput));
+    }
+
+    #[test]
+    fn smallest_and_largest_of_maxima() {
+        let plateau = set_of(&[(0.0, 0.2), (1.0, 1.0), (2.0, 1.0), (6.0, 1.0), (7.0, 0.5)]);
+        let som = DefuzzFactory::smallest_of_maxima()(&plateau).unwrap();
+        let mom = DefuzzFactory::mean_of_maxima()(&plateau).unwrap();
+        let lom = DefuzzFactory::largest_of_maxima()(&plateau).unwrap();
+        assert_eq!((som, mom, lom), (1.0, 3.0, 6.0));
+        // Sets with the same memberships give the same results, whatever the order of their caches.
+        for _ in 0..10 {
+            let shuffled = set_of(&[(6.0, 1.0), (7.0, 0.5), (2.0, 1.0), (0.0, 0.2), (1.0, 1.0)]);
+            assert_eq!(DefuzzFactory::smallest_of_maxima()(&shuffled), Ok(som));
+            assert_eq!(DefuzzFactory::largest_of_maxima()(&shuffled), Ok(lom));
+        }
+        assert_eq!(DefuzzFactory::largest_of_maxima()(&set_of(&[])),
+                   Err(DefuzzError::EmptyOutput));
+    }
+
+    #[test]
+    fn bisector() {
+        let df = DefuzzFactory::bisector();
+        let uniform = set_of(&[(0.0, 1.0), (1.0, 1.0), (2.0, 1.0), (3.0, 1.0), (4.0, 1.0)]);
+        assert_eq!(df(&uniform), Ok(2.0));
+        let pair = set_of(&[(0.0, 0.5), (1.0, 0.5)]);
+        assert_eq!(df(&pair), Ok(0.5));
+        // The half of the area is 2, it's crossed between 1.5 at 1 and 2.25 at 2.
+        let skewed = set_of(&[(0.0, 1.0), (1.0, 1.0), (2.0, 0.5), (10.0, 1.0), (11.0, 0.5)]);
+        let bisector = df(&skewed).unwrap();
+        assert!((bisector - 5.0 / 3.0).abs() < 1e-6);
+        assert!(bisector < DefuzzFactory::center_of_mass()(&skewed).unwrap());
+        assert_eq!(df(&set_of(&[(7.0, 0.2)])), Ok(7.0));
+        assert_eq!(df(&set_of(&[])), Err(DefuzzError::EmptyOutput));
+    }
+
+    #[test]
+    fn center_of_sums() {
+        let df = DefuzzFactory::center_of_sums();
+        let wide = set_of(&[(0.0, 0.5), (1.0, 0.5), (2.0, 0.5), (3.0, 0.5)]);
+        let narrow = set_of(&[(2.0, 1.0), (3.0, 1.0)]);
+        let sets = [wide, narrow];
+        // Moments 3 + 5 over areas 2 + 2.
+        assert_eq!(df(&sets), Ok(2.0));
+        let union = set_of(&[(0.0, 0.5), (1.0, 0.5), (2.0, 1.0), (3.0, 1.0)]);
+        assert_eq!(DefuzzFactory::center_of_mass()(&union), Ok(11.0 / 6.0));
+        assert_eq!(df(&[]), Err(DefuzzError::EmptyOutput));
+        assert_eq!(df(&[set_of(&[(1.0, 0.0)])]), Err(DefuzzError::EmptyOutput));
+    }
+
+    #[test]
+    fn weighted_average() {
+        let df = DefuzzFactory::weighted_average();
+        assert_eq!(df(&[(2.0, 0.5), (8.0, 0.25)]), Ok(4.0));
+        assert_eq!(df(&[(2.0, 0.0), (8.0, 0.0)]), Err(DefuzzError::EmptyOutput));
+        assert_eq!(df(&[]), Err(DefuzzError::EmptyOutput));
+    }
+
+    #[test]
+    fn centroid_sampled() {
+        let df = DefuzzFactory::centroid_sampled(0.0, 10.0, 101);
+        let first = Set::new_with_mem("Output", MembershipFactory::triangular(0.0, 2.0, 10.0));
+        let second = first.clone();
+        for x in 0..11 {
+            first.check(x as f32);
+        }
+        for x in 0..4 {
+            second.check(x as f32);
+        }
+        let com = DefuzzFactory::center_of_mass();
+        assert!((com(&first).unwrap() - com(&second).unwrap()).abs() > 1.0);
+        assert_eq!(df(&first), df(&second));
+        assert!((df(&first).unwrap() - 4.0).abs() < 1e-3);
+
+        // Rules' results have no membership function, they are interpolated.
+        let result = set_of(&[(2.0, 0.0), (4.0, 1.0), (6.0, 0.0)]);
+        assert!((df(&result).unwrap() - 4.0).abs() < 1e-5);
+        let result = set_of(&[(2.0, 1.0), (4.0, 1.0)]);
+        assert!((df(&result).unwrap() - 3.0).abs() < 1e-5);
+        assert_eq!(df(&set_of(&[])), Err(DefuzzError::EmptyOutput));
+        assert_eq!(DefuzzFactory::centroid_sampled(0.0, 1.0, 0)(&first),
+                   Err(DefuzzError::EmptyOutput));
+    }
 }
@@ -5,11 +5,131 @@
 //! Fuzzy logic mechanism is implemented in `InferenceMachine`.
 //! User can modify input variables with `update` method and get inference result with `compute` method.
 
-use set::UniversalSet;
-use ops::{LogicOps, SetOps};
-use rules::RuleSet;
-use functions::DefuzzFunc;
+extern crate ordered_float;
+
+use set::{Set, UniversalSet};
+use ops::{AggregationOp, ImplicationOp, LogicOps, MinImplication, MinMaxOps, SetOps, ZadehOps};
+use rules::{Rule, RuleActivation, RuleSet, RuleSetError};
+use variable::{LinguisticVariable, Role};
+use functions::{DefuzzError, DefuzzFactory, DefuzzFunc, MembershipFactory, SumsDefuzzFunc,
+                WeightedDefuzzFunc};
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::error::Error;
+use std::f32;
+use std::fmt;
+use std::rc::Rc;
+
+use self::ordered_float::OrderedFloat;
+
+/// Error of the fuzzy logic inference.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InferenceError {
+    /// Output set of the rules is empty, so there is no crisp result.
+    NoRuleFired,
+    /// Value of the input variable with given name is NaN or infinite.
+    InvalidValue(String),
+    /// Value of the input variable with given name is not set.
+    MissingValue(String),
+    /// Variable with given name is not bound to a universe and there is no universe named after it.
+    UnboundVariable(String),
+    /// Universe with given name doesn't exist.
+    UnknownUniverse(String),
+    /// Rules' results are in the universe, which is not of an output variable.
+    NotAnOutput(String),
+    /// Domain of the universe with given name is empty.
+    EmptyDomain(String),
+    /// Set doesn't exist in the universe.
+    UnknownSet {
+        /// Name of the universe.
+        universe: String,
+        /// Name of the missing set.
+        set: String,
+    },
+}
+
+impl fmt::Display for InferenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InferenceError::NoRuleFired => write!(f, "no rule fired"),
+            InferenceError::InvalidValue(ref variable) => {
+                write!(f, "value of {} is not a finite number", variable)
+            }
+            InferenceError::MissingValue(ref variable) => {
+                write!(f, "value of {} is not set", variable)
+            }
+            InferenceError::UnboundVariable(ref variable) => {
+                write!(f, "variable {} is not bound to a universe", variable)
+            }
+            InferenceError::UnknownUniverse(ref universe) => {
+                write!(f, "universe {} doesn't exist", universe)
+            }
+            InferenceError::NotAnOutput(ref universe) => {
+                write!(f, "universe {} is not of an output variable", universe)
+            }
+            InferenceError::EmptyDomain(ref universe) => {
+                write!(f, "domain of universe {} is empty", universe)
+            }
+            InferenceError::UnknownSet { ref universe, ref set } => {
+                write!(f, "set {} doesn't exist in universe {}", set, universe)
+            }
+        }
+    }
+}
+
+impl Error for InferenceError {
+    fn description(&self) -> &str {
+        match *self {
+            InferenceError::NoRuleFired => "no rule fired",
+            InferenceError::InvalidValue(_) => "input value is not a finite number",
+            InferenceError::MissingValue(_) => "input value is not set",
+            InferenceError::UnboundVariable(_) => "variable is not bound to a universe",
+            InferenceError::UnknownUniverse(_) => "universe doesn't exist",
+            InferenceError::NotAnOutput(_) => "universe is not of an output variable",
+            InferenceError::EmptyDomain(_) => "domain of universe is empty",
+            InferenceError::UnknownSet { .. } => "set doesn't exist",
+        }
+    }
+}
+
+impl From<DefuzzError> for InferenceError {
+    fn from(error: DefuzzError) -> InferenceError {
+        match error {
+            DefuzzError::EmptyOutput => InferenceError::NoRuleFired,
+        }
+    }
+}
+
+/// Result of the fuzzy logic inference.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InferenceResult {
+    /// Defuzzificated result.
+    pub value: f32,
+    /// Name of the universe of the rules' result sets.
+    pub output_universe: String,
+    /// Index of the rule with the highest firing strength among the rules concluding in
+    /// `output_universe`.
+    pub dominant_rule: usize,
+    /// Highest membership of the aggregated output set.
+    pub height: f32,
+    /// Sum of memberships of the aggregated output set.
+    pub mass: f32,
+    /// `true` if `value` was outside of the output universe's domain and was clamped into it.
+    pub clamped: bool,
+    /// Confidence in the result, computed from certainty factors of the fired rules.
+    ///
+    /// Rules without certainty factor are fully certain. See `Confidence`.
+    pub confidence: f32,
+}
+
+/// Method of combining certainty factors of the rules into the result's confidence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Confidence {
+    /// Mean of certainty factors weighted by firing strengths of the rules.
+    WeightedMean,
+    /// Certainty factor of the rule with the highest firing strength.
+    Max,
+}
 
 /// Structure which contains the implementation of fuzzy logic operations.
 pub struct InferenceOptions {
@@ -19,6 +139,50 @@ pub struct InferenceOptions {
     pub set_ops: Box<SetOps>,
     /// Contains defuzzification function.
     pub defuzz_func: Box<DefuzzFunc>,
+    /// Contains defuzzification function of the rules' results taken separately,
+    /// e.g. `DefuzzFactory::center_of_sums`.
+    ///
+    /// If set, `InferenceMachine::compute`, `compute_for` and `compute_outputs` use it
+    /// instead of `defuzz_func`.
+    pub sums_defuzz_func: Option<Box<SumsDefuzzFunc>>,
+    /// Contains implication, which computes the rules' results from their firing strengths.
+    pub implication: Box<ImplicationOp>,
+    /// Contains aggregation of the rules' results.
+    ///
+    /// If not set, the results are aggregated with `SetOps::union` of `set_ops`.
+    pub aggregation: Option<Box<AggregationOp>>,
+}
+
+impl InferenceOptions {
+    /// Constructs options of the Mamdani inference:
+    /// Zadeh logic operations, minimax set operations, min implication
+    /// and center of mass defuzzification.
+    pub fn mamdani() -> InferenceOptions {
+        InferenceOptions {
+            logic_ops: Box::new(ZadehOps {}),
+            set_ops: Box::new(MinMaxOps {}),
+            defuzz_func: DefuzzFactory::center_of_mass(),
+            sums_defuzz_func: None,
+            implication: Box::new(MinImplication {}),
+            aggregation: None,
+        }
+    }
+
+    /// Aggregates the rules' results with `aggregation` or, if it's not set,
+    /// with `SetOps::union` of `set_ops`.
+    ///
+    /// Returns the empty set if there are no results.
+    pub fn aggregate(&self, sets: Vec<Set>) -> Set {
+        let mut sets = sets.into_iter();
+        let mut result = sets.next().unwrap_or_else(Set::new_empty);
+        for mut set in sets {
+            match self.aggregation {
+                Some(ref aggregation) => aggregation.aggregate_into(&mut result, &mut set),
+                None => self.set_ops.union_into(&mut result, &mut set),
+            }
+        }
+        result
+    }
 }
 
 /// Structure which contains the evaluation context. Passed to `RuleSet`.
@@ -26,56 +190,1656 @@ pub struct InferenceContext<'a> {
     /// Reference to the Key-Value container, which contains input variables' values.
     pub values: &'a HashMap<String, f32>,
     /// Reference to the list of available universes.
-    pub universes: &'a mut HashMap<String, UniversalSet>,
+    pub universes: &'a HashMap<String, Rc<UniversalSet>>,
+    /// Reference to the names of the universes the variables are bound to.
+    ///
+    /// Variables which aren't there are bound to the universe with the same name.
+    pub bindings: &'a HashMap<String, String>,
+    /// Reference to the fuzzy values of input variables, which take precedence over `values`.
+    pub fuzzy_values: &'a HashMap<String, Set>,
     /// Reference to the evaluation options.
     pub options: &'a InferenceOptions,
 }
 
+impl<'a> InferenceContext<'a> {
+    /// Returns the value of the input variable.
+    pub fn value(&self, variable: &str) -> Result<f32, InferenceError> {
+        self.values
+            .get(variable)
+            .cloned()
+            .ok_or_else(|| InferenceError::MissingValue(variable.to_string()))
+    }
+
+    /// Returns the universe the variable is bound to.
+    pub fn universe(&self, variable: &str) -> Result<&'a UniversalSet, InferenceError> {
+        let universes = self.universes;
+        match self.bindings.get(variable) {
+            Some(name) => {
+                universes.get(name)
+                         .map(|universe| &**universe)
+                         .ok_or_else(|| InferenceError::UnknownUniverse(name.clone()))
+            }
+            None => {
+                universes.get(variable)
+                         .map(|universe| &**universe)
+                         .ok_or_else(|| InferenceError::UnboundVariable(variable.to_string()))
+            }
+        }
+    }
+
+    /// Returns the membership of the variable's value in the set of its universe.
+    ///
+    /// For a fuzzy value it's the sup-min composition: the highest minimum of memberships
+    /// in the value and in the set over the universe's domain and cached items of the value.
+    pub fn membership(&self, variable: &str, set: &str) -> Result<f32, InferenceError> {
+        let universe = self.universe(variable)?;
+        self.membership_in(universe, variable, set)
+    }
+
+    /// Same as `membership`, but with the universe the variable is bound to already looked up.
+    pub fn membership_in(&self,
+                         universe: &UniversalSet,
+                         variable: &str,
+                         set: &str)
+                         -> Result<f32, InferenceError> {
+        match universe.sets.get(set) {
+            Some(set) => {
+                match self.fuzzy_values.get(variable) {
+                    Some(input) => {
+                        let cached = input.cache.borrow().keys().map(|x| x.0).collect::<Vec<_>>();
+                        Ok(universe.domain()
+                                   .iter()
+                                   .chain(&cached)
+                                   .map(|&x| input.membership_at(x).min(set.check(x)))
+                                   .fold(0.0, f32::max))
+                    }
+                    None => Ok(set.check(self.value(variable)?)),
+                }
+            }
+            None => {
+                let name = self.bindings.get(variable).map_or(variable, |name| name.as_str());
+                Err(InferenceError::UnknownSet {
+                    universe: name.to_string(),
+                    set: set.to_string(),
+                })
+            }
+        }
+    }
+}
+
+/// Buffers reused by every `compute` call of `InferenceMachine`.
+///
+/// Buffers are cleared rather than freed, so once warmed up, computation doesn't allocate them again.
+struct ScratchSpace {
+    /// Firing strength of every rule.
+    strengths: Vec<f32>,
+    /// Result of the currently computed rule.
+    buffer: Set,
+    /// Aggregated result of all rules.
+    output: Set,
+}
+
+impl ScratchSpace {
+    /// Constructs the empty `ScratchSpace`.
+    fn new() -> ScratchSpace {
+        ScratchSpace {
+            strengths: Vec::new(),
+            buffer: Set::new_empty(),
+            output: Set::new_empty(),
+        }
+    }
+}
+
 /// Structure which contains the implementation of the fuzzy logic inference mechanism.
 pub struct InferenceMachine {
     /// List of rules to be evaluated.
     pub rules: RuleSet,
     /// HashMap of all universes. Access by name.
-    pub universes: HashMap<String, UniversalSet>,
+    ///
+    /// Universes may be shared with other machines, use `universe_mut` to modify one.
+    pub universes: HashMap<String, Rc<UniversalSet>>,
     /// Input variables' values.
     pub values: HashMap<String, f32>,
+    /// Names of the universes the variables are bound to. See `bind_variable`.
+    pub bindings: HashMap<String, String>,
+    /// Fuzzy values of input variables. See `set_fuzzy_value`.
+    pub fuzzy_values: HashMap<String, Set>,
     /// Evaluation options.
     pub options: InferenceOptions,
+    /// Buffers reused across computations.
+    scratch: ScratchSpace,
+    /// Method of computing the result's confidence.
+    confidence: Confidence,
 }
 
 impl InferenceMachine {
     /// Constructs the new `InferenceMachine`.
     ///
     /// This function moves all arguments to the structure.
-    pub fn new(rules: RuleSet,
-               universes: HashMap<String, UniversalSet>,
-               options: InferenceOptions)
-               -> InferenceMachine {
+    /// Universes are either owned `UniversalSet`s or `Rc<UniversalSet>`s shared with other machines.
+    /// Machines sharing a universe share its cached memberships too.
+    /// Universes are shared with `Rc` rather than `Arc`: sets cache memberships in `RefCell`s
+    /// and hold their membership functions in `Rc`s, so a universe can't cross threads anyway.
+    pub fn new<U: Into<Rc<UniversalSet>>>(rules: RuleSet,
+                                          universes: HashMap<String, U>,
+                                          options: InferenceOptions)
+                                          -> InferenceMachine {
         InferenceMachine {
             rules: rules,
-            universes: universes,
+            universes: universes.into_iter().map(|(name, universe)| (name, universe.into())).collect(),
             values: HashMap::new(),
+            bindings: HashMap::new(),
+            fuzzy_values: HashMap::new(),
             options: options,
+            scratch: ScratchSpace::new(),
+            confidence: Confidence::WeightedMean,
+        }
+    }
+
+    /// Sets the method of computing `InferenceResult::confidence`, `WeightedMean` by default.
+    pub fn set_confidence(&mut self, confidence: Confidence) {
+        self.confidence = confidence;
+    }
+
+    /// Constructs the new `InferenceMachine` from linguistic variables.
+    ///
+    /// Universes are stored by their names, input variables with other names are bound to them
+    /// and their default values are set.
+    /// Fails with `InferenceError::NotAnOutput` if some of the rules' results are not
+    /// in the universe of an output variable.
+    pub fn from_variables(rules: RuleSet,
+                          variables: Vec<LinguisticVariable>,
+                          options: InferenceOptions)
+                          -> Result<InferenceMachine, InferenceError> {
+        for name in rules.result_universes() {
+            let output = variables.iter().any(|variable| {
+                variable.role() == Role::Output && variable.universe().name() == name
+            });
+            if !output {
+                return Err(InferenceError::NotAnOutput(name.to_string()));
+            }
+        }
+        let mut universes = HashMap::new();
+        let mut bindings = HashMap::new();
+        let mut values = HashMap::new();
+        for variable in variables {
+            if variable.role() == Role::Input {
+                if let Some(default) = variable.default() {
+                    values.insert(variable.name().to_string(), default);
+                }
+                if variable.name() != variable.universe().name() {
+                    bindings.insert(variable.name().to_string(),
+                                    variable.universe().name().to_string());
+                }
+            }
+            let universe = variable.into_universe();
+            universes.insert(universe.name().to_string(), universe);
         }
+        let mut machine = InferenceMachine::new(rules, universes, options);
+        machine.bindings = bindings;
+        machine.update(&values)?;
+        Ok(machine)
+    }
+
+    /// Allocates the buffers used by `compute` up front.
+    ///
+    /// Call it before entering the real-time section, so computations don't grow the buffers.
+    pub fn preallocate(&mut self) {
+        let points = self.rules
+                         .result_universes()
+                         .iter()
+                         .filter_map(|name| self.universes.get(*name))
+                         .map(|universe| universe.domain().len())
+                         .max()
+                         .unwrap_or(0);
+        let scratch = &mut self.scratch;
+        scratch.strengths.reserve(self.rules.len());
+        scratch.buffer.cache.borrow_mut().reserve(points);
+        scratch.output.cache.borrow_mut().reserve(points);
+    }
+
+    /// Returns the mutable universe with given name.
+    ///
+    /// If the universe is shared with other machines, it's copied first (copy-on-write),
+    /// so the changes are visible only to this machine.
+    pub fn universe_mut(&mut self, name: &str) -> Option<&mut UniversalSet> {
+        self.universes.get_mut(name).map(Rc::make_mut)
+    }
+
+    /// Binds the input variable to the universe with given name.
+    ///
+    /// By default, variables are bound to the universe with the same name,
+    /// so several variables can share one universe only if they are bound explicitly.
+    pub fn bind_variable(&mut self, variable: &str, universe: &str) {
+        self.bindings.insert(variable.to_string(), universe.to_string());
     }
 
     /// Updates values in `values`.
     ///
     /// Basically, this method just clones the argument.
-    pub fn update(&mut self, values: &HashMap<String, f32>) {
+    /// Fuzzy values of the updated variables are removed.
+    /// Fails with `InferenceError::InvalidValue` if any value is NaN or infinite,
+    /// in which case no value is updated.
+    pub fn update(&mut self, values: &HashMap<String, f32>) -> Result<(), InferenceError> {
+        if let Some((variable, _)) = values.iter().find(|&(_, value)| !value.is_finite()) {
+            return Err(InferenceError::InvalidValue(variable.clone()));
+        }
+        for variable in values.keys() {
+            self.fuzzy_values.remove(variable);
+        }
         self.values = values.clone();
+        Ok(())
+    }
+
+    /// Sets the value of the input variable with given name, removing its fuzzy value.
+    ///
+    /// Fails with `InferenceError::InvalidValue` if the value is NaN or infinite.
+    pub fn set_value(&mut self, variable: &str, value: f32) -> Result<(), InferenceError> {
+        if !value.is_finite() {
+            return Err(InferenceError::InvalidValue(variable.to_string()));
+        }
+        self.fuzzy_values.remove(variable);
+        self.values.insert(variable.to_string(), value);
+        Ok(())
     }
 
-    /// Computes the result of the fuzzy logic inference.
+    /// Sets the fuzzy value of the input variable with given name.
     ///
-    /// Returns activated fuzzy rule's name and defuzzificated result.
-    pub fn compute(&mut self) -> (String, f32) {
-        let mut context = InferenceContext {
+    /// The fuzzy value takes precedence over the crisp one until `set_value` or `update`.
+    /// Conditions on the variable are matched by the sup-min composition,
+    /// see `InferenceContext::membership`.
+    pub fn set_fuzzy_value(&mut self, variable: &str, input: Set) {
+        self.fuzzy_values.insert(variable.to_string(), input);
+    }
+
+    /// Sets the fuzzy value "about `value`": the triangle with given spread on each side.
+    ///
+    /// Zero spread gives the singleton, which matches like the crisp `value`.
+    /// Fails with `InferenceError::InvalidValue` if the value or the spread is NaN or infinite,
+    /// or the spread is negative.
+    pub fn set_value_with_spread(&mut self,
+                                 variable: &str,
+                                 value: f32,
+                                 spread: f32)
+                                 -> Result<(), InferenceError> {
+        if !value.is_finite() || !spread.is_finite() || spread < 0.0 {
+            return Err(InferenceError::InvalidValue(variable.to_string()));
+        }
+        let name = format!("about {}", value);
+        let input = if spread > 0.0 {
+            let input = Set::new_with_mem(name,
+                                          MembershipFactory::triangular(value - spread,
+                                                                        value,
+                                                                        value + spread));
+            input.check(value);
+            input
+        } else {
+            let mut cache = HashMap::new();
+            cache.insert(OrderedFloat(value), 1.0);
+            Set::new_with_domain(name, RefCell::new(cache))
+        };
+        self.set_fuzzy_value(variable, input);
+        Ok(())
+    }
+
+    /// Computes the inference in `RuleSet::result_universe` for each row of values
+    /// of given variables, see `compute_for`.
+    ///
+    /// Values of other variables are kept, all values, including fuzzy ones,
+    /// are restored after the batch.
+    /// Buffers are reused between rows, as with repeated `compute` calls.
+    pub fn compute_batch(&mut self,
+                         variables: &[&str],
+                         rows: &[Vec<f32>])
+                         -> Vec<Result<InferenceResult, InferenceError>> {
+        let universe = self.rules.result_universe().to_string();
+        let saved = self.values.clone();
+        let saved_fuzzy = self.fuzzy_values.clone();
+        let results = rows.iter()
+                          .map(|row| {
+                              for (variable, &value) in variables.iter().zip(row) {
+                                  self.set_value(variable, value)?;
+                              }
+                              self.compute_for(&universe)
+                          })
+                          .collect();
+        self.values = saved;
+        self.fuzzy_values = saved_fuzzy;
+        results
+    }
+
+    /// Returns firing strengths of the rules, computed by the last `compute` call.
+    ///
+    /// Strengths are in the order of the rules and are available even if `compute` failed.
+    pub fn strengths(&self) -> &[f32] {
+        &self.scratch.strengths
+    }
+
+    /// Returns the aggregated output set, computed by the last `compute` call.
+    ///
+    /// If several universes were inferred, it's the output set of the last one.
+    pub fn output(&self) -> &Set {
+        &self.scratch.output
+    }
+
+    /// Computes the inference in `RuleSet::result_universe` and returns the name
+    /// of its set, which is the most similar to the aggregated output set, and its similarity.
+    ///
+    /// Sets are compared with `Set::similarity`.
+    /// See `approximate_linguistically_with`.
+    pub fn approximate_linguistically(&mut self) -> Result<(String, f32), InferenceError> {
+        self.approximate_linguistically_with(&|output: &Set, set: &Set| output.similarity(set))
+    }
+
+    /// Same as `approximate_linguistically`, but sets are compared with `similarity`.
+    ///
+    /// If several sets are equally similar, the first one by name is returned.
+    /// Fails like `compute_for`, e.g. with `InferenceError::NoRuleFired` if the output set
+    /// is empty.
+    pub fn approximate_linguistically_with(&mut self,
+                                           similarity: &Fn(&Set, &Set) -> f32)
+                                           -> Result<(String, f32), InferenceError> {
+        let name = self.rules.result_universe().to_string();
+        self.compute_for(&name)?;
+        // `compute_for` fails if the universe doesn't exist.
+        let universe = &self.universes[&name];
+        let mut names = universe.sets.keys().collect::<Vec<_>>();
+        names.sort();
+        let mut best: Option<(&String, f32)> = None;
+        for name in names {
+            let score = similarity(&self.scratch.output, &universe.sets[name]);
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((name, score));
+            }
+        }
+        best.map(|(name, score)| (name.clone(), score))
+            .ok_or(InferenceError::NoRuleFired)
+    }
+
+    /// Returns the description of the whole system, same as `Display`.
+    pub fn summary(&self) -> String {
+        format!("{}", self)
+    }
+
+    /// Computes the result of the fuzzy logic inference in every output universe.
+    ///
+    /// Returns the results by the names of `RuleSet::result_universes`.
+    /// Fails with the error of the first universe which can't be inferred,
+    /// use `compute_outputs` to get the results of the other universes in that case.
+    pub fn compute(&mut self) -> Result<HashMap<String, InferenceResult>, InferenceError> {
+        self.evaluate()?;
+        let context = InferenceContext {
+            values: &self.values,
+            universes: &self.universes,
+            bindings: &self.bindings,
+            fuzzy_values: &self.fuzzy_values,
+            options: &self.options,
+        };
+        let scratch = &mut self.scratch;
+        let mut results = HashMap::new();
+        for universe in self.rules.result_universes() {
+            let result = infer(&self.rules, &context, self.confidence, scratch, universe)?;
+            results.insert(universe.to_string(), result);
+        }
+        Ok(results)
+    }
+
+    /// Computes the result of the fuzzy logic inference in the universe with given name.
+    ///
+    /// Rules concluding in other universes are evaluated, but their results are not computed.
+    /// Fails with `InferenceError::UnknownUniverse` if there is no such universe,
+    /// with `InferenceError::NoRuleFired` if firing strengths of all rules concluding in it
+    /// are below `f32::EPSILON` or the output set is empty,
+    /// and with the error of the first rule which can't be evaluated.
+    pub fn compute_for(&mut self, universe: &str) -> Result<InferenceResult, InferenceError> {
+        if !self.universes.contains_key(universe) {
+            return Err(InferenceError::UnknownUniverse(universe.to_string()));
+        }
+        self.evaluate()?;
+        let context = InferenceContext {
+            values: &self.values,
+            universes: &self.universes,
+            bindings: &self.bindings,
+            fuzzy_values: &self.fuzzy_values,
+            options: &self.options,
+        };
+        infer(&self.rules, &context, self.confidence, &mut self.scratch, universe)
+    }
+
+    /// Computes the inference in `RuleSet::result_universe` like `compute_for`
+    /// and describes how strongly each rule fired.
+    ///
+    /// Returns the result's value and the rules' activations sorted by descending strength.
+    pub fn compute_traced(&mut self) -> Result<(f32, Vec<RuleActivation>), InferenceError> {
+        let universe = self.rules.result_universe().to_string();
+        let value = self.compute_for(&universe)?.value;
+        Ok((value, self.rules.activations(&self.scratch.strengths)))
+    }
+
+    /// Aggregates the output set in `RuleSet::result_universe` once and defuzzifies it
+    /// with each of named `methods`.
+    ///
+    /// All methods get the same aggregated set, which is also available through `output`.
+    /// `InferenceOptions::sums_defuzz_func` is not used.
+    /// Results are clamped into the output universe's domain, as in `compute_for`.
+    /// Fails like `compute_for`, or with the error of the first method which fails.
+    pub fn compute_multi_defuzz(&mut self,
+                                methods: &[(&str, Box<DefuzzFunc>)])
+                                -> Result<HashMap<String, f32>, InferenceError> {
+        self.aggregate()?;
+        let mut results = HashMap::with_capacity(methods.len());
+        for &(name, ref method) in methods {
+            let value = (**method)(&self.scratch.output)?;
+            results.insert(name.to_string(), self.clamp(value).0);
+        }
+        Ok(results)
+    }
+
+    /// Computes the inference in `RuleSet::result_universe` with the fast path
+    /// of the height methods, e.g. `DefuzzFactory::weighted_average`.
+    ///
+    /// The rules' results are represented by the peaks of their sets (see `Set::peak`)
+    /// weighted by the firing strengths, so the output universe's sets are not computed
+    /// and their caches are not touched. Firing strengths are available through `strengths`.
+    /// Fails like `compute_for`, or with `InferenceError::NoRuleFired` if all result sets
+    /// are empty.
+    pub fn compute_weighted(&mut self,
+                            defuzz_func: &WeightedDefuzzFunc)
+                            -> Result<f32, InferenceError> {
+        self.evaluate()?;
+        let name = self.rules.result_universe();
+        let universe = self.universes
+                           .get(name)
+                           .ok_or_else(|| InferenceError::UnknownUniverse(name.to_string()))?;
+        let mut points = Vec::with_capacity(self.scratch.strengths.len());
+        for (rule, &strength) in self.rules.iter().zip(&self.scratch.strengths) {
+            // Rules without a consequent in the output universe don't contribute to it.
+            let set = match rule.result_set_in(name) {
+                Some(set) => set,
+                None => continue,
+            };
+            let peak = universe.sets
+                               .get(set)
+                               .ok_or_else(|| {
+                                   InferenceError::UnknownSet {
+                                       universe: name.to_string(),
+                                       set: set.to_string(),
+                                   }
+                               })?
+                               .peak(universe.domain());
+            if let Some(peak) = peak {
+                points.push((peak, strength));
+            }
+        }
+        if self.scratch.strengths.iter().all(|&strength| strength < f32::EPSILON) {
+            return Err(InferenceError::NoRuleFired);
+        }
+        Ok(self.clamp(defuzz_func(&points)?).0)
+    }
+
+    /// Appends the rule to the rules of the machine, see `RuleSet::add_rule`.
+    pub fn add_rule(&mut self, rule: Rule) -> Result<(), RuleSetError> {
+        self.rules.add_rule(rule)
+    }
+
+    /// Removes the rule with given index from the machine, see `RuleSet::remove_rule`.
+    pub fn remove_rule(&mut self, index: usize) -> Result<Rule, RuleSetError> {
+        self.rules.remove_rule(index)
+    }
+
+    /// Replaces the rule with given index in the machine, see `RuleSet::replace_rule`.
+    pub fn replace_rule(&mut self, index: usize, rule: Rule) -> Result<Rule, RuleSetError> {
+        self.rules.replace_rule(index, rule)
+    }
+
+    /// Computes the inference for every output universe of the rules.
+    ///
+    /// Conditions of the rules are evaluated once, then each universe is inferred
+    /// like with `compute_for`. Returns the result or the error of every universe
+    /// by the names of `RuleSet::result_universes`,
+    /// `output` holds the aggregated set of the last universe afterwards.
+    /// Fails only with the error of the first rule which can't be evaluated.
+    pub fn compute_outputs(&mut self)
+                           -> Result<HashMap<String, Result<InferenceResult, InferenceError>>,
+                                     InferenceError> {
+        self.evaluate()?;
+        let context = InferenceContext {
+            values: &self.values,
+            universes: &self.universes,
+            bindings: &self.bindings,
+            fuzzy_values: &self.fuzzy_values,
+            options: &self.options,
+        };
+        let scratch = &mut self.scratch;
+        let mut results = HashMap::new();
+        for universe in self.rules.result_universes() {
+            let result = infer(&self.rules, &context, self.confidence, scratch, universe);
+            results.insert(universe.to_string(), result);
+        }
+        Ok(results)
+    }
+
+    /// Evaluates firing strengths of all rules into the scratch space.
+    fn evaluate(&mut self) -> Result<(), InferenceError> {
+        let context = InferenceContext {
             values: &self.values,
-            universes: &mut self.universes,
+            universes: &self.universes,
+            bindings: &self.bindings,
+            fuzzy_values: &self.fuzzy_values,
             options: &self.options,
         };
-        let result = self.rules.compute_all(&mut context);
-        (result.name.clone(), (*self.options.defuzz_func)(&result))
+        self.rules.firing_strengths_into(&context, &mut self.scratch.strengths)
+    }
+
+    /// Computes firing strengths and the aggregated output set into the scratch space.
+    ///
+    /// Returns index of the rule with the highest firing strength.
+    fn aggregate(&mut self) -> Result<usize, InferenceError> {
+        let context = InferenceContext {
+            values: &self.values,
+            universes: &self.universes,
+            bindings: &self.bindings,
+            fuzzy_values: &self.fuzzy_values,
+            options: &self.options,
+        };
+        let scratch = &mut self.scratch;
+        self.rules.compute_all_into(&context,
+                                    &mut scratch.strengths,
+                                    &mut scratch.buffer,
+                                    &mut scratch.output)?;
+        dominant_rule(&self.rules, &scratch.strengths, self.rules.result_universe())
+            .ok_or(InferenceError::NoRuleFired)
+    }
+
+    /// Clamps `value` into the output universe's domain.
+    /// Returns `true` as the second element if the value was changed.
+    fn clamp(&self, value: f32) -> (f32, bool) {
+        clamp_into(self.universes.get(self.rules.result_universe()).map(|u| &**u), value)
+    }
+}
+
+/// Infers the result in `universe` from the firing strengths in `scratch`.
+///
+/// With `InferenceOptions::sums_defuzz_func` the rules' results are computed once
+/// and aggregated into `scratch.output` after their defuzzification.
+fn infer(rules: &RuleSet,
+         context: &InferenceContext,
+         confidence: Confidence,
+         scratch: &mut ScratchSpace,
+         universe: &str)
+         -> Result<InferenceResult, InferenceError> {
+    let sums = match context.options.sums_defuzz_func {
+        Some(ref sums_defuzz_func) => {
+            Some((sums_defuzz_func, rules.results_in(context, universe, &scratch.strengths)?))
+        }
+        None => {
+            rules.aggregate_into(context,
+                                 universe,
+                                 &scratch.strengths,
+                                 &mut scratch.buffer,
+                                 &mut scratch.output)?;
+            None
+        }
+    };
+    let dominant_rule = dominant_rule(rules, &scratch.strengths, universe)
+                            .ok_or(InferenceError::NoRuleFired)?;
+    let value = match sums {
+        Some((sums_defuzz_func, results)) => {
+            let value = (**sums_defuzz_func)(&results)?;
+            scratch.output = context.options.aggregate(results);
+            value
+        }
+        None => (*context.options.defuzz_func)(&scratch.output)?,
+    };
+    let (value, clamped) = clamp_into(context.universes.get(universe).map(|u| &**u), value);
+    let (height, mass) = scratch.output
+                                .cache
+                                .borrow()
+                                .values()
+                                .fold((0.0, 0.0), |(height, mass): (f32, f32), &mem| {
+                                    (height.max(mem), mass + mem)
+                                });
+    Ok(InferenceResult {
+        value: value,
+        output_universe: universe.to_string(),
+        dominant_rule: dominant_rule,
+        height: height,
+        mass: mass,
+        clamped: clamped,
+        confidence: combine_certainties(rules,
+                                        &scratch.strengths,
+                                        universe,
+                                        dominant_rule,
+                                        confidence),
+    })
+}
+
+/// Returns index of the rule with the highest firing strength among the rules concluding
+/// in `universe`, or `None` if all their strengths are below `f32::EPSILON`.
+fn dominant_rule(rules: &RuleSet, strengths: &[f32], universe: &str) -> Option<usize> {
+    let mut dominant_rule = None;
+    let mut max_strength = f32::EPSILON;
+    for (index, (rule, &strength)) in rules.iter().zip(strengths).enumerate() {
+        if rule.result_set_in(universe).is_some() && strength >= max_strength {
+            dominant_rule = Some(index);
+            max_strength = strength;
+        }
+    }
+    dominant_rule
+}
+
+/// Combines certainty factors of the fired rules concluding in `universe`.
+fn combine_certainties(rules: &RuleSet,
+                       strengths: &[f32],
+                       universe: &str,
+                       dominant_rule: usize,
+                       confidence: Confidence)
+                       -> f32 {
+    let certainty = |rule: &Rule| rule.certainty().unwrap_or(1.0);
+    match confidence {
+        Confidence::WeightedMean => {
+            let mut weighted = 0.0;
+            let mut total = 0.0;
+            for (rule, &strength) in rules.iter().zip(strengths) {
+                if rule.result_set_in(universe).is_some() {
+                    weighted += strength * certainty(rule);
+                    total += strength;
+                }
+            }
+            weighted / total
+        }
+        Confidence::Max => certainty(&rules.rules()[dominant_rule]),
+    }
+}
+
+/// Clamps `value` into the domain of `universe`.
+/// Returns `true` as the second element if the value was changed.
+fn clamp_into(universe: Option<&UniversalSet>, value: f32) -> (f32, bool) {
+    if let Some(universe) = universe {
+        let domain = universe.domain();
+        if !domain.is_empty() {
+            let min = domain.iter().cloned().fold(f32::INFINITY, f32::min);
+            let max = domain.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+            if value < min || value > max {
+                return (value.max(min).min(max), true);
+            }
+        }
+    }
+    (value, false)
+}
+
+/// Describes universes, bindings, rules, operations and input values.
+///
+/// Everything but the rules is sorted by name, so descriptions of two machines can be compared.
+impl fmt::Display for InferenceMachine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "(InferenceMachine")?;
+        writeln!(f, "universes:")?;
+        let mut universes = self.universes.iter().collect::<Vec<_>>();
+        universes.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, universe) in universes {
+            let domain = universe.domain();
+            let mut sets = universe.sets.keys().map(|set| set.as_str()).collect::<Vec<_>>();
+            sets.sort();
+            if domain.is_empty() {
+                write!(f, "\t{} no domain", name)?;
+            } else {
+                let min = domain.iter().cloned().fold(f32::INFINITY, f32::min);
+                let max = domain.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+                write!(f, "\t{} [{}, {}] {} points", name, min, max, domain.len())?;
+            }
+            writeln!(f, ": {}", sets.join(", "))?;
+        }
+        if !self.bindings.is_empty() {
+            writeln!(f, "bindings:")?;
+            let mut bindings = self.bindings.iter().collect::<Vec<_>>();
+            bindings.sort();
+            for (variable, universe) in bindings {
+                writeln!(f, "\t{} -> {}", variable, universe)?;
+            }
+        }
+        writeln!(f, "rules:")?;
+        writeln!(f, "{}", self.rules)?;
+        writeln!(f, "logic ops: {}", self.options.logic_ops.name())?;
+        writeln!(f, "set ops: {}", self.options.set_ops.name())?;
+        writeln!(f, "implication: {}", self.options.implication.name())?;
+        if let Some(ref aggregation) = self.options.aggregation {
+            writeln!(f, "aggregation: {}", aggregation.name())?;
+        }
+        if self.options.sums_defuzz_func.is_some() {
+            writeln!(f, "defuzzification: rules' results taken separately")?;
+        } else {
+            writeln!(f, "defuzzification: aggregated set")?;
+        }
+        writeln!(f, "values:")?;
+        let mut values = self.values.iter().collect::<Vec<_>>();
+        values.sort_by(|a, b| a.0.cmp(b.0));
+        for (variable, value) in values {
+            writeln!(f, "\t{} = {}", variable, value)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use functions::{DefuzzFactory, MembershipFactory};
+    use ops::{GodelImplication, KleeneDienesImplication, LukasiewiczImplication, MinAggregation,
+              SumAggregation};
+    use rules::{And, Is, Or, Rule};
+
+    fn universes() -> HashMap<String, UniversalSet> {
+        let mut temp = UniversalSet::new("temp".to_string());
+        temp.set_domain((0..41).map(|x| x as f32).collect());
+        temp.create_set("cold".to_string(),
+                        MembershipFactory::triangular(-20.0, 0.0, 25.0)).unwrap();
+        temp.create_set("hot".to_string(),
+                        MembershipFactory::triangular(15.0, 40.0, 60.0)).unwrap();
+        let mut fan = UniversalSet::new("fan".to_string());
+        fan.set_domain((0..101).map(|x| x as f32 / 10.0).collect());
+        fan.create_set("slow".to_string(), MembershipFactory::triangular(-5.0, 0.0, 6.0)).unwrap();
+        fan.create_set("fast".to_string(), MembershipFactory::triangular(4.0, 10.0, 15.0)).unwrap();
+        let mut universes = HashMap::new();
+        universes.insert("temp".to_string(), temp);
+        universes.insert("fan".to_string(), fan);
+        universes
+    }
+
+    fn options() -> InferenceOptions {
+        InferenceOptions::mamdani()
+    }
+
+    /// Rules "if temp is cold then fan is `when_cold`" and "if temp is hot then fan is `when_hot`".
+    fn rules(when_cold: &str, when_hot: &str) -> RuleSet {
+        let rules = vec![Rule::new(Box::new(Is::new("temp".to_string(), "cold".to_string())),
+                                   "fan".to_string(),
+                                   when_cold.to_string()),
+                         Rule::new(Box::new(Is::new("temp".to_string(), "hot".to_string())),
+                                   "fan".to_string(),
+                                   when_hot.to_string())];
+        RuleSet::new(rules).unwrap()
+    }
+
+    fn machine() -> InferenceMachine {
+        InferenceMachine::new(rules("slow", "fast"), universes(), options())
+    }
+
+    fn compute_at(machine: &mut InferenceMachine,
+                  temp: f32)
+                  -> Result<InferenceResult, InferenceError> {
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), temp);
+        machine.update(&values).unwrap();
+        let universe = machine.rules.result_universe().to_string();
+        machine.compute_for(&universe)
+    }
+
+    #[test]
+    fn max_and_sum_aggregation() {
+        let mut universes = universes();
+        universes.get_mut("fan")
+                 .unwrap()
+                 .create_set("medium".to_string(), MembershipFactory::triangular(2.0, 5.0, 8.0))
+                 .unwrap();
+        let rule = |condition: &str, result: &str| {
+            Rule::new(Box::new(Is::new("temp".to_string(), condition.to_string())),
+                      "fan".to_string(),
+                      result.to_string())
+        };
+        let mut outputs = Vec::new();
+        for &sum in &[false, true] {
+            let rules = vec![rule("cold", "slow"), rule("cold", "medium"), rule("hot", "fast")];
+            let mut options = options();
+            if sum {
+                options.aggregation = Some(Box::new(SumAggregation {}));
+            }
+            let mut machine =
+                InferenceMachine::new(RuleSet::new(rules).unwrap(), universes.clone(), options);
+            outputs.push(compute_at(&mut machine, 18.0).unwrap().value);
+        }
+        // "cold" is 0.28 and "hot" is 0.12, so the results of all three rules overlap.
+        // Expected values were computed separately.
+        assert!((outputs[0] - 4.343705).abs() < 1e-4);
+        assert!((outputs[1] - 4.433581).abs() < 1e-4);
+    }
+
+    #[test]
+    fn conjunctive_aggregation() {
+        let build = |implication: Box<ImplicationOp>, aggregation: Option<Box<AggregationOp>>| {
+            let rules = vec![Rule::new(Box::new(Is::new("temp".to_string(), "cold".to_string())),
+                                       "fan".to_string(),
+                                       "slow".to_string()),
+                             Rule::new(Box::new(Is::new("temp".to_string(), "hot".to_string())),
+                                       "fan".to_string(),
+                                       "fast".to_string())];
+            let mut options = options();
+            options.implication = implication;
+            options.aggregation = aggregation;
+            InferenceMachine::new(RuleSet::new(rules).unwrap(), universes(), options)
+        };
+        let implications: Vec<fn() -> Box<ImplicationOp>> =
+            vec![|| Box::new(LukasiewiczImplication {}),
+                 || Box::new(KleeneDienesImplication {}),
+                 || Box::new(GodelImplication {})];
+        for implication in implications {
+            let name = implication().name().to_string();
+            // Only "hot" fires at 38, "cold" gives 1 everywhere and hides it in the union.
+            let mut union = build(implication(), None);
+            let value = compute_at(&mut union, 38.0).unwrap().value;
+            assert!((value - 5.0).abs() < 1e-4, "{}: {}", name, value);
+
+            let mut machine = build(implication(), Some(Box::new(MinAggregation {})));
+            let hot = compute_at(&mut machine, 38.0).unwrap().value;
+            let cold = compute_at(&mut machine, 2.0).unwrap().value;
+            assert!(hot > 7.0, "{}: {}", name, hot);
+            assert!(cold < 3.0, "{}: {}", name, cold);
+        }
+    }
+
+    #[test]
+    fn compute_reuses_scratch() {
+        let mut machine = machine();
+        machine.preallocate();
+        for i in 0..41 {
+            let mut values = HashMap::new();
+            values.insert("temp".to_string(), i as f32);
+            machine.update(&values).unwrap();
+            let result = machine.compute_for("fan");
+            let expected = {
+                let context = InferenceContext {
+                    values: &machine.values,
+                    universes: &machine.universes,
+                    bindings: &machine.bindings,
+                    fuzzy_values: &machine.fuzzy_values,
+                    options: &machine.options,
+                };
+                machine.rules.compute_all(&context).unwrap()
+            };
+            assert_eq!(*machine.scratch.output.cache.borrow(), *expected.cache.borrow());
+            match (result, (*machine.options.defuzz_func)(&expected)) {
+                (Ok(InferenceResult { value, .. }), Ok(expected_value)) => {
+                    // Sets iterate their caches in different order, so sums may differ in the last bits.
+                    assert!((value - expected_value).abs() <= 1e-5 * expected_value.abs());
+                }
+                (Err(error), Err(expected_error)) => assert_eq!(error, expected_error.into()),
+                (result, expected) => panic!("{:?} != {:?}", result, expected),
+            }
+        }
+    }
+
+    #[test]
+    fn fresh_consequent_caches() {
+        let mut universes = universes();
+        let mut fan = UniversalSet::new("fan".to_string());
+        fan.create_set("slow".to_string(), MembershipFactory::triangular(-5.0, 0.0, 6.0)).unwrap();
+        fan.create_set("fast".to_string(), MembershipFactory::triangular(4.0, 10.0, 15.0)).unwrap();
+        fan.set_domain((0..101).map(|x| x as f32 / 10.0).collect());
+        universes.insert("fan".to_string(), fan);
+        let mut machine = InferenceMachine::new(rules("slow", "fast"), universes, options());
+        let value = compute_at(&mut machine, 35.0).unwrap().value;
+        assert!(machine.scratch.output.cache.borrow().values().any(|&mem| mem > 0.0));
+        assert!(value.is_finite());
+        assert!(value > 5.0 && value < 10.0);
+    }
+
+    #[test]
+    fn inference_result() {
+        let mut machine = machine();
+        let result = compute_at(&mut machine, 10.0).unwrap();
+        assert_eq!(result.output_universe, "fan");
+        // "cold" is 0.6 at 10, "hot" is 0.
+        assert_eq!(result.dominant_rule, 0);
+        assert!((result.height - 0.6).abs() < 1e-6);
+        assert!(!result.clamped);
+        assert!(result.value > 0.0 && result.value < 5.0);
+
+        let result = compute_at(&mut machine, 38.0).unwrap();
+        assert_eq!(result.dominant_rule, 1);
+        assert!((result.height - 0.92).abs() < 1e-6);
+        let strong = result.mass;
+        assert!(result.value > 5.0 && result.value < 10.0);
+
+        let result = compute_at(&mut machine, 40.0).unwrap();
+        assert_eq!(result.height, 1.0);
+        assert!(result.mass > strong);
+        // "cold" is 0.05 at -19.
+        let result = compute_at(&mut machine, -19.0).unwrap();
+        assert!((result.height - 0.05).abs() < 1e-6);
+        assert!(result.mass > 0.0 && result.mass < strong / 10.0);
+    }
+
+    #[test]
+    fn multi_defuzz() {
+        use std::cell::RefCell;
+
+        let mean_of_maximum = |set: &Set| -> Result<f32, DefuzzError> {
+            let cache = set.cache.borrow();
+            let height = cache.values().fold(0.0, |height: f32, &mem| height.max(mem));
+            let maxima = cache.iter()
+                              .filter(|&(_, &mem)| mem == height)
+                              .map(|(x, _)| x.into_inner())
+                              .collect::<Vec<_>>();
+            if maxima.is_empty() {
+                return Err(DefuzzError::EmptyOutput);
+            }
+            Ok(maxima.iter().sum::<f32>() / maxima.len() as f32)
+        };
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let recorder = |seen: &Rc<RefCell<Vec<usize>>>, value: f32| -> Box<DefuzzFunc> {
+            let seen = seen.clone();
+            Box::new(move |set: &Set| {
+                seen.borrow_mut().push(set as *const Set as usize);
+                Ok(value)
+            })
+        };
+        let methods: Vec<(&str, Box<DefuzzFunc>)> =
+            vec![("centroid", DefuzzFactory::center_of_mass()),
+                 ("mom", Box::new(mean_of_maximum)),
+                 ("first", recorder(&seen, 1.0)),
+                 ("second", recorder(&seen, 20.0))];
+        let mut separate = machine();
+        let mut machine = machine();
+        for &temp in &[5.0, 20.0, 38.0] {
+            let mut values = HashMap::new();
+            values.insert("temp".to_string(), temp);
+            machine.update(&values).unwrap();
+            separate.update(&values).unwrap();
+            seen.borrow_mut().clear();
+            let results = machine.compute_multi_defuzz(&methods).unwrap();
+            assert_eq!(results.len(), 4);
+            let output = machine.output() as *const Set as usize;
+            assert_eq!(*seen.borrow(), vec![output, output]);
+            assert_eq!(results["first"], 1.0);
+            // Results are clamped as in `compute`.
+            assert_eq!(results["second"], 10.0);
+            let separate_methods: Vec<(&str, Box<DefuzzFunc>)> =
+                vec![("centroid", DefuzzFactory::center_of_mass()),
+                     ("mom", Box::new(mean_of_maximum))];
+            for (name, method) in separate_methods {
+                separate.options.defuzz_func = method;
+                let expected = separate.compute_for("fan").unwrap().value;
+                assert!((results[name] - expected).abs() <= 1e-5 * expected.abs());
+            }
+        }
+
+        machine.set_value("temp", 100.0).unwrap();
+        assert_eq!(machine.compute_multi_defuzz(&methods), Err(InferenceError::NoRuleFired));
+    }
+
+    #[test]
+    fn certainty_factors() {
+        let rules = vec![Rule::new(Box::new(Is::new("temp".to_string(), "cold".to_string())),
+                                   "fan".to_string(),
+                                   "slow".to_string())
+                             .with_certainty(0.5),
+                         Rule::new(Box::new(Is::new("temp".to_string(), "hot".to_string())),
+                                   "fan".to_string(),
+                                   "fast".to_string())];
+        let mut certain = machine();
+        let rules = RuleSet::new(rules).unwrap();
+        let mut machine = InferenceMachine::new(rules, universes(), options());
+        for i in 0..41 {
+            let expected = compute_at(&mut certain, i as f32).unwrap();
+            let result = compute_at(&mut machine, i as f32).unwrap();
+            assert_eq!(expected.confidence, 1.0);
+            // Sets iterate their caches in different order, so sums may differ in the last bits.
+            assert!((result.value - expected.value).abs() <= 1e-5 * expected.value.abs());
+            assert_eq!(result.height, expected.height);
+        }
+
+        // Only "cold" fires at 10 and only "hot" fires at 38.
+        assert_eq!(compute_at(&mut machine, 10.0).unwrap().confidence, 0.5);
+        assert_eq!(compute_at(&mut machine, 38.0).unwrap().confidence, 1.0);
+        // Both fire with 0.2 at 20.
+        assert!((compute_at(&mut machine, 20.0).unwrap().confidence - 0.75).abs() < 1e-6);
+        machine.set_confidence(Confidence::Max);
+        assert_eq!(compute_at(&mut machine, 10.0).unwrap().confidence, 0.5);
+        assert_eq!(compute_at(&mut machine, 22.0).unwrap().confidence, 1.0);
+        assert_eq!(compute_at(&mut machine, 18.0).unwrap().confidence, 0.5);
+    }
+
+    #[test]
+    fn fuzzy_values() {
+        let mut crisp = machine();
+        let mut machine = machine();
+        for i in 0..41 {
+            let expected = compute_at(&mut crisp, i as f32 + 0.5);
+            machine.set_value_with_spread("temp", i as f32 + 0.5, 0.0).unwrap();
+            let result = machine.compute_for("fan");
+            assert_eq!(machine.strengths(), crisp.strengths());
+            match (result, expected) {
+                (Ok(result), Ok(expected)) => {
+                    assert!((result.value - expected.value).abs() <= 1e-5 * expected.value.abs());
+                }
+                (result, expected) => assert_eq!(result, expected),
+            }
+        }
+
+        // Wider inputs match more: strengths grow and change slower with the value.
+        let mut previous: Option<(Vec<Vec<f32>>, f32)> = None;
+        for &spread in &[0.0, 1.0, 2.0, 4.0, 8.0] {
+            let strengths = (0..41)
+                                .map(|i| {
+                                    machine.set_value_with_spread("temp", i as f32, spread)
+                                           .unwrap();
+                                    let _ = machine.compute_for("fan");
+                                    machine.strengths().to_vec()
+                                })
+                                .collect::<Vec<_>>();
+            let steepness = strengths.windows(2)
+                                     .flat_map(|pair| {
+                                         pair[0].iter()
+                                                .zip(&pair[1])
+                                                .map(|(a, b)| (a - b).abs())
+                                                .collect::<Vec<_>>()
+                                     })
+                                     .fold(0.0, f32::max);
+            if let Some((previous, previous_steepness)) = previous {
+                for (wider, narrower) in strengths.iter().zip(&previous) {
+                    for (wider, narrower) in wider.iter().zip(narrower) {
+                        assert!(wider + 1e-6 >= *narrower);
+                    }
+                }
+                assert!(steepness <= previous_steepness + 1e-6);
+            }
+            previous = Some((strengths, steepness));
+        }
+
+        machine.set_value("temp", 10.0).unwrap();
+        assert!(machine.fuzzy_values.is_empty());
+        assert_eq!(machine.set_value_with_spread("temp", 10.0, -1.0),
+                   Err(InferenceError::InvalidValue("temp".to_string())));
+    }
+
+    #[test]
+    fn center_of_sums() {
+        let mut options = options();
+        options.sums_defuzz_func = Some(DefuzzFactory::center_of_sums());
+        let mut universes = universes();
+        universes.get_mut("fan")
+                 .unwrap()
+                 .replace_set("fast".to_string(), MembershipFactory::triangular(0.0, 4.0, 10.0));
+        let mut sums = InferenceMachine::new(rules("slow", "fast"), universes.clone(), options);
+        let mut mass = InferenceMachine::new(rules("slow", "fast"), universes, self::options());
+        for &temp in &[5.0, 20.0, 35.0] {
+            let value = compute_at(&mut sums, temp).unwrap().value;
+            let context = InferenceContext {
+                values: &sums.values,
+                universes: &sums.universes,
+                bindings: &sums.bindings,
+                fuzzy_values: &sums.fuzzy_values,
+                options: &sums.options,
+            };
+            let sets = sums.rules.compute_each(&context).unwrap();
+            assert_eq!(sets.len(), 2);
+            let expected = DefuzzFactory::center_of_sums()(&sets).unwrap();
+            // Sets iterate their caches in different order, so sums may differ in the last bits.
+            assert!((value - expected).abs() <= 1e-5 * expected.abs());
+        }
+        // Both rules fire with 0.2 and their results overlap, the overlap counts twice.
+        let value = compute_at(&mut sums, 20.0).unwrap().value;
+        let expected = compute_at(&mut mass, 20.0).unwrap().value;
+        assert!((value - expected).abs() > 0.1);
+        assert_eq!(compute_at(&mut sums, 100.0), Err(InferenceError::NoRuleFired));
+    }
+
+    #[test]
+    fn weighted_average() {
+        let mut machine = machine();
+        let defuzz_func = DefuzzFactory::weighted_average();
+        let stats = |machine: &InferenceMachine| {
+            machine.universes["fan"]
+                .sets
+                .values()
+                .map(|set| (set.cache_stats(), set.cache.borrow().len()))
+                .collect::<Vec<_>>()
+        };
+        let before = stats(&machine);
+        // "cold" is 0.6 at 10 and "hot" is 0, the peak of "slow" is 0.
+        machine.set_value("temp", 10.0).unwrap();
+        assert_eq!(machine.compute_weighted(&*defuzz_func), Ok(0.0));
+        // Both are 0.2 at 20, the peak of "fast" is 10.
+        machine.set_value("temp", 20.0).unwrap();
+        assert!((machine.compute_weighted(&*defuzz_func).unwrap() - 5.0).abs() < 1e-6);
+        assert_eq!(machine.strengths().len(), 2);
+        machine.set_value("temp", 100.0).unwrap();
+        assert_eq!(machine.compute_weighted(&*defuzz_func), Err(InferenceError::NoRuleFired));
+        assert_eq!(stats(&machine), before);
+
+        machine.set_value("temp", 20.0).unwrap();
+        machine.compute_for("fan").unwrap();
+        assert!(stats(&machine) != before);
+    }
+
+    #[test]
+    fn clamped_result() {
+        let mut options = options();
+        options.defuzz_func = Box::new(|_: &Set| Ok(20.0));
+        let mut machine = InferenceMachine::new(rules("slow", "fast"), universes(), options);
+        let result = compute_at(&mut machine, 10.0).unwrap();
+        assert_eq!(result.value, 10.0);
+        assert!(result.clamped);
+    }
+
+    #[test]
+    fn no_rule_fired() {
+        let mut machine = machine();
+        assert_eq!(compute_at(&mut machine, 100.0), Err(InferenceError::NoRuleFired));
+        assert_eq!(machine.strengths(), &[0.0, 0.0]);
+        assert_eq!(compute_at(&mut machine, -50.0), Err(InferenceError::NoRuleFired));
+        assert_eq!(machine.strengths(), &[0.0, 0.0]);
+        assert!(compute_at(&mut machine, 20.0).is_ok());
+        assert!(machine.strengths().iter().all(|&strength| strength > 0.0));
+    }
+
+    #[test]
+    fn empty_output_of_every_defuzzifier() {
+        let defuzz_funcs = vec![DefuzzFactory::center_of_mass(),
+                                DefuzzFactory::mean_of_maxima(),
+                                DefuzzFactory::smallest_of_maxima(),
+                                DefuzzFactory::largest_of_maxima(),
+                                DefuzzFactory::bisector(),
+                                DefuzzFactory::centroid_sampled(0.0, 10.0, 101)];
+        let mut universes = universes();
+        // The rule fires, but its result is zero over the domain.
+        universes.get_mut("fan")
+                 .unwrap()
+                 .create_set("none".to_string(), MembershipFactory::triangular(20.0, 30.0, 40.0))
+                 .unwrap();
+        for defuzz_func in defuzz_funcs {
+            let mut options = options();
+            options.defuzz_func = defuzz_func;
+            let rules = rules("none", "fast");
+            let mut machine = InferenceMachine::new(rules, universes.clone(), options);
+            assert_eq!(compute_at(&mut machine, 0.0), Err(InferenceError::NoRuleFired));
+            assert_eq!(compute_at(&mut machine, 100.0), Err(InferenceError::NoRuleFired));
+            assert!(compute_at(&mut machine, 40.0).unwrap().value.is_finite());
+        }
+        let mut options = options();
+        options.sums_defuzz_func = Some(DefuzzFactory::center_of_sums());
+        let mut machine = InferenceMachine::new(rules("none", "fast"), universes, options);
+        assert_eq!(compute_at(&mut machine, 0.0), Err(InferenceError::NoRuleFired));
+    }
+
+    #[test]
+    fn reject_invalid_values() {
+        let mut machine = machine();
+        let expected = compute_at(&mut machine, 20.0).unwrap();
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), f32::NAN);
+        assert_eq!(machine.update(&values),
+                   Err(InferenceError::InvalidValue("temp".to_string())));
+        assert_eq!(machine.set_value("temp", f32::INFINITY),
+                   Err(InferenceError::InvalidValue("temp".to_string())));
+        assert_eq!(machine.values["temp"], 20.0);
+        for set in machine.universes["temp"].sets.values() {
+            assert!(set.cache.borrow().keys().all(|key| !key.0.is_nan()));
+        }
+        assert!((machine.compute_for("fan").unwrap().value - expected.value).abs() < 1e-5);
+        machine.set_value("temp", 21.0).unwrap();
+        assert!(machine.compute_for("fan").is_ok());
+    }
+
+    #[test]
+    fn bound_variables() {
+        let condition = And::new(Is::new("inlet".to_string(), "hot".to_string()),
+                                 Is::new("outlet".to_string(), "cold".to_string()));
+        let rules = vec![Rule::new(Box::new(condition), "fan".to_string(), "fast".to_string())];
+        let rules = RuleSet::new(rules).unwrap();
+        let mut machine = InferenceMachine::new(rules, universes(), options());
+        machine.bind_variable("inlet", "temp");
+        machine.bind_variable("outlet", "temp");
+        machine.set_value("inlet", 38.0).unwrap();
+        machine.set_value("outlet", 5.0).unwrap();
+        {
+            let context = InferenceContext {
+                values: &machine.values,
+                universes: &machine.universes,
+                bindings: &machine.bindings,
+                fuzzy_values: &machine.fuzzy_values,
+                options: &machine.options,
+            };
+            assert!((context.membership("inlet", "hot").unwrap() - 0.92).abs() < 1e-6);
+            assert!((context.membership("outlet", "cold").unwrap() - 0.8).abs() < 1e-6);
+            assert_eq!(context.membership("outlet", "hot"), Ok(0.0));
+            assert_eq!(context.membership("outlet", "warm"),
+                       Err(InferenceError::UnknownSet {
+                           universe: "temp".to_string(),
+                           set: "warm".to_string(),
+                       }));
+        }
+        assert!(machine.compute_for("fan").is_ok());
+        assert!((machine.strengths()[0] - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn unbound_variable() {
+        let rules = vec![Rule::new(Box::new(Is::new("pressure".to_string(), "high".to_string())),
+                                   "fan".to_string(),
+                                   "fast".to_string())];
+        let rules = RuleSet::new(rules).unwrap();
+        let mut machine = InferenceMachine::new(rules, universes(), options());
+        machine.set_value("pressure", 1.0).unwrap();
+        assert_eq!(machine.compute_for("fan"),
+                   Err(InferenceError::UnboundVariable("pressure".to_string())));
+        machine.bind_variable("pressure", "barometer");
+        assert_eq!(machine.compute_for("fan"),
+                   Err(InferenceError::UnknownUniverse("barometer".to_string())));
+    }
+
+    #[test]
+    fn summary() {
+        let mut machine = machine();
+        machine.set_value("temp", 20.5).unwrap();
+        let summary = machine.summary();
+        assert_eq!(summary,
+                   "(InferenceMachine\n\
+                    universes:\n\
+                    \tfan [0, 10] 101 points: fast, slow\n\
+                    \ttemp [0, 40] 41 points: cold, hot\n\
+                    rules:\n\
+                    (RuleSet\n\
+                    \t(Rule fan:slow if:(is temp cold))\n\
+                    \t(Rule fan:fast if:(is temp hot))\n\
+                    )\n\
+                    logic ops: zadeh\n\
+                    set ops: minmax\n\
+                    implication: min\n\
+                    defuzzification: aggregated set\n\
+                    values:\n\
+                    \ttemp = 20.5\n\
+                    )");
+        assert_eq!(machine.summary(), format!("{}", machine));
+
+        let rules = vec![Rule::new(Box::new(Is::new("temp".to_string(), "cold".to_string())),
+                                   "fan".to_string(),
+                                   "slow".to_string()),
+                         Rule::new(Box::new(Is::new("temp".to_string(), "hot".to_string())),
+                                   "fan".to_string(),
+                                   "fast".to_string()),
+                         Rule::new(Box::new(Is::new("temp".to_string(), "hot".to_string())),
+                                   "fan".to_string(),
+                                   "slow".to_string())];
+        machine.rules = RuleSet::new(rules).unwrap();
+        assert!(machine.summary() != summary);
+        assert!(machine.summary().contains("\t(Rule fan:slow if:(is temp hot))\n"));
+
+        machine.options.sums_defuzz_func = Some(DefuzzFactory::center_of_sums());
+        assert!(machine.summary().contains("defuzzification: rules' results taken separately\n"));
+    }
+
+    #[test]
+    fn linguistic_approximation() {
+        let mut machine = machine();
+        machine.set_value("temp", 40.0).unwrap();
+        let (name, score) = machine.approximate_linguistically().unwrap();
+        assert_eq!(name, "fast");
+        assert!((score - 1.0).abs() < 1e-6);
+
+        // Both rules fire with 0.2, the output straddles both sets.
+        machine.set_value("temp", 20.0).unwrap();
+        let (name, score) = machine.approximate_linguistically().unwrap();
+        let slow = machine.scratch.output.similarity(&machine.universes["fan"].sets["slow"]);
+        let fast = machine.scratch.output.similarity(&machine.universes["fan"].sets["fast"]);
+        assert_eq!(score, slow.max(fast));
+        assert_eq!(name, if slow > fast { "slow" } else { "fast" });
+        assert!(score < 0.5);
+
+        // Ties are resolved by name.
+        let (name, score) = machine.approximate_linguistically_with(&|_: &Set, _: &Set| 0.5)
+                                   .unwrap();
+        assert_eq!((name.as_str(), score), ("fast", 0.5));
+
+        machine.set_value("temp", 100.0).unwrap();
+        assert_eq!(machine.approximate_linguistically(), Err(InferenceError::NoRuleFired));
+    }
+
+    #[test]
+    fn linguistic_variables() {
+        let mut universes = universes();
+        let temp = universes.remove("temp").unwrap();
+        let fan = universes.remove("fan").unwrap();
+        let variables = vec![LinguisticVariable::input("inlet", temp.clone())
+                                 .with_unit("C")
+                                 .with_default(35.0),
+                             LinguisticVariable::output("fan", fan.clone())];
+        let condition = Is::new("inlet".to_string(), "hot".to_string());
+        let rule = Rule::new(Box::new(condition), "fan".to_string(), "fast".to_string());
+        let mut machine = InferenceMachine::from_variables(RuleSet::new(vec![rule]).unwrap(),
+                                                           variables,
+                                                           options())
+                              .unwrap();
+        assert_eq!(machine.values["inlet"], 35.0);
+        assert_eq!(machine.bindings["inlet"], "temp");
+        assert!(machine.compute_for("fan").unwrap().value > 5.0);
+
+        // Results of the rules are aggregated only in output universes.
+        let variables = vec![LinguisticVariable::input("temp", temp),
+                             LinguisticVariable::input("fan", fan)];
+        assert_eq!(InferenceMachine::from_variables(rules("slow", "fast"), variables, options())
+                       .err(),
+                   Some(InferenceError::NotAnOutput("fan".to_string())));
+    }
+
+    #[test]
+    fn traced_computation() {
+        let mut machine = machine();
+        let hot = Box::new(Is::new("temp".to_string(), "hot".to_string()));
+        machine.add_rule(Rule::with_weight(hot, "fan".to_string(), "slow".to_string(), 0.5))
+               .unwrap();
+        let (value, activations) = {
+            let mut values = HashMap::new();
+            values.insert("temp".to_string(), 22.0);
+            machine.update(&values).unwrap();
+            machine.compute_traced().unwrap()
+        };
+        assert_eq!(value, machine.compute_for("fan").unwrap().value);
+        assert_eq!(activations.iter().map(|activation| activation.index).collect::<Vec<_>>(),
+                   vec![1, 2, 0]);
+        assert_eq!(activations[0].result_set, Some("fast".to_string()));
+        assert_eq!(activations[0].rule, machine.rules.rules()[1].to_string());
+        assert!(activations.windows(2).all(|pair| pair[0].strength >= pair[1].strength));
+
+        let context = InferenceContext {
+            values: &machine.values,
+            universes: &machine.universes,
+            bindings: &machine.bindings,
+            fuzzy_values: &machine.fuzzy_values,
+            options: &machine.options,
+        };
+        let rules = machine.rules.rules();
+        for activation in &activations {
+            let rule = &rules[activation.index];
+            let condition = rule.condition().eval(&context).unwrap();
+            assert!((activation.strength - condition * rule.weight()).abs() < 1e-6);
+        }
+        let (set, traced) = machine.rules.compute_with_trace(&context).unwrap();
+        assert_eq!(traced, activations);
+        assert_eq!(set, *machine.output());
+    }
+
+    #[test]
+    fn rule_management() {
+        let mut machine = machine();
+        let original = compute_at(&mut machine, 35.0).unwrap().value;
+        let hot = || Box::new(Is::new("temp".to_string(), "hot".to_string()));
+        machine.add_rule(Rule::new(hot(), "fan".to_string(), "slow".to_string())).unwrap();
+        assert_eq!(machine.rules.len(), 3);
+        let contradicted = compute_at(&mut machine, 35.0).unwrap().value;
+        assert!(contradicted < original - 1.0);
+
+        let removed = machine.remove_rule(2).unwrap();
+        assert_eq!(removed.result_set(), "slow");
+        assert!((compute_at(&mut machine, 35.0).unwrap().value - original).abs() < 1e-6);
+
+        let replaced = machine.replace_rule(1, removed).unwrap();
+        assert_eq!(replaced.result_set(), "fast");
+        assert_eq!(machine.rules.iter().map(|rule| rule.result_set()).collect::<Vec<_>>(),
+                   vec!["slow", "slow"]);
+        assert!(compute_at(&mut machine, 35.0).unwrap().value < contradicted);
+        assert_eq!(machine.replace_rule(2, replaced).err(),
+                   Some(RuleSetError::InvalidIndex { index: 2, len: 2 }));
+
+        // A rule set is never empty, so the last rule stays.
+        machine.remove_rule(0).unwrap();
+        assert_eq!(machine.remove_rule(0).err(), Some(RuleSetError::LastRule));
+        assert_eq!(machine.rules.len(), 1);
+    }
+
+    fn valve() -> UniversalSet {
+        let mut valve = UniversalSet::new("valve".to_string());
+        valve.set_domain((0..101).map(|x| x as f32).collect());
+        valve.create_set("closed".to_string(),
+                         MembershipFactory::triangular(-50.0, 0.0, 60.0)).unwrap();
+        valve.create_set("open".to_string(),
+                         MembershipFactory::triangular(40.0, 100.0, 150.0)).unwrap();
+        valve
+    }
+
+    #[test]
+    fn multiple_outputs() {
+        let mut universes = universes();
+        universes.insert("valve".to_string(), valve());
+        let when = |set: &str| Box::new(Is::new("temp".to_string(), set.to_string()));
+        let rules = vec![Rule::new(when("cold"), "fan".to_string(), "slow".to_string())
+                             .with_consequent("valve".to_string(), "closed".to_string()),
+                         Rule::new(when("hot"), "fan".to_string(), "fast".to_string())
+                             .with_consequent("valve".to_string(), "open".to_string())];
+        let rules = RuleSet::new(rules).unwrap();
+        assert_eq!(rules.result_universes(), vec!["fan", "valve"]);
+        let mut machine = InferenceMachine::new(rules, universes.clone(), options());
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), 30.0);
+        machine.update(&values).unwrap();
+        let outputs = machine.compute_outputs().unwrap();
+        assert_eq!(outputs.len(), 2);
+
+        // Each output matches a machine inferring it alone.
+        let fan = compute_at(&mut machine_for(universes.clone(), "fan", "slow", "fast"), 30.0)
+                      .unwrap();
+        let result = outputs["fan"].as_ref().unwrap();
+        assert_eq!(result.dominant_rule, 1);
+        assert!((result.value - fan.value).abs() < 1e-5);
+        let valve =
+            compute_at(&mut machine_for(universes.clone(), "valve", "closed", "open"), 30.0)
+                .unwrap();
+        let result = outputs["valve"].as_ref().unwrap();
+        assert_eq!(result.output_universe, "valve");
+        assert_eq!(result.dominant_rule, 1);
+        assert!((result.value - valve.value).abs() < 1e-4);
+        assert!(result.value > 50.0);
+
+        // `compute` infers every output, `compute_for` a single one.
+        let results = machine.compute().unwrap();
+        assert_eq!(results.len(), 2);
+        assert!((results["valve"].value - valve.value).abs() < 1e-4);
+        assert!((machine.compute_for("fan").unwrap().value - fan.value).abs() < 1e-5);
+        assert_eq!(machine.compute_for("pressure"),
+                   Err(InferenceError::UnknownUniverse("pressure".to_string())));
+
+        // Sums of the rules' results are defuzzified in every output.
+        machine.options.sums_defuzz_func = Some(DefuzzFactory::center_of_sums());
+        let outputs = machine.compute_outputs().unwrap();
+        let terms = [("fan", "slow", "fast"), ("valve", "closed", "open")];
+        for &(output, when_cold, when_hot) in &terms {
+            let mut alone = machine_for(universes.clone(), output, when_cold, when_hot);
+            alone.options.sums_defuzz_func = Some(DefuzzFactory::center_of_sums());
+            let expected = compute_at(&mut alone, 30.0).unwrap();
+            assert!((outputs[output].as_ref().unwrap().value - expected.value).abs() < 1e-4);
+        }
+        // The aggregated set of the last output is kept.
+        let mut alone = machine_for(universes, "valve", "closed", "open");
+        alone.options.sums_defuzz_func = Some(DefuzzFactory::center_of_sums());
+        compute_at(&mut alone, 30.0).unwrap();
+        assert_eq!(alone.output(), machine.output());
+    }
+
+    #[test]
+    fn first_consequent_in_other_universe() {
+        let mut universes = universes();
+        universes.insert("valve".to_string(), valve());
+        let when = |set: &str| Box::new(Is::new("temp".to_string(), set.to_string()));
+        // The second rule concludes in "valve" first, the third one only in "valve".
+        let rules = vec![Rule::new(when("cold"), "fan".to_string(), "slow".to_string()),
+                         Rule::new(when("hot"), "valve".to_string(), "open".to_string())
+                             .with_consequent("fan".to_string(), "fast".to_string()),
+                         Rule::new(when("hot"), "valve".to_string(), "closed".to_string())];
+        let mut machine =
+            InferenceMachine::new(RuleSet::new(rules).unwrap(), universes.clone(), options());
+        let mut alone = machine_for(universes, "fan", "slow", "fast");
+        let defuzz_func = DefuzzFactory::weighted_average();
+        for &temp in &[10.0, 20.0, 30.0] {
+            machine.set_value("temp", temp).unwrap();
+            alone.set_value("temp", temp).unwrap();
+            assert_eq!(machine.compute_weighted(&*defuzz_func),
+                       alone.compute_weighted(&*defuzz_func));
+            assert_eq!(machine.strengths().len(), 3);
+        }
+
+        let (_, activations) = machine.compute_traced().unwrap();
+        let result_sets = activations.iter()
+                                     .map(|activation| {
+                                         (activation.index, activation.result_set.clone())
+                                     })
+                                     .collect::<HashMap<_, _>>();
+        assert_eq!(result_sets[&1], Some("fast".to_string()));
+        assert_eq!(result_sets[&2], None);
+    }
+
+    #[test]
+    fn dominant_rule_in_result_universe() {
+        let mut universes = universes();
+        universes.insert("valve".to_string(), valve());
+        let when = |set: &str| Box::new(Is::new("temp".to_string(), set.to_string()));
+        // "hot" fires stronger at 22, but only concludes in "valve".
+        let rules = vec![Rule::new(when("cold"), "fan".to_string(), "slow".to_string()),
+                         Rule::new(when("hot"), "valve".to_string(), "open".to_string())
+                             .with_certainty(0.2)];
+        let mut machine =
+            InferenceMachine::new(RuleSet::new(rules).unwrap(), universes, options());
+        let result = compute_at(&mut machine, 22.0).unwrap();
+        assert!(machine.strengths()[1] > machine.strengths()[0]);
+        assert_eq!(result.dominant_rule, 0);
+        assert_eq!(result.confidence, 1.0);
+        machine.set_confidence(Confidence::Max);
+        assert_eq!(compute_at(&mut machine, 22.0).unwrap().confidence, 1.0);
+        // No rule concluding in "fan" fires at 30.
+        assert_eq!(compute_at(&mut machine, 30.0).err(), Some(InferenceError::NoRuleFired));
+        assert_eq!(machine.compute().err(), Some(InferenceError::NoRuleFired));
+        // Other outputs are still inferred.
+        let outputs = machine.compute_outputs().unwrap();
+        assert_eq!(outputs["fan"], Err(InferenceError::NoRuleFired));
+        assert_eq!(outputs["valve"].as_ref().unwrap().confidence, 0.2);
+    }
+
+    #[test]
+    fn two_inputs_two_outputs() {
+        let mut temp = UniversalSet::new("temp".to_string());
+        temp.set_domain((0..41).map(|x| x as f32).collect());
+        temp.create_set("cold".to_string(),
+                        MembershipFactory::triangular(-40.0, 0.0, 40.0)).unwrap();
+        temp.create_set("hot".to_string(), MembershipFactory::triangular(0.0, 40.0, 80.0)).unwrap();
+        let mut humidity = UniversalSet::new("humidity".to_string());
+        humidity.set_domain((0..101).map(|x| x as f32).collect());
+        humidity.create_set("dry".to_string(),
+                            MembershipFactory::triangular(-100.0, 0.0, 100.0)).unwrap();
+        humidity.create_set("wet".to_string(),
+                            MembershipFactory::triangular(0.0, 100.0, 200.0)).unwrap();
+        let mut fan = UniversalSet::new("fan".to_string());
+        fan.set_domain((0..1001).map(|x| x as f32 / 100.0).collect());
+        fan.create_set("slow".to_string(), MembershipFactory::triangular(0.0, 2.0, 4.0)).unwrap();
+        fan.create_set("fast".to_string(), MembershipFactory::triangular(6.0, 8.0, 10.0)).unwrap();
+        let mut valve = UniversalSet::new("valve".to_string());
+        valve.set_domain((0..1001).map(|x| x as f32 / 10.0).collect());
+        valve.create_set("closed".to_string(),
+                         MembershipFactory::triangular(0.0, 20.0, 40.0)).unwrap();
+        valve.create_set("open".to_string(),
+                         MembershipFactory::triangular(60.0, 80.0, 100.0)).unwrap();
+        let mut universes = HashMap::new();
+        for universe in [temp, humidity, fan, valve] {
+            universes.insert(universe.name().to_string(), universe);
+        }
+        let is = |variable: &str, set: &str| Is::new(variable.to_string(), set.to_string());
+        let rules = vec![Rule::new(Box::new(And::new(is("temp", "cold"), is("humidity", "dry"))),
+                                   "fan".to_string(),
+                                   "slow".to_string())
+                             .with_consequent("valve".to_string(), "closed".to_string()),
+                         Rule::new(Box::new(Or::new(is("temp", "hot"), is("humidity", "wet"))),
+                                   "fan".to_string(),
+                                   "fast".to_string()),
+                         Rule::new(Box::new(is("humidity", "wet")),
+                                   "valve".to_string(),
+                                   "open".to_string())];
+        let mut machine = InferenceMachine::new(RuleSet::new(rules).unwrap(), universes, options());
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), 10.0);
+        values.insert("humidity".to_string(), 20.0);
+        machine.update(&values).unwrap();
+
+        // Firing strengths are min(0.75, 0.8), max(0.25, 0.2) and 0.2. A triangle with base `w`
+        // clipped at `h` has area w * h * (1 - h / 2), its centroid stays at the peak.
+        let area = |base: f32, height: f32| base * height * (1.0 - height / 2.0);
+        let (slow, fast) = (area(4.0, 0.75), area(4.0, 0.25));
+        let (closed, open) = (area(40.0, 0.75), area(40.0, 0.2));
+        let expected_fan = (2.0 * slow + 8.0 * fast) / (slow + fast);
+        let expected_valve = (20.0 * closed + 80.0 * open) / (closed + open);
+        let outputs = machine.compute().unwrap();
+        assert_eq!(outputs["fan"].dominant_rule, 0);
+        assert!((outputs["fan"].value - expected_fan).abs() < 1e-3);
+        assert_eq!(outputs["valve"].dominant_rule, 0);
+        assert!((outputs["valve"].value - expected_valve).abs() < 1e-2);
+
+        let context = InferenceContext {
+            values: &machine.values,
+            universes: &machine.universes,
+            bindings: &machine.bindings,
+            fuzzy_values: &machine.fuzzy_values,
+            options: &machine.options,
+        };
+        let sets = machine.rules.compute_outputs(&context).unwrap();
+        assert_eq!(sets.len(), 2);
+        assert!((sets["valve"].height() - 0.75).abs() < 1e-6);
+        assert!((sets["valve"].membership_at(80.0) - 0.2).abs() < 1e-6);
+        assert_eq!(sets["fan"], machine.rules.compute_all(&context).unwrap());
+    }
+
+    fn machine_for(universes: HashMap<String, UniversalSet>,
+                   output: &str,
+                   when_cold: &str,
+                   when_hot: &str)
+                   -> InferenceMachine {
+        let rules = vec![Rule::new(Box::new(Is::new("temp".to_string(), "cold".to_string())),
+                                   output.to_string(),
+                                   when_cold.to_string()),
+                         Rule::new(Box::new(Is::new("temp".to_string(), "hot".to_string())),
+                                   output.to_string(),
+                                   when_hot.to_string())];
+        InferenceMachine::new(RuleSet::new(rules).unwrap(), universes, options())
+    }
+
+    #[test]
+    fn shared_universes() {
+        let shared = universes()
+                         .into_iter()
+                         .map(|(name, universe)| (name, Rc::new(universe)))
+                         .collect::<HashMap<_, _>>();
+        let mut first = InferenceMachine::new(rules("slow", "fast"), shared.clone(), options());
+        let mut second = InferenceMachine::new(rules("fast", "slow"), shared.clone(), options());
+        assert!(Rc::ptr_eq(&first.universes["temp"], &second.universes["temp"]));
+
+        // Both machines use the memberships precomputed over the shared domain.
+        let misses = shared["temp"].sets["cold"].cache_stats().misses;
+        let first_result = compute_at(&mut first, 10.0).unwrap();
+        let second_result = compute_at(&mut second, 10.0).unwrap();
+        assert_eq!(shared["temp"].sets["cold"].cache_stats().misses, misses);
+        assert_eq!(shared["temp"].sets["cold"].cache_stats().hits, 2);
+
+        let mut owned_first = machine();
+        let mut owned_second = InferenceMachine::new(rules("fast", "slow"), universes(), options());
+        let owned_first_result = compute_at(&mut owned_first, 10.0).unwrap();
+        assert_eq!(first_result.dominant_rule, owned_first_result.dominant_rule);
+        assert!((first_result.value - owned_first_result.value).abs() < 1e-5);
+        let owned_second_result = compute_at(&mut owned_second, 10.0).unwrap();
+        assert!((second_result.value - owned_second_result.value).abs() < 1e-5);
+        assert!(first_result.value < second_result.value);
+    }
+
+    #[test]
+    fn universe_copy_on_write() {
+        let shared = universes()
+                         .into_iter()
+                         .map(|(name, universe)| (name, Rc::new(universe)))
+                         .collect::<HashMap<_, _>>();
+        let mut first = InferenceMachine::new(rules("slow", "fast"), shared.clone(), options());
+        let second = InferenceMachine::new(rules("fast", "slow"), shared.clone(), options());
+        first.universe_mut("fan")
+             .unwrap()
+             .create_set("medium".to_string(),
+                         MembershipFactory::triangular(3.0, 5.0, 7.0)).unwrap();
+        assert!(first.universes["fan"].sets.contains_key("medium"));
+        assert!(!second.universes["fan"].sets.contains_key("medium"));
+        assert!(!Rc::ptr_eq(&first.universes["fan"], &second.universes["fan"]));
+        assert!(Rc::ptr_eq(&first.universes["temp"], &second.universes["temp"]));
     }
 }
@@ -0,0 +1,496 @@
+//! This module contains methods to derive fuzzy sets from data.
+//!
+//! `fcm` clusters one-dimensional samples with fuzzy c-means,
+//! `FcmResult::install` turns the clusters into sets of a universe.
+//! `wang_mendel` generates rules from examples over already partitioned universes.
+use functions::{MembershipFactory, MembershipFunction};
+use rules::{And, Expression, Is, Rule, RuleSet};
+use set::{Set, SetError, UniversalSet};
+use std::collections::HashMap;
+use std::error::Error;
+use std::f32;
+use std::fmt;
+
+/// Error of learning from data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LearnError {
+    /// There are no records.
+    NoData,
+    /// Sample with given index is NaN or infinite.
+    InvalidSample(usize),
+    /// The first record has no input variables.
+    NoInputs,
+    /// Universe with given name doesn't exist.
+    UnknownUniverse(String),
+    /// Universe with given name has no sets.
+    NoSets(String),
+    /// Record with given index has no value of the variable.
+    MissingValue {
+        /// Index of the record.
+        record: usize,
+        /// Name of the variable.
+        variable: String,
+    },
+    /// No record has a positive membership in the sets, so no rule is generated.
+    NoRules,
+}
+
+impl fmt::Display for LearnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LearnError::NoData => write!(f, "there are no records"),
+            LearnError::InvalidSample(index) => {
+                write!(f, "sample {} is not a finite number", index)
+            }
+            LearnError::NoInputs => write!(f, "there are no input variables"),
+            LearnError::UnknownUniverse(ref universe) => {
+                write!(f, "universe {} doesn't exist", universe)
+            }
+            LearnError::NoSets(ref universe) => write!(f, "universe {} has no sets", universe),
+            LearnError::MissingValue { record, ref variable } => {
+                write!(f, "record {} has no value of {}", record, variable)
+            }
+            LearnError::NoRules => write!(f, "no rule is generated"),
+        }
+    }
+}
+
+impl Error for LearnError {
+    fn description(&self) -> &str {
+        match *self {
+            LearnError::NoData => "there are no records",
+            LearnError::InvalidSample(_) => "sample is not a finite number",
+            LearnError::NoInputs => "there are no input variables",
+            LearnError::UnknownUniverse(_) => "universe doesn't exist",
+            LearnError::NoSets(_) => "universe has no sets",
+            LearnError::MissingValue { .. } => "record has no value of the variable",
+            LearnError::NoRules => "no rule is generated",
+        }
+    }
+}
+
+/// Result of the fuzzy c-means clustering.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FcmResult {
+    /// Centers of the clusters in ascending order.
+    pub centers: Vec<f32>,
+    /// Membership matrix, `memberships[i][k]` is the membership of the `k`-th sample
+    /// in the `i`-th cluster. Memberships of each sample sum to 1.
+    pub memberships: Vec<Vec<f32>>,
+    /// Spreads of the clusters: standard deviations of the samples weighted by memberships.
+    pub spreads: Vec<f32>,
+    /// Number of iterations done.
+    pub iterations: usize,
+}
+
+/// Shape of the sets derived from clusters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TermShape {
+    /// Gaussian function centered at the cluster's center, with the cluster's spread as width.
+    Gaussian,
+    /// Triangular function peaking at the cluster's center with feet at the neighbouring centers.
+    /// Outer feet are three spreads away from the center.
+    Triangular,
+}
+
+/// Clusters the samples into `clusters` fuzzy clusters with fuzzifier `m`.
+///
+/// Centers start at evenly spaced quantiles of the samples, so the result is deterministic.
+/// Iterations stop when no center moves further than `tol` or after `max_iter` iterations.
+/// Fails with `LearnError::NoData` if there are no samples
+/// and with `LearnError::InvalidSample` if any of them is NaN or infinite.
+///
+/// # Panics
+///
+/// Panics if there are no clusters, or if `m` is not greater than 1.
+pub fn fcm(samples: &[f32],
+           clusters: usize,
+           m: f32,
+           tol: f32,
+           max_iter: usize)
+           -> Result<FcmResult, LearnError> {
+    if samples.is_empty() {
+        return Err(LearnError::NoData);
+    }
+    if let Some(index) = samples.iter().position(|sample| !sample.is_finite()) {
+        return Err(LearnError::InvalidSample(index));
+    }
+    assert!(clusters > 0, "number of clusters must be positive");
+    assert!(m > 1.0, "fuzzifier must be greater than 1");
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mut centers = (0..clusters)
+                          .map(|i| {
+                              let q = (2 * i + 1) as f32 / (2 * clusters) as f32;
+                              sorted[((q * sorted.len() as f32) as usize).min(sorted.len() - 1)]
+                          })
+                          .collect::<Vec<_>>();
+    let mut memberships = vec![vec![0.0; samples.len()]; clusters];
+    let mut iterations = 0;
+    while iterations < max_iter {
+        iterations += 1;
+        update_memberships(samples, &centers, m, &mut memberships);
+        let mut shift: f32 = 0.0;
+        for (center, row) in centers.iter_mut().zip(&memberships) {
+            let (weighted, total) = samples.iter()
+                                           .zip(row)
+                                           .fold((0.0, 0.0), |(weighted, total), (&x, &u)| {
+                                               let w = u.powf(m);
+                                               (weighted + w * x, total + w)
+                                           });
+            if total > 0.0 {
+                let next = weighted / total;
+                shift = shift.max((next - *center).abs());
+                *center = next;
+            }
+        }
+        if shift <= tol {
+            break;
+        }
+    }
+    update_memberships(samples, &centers, m, &mut memberships);
+
+    let mut order = (0..clusters).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| centers[a].partial_cmp(&centers[b]).unwrap());
+    let centers = order.iter().map(|&i| centers[i]).collect::<Vec<_>>();
+    let memberships = order.iter().map(|&i| memberships[i].clone()).collect::<Vec<_>>();
+    let spreads = centers.iter()
+                         .zip(&memberships)
+                         .map(|(&center, row)| {
+                             let (weighted, total) =
+                                 samples.iter()
+                                        .zip(row)
+                                        .fold((0.0, 0.0), |(weighted, total), (&x, &u)| {
+                                            let w = u.powf(m);
+                                            (weighted + w * (x - center).powi(2), total + w)
+                                        });
+                             if total > 0.0 {
+                                 (weighted / total).sqrt()
+                             } else {
+                                 0.0
+                             }
+                         })
+                         .collect();
+    Ok(FcmResult {
+        centers: centers,
+        memberships: memberships,
+        spreads: spreads,
+        iterations: iterations,
+    })
+}
+
+/// Computes memberships of the samples in the clusters with given centers.
+fn update_memberships(samples: &[f32], centers: &[f32], m: f32, memberships: &mut [Vec<f32>]) {
+    let exponent = 2.0 / (m - 1.0);
+    for (k, &x) in samples.iter().enumerate() {
+        if let Some(hit) = centers.iter().position(|&center| center == x) {
+            for (i, row) in memberships.iter_mut().enumerate() {
+                row[k] = if i == hit { 1.0 } else { 0.0 };
+            }
+            continue;
+        }
+        for (i, &center) in centers.iter().enumerate() {
+            let distance = (x - center).abs();
+            let sum = centers.iter()
+                             .map(|&other| (distance / (x - other).abs()).powf(exponent))
+                             .fold(0.0, |sum, ratio| sum + ratio);
+            memberships[i][k] = 1.0 / sum;
+        }
+    }
+}
+
+impl FcmResult {
+    /// Returns labels of the sets derived from the clusters, from the lowest center up.
+    ///
+    /// Two, three and five clusters get linguistic labels, `term0`, `term1`, ... are used elsewise.
+    pub fn labels(&self) -> Vec<String> {
+        let labels: &[&str] = match self.centers.len() {
+            2 => &["low", "high"],
+            3 => &["low", "medium", "high"],
+            5 => &["very_low", "low", "medium", "high", "very_high"],
+            n => return (0..n).map(|i| format!("term{}", i)).collect(),
+        };
+        labels.iter().map(|label| label.to_string()).collect()
+    }
+
+    /// Returns membership functions of the given shape, one per cluster.
+    pub fn membership_functions(&self, shape: TermShape) -> Vec<Box<MembershipFunction>> {
+        let count = self.centers.len();
+        (0..count)
+            .map(|i| {
+                let center = self.centers[i];
+                let spread = self.spreads[i].max(f32::EPSILON * center.abs().max(1.0));
+                match shape {
+                    TermShape::Gaussian => MembershipFactory::gaussian(1.0, center, spread),
+                    TermShape::Triangular => {
+                        let left = if i > 0 {
+                            self.centers[i - 1]
+                        } else {
+                            center - 3.0 * spread
+                        };
+                        let right = if i + 1 < count {
+                            self.centers[i + 1]
+                        } else {
+                            center + 3.0 * spread
+                        };
+                        MembershipFactory::triangular(left, center, right)
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Creates sets of the given shape in the universe and returns their labels.
+    ///
+    /// Fails with `SetError::DuplicateSet` if the universe already has a set with the label,
+    /// sets created before the failure are kept.
+    pub fn install(&self,
+                   universe: &mut UniversalSet,
+                   shape: TermShape)
+                   -> Result<Vec<String>, SetError> {
+        let labels = self.labels();
+        for (label, membership) in labels.iter().zip(self.membership_functions(shape)) {
+            universe.create_set(label.clone(), membership)?;
+        }
+        Ok(labels)
+    }
+}
+
+/// Generates rules from `(inputs, output)` records with the Wang-Mendel method.
+///
+/// Inputs are the variables of the first record, every variable is in the universe of its name.
+/// Fails with `LearnError::NoInputs` if the first record has no variables.
+/// For each record the set with the highest membership is picked in every universe,
+/// forming the rule "if all inputs are in their sets then `output` is in its set"
+/// with the degree equal to the product of the memberships.
+/// Of the rules with the same condition only the one with the highest degree is kept.
+/// Ties between sets are resolved by their names, rules are sorted by their conditions.
+pub fn wang_mendel(data: &[(HashMap<String, f32>, f32)],
+                   universes: &HashMap<String, UniversalSet>,
+                   output: &str)
+                   -> Result<RuleSet, LearnError> {
+    if data.is_empty() {
+        return Err(LearnError::NoData);
+    }
+    let mut variables = data[0].0.keys().map(|variable| variable.as_str()).collect::<Vec<_>>();
+    if variables.is_empty() {
+        return Err(LearnError::NoInputs);
+    }
+    variables.sort();
+    let mut inputs = Vec::with_capacity(variables.len());
+    for variable in &variables {
+        inputs.push(partition(universes, variable)?);
+    }
+    let outputs = partition(universes, output)?;
+
+    let mut best: HashMap<Vec<&str>, (&str, f32)> = HashMap::new();
+    for (record, &(ref values, value)) in data.iter().enumerate() {
+        let mut condition = Vec::with_capacity(variables.len());
+        let (set, mut degree) = strongest(&outputs, value);
+        for (variable, sets) in variables.iter().zip(&inputs) {
+            let value = *values.get(*variable).ok_or_else(|| {
+                LearnError::MissingValue {
+                    record: record,
+                    variable: variable.to_string(),
+                }
+            })?;
+            let (set, mem) = strongest(sets, value);
+            condition.push(set);
+            degree *= mem;
+        }
+        if degree <= 0.0 {
+            continue;
+        }
+        let entry = best.entry(condition).or_insert((set, degree));
+        if degree > entry.1 {
+            *entry = (set, degree);
+        }
+    }
+
+    let mut best = best.into_iter().collect::<Vec<_>>();
+    best.sort_by(|a, b| a.0.cmp(&b.0));
+    let rules = best.into_iter()
+                    .map(|(condition, (set, _))| {
+                        let mut terms = variables.iter()
+                                                 .zip(condition)
+                                                 .map(|(variable, set)| {
+                                                     Box::new(Is::new(variable.to_string(),
+                                                                      set.to_string())) as
+                                                     Box<Expression>
+                                                 });
+                        let first = terms.next().unwrap();
+                        let condition = terms.fold(first, |left, right| {
+                            Box::new(And::new(left, right)) as Box<Expression>
+                        });
+                        Rule::new(condition, output.to_string(), set.to_string())
+                    })
+                    .collect::<Vec<_>>();
+    RuleSet::new(rules).map_err(|_| LearnError::NoRules)
+}
+
+/// Returns sets of the universe sorted by name.
+fn partition<'a>(universes: &'a HashMap<String, UniversalSet>,
+                 name: &str)
+                 -> Result<Vec<(&'a str, &'a Set)>, LearnError> {
+    let universe = universes.get(name)
+                            .ok_or_else(|| LearnError::UnknownUniverse(name.to_string()))?;
+    if universe.sets.is_empty() {
+        return Err(LearnError::NoSets(name.to_string()));
+    }
+    let mut sets = universe.sets
+                           .iter()
+                           .map(|(name, set)| (name.as_str(), set))
+                           .collect::<Vec<_>>();
+    sets.sort_by(|a, b| a.0.cmp(b.0));
+    Ok(sets)
+}
+
+/// Returns the set with the highest membership of the value, and the membership.
+fn strongest<'a>(sets: &[(&'a str, &Set)], value: f32) -> (&'a str, f32) {
+    let mut best = (sets[0].0, sets[0].1.membership_at(value));
+    for &(name, set) in &sets[1..] {
+        let mem = set.membership_at(value);
+        if mem > best.1 {
+            best = (name, mem);
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Samples around 0, 10 and 20 with deterministic noise.
+    fn samples() -> Vec<f32> {
+        (0..90).map(|i| (i % 3) as f32 * 10.0 + ((i * 7) % 11) as f32 / 10.0 - 0.5).collect()
+    }
+
+    #[test]
+    fn separated_modes() {
+        let samples = samples();
+        let result = fcm(&samples, 3, 2.0, 1e-4, 100).unwrap();
+        assert!(result.iterations < 100);
+        for (center, expected) in result.centers.iter().zip(&[0.0, 10.0, 20.0]) {
+            assert!((center - expected).abs() < 0.2, "{:?}", result.centers);
+        }
+        for k in 0..samples.len() {
+            let sum = result.memberships.iter().map(|row| row[k]).fold(0.0, |a, b| a + b);
+            assert!((sum - 1.0).abs() < 1e-4);
+            assert!(result.memberships.iter().all(|row| row[k] >= 0.0 && row[k] <= 1.0));
+            let nearest = ((samples[k] + 0.5) / 10.0).round() as usize;
+            assert!(result.memberships[nearest][k] > 0.9);
+        }
+        assert!(result.spreads.iter().all(|&spread| spread > 0.0 && spread < 1.0));
+    }
+
+    #[test]
+    fn invalid_samples() {
+        assert_eq!(fcm(&[], 3, 2.0, 1e-4, 100), Err(LearnError::NoData));
+        let mut samples = samples();
+        samples[4] = f32::NAN;
+        assert_eq!(fcm(&samples, 3, 2.0, 1e-4, 100), Err(LearnError::InvalidSample(4)));
+        samples[4] = 0.0;
+        samples[2] = f32::NEG_INFINITY;
+        assert_eq!(fcm(&samples, 3, 2.0, 1e-4, 100), Err(LearnError::InvalidSample(2)));
+    }
+
+    #[test]
+    fn install_terms() {
+        let result = fcm(&samples(), 3, 2.0, 1e-4, 100).unwrap();
+        let mut universe = UniversalSet::new("level".to_string());
+        assert_eq!(result.install(&mut universe, TermShape::Gaussian).unwrap(),
+                   vec!["low", "medium", "high"]);
+        assert!(universe.sets["medium"].check(result.centers[1]) > 0.999);
+        assert!(universe.sets["medium"].check(result.centers[0]) < 0.01);
+
+        let mut universe = UniversalSet::new("level".to_string());
+        result.install(&mut universe, TermShape::Triangular).unwrap();
+        let x = (result.centers[0] + result.centers[1]) / 2.0;
+        let sum = universe.sets["low"].check(x) + universe.sets["medium"].check(x);
+        assert!((sum - 1.0).abs() < 1e-4);
+        assert_eq!(result.install(&mut universe, TermShape::Triangular),
+                   Err(SetError::DuplicateSet("low".to_string())));
+    }
+
+    /// Universe with triangular sets peaking at given points, each with feet at the neighbours.
+    fn partition(name: &str, peaks: &[(&str, f32)]) -> UniversalSet {
+        let mut universe = UniversalSet::new(name.to_string());
+        for (i, &(set, peak)) in peaks.iter().enumerate() {
+            let left = if i > 0 { peaks[i - 1].1 } else { peak - 1.0 };
+            let right = peaks.get(i + 1).map_or(peak + 1.0, |&(_, right)| right);
+            universe.create_set(set.to_string(), MembershipFactory::triangular(left, peak, right))
+                    .unwrap();
+        }
+        universe
+    }
+
+    #[test]
+    fn recover_rules() {
+        let temp = [("cold", 0.0), ("warm", 20.0), ("hot", 40.0)];
+        let humidity = [("dry", 0.0), ("wet", 100.0)];
+        let fan = [("slow", 0.0), ("medium", 5.0), ("fast", 10.0)];
+        let table = [("dry", "cold", 0.0),
+                     ("dry", "warm", 5.0),
+                     ("dry", "hot", 10.0),
+                     ("wet", "cold", 0.0),
+                     ("wet", "warm", 0.0),
+                     ("wet", "hot", 5.0)];
+        let mut universes = HashMap::new();
+        universes.insert("temp".to_string(), partition("temp", &temp));
+        universes.insert("humidity".to_string(), partition("humidity", &humidity));
+        universes.insert("fan".to_string(), partition("fan", &fan));
+
+        // Outputs are the averages of the table's peaks weighted by the products of memberships.
+        let mut data = Vec::new();
+        for t in 0..21 {
+            for h in 0..11 {
+                let mut values = HashMap::new();
+                values.insert("temp".to_string(), t as f32 * 2.0);
+                values.insert("humidity".to_string(), h as f32 * 10.0);
+                let (weighted, total) = table.iter().fold((0.0, 0.0), |(weighted, total), row| {
+                    let w = universes["humidity"].sets[row.0].check(values["humidity"]) *
+                            universes["temp"].sets[row.1].check(values["temp"]);
+                    (weighted + w * row.2, total + w)
+                });
+                data.push((values, weighted / total));
+            }
+        }
+
+        let rules = wang_mendel(&data, &universes, "fan").unwrap();
+        assert_eq!(rules.len(), table.len());
+        let rules = format!("{}", rules);
+        for &(humidity, temp, peak) in &table {
+            let set = fan.iter().find(|&&(_, p)| p == peak).unwrap().0;
+            let rule = format!("(Rule fan:{} if:(and (is humidity {}) (is temp {})))",
+                               set,
+                               humidity,
+                               temp);
+            assert!(rules.contains(&rule), "{} not in {}", rule, rules);
+        }
+    }
+
+    #[test]
+    fn learn_errors() {
+        let mut universes = HashMap::new();
+        universes.insert("temp".to_string(), partition("temp", &[("cold", 0.0), ("hot", 40.0)]));
+        universes.insert("fan".to_string(), partition("fan", &[("slow", 0.0), ("fast", 10.0)]));
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), 10.0);
+        assert_eq!(wang_mendel(&[], &universes, "fan").err(), Some(LearnError::NoData));
+        assert_eq!(wang_mendel(&[(HashMap::new(), 1.0)], &universes, "fan").err(),
+                   Some(LearnError::NoInputs));
+        assert_eq!(wang_mendel(&[(values.clone(), 1.0)], &universes, "speed").err(),
+                   Some(LearnError::UnknownUniverse("speed".to_string())));
+        assert_eq!(wang_mendel(&[(values.clone(), 1.0), (HashMap::new(), 2.0)],
+                               &universes,
+                               "fan")
+                       .err(),
+                   Some(LearnError::MissingValue {
+                       record: 1,
+                       variable: "temp".to_string(),
+                   }));
+        assert_eq!(wang_mendel(&[(values, 100.0)], &universes, "fan").err(),
+                   Some(LearnError::NoRules));
+    }
+}
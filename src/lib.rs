@@ -5,11 +5,50 @@
 //! Rules are constructed with logical operations but implementation of operations is chosen by user.
 //!
 //! User is available to implement his own functions and operations.
+//!
+//! Membership functions are of the single `MembershipFunction` type, re-exported here
+//! along with `MembershipFactory`:
+//!
+//! ```
+//! use fuzzy_logic::{MembershipFactory, MembershipFunction};
+//! use fuzzy_logic::set::{Set, UniversalSet};
+//!
+//! let triangular: Box<MembershipFunction> = MembershipFactory::triangular(0.0, 1.0, 2.0);
+//! let set = Set::new_with_mem("Set", triangular);
+//! assert_eq!(set.check(1.0), 1.0);
+//!
+//! let mut universe = UniversalSet::new("Universe".to_string());
+//! universe.create_set("Set".to_string(), MembershipFactory::triangular(0.0, 1.0, 2.0)).unwrap();
+//! assert_eq!(universe.sets["Set"].check(1.0), 1.0);
+//! ```
+#[macro_use]
+mod macros;
+
 pub mod functions;
 pub mod set;
 pub mod ops;
 pub mod rules;
 pub mod inference;
+pub mod program;
+pub mod arena;
+pub mod builder;
+pub mod variable;
+pub mod surface;
+pub mod learning;
+pub mod tuning;
+pub mod controllers;
+pub mod type2;
+pub mod numbers;
+pub mod measures;
+pub mod analysis;
+pub mod evaluation;
+pub mod simulation;
+#[cfg(feature = "quickcheck")]
+pub mod arbitrary;
+#[cfg(feature = "plot")]
+pub mod plot;
+
+pub use functions::{MembershipFactory, MembershipFunction};
 
 #[cfg(test)]
 mod test {
@@ -0,0 +1,207 @@
+//! This module contains the `fuzzy_rules!` macro.
+//!
+//! The macro builds `Vec<Rule>` from rules written as
+//! `if <condition> => <universe> is <set>;`.
+//! Conditions consist of `<variable> is <set>` terms combined with `&&`, `||`, `!` and parentheses.
+//! `&&` binds tighter than `||`, both are left associative.
+
+/// Builds `Vec<Rule>` from the readable rules.
+///
+/// # Usage
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate fuzzy_logic;
+///
+/// # fn main() {
+/// let rules = fuzzy_rules! {
+///     if temp is hot && humidity is !low => fan is fast;
+///     if temp is cold || (temp is warm && !(humidity is high)) => fan is off;
+/// };
+/// assert_eq!(format!("{}", rules[0]),
+///            "(Rule fan:fast if:(and (is temp hot) (not (is humidity low))))");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! fuzzy_rules {
+    // Collects the rules.
+    (@rules [$($done:expr,)*]) => {
+        vec![$($done),*]
+    };
+    (@rules [$($done:expr,)*] if $($rest:tt)+) => {
+        fuzzy_rules!(@split [$($done,)*] [] $($rest)+)
+    };
+    (@rules [$($done:expr,)*] $($rest:tt)+) => {
+        compile_error!(concat!("expected `if <condition> => <universe> is <set>;`, found `",
+                               stringify!($($rest)+), "`"))
+    };
+
+    // Separates the condition from the result.
+    (@split [$($done:expr,)*] [$($condition:tt)+]
+     => $universe:ident is $set:ident; $($rest:tt)*) => {
+        fuzzy_rules!(@rules [$($done,)*
+                             $crate::rules::Rule::new(Box::new(fuzzy_rules!(@or [] $($condition)+)),
+                                                      stringify!($universe).to_string(),
+                                                      stringify!($set).to_string()),]
+                     $($rest)*)
+    };
+    (@split [$($done:expr,)*] [$($condition:tt)*] => $($rest:tt)*) => {
+        compile_error!(concat!("expected `<universe> is <set>;` after `=>`, found `",
+                               stringify!($($rest)*), "`"))
+    };
+    (@split [$($done:expr,)*] [$($condition:tt)*] $next:tt $($rest:tt)*) => {
+        fuzzy_rules!(@split [$($done,)*] [$($condition)* $next] $($rest)*)
+    };
+    (@split [$($done:expr,)*] [$($condition:tt)*]) => {
+        compile_error!(concat!("expected `=> <universe> is <set>;` after `",
+                               stringify!($($condition)*), "`"))
+    };
+
+    // Parses `||` of the operands, the first one is collected in brackets.
+    (@or [$($operand:tt)+] || $($rest:tt)+) => {
+        fuzzy_rules!(@or_tail (fuzzy_rules!(@and [] $($operand)+)) [] $($rest)+)
+    };
+    (@or [$($operand:tt)*] $next:tt $($rest:tt)*) => {
+        fuzzy_rules!(@or [$($operand)* $next] $($rest)*)
+    };
+    (@or [$($operand:tt)+]) => {
+        fuzzy_rules!(@and [] $($operand)+)
+    };
+    (@or_tail ($left:expr) [$($operand:tt)+] || $($rest:tt)+) => {
+        fuzzy_rules!(@or_tail ($crate::rules::Or::new($left, fuzzy_rules!(@and [] $($operand)+)))
+                     []
+                     $($rest)+)
+    };
+    (@or_tail ($left:expr) [$($operand:tt)*] $next:tt $($rest:tt)*) => {
+        fuzzy_rules!(@or_tail ($left) [$($operand)* $next] $($rest)*)
+    };
+    (@or_tail ($left:expr) [$($operand:tt)+]) => {
+        $crate::rules::Or::new($left, fuzzy_rules!(@and [] $($operand)+))
+    };
+
+    // Parses `&&` of the operands.
+    (@and [$($operand:tt)+] && $($rest:tt)+) => {
+        fuzzy_rules!(@and_tail (fuzzy_rules!(@term $($operand)+)) [] $($rest)+)
+    };
+    (@and [$($operand:tt)*] $next:tt $($rest:tt)*) => {
+        fuzzy_rules!(@and [$($operand)* $next] $($rest)*)
+    };
+    (@and [$($operand:tt)+]) => {
+        fuzzy_rules!(@term $($operand)+)
+    };
+    (@and_tail ($left:expr) [$($operand:tt)+] && $($rest:tt)+) => {
+        fuzzy_rules!(@and_tail ($crate::rules::And::new($left, fuzzy_rules!(@term $($operand)+)))
+                     []
+                     $($rest)+)
+    };
+    (@and_tail ($left:expr) [$($operand:tt)*] $next:tt $($rest:tt)*) => {
+        fuzzy_rules!(@and_tail ($left) [$($operand)* $next] $($rest)*)
+    };
+    (@and_tail ($left:expr) [$($operand:tt)+]) => {
+        $crate::rules::And::new($left, fuzzy_rules!(@term $($operand)+))
+    };
+
+    // Parses the term: `<variable> is <set>`, `<variable> is !<set>`, negation or parentheses.
+    (@term $variable:ident is ! $set:ident) => {
+        $crate::rules::Not::new(fuzzy_rules!(@term $variable is $set))
+    };
+    (@term $variable:ident is $set:ident) => {
+        $crate::rules::Is::new(stringify!($variable).to_string(), stringify!($set).to_string())
+    };
+    (@term ! $($term:tt)+) => {
+        $crate::rules::Not::new(fuzzy_rules!(@term $($term)+))
+    };
+    (@term ($($condition:tt)+)) => {
+        fuzzy_rules!(@or [] $($condition)+)
+    };
+    (@term $($term:tt)*) => {
+        compile_error!(concat!("expected `<variable> is <set>`, found `",
+                               stringify!($($term)*), "`"))
+    };
+
+    ($($rules:tt)*) => {
+        fuzzy_rules!(@rules [] $($rules)*)
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use functions::MembershipFactory;
+    use inference::{InferenceContext, InferenceOptions};
+    use rules::{And, Expression, Is, Not, Or, Rule};
+    use set::UniversalSet;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    fn is(variable: &str, set: &str) -> Is {
+        Is::new(variable.to_string(), set.to_string())
+    }
+
+    fn rule<E: 'static + Expression>(condition: E, universe: &str, set: &str) -> Rule {
+        Rule::new(Box::new(condition), universe.to_string(), set.to_string())
+    }
+
+    #[test]
+    fn macro_matches_constructors() {
+        let rules = fuzzy_rules! {
+            if temp is hot && humidity is !low => fan is fast;
+            if temp is cold => fan is off;
+            if temp is cold || temp is hot && !(humidity is low || humidity is high) => fan is slow;
+            if (temp is cold || temp is hot) && humidity is low && temp is !cold => fan is off;
+        };
+        let expected = vec![rule(And::new(is("temp", "hot"), Not::new(is("humidity", "low"))),
+                                 "fan",
+                                 "fast"),
+                            rule(is("temp", "cold"), "fan", "off"),
+                            rule(Or::new(is("temp", "cold"),
+                                         And::new(is("temp", "hot"),
+                                                  Not::new(Or::new(is("humidity", "low"),
+                                                                   is("humidity", "high"))))),
+                                 "fan",
+                                 "slow"),
+                            rule(And::new(And::new(Or::new(is("temp", "cold"), is("temp", "hot")),
+                                                   is("humidity", "low")),
+                                          Not::new(is("temp", "cold"))),
+                                 "fan",
+                                 "off")];
+        assert_eq!(rules.len(), expected.len());
+
+        let mut universes = HashMap::new();
+        for name in &["temp", "humidity"] {
+            let mut universe = UniversalSet::new(name.to_string());
+            for (i, set) in ["cold", "low", "hot", "high"].iter().enumerate() {
+                let peak = (i % 2) as f32;
+                universe.create_set(set.to_string(),
+                                    MembershipFactory::triangular(peak - 1.0, peak, peak + 1.0))
+                        .unwrap();
+            }
+            universes.insert(name.to_string(), Rc::new(universe));
+        }
+        let mut fan = UniversalSet::new("fan".to_string());
+        fan.set_domain((0..11).map(|x| x as f32 / 10.0).collect());
+        for set in &["off", "slow", "fast"] {
+            fan.create_set(set.to_string(), MembershipFactory::triangular(0.0, 0.5, 1.0)).unwrap();
+        }
+        universes.insert("fan".to_string(), Rc::new(fan));
+        let options = InferenceOptions::mamdani();
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        for i in 0..5 {
+            let mut values = HashMap::new();
+            values.insert("temp".to_string(), i as f32 / 4.0);
+            values.insert("humidity".to_string(), 1.0 - i as f32 / 3.0);
+            let context = InferenceContext {
+                values: &values,
+                universes: &universes,
+                bindings: &bindings,
+                fuzzy_values: &fuzzy_values,
+                options: &options,
+            };
+            for (rule, expected) in rules.iter().zip(&expected) {
+                assert_eq!(format!("{}", rule), format!("{}", expected));
+                assert_eq!(*rule.compute(&context).unwrap().remove(0).cache.borrow(),
+                           *expected.compute(&context).unwrap().remove(0).cache.borrow());
+            }
+        }
+    }
+}
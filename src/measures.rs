@@ -0,0 +1,135 @@
+//! This module contains possibility and necessity measures.
+//!
+//! Measures are evaluated over the union of cached points of the sets or over the given domain.
+//! Following the conventions for empty ranges, the possibility over no points is 0
+//! and the necessity is 1.
+use set::Set;
+use std::f32;
+
+/// Returns the possibility of `b` given `a`: supremum of `min(μa(x), μb(x))`.
+pub fn possibility(a: &Set, b: &Set) -> f32 {
+    possibility_over(a, b, &cached_points(&[a, b]))
+}
+
+/// Returns the necessity of `b` given `a`: infimum of `max(1 - μa(x), μb(x))`.
+pub fn necessity(a: &Set, b: &Set) -> f32 {
+    necessity_over(a, b, &cached_points(&[a, b]))
+}
+
+/// Returns the possibility of `b` given `a` over the domain.
+pub fn possibility_over(a: &Set, b: &Set, domain: &[f32]) -> f32 {
+    domain.iter()
+          .map(|&x| a.membership_at(x).min(b.membership_at(x)))
+          .fold(0.0, f32::max)
+}
+
+/// Returns the necessity of `b` given `a` over the domain.
+pub fn necessity_over(a: &Set, b: &Set, domain: &[f32]) -> f32 {
+    domain.iter()
+          .map(|&x| (1.0 - a.membership_at(x)).max(b.membership_at(x)))
+          .fold(1.0, f32::min)
+}
+
+/// Returns the possibility of the crisp interval `[min, max]` given `a`.
+///
+/// Cached points of `a` and the ends of the interval are considered,
+/// so `possibility_in(output, (80.0, f32::INFINITY))` tells how possible it is
+/// that the output exceeds 80.
+pub fn possibility_in(a: &Set, interval: (f32, f32)) -> f32 {
+    points_with_ends(a, interval).into_iter()
+                                  .filter(|&x| interval.0 <= x && x <= interval.1)
+                                  .map(|x| a.membership_at(x))
+                                  .fold(0.0, f32::max)
+}
+
+/// Returns the necessity of the crisp interval `[min, max]` given `a`.
+///
+/// It's 1 minus the highest membership outside the interval, over cached points of `a`.
+pub fn necessity_in(a: &Set, interval: (f32, f32)) -> f32 {
+    cached_points(&[a]).into_iter()
+                       .filter(|&x| x < interval.0 || x > interval.1)
+                       .map(|x| 1.0 - a.membership_at(x))
+                       .fold(1.0, f32::min)
+}
+
+/// Returns cached points of the sets, sorted and without duplicates.
+fn cached_points(sets: &[&Set]) -> Vec<f32> {
+    let mut points = sets.iter()
+                         .flat_map(|set| {
+                             set.cache.borrow().keys().map(|key| key.0).collect::<Vec<_>>()
+                         })
+                         .collect::<Vec<_>>();
+    points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    points.dedup();
+    points
+}
+
+/// Returns cached points of the set along with the finite ends of the interval.
+fn points_with_ends(a: &Set, interval: (f32, f32)) -> Vec<f32> {
+    let mut points = cached_points(&[a]);
+    points.extend([interval.0, interval.1].iter().cloned().filter(|x| x.is_finite()));
+    points
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use functions::MembershipFactory;
+    use set::UniversalSet;
+
+    /// Universe over [0, 10] with triangular sets.
+    fn universe(sets: &[(&str, f32, f32, f32)]) -> UniversalSet {
+        let mut universe = UniversalSet::new("x".to_string());
+        universe.set_domain((0..101).map(|x| x as f32 / 10.0).collect());
+        for &(name, a, b, c) in sets {
+            universe.create_set(name.to_string(), MembershipFactory::triangular(a, b, c))
+                    .unwrap();
+        }
+        universe
+    }
+
+    #[test]
+    fn known_measures() {
+        let universe = universe(&[("narrow", 4.0, 5.0, 6.0),
+                                  ("wide", 2.0, 5.0, 8.0),
+                                  ("left", 0.0, 2.0, 4.0),
+                                  ("right", 2.0, 4.0, 6.0),
+                                  ("far", 7.0, 9.0, 10.0)]);
+        let sets = &universe.sets;
+        // Nested: narrow is inside wide.
+        assert!((possibility(&sets["narrow"], &sets["wide"]) - 1.0).abs() < 1e-6);
+        assert!(necessity(&sets["narrow"], &sets["wide"]) > 0.6);
+        assert!(necessity(&sets["wide"], &sets["narrow"]) < 0.4);
+        // Overlapping: sides cross at 0.5.
+        assert!((possibility(&sets["left"], &sets["right"]) - 0.5).abs() < 1e-6);
+        assert!((necessity(&sets["left"], &sets["right"]) - 0.0).abs() < 1e-6);
+        // Disjoint.
+        assert_eq!(possibility(&sets["left"], &sets["far"]), 0.0);
+        assert_eq!(necessity(&sets["left"], &sets["far"]), 0.0);
+
+        let domain = [3.0, 5.0];
+        assert!((possibility_over(&sets["left"], &sets["right"], &domain) - 0.5).abs() < 1e-6);
+        assert!((necessity_over(&sets["left"], &sets["right"], &domain) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn crisp_intervals() {
+        let universe = universe(&[("wide", 2.0, 5.0, 8.0)]);
+        let wide = &universe.sets["wide"];
+        assert!((possibility_in(wide, (6.5, f32::INFINITY)) - 0.5).abs() < 1e-6);
+        assert_eq!(possibility_in(wide, (9.0, f32::INFINITY)), 0.0);
+        assert!((necessity_in(wide, (2.0, 8.0)) - 1.0).abs() < 1e-6);
+        // Highest membership outside is at 3.4, the last cached point before 3.5.
+        assert!((necessity_in(wide, (3.5, 10.0)) - (1.0 - 1.4 / 3.0)).abs() < 1e-5);
+        assert_eq!(necessity_in(wide, (6.0, 10.0)), 0.0);
+    }
+
+    #[test]
+    fn empty_sets() {
+        let empty = Set::new_empty();
+        assert_eq!(possibility(&empty, &empty), 0.0);
+        assert_eq!(necessity(&empty, &empty), 1.0);
+        assert_eq!(possibility_in(&empty, (0.0, f32::INFINITY)), 0.0);
+        assert_eq!(necessity_in(&empty, (0.0, 1.0)), 1.0);
+    }
+}
@@ -0,0 +1,413 @@
+//! This module contains fuzzy numbers and their arithmetic.
+//!
+//! Addition, subtraction and multiplication by a scalar are exact.
+//! Multiplication and division of two numbers are computed on the alpha-cuts at 0 (support)
+//! and 1 (core), the result is the number with straight sides through them.
+//! Exact results have curved sides, so memberships in between are approximated.
+extern crate ordered_float;
+
+use functions::MembershipFunction;
+use set::Set;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error::Error;
+use std::f32;
+use std::fmt;
+use std::ops::{Add, Mul, Sub};
+
+use self::ordered_float::OrderedFloat;
+
+/// Error of the fuzzy number arithmetic.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NumberError {
+    /// Support of the divisor contains zero.
+    DivisionByZero,
+    /// Set has no element with positive membership.
+    EmptySet,
+}
+
+impl fmt::Display for NumberError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NumberError::DivisionByZero => write!(f, "support of the divisor contains zero"),
+            NumberError::EmptySet => write!(f, "set has no element with positive membership"),
+        }
+    }
+}
+
+impl Error for NumberError {
+    fn description(&self) -> &str {
+        match *self {
+            NumberError::DivisionByZero => "support of the divisor contains zero",
+            NumberError::EmptySet => "set has no element with positive membership",
+        }
+    }
+}
+
+/// Fuzzy number with the triangular membership function: support `[a, c]` and peak `b`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriangularFuzzyNumber {
+    /// Left end of the support.
+    pub a: f32,
+    /// Peak.
+    pub b: f32,
+    /// Right end of the support.
+    pub c: f32,
+}
+
+/// Fuzzy number with the trapezoidal membership function: support `[a, d]` and core `[b, c]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrapezoidalFuzzyNumber {
+    /// Left end of the support.
+    pub a: f32,
+    /// Left end of the core.
+    pub b: f32,
+    /// Right end of the core.
+    pub c: f32,
+    /// Right end of the support.
+    pub d: f32,
+}
+
+impl TriangularFuzzyNumber {
+    /// Constructs the number.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `a <= b <= c`.
+    pub fn new(a: f32, b: f32, c: f32) -> TriangularFuzzyNumber {
+        assert!(a <= b && b <= c, "fuzzy number must have a <= b <= c");
+        TriangularFuzzyNumber { a: a, b: b, c: c }
+    }
+
+    /// Returns the membership of `x`.
+    pub fn membership(&self, x: f32) -> f32 {
+        TrapezoidalFuzzyNumber::from(*self).membership(x)
+    }
+
+    /// Returns the interval of elements with membership at least `alpha`.
+    pub fn alpha_cut(&self, alpha: f32) -> (f32, f32) {
+        TrapezoidalFuzzyNumber::from(*self).alpha_cut(alpha)
+    }
+
+    /// Returns the centroid, used to rank numbers.
+    pub fn centroid(&self) -> f32 {
+        (self.a + self.b + self.c) / 3.0
+    }
+
+    /// Compares numbers by their centroids.
+    pub fn cmp_centroid(&self, other: &TriangularFuzzyNumber) -> Ordering {
+        self.centroid().partial_cmp(&other.centroid()).unwrap_or(Ordering::Equal)
+    }
+
+    /// Divides the numbers, see the module's documentation for the approximation.
+    ///
+    /// Fails with `NumberError::DivisionByZero` if the support of `other` contains zero.
+    pub fn checked_div(&self,
+                       other: &TriangularFuzzyNumber)
+                       -> Result<TriangularFuzzyNumber, NumberError> {
+        TrapezoidalFuzzyNumber::from(*self).checked_div(&TrapezoidalFuzzyNumber::from(*other))
+                                           .map(|number| number.to_triangular())
+    }
+
+    /// Returns the membership function of the number.
+    pub fn membership_function(&self) -> Box<MembershipFunction> {
+        TrapezoidalFuzzyNumber::from(*self).membership_function()
+    }
+
+    /// Samples the number over the domain into the set with given name.
+    pub fn to_set(&self, name: &str, domain: &[f32]) -> Set {
+        TrapezoidalFuzzyNumber::from(*self).to_set(name, domain)
+    }
+
+    /// Fits the number to the cached memberships of the set, see `TrapezoidalFuzzyNumber`.
+    ///
+    /// The peak is the middle of the core.
+    pub fn from_set(set: &Set) -> Result<TriangularFuzzyNumber, NumberError> {
+        TrapezoidalFuzzyNumber::from_set(set).map(|number| number.to_triangular())
+    }
+}
+
+impl TrapezoidalFuzzyNumber {
+    /// Constructs the number.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `a <= b <= c <= d`.
+    pub fn new(a: f32, b: f32, c: f32, d: f32) -> TrapezoidalFuzzyNumber {
+        assert!(a <= b && b <= c && c <= d,
+                "fuzzy number must have a <= b <= c <= d");
+        TrapezoidalFuzzyNumber {
+            a: a,
+            b: b,
+            c: c,
+            d: d,
+        }
+    }
+
+    /// Returns the membership of `x`.
+    pub fn membership(&self, x: f32) -> f32 {
+        if x < self.a || x > self.d {
+            0.0
+        } else if x < self.b {
+            (x - self.a) / (self.b - self.a)
+        } else if x <= self.c {
+            1.0
+        } else {
+            (self.d - x) / (self.d - self.c)
+        }
+    }
+
+    /// Returns the interval of elements with membership at least `alpha`.
+    ///
+    /// `alpha` is clamped into [0, 1], the cut at 0 is the support.
+    pub fn alpha_cut(&self, alpha: f32) -> (f32, f32) {
+        let alpha = alpha.max(0.0).min(1.0);
+        (self.a + alpha * (self.b - self.a), self.d - alpha * (self.d - self.c))
+    }
+
+    /// Returns the centroid, used to rank numbers.
+    pub fn centroid(&self) -> f32 {
+        let (a, b, c, d) = (self.a, self.b, self.c, self.d);
+        let area = d + c - a - b;
+        if area <= 0.0 {
+            return a;
+        }
+        (d * d + c * c + d * c - a * a - b * b - a * b) / (3.0 * area)
+    }
+
+    /// Compares numbers by their centroids.
+    pub fn cmp_centroid(&self, other: &TrapezoidalFuzzyNumber) -> Ordering {
+        self.centroid().partial_cmp(&other.centroid()).unwrap_or(Ordering::Equal)
+    }
+
+    /// Divides the numbers, see the module's documentation for the approximation.
+    ///
+    /// Fails with `NumberError::DivisionByZero` if the support of `other` contains zero.
+    pub fn checked_div(&self,
+                       other: &TrapezoidalFuzzyNumber)
+                       -> Result<TrapezoidalFuzzyNumber, NumberError> {
+        if other.a <= 0.0 && 0.0 <= other.d {
+            return Err(NumberError::DivisionByZero);
+        }
+        let support = interval_mul((self.a, self.d), (1.0 / other.d, 1.0 / other.a));
+        let core = interval_mul((self.b, self.c), (1.0 / other.c, 1.0 / other.b));
+        Ok(TrapezoidalFuzzyNumber::new(support.0, core.0, core.1, support.1))
+    }
+
+    /// Returns the membership function of the number.
+    pub fn membership_function(&self) -> Box<MembershipFunction> {
+        let number = *self;
+        Box::new(move |x| number.membership(x))
+    }
+
+    /// Samples the number over the domain into the set with given name.
+    ///
+    /// Points with zero membership aren't stored.
+    pub fn to_set(&self, name: &str, domain: &[f32]) -> Set {
+        let cache = domain.iter()
+                          .map(|&x| (x, self.membership(x)))
+                          .filter(|&(_, mem)| mem > 0.0)
+                          .map(|(x, mem)| (OrderedFloat(x), mem))
+                          .collect::<HashMap<_, _>>();
+        Set::new_with_domain(name, RefCell::new(cache))
+    }
+
+    /// Fits the number to the cached memberships of the set.
+    ///
+    /// The core spans the points with the highest membership. Sides are lines from the core
+    /// through the outermost points with positive membership, extended down to zero,
+    /// so numbers sampled with `to_set` are recovered.
+    /// Fails with `NumberError::EmptySet` if no membership is positive.
+    pub fn from_set(set: &Set) -> Result<TrapezoidalFuzzyNumber, NumberError> {
+        let mut points = set.cache
+                            .borrow()
+                            .iter()
+                            .filter(|&(_, &mem)| mem > 0.0)
+                            .map(|(x, &mem)| (x.0, mem))
+                            .collect::<Vec<_>>();
+        if points.is_empty() {
+            return Err(NumberError::EmptySet);
+        }
+        points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let height = points.iter().fold(0.0, |height: f32, point| height.max(point.1));
+        let core = points.iter()
+                         .filter(|point| point.1 >= height - 1e-6)
+                         .map(|point| point.0)
+                         .collect::<Vec<_>>();
+        let (b, c) = (core[0], core[core.len() - 1]);
+        let (first, last) = (points[0], points[points.len() - 1]);
+        let side = |(x, mem): (f32, f32), peak: f32| {
+            if mem >= height {
+                x
+            } else {
+                x - mem * (peak - x) / (height - mem)
+            }
+        };
+        Ok(TrapezoidalFuzzyNumber::new(side(first, b), b, c, side(last, c)))
+    }
+
+    /// Returns the triangular number with the peak in the middle of the core.
+    fn to_triangular(self) -> TriangularFuzzyNumber {
+        TriangularFuzzyNumber::new(self.a, (self.b + self.c) / 2.0, self.d)
+    }
+}
+
+/// Returns the product of the intervals.
+fn interval_mul(x: (f32, f32), y: (f32, f32)) -> (f32, f32) {
+    let products = [x.0 * y.0, x.0 * y.1, x.1 * y.0, x.1 * y.1];
+    (products.iter().cloned().fold(f32::INFINITY, f32::min),
+     products.iter().cloned().fold(f32::NEG_INFINITY, f32::max))
+}
+
+impl From<TriangularFuzzyNumber> for TrapezoidalFuzzyNumber {
+    fn from(number: TriangularFuzzyNumber) -> TrapezoidalFuzzyNumber {
+        TrapezoidalFuzzyNumber::new(number.a, number.b, number.b, number.c)
+    }
+}
+
+impl Add for TrapezoidalFuzzyNumber {
+    type Output = TrapezoidalFuzzyNumber;
+
+    fn add(self, other: TrapezoidalFuzzyNumber) -> TrapezoidalFuzzyNumber {
+        TrapezoidalFuzzyNumber::new(self.a + other.a,
+                                    self.b + other.b,
+                                    self.c + other.c,
+                                    self.d + other.d)
+    }
+}
+
+impl Sub for TrapezoidalFuzzyNumber {
+    type Output = TrapezoidalFuzzyNumber;
+
+    fn sub(self, other: TrapezoidalFuzzyNumber) -> TrapezoidalFuzzyNumber {
+        TrapezoidalFuzzyNumber::new(self.a - other.d,
+                                    self.b - other.c,
+                                    self.c - other.b,
+                                    self.d - other.a)
+    }
+}
+
+impl Mul<f32> for TrapezoidalFuzzyNumber {
+    type Output = TrapezoidalFuzzyNumber;
+
+    fn mul(self, k: f32) -> TrapezoidalFuzzyNumber {
+        if k >= 0.0 {
+            TrapezoidalFuzzyNumber::new(self.a * k, self.b * k, self.c * k, self.d * k)
+        } else {
+            TrapezoidalFuzzyNumber::new(self.d * k, self.c * k, self.b * k, self.a * k)
+        }
+    }
+}
+
+/// Approximated product, see the module's documentation.
+impl Mul for TrapezoidalFuzzyNumber {
+    type Output = TrapezoidalFuzzyNumber;
+
+    fn mul(self, other: TrapezoidalFuzzyNumber) -> TrapezoidalFuzzyNumber {
+        let support = interval_mul((self.a, self.d), (other.a, other.d));
+        let core = interval_mul((self.b, self.c), (other.b, other.c));
+        TrapezoidalFuzzyNumber::new(support.0, core.0, core.1, support.1)
+    }
+}
+
+impl Add for TriangularFuzzyNumber {
+    type Output = TriangularFuzzyNumber;
+
+    fn add(self, other: TriangularFuzzyNumber) -> TriangularFuzzyNumber {
+        TriangularFuzzyNumber::new(self.a + other.a, self.b + other.b, self.c + other.c)
+    }
+}
+
+impl Sub for TriangularFuzzyNumber {
+    type Output = TriangularFuzzyNumber;
+
+    fn sub(self, other: TriangularFuzzyNumber) -> TriangularFuzzyNumber {
+        TriangularFuzzyNumber::new(self.a - other.c, self.b - other.b, self.c - other.a)
+    }
+}
+
+impl Mul<f32> for TriangularFuzzyNumber {
+    type Output = TriangularFuzzyNumber;
+
+    fn mul(self, k: f32) -> TriangularFuzzyNumber {
+        (TrapezoidalFuzzyNumber::from(self) * k).to_triangular()
+    }
+}
+
+/// Approximated product, see the module's documentation.
+impl Mul for TriangularFuzzyNumber {
+    type Output = TriangularFuzzyNumber;
+
+    fn mul(self, other: TriangularFuzzyNumber) -> TriangularFuzzyNumber {
+        (TrapezoidalFuzzyNumber::from(self) * TrapezoidalFuzzyNumber::from(other)).to_triangular()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn triangular(a: f32, b: f32, c: f32) -> TriangularFuzzyNumber {
+        TriangularFuzzyNumber::new(a, b, c)
+    }
+
+    #[test]
+    fn textbook_arithmetic() {
+        assert_eq!(triangular(1.0, 2.0, 3.0) + triangular(2.0, 3.0, 4.0),
+                   triangular(3.0, 5.0, 7.0));
+        assert_eq!(triangular(1.0, 2.0, 3.0) - triangular(2.0, 3.0, 4.0),
+                   triangular(-3.0, -1.0, 1.0));
+        assert_eq!(triangular(1.0, 2.0, 3.0) * -2.0, triangular(-6.0, -4.0, -2.0));
+        assert_eq!(triangular(1.0, 2.0, 3.0) * triangular(2.0, 3.0, 4.0),
+                   triangular(2.0, 6.0, 12.0));
+        assert_eq!(triangular(2.0, 6.0, 12.0).checked_div(&triangular(2.0, 3.0, 4.0)),
+                   Ok(triangular(0.5, 2.0, 6.0)));
+        assert_eq!(triangular(1.0, 2.0, 3.0).checked_div(&triangular(-1.0, 1.0, 2.0)),
+                   Err(NumberError::DivisionByZero));
+        assert_eq!(triangular(1.0, 2.0, 3.0).checked_div(&triangular(0.0, 1.0, 2.0)),
+                   Err(NumberError::DivisionByZero));
+
+        let a = TrapezoidalFuzzyNumber::new(1.0, 2.0, 3.0, 4.0);
+        let b = TrapezoidalFuzzyNumber::new(1.0, 1.0, 2.0, 5.0);
+        assert_eq!(a + b, TrapezoidalFuzzyNumber::new(2.0, 3.0, 5.0, 9.0));
+        assert_eq!(a - b, TrapezoidalFuzzyNumber::new(-4.0, 0.0, 2.0, 3.0));
+        assert_eq!(a * b, TrapezoidalFuzzyNumber::new(1.0, 2.0, 6.0, 20.0));
+        assert_eq!(a.alpha_cut(0.5), (1.5, 3.5));
+    }
+
+    #[test]
+    fn ranking() {
+        let mut numbers = [triangular(4.0, 5.0, 6.0),
+                           triangular(0.0, 1.0, 8.0),
+                           triangular(1.0, 2.0, 3.0)];
+        numbers.sort_by(|a, b| a.cmp_centroid(b));
+        assert_eq!(numbers[0], triangular(1.0, 2.0, 3.0));
+        assert_eq!(numbers[2], triangular(4.0, 5.0, 6.0));
+        let symmetric = TrapezoidalFuzzyNumber::new(0.0, 1.0, 3.0, 4.0);
+        assert!((symmetric.centroid() - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn set_round_trip() {
+        let domain = (0..101).map(|x| x as f32 / 10.0).collect::<Vec<_>>();
+        let number = triangular(1.25, 4.0, 7.5);
+        let set = number.to_set("number", &domain);
+        assert!((set.check(4.0) - 1.0).abs() < 1e-6);
+        let recovered = TriangularFuzzyNumber::from_set(&set).unwrap();
+        assert!((recovered.a - number.a).abs() < 1e-4, "{:?}", recovered);
+        assert!((recovered.b - number.b).abs() < 1e-4, "{:?}", recovered);
+        assert!((recovered.c - number.c).abs() < 1e-4, "{:?}", recovered);
+
+        let number = TrapezoidalFuzzyNumber::new(0.5, 2.0, 3.0, 9.25);
+        let recovered = TrapezoidalFuzzyNumber::from_set(&number.to_set("number", &domain))
+                            .unwrap();
+        for (x, y) in [number.a, number.b, number.c, number.d]
+                          .iter()
+                          .zip(&[recovered.a, recovered.b, recovered.c, recovered.d]) {
+            assert!((x - y).abs() < 1e-4, "{:?}", recovered);
+        }
+        assert_eq!(TrapezoidalFuzzyNumber::from_set(&Set::new_empty()),
+                   Err(NumberError::EmptySet));
+    }
+}
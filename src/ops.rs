@@ -3,9 +3,15 @@
 //! Fuzzy set operations and fuzzy logic operations are defined here.
 //!
 //! User can implement his own operations by implementing `LogicOps` or `SetOps` traits.
+extern crate ordered_float;
+
 use set::Set;
 use std::collections::HashMap;
 use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use self::ordered_float::OrderedFloat;
 
 /// Abstraction over set operations. Doesn't contain default implementation.
 pub trait SetOps {
@@ -13,6 +19,58 @@ pub trait SetOps {
     fn union(&self, left: &mut Set, right: &mut Set) -> Set;
     /// Intersection of fuzzy sets.
     fn intersect(&self, left: &mut Set, right: &mut Set) -> Set;
+
+    /// Name of the operations, used to describe the inference.
+    fn name(&self) -> &str {
+        "custom"
+    }
+
+    /// Union of fuzzy sets, stored into `left`.
+    ///
+    /// Used by the inference to aggregate rules without allocating new sets.
+    /// Default implementation replaces `left` with the result of `union`.
+    fn union_into(&self, left: &mut Set, right: &mut Set) {
+        *left = self.union(left, right);
+    }
+
+    /// Intersection of fuzzy sets, which doesn't modify the caches of the arguments.
+    ///
+    /// Default implementation takes the minimum of memberships at the keys of both caches.
+    /// Memberships missing from a cache are calculated by the membership function if present,
+    /// otherwise they are 0. If both sets have membership functions, the result has
+    /// their minimum as its own.
+    fn intersection(&self, left: &Set, right: &Set) -> Set {
+        let mut keys = left.cache.borrow().keys().cloned().collect::<Vec<_>>();
+        keys.extend(right.cache.borrow().keys().cloned());
+        let result = keys.into_iter()
+                         .map(|k| (k, left.membership_at(k.0).min(right.membership_at(k.0))))
+                         .collect();
+        let mut set = Set::new_with_domain(format!("{} INTERSECT {}", left.name, right.name),
+                                           RefCell::new(result));
+        if let (Some(l), Some(r)) = (left.membership.clone(), right.membership.clone()) {
+            set.membership = Some(Rc::new(move |x| l(x).min(r(x))));
+        }
+        set
+    }
+
+    /// Complement of fuzzy set: `1 - membership` at every point.
+    ///
+    /// Default implementation complements the cached memberships, including those which
+    /// become 0, so they stay cached. If the set has a membership function, the result
+    /// has its complement as its own, otherwise memberships outside the cache are 0.
+    fn complement(&self, set: &Set) -> Set {
+        let result = set.cache
+                        .borrow()
+                        .iter()
+                        .map(|(&k, &v)| (k, 1.0 - v))
+                        .collect();
+        let mut complement = Set::new_with_domain(format!("NOT {}", set.name),
+                                                  RefCell::new(result));
+        if let Some(f) = set.membership.clone() {
+            complement.membership = Some(Rc::new(move |x| 1.0 - f(x)));
+        }
+        complement
+    }
 }
 
 /// Implementation of commonly used minimax set operations.
@@ -22,7 +80,14 @@ impl SetOps for MinMaxOps {
     /// Union of fuzzy sets.
     ///
     /// Values with highest memberships are copied to the result set.
+    /// Union with the empty set is a copy of the other set.
     fn union(&self, left: &mut Set, right: &mut Set) -> Set {
+        if left.is_empty() {
+            return right.clone();
+        }
+        if right.is_empty() {
+            return left.clone();
+        }
         let mut result = HashMap::new();
         for (k, v) in left.cache.borrow().iter() {
             let right_mem = right.check(k.into_inner());
@@ -35,7 +100,37 @@ impl SetOps for MinMaxOps {
             let left_mem = left.check(k.into_inner());
             result.insert(*k, v.max(left_mem));
         }
-        Set::new_with_domain(format!("{} UNION {}", left.name, right.name), RefCell::new(result))
+        Set::new_with_domain(union_name(&left.name, &right.name), RefCell::new(result))
+    }
+
+    /// Union of fuzzy sets, stored into `left`.
+    ///
+    /// Same as `union`, but reuses the cache of `left`.
+    fn union_into(&self, left: &mut Set, right: &mut Set) {
+        if right.is_empty() {
+            return;
+        }
+        if left.is_empty() {
+            left.cache.borrow_mut().extend(right.cache.borrow().iter());
+            left.membership = right.membership.clone();
+            left.name = right.name.clone();
+            return;
+        }
+        {
+            let mut result = left.cache.borrow_mut();
+            for (k, v) in result.iter_mut() {
+                *v = v.max(right.check(k.into_inner()));
+            }
+            for (k, v) in right.cache.borrow().iter() {
+                if result.contains_key(k) {
+                    continue;
+                }
+                let left_mem = left.membership.as_ref().map_or(0.0, |f| f(k.into_inner()));
+                result.insert(*k, v.max(left_mem));
+            }
+        }
+        left.membership = None;
+        left.name = union_name(&left.name, &right.name);
     }
 
     /// Intersection of fuzzy sets.
@@ -51,6 +146,87 @@ impl SetOps for MinMaxOps {
         }
         Set::new_with_domain(format!("{} INTERSECT {}", left.name, right.name), RefCell::new(result))
     }
+
+    fn name(&self) -> &str {
+        "minmax"
+    }
+}
+
+/// Implementation of algebraic set operations: algebraic sum and algebraic product.
+///
+/// Together with `AlgebraicOps` and `ProductImplication` gives Larsen inference.
+pub struct AlgebraicSetOps;
+
+impl SetOps for AlgebraicSetOps {
+    /// Union of fuzzy sets.
+    ///
+    /// Memberships at the keys of both caches are `left + right - left * right`,
+    /// memberships missing from a cache are 0.
+    /// Union with the empty set is a copy of the other set.
+    fn union(&self, left: &mut Set, right: &mut Set) -> Set {
+        if left.is_empty() {
+            return right.clone();
+        }
+        if right.is_empty() {
+            return left.clone();
+        }
+        let result = combine_caches(left, right, |l, r| l + r - l * r);
+        Set::new_with_domain(union_name(&left.name, &right.name), RefCell::new(result))
+    }
+
+    /// Intersection of fuzzy sets.
+    ///
+    /// Memberships at the keys of both caches are `left * right`,
+    /// memberships missing from a cache are 0. Zero memberships are not included.
+    fn intersect(&self, left: &mut Set, right: &mut Set) -> Set {
+        let mut result = combine_caches(left, right, |l, r| l * r);
+        result.retain(|_, mem| *mem > 0.0);
+        let name = format!("{} INTERSECT {}", left.name, right.name);
+        Set::new_with_domain(name, RefCell::new(result))
+    }
+
+    fn name(&self) -> &str {
+        "algebraic"
+    }
+}
+
+/// Combines cached memberships of `left` and `right` at the keys of both caches.
+/// Memberships missing from a cache are 0.
+fn combine_caches<F>(left: &Set, right: &Set, f: F) -> HashMap<OrderedFloat<f32>, f32>
+    where F: Fn(f32, f32) -> f32
+{
+    let (left, right) = (left.cache.borrow(), right.cache.borrow());
+    let mut result = HashMap::with_capacity(left.len().max(right.len()));
+    for (k, &l) in left.iter() {
+        result.insert(*k, f(l, right.get(k).cloned().unwrap_or(0.0)));
+    }
+    for (k, &r) in right.iter() {
+        if !left.contains_key(k) {
+            result.insert(*k, f(0.0, r));
+        }
+    }
+    result
+}
+
+/// Returns the name of the union of `left` and `right`.
+fn union_name(left: &str, right: &str) -> Arc<str> {
+    const UNION: &'static str = " UNION ";
+    let mut name = String::with_capacity(left.len() + UNION.len() + right.len());
+    name.push_str(left);
+    name.push_str(UNION);
+    name.push_str(right);
+    Arc::from(name)
+}
+
+/// Name of the set aggregated from `left` and `right`, e.g. "left MAX right".
+fn aggregation_name(left: &str, aggregation: &str, right: &str) -> Arc<str> {
+    let mut name = String::with_capacity(left.len() + aggregation.len() + right.len() + 2);
+    name.push_str(left);
+    name.push(' ');
+    name.extend(aggregation.chars().flat_map(char::to_uppercase));
+    name.push(' ');
+    name.push_str(right);
+    Arc::from(name)
 }
 
 /// Abstraction over fuzzy logic operations. Doesn't contain default implementation.
@@ -61,6 +237,25 @@ pub trait LogicOps {
     fn or(&self, left: f32, right: f32) -> f32;
     /// Fuzzy logic NOT operation.
     fn not(&self, value: f32) -> f32;
+
+    /// Fuzzy logic XOR operation.
+    ///
+    /// Default implementation returns `(left and not right) or (not left and right)`.
+    fn xor(&self, left: f32, right: f32) -> f32 {
+        self.or(self.and(left, self.not(right)), self.and(self.not(left), right))
+    }
+
+    /// Fuzzy logic equivalence.
+    ///
+    /// Default implementation returns `not (left xor right)`.
+    fn equiv(&self, left: f32, right: f32) -> f32 {
+        self.not(self.xor(left, right))
+    }
+
+    /// Name of the operations, used to describe the inference.
+    fn name(&self) -> &str {
+        "custom"
+    }
 }
 
 /// Implementation of commonly used Zadeh fuzzy logic operations.
@@ -120,4 +315,848 @@ impl LogicOps for ZadehOps {
     fn not(&self, value: f32) -> f32 {
         1.0 - value
     }
+
+    fn name(&self) -> &str {
+        "zadeh"
+    }
+}
+
+/// Implementation of algebraic fuzzy logic operations: product and probabilistic sum.
+///
+/// Unlike `ZadehOps`, the result depends on both operands everywhere,
+/// which gives smoother responses of the inference.
+pub struct AlgebraicOps;
+
+impl LogicOps for AlgebraicOps {
+    /// Returns the product of arguments.
+    fn and(&self, left: f32, right: f32) -> f32 {
+        left * right
+    }
+
+    /// Returns the probabilistic sum of arguments: `left + right - left * right`.
+    fn or(&self, left: f32, right: f32) -> f32 {
+        left + right - left * right
+    }
+
+    /// Returns inversed logical value.
+    fn not(&self, value: f32) -> f32 {
+        1.0 - value
+    }
+
+    fn name(&self) -> &str {
+        "algebraic"
+    }
+}
+
+/// Implementation of Łukasiewicz fuzzy logic operations: bounded difference and bounded sum.
+pub struct LukasiewiczOps;
+
+impl LogicOps for LukasiewiczOps {
+    /// Returns `max(0, left + right - 1)`.
+    fn and(&self, left: f32, right: f32) -> f32 {
+        (left + right - 1.0).max(0.0)
+    }
+
+    /// Returns `min(1, left + right)`.
+    fn or(&self, left: f32, right: f32) -> f32 {
+        (left + right).min(1.0)
+    }
+
+    /// Returns inversed logical value.
+    fn not(&self, value: f32) -> f32 {
+        1.0 - value
+    }
+
+    fn name(&self) -> &str {
+        "lukasiewicz"
+    }
+}
+
+/// Implementation of Hamacher family of fuzzy logic operations.
+///
+/// `gamma` of 1 gives `AlgebraicOps`, 0 gives the Hamacher product and larger values
+/// give more drastic conjunction.
+pub struct HamacherOps {
+    gamma: f32,
+}
+
+impl HamacherOps {
+    /// Constructs operations with given `gamma`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `gamma` is negative or not finite.
+    pub fn new(gamma: f32) -> HamacherOps {
+        assert!(gamma >= 0.0 && gamma.is_finite(),
+                "gamma must be a non-negative number, got {}",
+                gamma);
+        HamacherOps { gamma: gamma }
+    }
+}
+
+impl LogicOps for HamacherOps {
+    /// Returns `left * right / (gamma + (1 - gamma) * (left + right - left * right))`,
+    /// zero if both arguments are zero.
+    fn and(&self, left: f32, right: f32) -> f32 {
+        let product = left * right;
+        if product == 0.0 {
+            return 0.0;
+        }
+        let denominator = self.gamma + (1.0 - self.gamma) * (left + right - product);
+        (product / denominator).max(0.0).min(1.0)
+    }
+
+    /// Returns the dual of `and`: `1 - and(1 - left, 1 - right)`.
+    fn or(&self, left: f32, right: f32) -> f32 {
+        1.0 - self.and(1.0 - left, 1.0 - right)
+    }
+
+    /// Returns inversed logical value.
+    fn not(&self, value: f32) -> f32 {
+        1.0 - value
+    }
+
+    fn name(&self) -> &str {
+        "hamacher"
+    }
+}
+
+/// Implementation of Yager family of fuzzy logic operations.
+///
+/// `w` of 1 gives `LukasiewiczOps`, larger values approach `ZadehOps`.
+pub struct YagerOps {
+    w: f32,
+}
+
+impl YagerOps {
+    /// Constructs operations with given exponent `w`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `w` is not positive or not finite.
+    pub fn new(w: f32) -> YagerOps {
+        assert!(w > 0.0 && w.is_finite(), "w must be a positive number, got {}", w);
+        YagerOps { w: w }
+    }
+
+    /// Returns `min(1, (x^w + y^w)^(1/w))`, computed without underflow for large `w`.
+    fn norm(&self, x: f32, y: f32) -> f32 {
+        let (min, max) = if x < y { (x, y) } else { (y, x) };
+        if max <= 0.0 {
+            return 0.0;
+        }
+        (max * (1.0 + (min / max).powf(self.w)).powf(1.0 / self.w)).min(1.0)
+    }
+}
+
+impl LogicOps for YagerOps {
+    /// Returns `1 - min(1, ((1 - left)^w + (1 - right)^w)^(1/w))`.
+    fn and(&self, left: f32, right: f32) -> f32 {
+        1.0 - self.norm(1.0 - left, 1.0 - right)
+    }
+
+    /// Returns `min(1, (left^w + right^w)^(1/w))`.
+    fn or(&self, left: f32, right: f32) -> f32 {
+        self.norm(left, right)
+    }
+
+    /// Returns inversed logical value.
+    fn not(&self, value: f32) -> f32 {
+        1.0 - value
+    }
+
+    fn name(&self) -> &str {
+        "yager"
+    }
+}
+
+/// Implementation of Einstein fuzzy logic operations: Einstein product and Einstein sum.
+pub struct EinsteinOps;
+
+impl LogicOps for EinsteinOps {
+    /// Returns `left * right / (2 - (left + right - left * right))`.
+    fn and(&self, left: f32, right: f32) -> f32 {
+        let product = left * right;
+        product / (2.0 - (left + right - product))
+    }
+
+    /// Returns `(left + right) / (1 + left * right)`.
+    fn or(&self, left: f32, right: f32) -> f32 {
+        (left + right) / (1.0 + left * right)
+    }
+
+    /// Returns inversed logical value.
+    fn not(&self, value: f32) -> f32 {
+        1.0 - value
+    }
+
+    fn name(&self) -> &str {
+        "einstein"
+    }
+}
+
+/// Default tolerance of `DrasticOps` comparisons with 0 and 1.
+pub const DRASTIC_EPSILON: f32 = 1e-6;
+
+/// Implementation of drastic fuzzy logic operations: drastic product and drastic sum.
+///
+/// Conjunction is non-zero only if one of the arguments is fully true, so only rules
+/// with fully satisfied antecedents contribute to the result.
+pub struct DrasticOps {
+    epsilon: f32,
+}
+
+impl DrasticOps {
+    /// Constructs operations which compare values with 0 and 1 within `DRASTIC_EPSILON`.
+    pub fn new() -> DrasticOps {
+        DrasticOps::with_epsilon(DRASTIC_EPSILON)
+    }
+
+    /// Constructs operations which compare values with 0 and 1 within `epsilon`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `epsilon` is negative or not finite.
+    pub fn with_epsilon(epsilon: f32) -> DrasticOps {
+        assert!(epsilon >= 0.0 && epsilon.is_finite(),
+                "epsilon must be a non-negative number, got {}",
+                epsilon);
+        DrasticOps { epsilon: epsilon }
+    }
+}
+
+impl Default for DrasticOps {
+    fn default() -> DrasticOps {
+        DrasticOps::new()
+    }
+}
+
+impl LogicOps for DrasticOps {
+    /// Returns `right` if `left` is 1, `left` if `right` is 1 and 0 otherwise.
+    fn and(&self, left: f32, right: f32) -> f32 {
+        if 1.0 - left <= self.epsilon {
+            right
+        } else if 1.0 - right <= self.epsilon {
+            left
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns `right` if `left` is 0, `left` if `right` is 0 and 1 otherwise.
+    fn or(&self, left: f32, right: f32) -> f32 {
+        if left <= self.epsilon {
+            right
+        } else if right <= self.epsilon {
+            left
+        } else {
+            1.0
+        }
+    }
+
+    /// Returns inversed logical value.
+    fn not(&self, value: f32) -> f32 {
+        1.0 - value
+    }
+
+    fn name(&self) -> &str {
+        "drastic"
+    }
+}
+
+/// Abstraction over fuzzy implication, which computes the consequent of the rule.
+pub trait ImplicationOp {
+    /// Returns the membership of the rule's result given firing `strength` of the rule
+    /// and `membership` of the consequent set.
+    fn apply(&self, strength: f32, membership: f32) -> f32;
+
+    /// Name of the implication, used to describe the inference.
+    fn name(&self) -> &str {
+        "custom"
+    }
+}
+
+/// Mamdani implication: clips the consequent by the firing strength.
+pub struct MinImplication;
+
+impl ImplicationOp for MinImplication {
+    /// Returns minimum of arguments.
+    fn apply(&self, strength: f32, membership: f32) -> f32 {
+        membership.min(strength)
+    }
+
+    fn name(&self) -> &str {
+        "min"
+    }
+}
+
+/// Larsen implication: scales the consequent by the firing strength.
+pub struct ProductImplication;
+
+impl ImplicationOp for ProductImplication {
+    /// Returns product of arguments.
+    fn apply(&self, strength: f32, membership: f32) -> f32 {
+        strength * membership
+    }
+
+    fn name(&self) -> &str {
+        "product"
+    }
+}
+
+/// Lukasiewicz implication.
+///
+/// Results are at least `1 - strength`, so a rule which doesn't fire gives 1 everywhere.
+/// Aggregate them conjunctively, e.g. with `MinAggregation`.
+pub struct LukasiewiczImplication;
+
+impl ImplicationOp for LukasiewiczImplication {
+    /// Returns `min(1, 1 - strength + membership)`.
+    fn apply(&self, strength: f32, membership: f32) -> f32 {
+        (1.0 - strength + membership).min(1.0)
+    }
+
+    fn name(&self) -> &str {
+        "lukasiewicz"
+    }
+}
+
+/// Kleene-Dienes implication.
+///
+/// Like `LukasiewiczImplication`, gives at least `1 - strength`
+/// and needs conjunctive aggregation such as `MinAggregation`.
+pub struct KleeneDienesImplication;
+
+impl ImplicationOp for KleeneDienesImplication {
+    /// Returns `max(1 - strength, membership)`.
+    fn apply(&self, strength: f32, membership: f32) -> f32 {
+        (1.0 - strength).max(membership)
+    }
+
+    fn name(&self) -> &str {
+        "kleene-dienes"
+    }
+}
+
+/// Godel implication.
+///
+/// A rule which doesn't fire gives 1 everywhere, so results should be aggregated
+/// conjunctively, e.g. with `MinAggregation`.
+pub struct GodelImplication;
+
+impl ImplicationOp for GodelImplication {
+    /// Returns 1 if `strength` doesn't exceed `membership` and `membership` otherwise.
+    fn apply(&self, strength: f32, membership: f32) -> f32 {
+        if strength <= membership { 1.0 } else { membership }
+    }
+
+    fn name(&self) -> &str {
+        "godel"
+    }
+}
+
+/// Abstraction over aggregation of the rules' results.
+pub trait AggregationOp {
+    /// Combines the `accumulated` membership of the previous rules' results
+    /// with the `membership` of the next result.
+    fn combine(&self, accumulated: f32, membership: f32) -> f32;
+
+    /// Name of the aggregation, used to describe the inference.
+    fn name(&self) -> &str {
+        "custom"
+    }
+
+    /// Aggregates `right` into `left`, combining memberships at the keys of both caches.
+    ///
+    /// The result is named after both sets and the aggregation, e.g. "left MAX right".
+    fn aggregate_into(&self, left: &mut Set, right: &mut Set) {
+        if right.is_empty() {
+            return;
+        }
+        if left.is_empty() {
+            left.cache.borrow_mut().extend(right.cache.borrow().iter());
+            left.membership = right.membership.clone();
+            left.name = right.name.clone();
+            return;
+        }
+        {
+            let mut result = left.cache.borrow_mut();
+            for (k, v) in result.iter_mut() {
+                *v = self.combine(*v, right.check(k.into_inner()));
+            }
+            for (k, v) in right.cache.borrow().iter() {
+                if result.contains_key(k) {
+                    continue;
+                }
+                let left_mem = left.membership.as_ref().map_or(0.0, |f| f(k.into_inner()));
+                result.insert(*k, self.combine(left_mem, *v));
+            }
+        }
+        left.membership = None;
+        left.name = aggregation_name(&left.name, self.name(), &right.name);
+    }
+}
+
+/// Aggregation by maximum, same as the union of `MinMaxOps`.
+pub struct MaxAggregation;
+
+impl AggregationOp for MaxAggregation {
+    /// Returns maximum of arguments.
+    fn combine(&self, accumulated: f32, membership: f32) -> f32 {
+        accumulated.max(membership)
+    }
+
+    fn name(&self) -> &str {
+        "max"
+    }
+}
+
+/// Aggregation by minimum: the conjunction of the rules' results.
+///
+/// Used with implications which give 1 for rules which don't fire,
+/// e.g. `LukasiewiczImplication`, `KleeneDienesImplication` and `GodelImplication`.
+pub struct MinAggregation;
+
+impl AggregationOp for MinAggregation {
+    /// Returns minimum of arguments.
+    fn combine(&self, accumulated: f32, membership: f32) -> f32 {
+        accumulated.min(membership)
+    }
+
+    fn name(&self) -> &str {
+        "min"
+    }
+}
+
+/// Aggregation by bounded sum: the sum of memberships clamped to 1.
+pub struct BoundedSumAggregation;
+
+impl AggregationOp for BoundedSumAggregation {
+    /// Returns `min(1, accumulated + membership)`.
+    fn combine(&self, accumulated: f32, membership: f32) -> f32 {
+        (accumulated + membership).min(1.0)
+    }
+
+    fn name(&self) -> &str {
+        "bounded sum"
+    }
+}
+
+/// Aggregation by probabilistic sum.
+pub struct ProbabilisticSumAggregation;
+
+impl AggregationOp for ProbabilisticSumAggregation {
+    /// Returns `accumulated + membership - accumulated * membership`.
+    fn combine(&self, accumulated: f32, membership: f32) -> f32 {
+        accumulated + membership - accumulated * membership
+    }
+
+    fn name(&self) -> &str {
+        "probabilistic sum"
+    }
+}
+
+/// Aggregation by plain sum.
+///
+/// Memberships of the result may exceed 1 and are kept as they are, so it should be defuzzified
+/// with a function which normalizes the weights, e.g. `DefuzzFactory::center_of_mass`.
+/// `InferenceResult::height` may exceed 1 too. Use `BoundedSumAggregation` to clamp them.
+pub struct SumAggregation;
+
+impl AggregationOp for SumAggregation {
+    /// Returns `accumulated + membership`.
+    fn combine(&self, accumulated: f32, membership: f32) -> f32 {
+        accumulated + membership
+    }
+
+    fn name(&self) -> &str {
+        "sum"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use functions::MembershipFactory;
+    use inference::{InferenceMachine, InferenceOptions};
+    use rules::RuleSet;
+    use set::UniversalSet;
+
+    fn set() -> Set {
+        let set = Set::new_with_mem("Set", MembershipFactory::triangular(0.0, 5.0, 10.0));
+        for i in 0..11 {
+            set.check(i as f32);
+        }
+        set
+    }
+
+    #[test]
+    fn empty_union() {
+        let ops = MinMaxOps {};
+        for &empty_left in &[true, false] {
+            let (mut left, mut right) = if empty_left {
+                (Set::new_empty(), set())
+            } else {
+                (set(), Set::new_empty())
+            };
+            let union = ops.union(&mut left, &mut right);
+            assert_eq!(&*union.name, "Set");
+            assert_eq!(*union.cache.borrow(), *set().cache.borrow());
+            ops.union_into(&mut left, &mut right);
+            assert_eq!(&*left.name, "Set");
+            assert_eq!(*left.cache.borrow(), *set().cache.borrow());
+        }
+        let union = ops.union(&mut Set::new_empty(), &mut Set::new_empty());
+        assert!(union.is_empty());
+    }
+
+    /// Checks boundary conditions, commutativity, associativity and monotonicity
+    /// of the operations on the grid.
+    fn check_axioms(ops: &LogicOps) {
+        let grid = (0..11).map(|i| i as f32 / 10.0).collect::<Vec<_>>();
+        let close = |a: f32, b: f32| (a - b).abs() <= 1e-5;
+        assert_eq!((ops.not(0.0), ops.not(1.0)), (1.0, 0.0));
+        for &a in &grid {
+            assert!(close(ops.and(a, 1.0), a) && close(ops.and(a, 0.0), 0.0));
+            assert!(close(ops.or(a, 0.0), a) && close(ops.or(a, 1.0), 1.0));
+            for &b in &grid {
+                let (and, or) = (ops.and(a, b), ops.or(a, b));
+                assert!(0.0 <= and && and <= 1.0 && 0.0 <= or && or <= 1.0);
+                assert!(close(and, ops.and(b, a)) && close(or, ops.or(b, a)));
+                if a < 1.0 {
+                    assert!(and <= ops.and(a + 0.1, b) + 1e-5);
+                    assert!(or <= ops.or(a + 0.1, b) + 1e-5);
+                }
+                for &c in &grid {
+                    assert!(close(ops.and(a, ops.and(b, c)), ops.and(and, c)));
+                    assert!(close(ops.or(a, ops.or(b, c)), ops.or(or, c)));
+                }
+            }
+        }
+    }
+
+    /// Returns the fan speed for temperatures 0..40 with the humidity of 50
+    /// and given logic operations.
+    fn response(logic_ops: Box<LogicOps>) -> Vec<f32> {
+        let mut temp = UniversalSet::new("temp".to_string());
+        temp.set_domain((0..41).map(|x| x as f32).collect());
+        temp.create_set("cold".to_string(), MembershipFactory::triangular(-20.0, 0.0, 25.0))
+            .unwrap();
+        temp.create_set("hot".to_string(), MembershipFactory::triangular(15.0, 40.0, 65.0))
+            .unwrap();
+        let mut humidity = UniversalSet::new("humidity".to_string());
+        humidity.create_set("dry".to_string(), MembershipFactory::triangular(-100.0, 0.0, 100.0))
+                .unwrap();
+        humidity.create_set("humid".to_string(), MembershipFactory::triangular(0.0, 100.0, 200.0))
+                .unwrap();
+        let mut fan = UniversalSet::new("fan".to_string());
+        fan.set_domain((0..101).map(|x| x as f32 / 10.0).collect());
+        fan.create_set("slow".to_string(), MembershipFactory::triangular(-5.0, 0.0, 6.0)).unwrap();
+        fan.create_set("fast".to_string(), MembershipFactory::triangular(4.0, 10.0, 15.0))
+           .unwrap();
+        let mut universes = HashMap::new();
+        universes.insert("temp".to_string(), temp);
+        universes.insert("humidity".to_string(), humidity);
+        universes.insert("fan".to_string(), fan);
+        let rules = fuzzy_rules! {
+            if temp is hot && humidity is humid => fan is fast;
+            if temp is cold || humidity is dry => fan is slow;
+        };
+        let mut options = InferenceOptions::mamdani();
+        options.logic_ops = logic_ops;
+        let mut machine = InferenceMachine::new(RuleSet::new(rules).unwrap(), universes, options);
+        machine.set_value("humidity", 50.0).unwrap();
+        (0..41)
+            .map(|t| {
+                machine.set_value("temp", t as f32).unwrap();
+                machine.compute_for("fan").map(|result| result.value).unwrap_or(f32::NAN)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn algebraic_ops() {
+        check_axioms(&AlgebraicOps {});
+        assert_eq!(AlgebraicOps {}.and(0.5, 0.4), 0.2);
+        assert_eq!(AlgebraicOps {}.or(0.5, 0.4), 0.7);
+
+        // With the humidity of 50, "hot" dominates the first rule above 27.5 only with min.
+        let zadeh = response(Box::new(ZadehOps {}));
+        let algebraic = response(Box::new(AlgebraicOps {}));
+        assert_eq!(zadeh[30], zadeh[40]);
+        assert!(algebraic[26..].windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn lukasiewicz_ops() {
+        let ops = LukasiewiczOps {};
+        check_axioms(&ops);
+        assert_eq!((ops.and(0.3, 0.6), ops.and(0.5, 0.5)), (0.0, 0.0));
+        assert!((ops.and(0.7, 0.6) - 0.3).abs() < 1e-6);
+        assert_eq!((ops.or(0.7, 0.6), ops.or(0.2, 0.3)), (1.0, 0.5));
+
+        // The first rule doesn't fire until "hot" exceeds 0.5 at 27.5 and "cold" is zero from 25,
+        // so the response is constant between them and then grows.
+        let zadeh = response(Box::new(ZadehOps {}));
+        let lukasiewicz = response(Box::new(LukasiewiczOps {}));
+        assert!(lukasiewicz[25..28].windows(2).all(|pair| pair[0] == pair[1]));
+        assert!(lukasiewicz[28..].windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(zadeh[20] > lukasiewicz[20]);
+    }
+
+    #[test]
+    fn hamacher_ops() {
+        for &gamma in &[0.0, 0.5, 1.0, 2.0, 10.0] {
+            let ops = HamacherOps::new(gamma);
+            check_axioms(&ops);
+            assert_eq!(ops.and(0.0, 0.0), 0.0);
+            assert_eq!(ops.or(1.0, 1.0), 1.0);
+            for i in 0..101 {
+                for j in 0..101 {
+                    let (a, b) = (i as f32 / 100.0, j as f32 / 100.0);
+                    let (and, or) = (ops.and(a, b), ops.or(a, b));
+                    assert!(0.0 <= and && and <= 1.0 && 0.0 <= or && or <= 1.0);
+                }
+            }
+        }
+        let (hamacher, algebraic) = (HamacherOps::new(1.0), AlgebraicOps {});
+        for i in 0..11 {
+            for j in 0..11 {
+                let (a, b) = (i as f32 / 10.0, j as f32 / 10.0);
+                assert!((hamacher.and(a, b) - algebraic.and(a, b)).abs() < 1e-6);
+                assert!((hamacher.or(a, b) - algebraic.or(a, b)).abs() < 1e-6);
+            }
+        }
+        // Hamacher product: 0.25 / (0.5 + 0.5 - 0.25).
+        assert!((HamacherOps::new(0.0).and(0.5, 0.5) - 1.0 / 3.0).abs() < 1e-6);
+        assert!(HamacherOps::new(10.0).and(0.5, 0.5) < 0.25);
+    }
+
+    #[test]
+    #[should_panic(expected = "gamma must be a non-negative number")]
+    fn negative_hamacher_gamma() {
+        HamacherOps::new(-1.0);
+    }
+
+    #[test]
+    fn yager_ops() {
+        for &w in &[0.5, 1.0, 2.0, 10.0] {
+            check_axioms(&YagerOps::new(w));
+        }
+        let pairs = vec![(YagerOps::new(1.0), Box::new(LukasiewiczOps {}) as Box<LogicOps>, 1e-6),
+                         (YagerOps::new(1e4), Box::new(ZadehOps {}), 1e-3)];
+        for (yager, expected, tolerance) in pairs {
+            for i in 0..21 {
+                for j in 0..21 {
+                    let (a, b) = (i as f32 / 20.0, j as f32 / 20.0);
+                    assert!((yager.and(a, b) - expected.and(a, b)).abs() <= tolerance);
+                    assert!((yager.or(a, b) - expected.or(a, b)).abs() <= tolerance);
+                }
+            }
+        }
+
+        let zadeh = response(Box::new(ZadehOps {}));
+        let close = response(Box::new(YagerOps::new(1e4)));
+        let lukasiewicz = response(Box::new(LukasiewiczOps {}));
+        let yager = response(Box::new(YagerOps::new(1.0)));
+        for t in 0..41 {
+            assert!((close[t] - zadeh[t]).abs() < 1e-2);
+            assert!((yager[t] - lukasiewicz[t]).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "w must be a positive number")]
+    fn zero_yager_exponent() {
+        YagerOps::new(0.0);
+    }
+
+    #[test]
+    fn einstein_ops() {
+        let ops = EinsteinOps {};
+        check_axioms(&ops);
+        assert!((ops.and(0.6, 0.5) - 0.25).abs() < 1e-6);
+        assert!((ops.or(0.5, 0.5) - 0.8).abs() < 1e-6);
+
+        // At 30 "hot" is 0.6, "cold" is 0, "humid" and "dry" are 0.5, so the rules fire
+        // with 0.25 and 0.5. The centroid of the clipped sets was computed separately.
+        let einstein = response(Box::new(EinsteinOps {}));
+        assert!((einstein[30] - 4.135194).abs() < 1e-4);
+    }
+
+    #[test]
+    fn drastic_ops() {
+        let ops = DrasticOps::new();
+        check_axioms(&ops);
+        assert_eq!(ops.and(0.9, 0.9), 0.0);
+        assert_eq!(ops.and(1.0 - 1e-7, 0.3), 0.3);
+        assert_eq!(ops.or(0.1, 0.1), 1.0);
+        assert_eq!(DrasticOps::with_epsilon(0.2).and(0.9, 0.4), 0.4);
+        assert_eq!(DrasticOps::default().epsilon, DRASTIC_EPSILON);
+
+        // At 30 "hot" is 0.6 and "humid" is 0.5, so the first rule is only partially
+        // satisfied and contributes nothing: the result is the centroid of "slow" clipped
+        // at 0.5 alone.
+        let drastic = response(Box::new(DrasticOps::new()));
+        let default = response(Box::new(ZadehOps {}));
+        assert!((drastic[30] - 2.307326).abs() < 1e-4);
+        assert!(default[30] > drastic[30] + 1.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn negative_drastic_epsilon() {
+        DrasticOps::with_epsilon(-0.1);
+    }
+
+    #[test]
+    fn implications() {
+        let cases: Vec<(Box<ImplicationOp>, [f32; 3])> =
+            vec![(Box::new(MinImplication {}), [0.5, 0.2, 0.0]),
+                 (Box::new(ProductImplication {}), [0.4, 0.1, 0.0]),
+                 (Box::new(LukasiewiczImplication {}), [1.0, 0.7, 0.5]),
+                 (Box::new(KleeneDienesImplication {}), [0.8, 0.5, 0.5]),
+                 (Box::new(GodelImplication {}), [1.0, 0.2, 0.0])];
+        for (implication, expected) in cases {
+            let actual = [implication.apply(0.5, 0.8),
+                          implication.apply(0.5, 0.2),
+                          implication.apply(0.5, 0.0)];
+            for (a, e) in actual.iter().zip(expected.iter()) {
+                assert!((a - e).abs() < 1e-6, "{}: {:?}", implication.name(), actual);
+            }
+        }
+    }
+
+    #[test]
+    fn aggregations() {
+        let cases: Vec<(Box<AggregationOp>, f32, &str)> =
+            vec![(Box::new(MaxAggregation {}), 0.6, "left MAX right"),
+                 (Box::new(BoundedSumAggregation {}), 1.0, "left BOUNDED SUM right"),
+                 (Box::new(ProbabilisticSumAggregation {}), 0.8, "left PROBABILISTIC SUM right"),
+                 (Box::new(SumAggregation {}), 1.1, "left SUM right")];
+        for (aggregation, expected, expected_name) in cases {
+            let mut left = Set::new_with_domain("left", RefCell::new(HashMap::new()));
+            left.cache.borrow_mut().insert(OrderedFloat(1.0), 0.5);
+            let mut right = Set::new_with_domain("right", RefCell::new(HashMap::new()));
+            right.cache.borrow_mut().insert(OrderedFloat(1.0), 0.6);
+            right.cache.borrow_mut().insert(OrderedFloat(2.0), 0.3);
+            aggregation.aggregate_into(&mut left, &mut right);
+            let cache = left.cache.borrow();
+            let name = aggregation.name();
+            assert!((cache[&OrderedFloat(1.0)] - expected).abs() < 1e-6, "{}", name);
+            assert!((cache[&OrderedFloat(2.0)] - 0.3).abs() < 1e-6, "{}", name);
+            assert_eq!(&*left.name, expected_name);
+        }
+
+        // Keys missing from `left` have the membership 0, which is kept by the minimum.
+        let mut left = Set::new_with_domain("left", RefCell::new(HashMap::new()));
+        left.cache.borrow_mut().insert(OrderedFloat(1.0), 0.5);
+        let mut right = Set::new_with_domain("right", RefCell::new(HashMap::new()));
+        right.cache.borrow_mut().insert(OrderedFloat(1.0), 0.6);
+        right.cache.borrow_mut().insert(OrderedFloat(2.0), 0.3);
+        MinAggregation {}.aggregate_into(&mut left, &mut right);
+        assert_eq!(left.cache.borrow()[&OrderedFloat(1.0)], 0.5);
+        assert_eq!(left.cache.borrow()[&OrderedFloat(2.0)], 0.0);
+        assert_eq!(&*left.name, "left MIN right");
+    }
+
+    #[test]
+    fn sum_aggregation_above_one() {
+        let mut temp = UniversalSet::new("temp".to_string());
+        temp.set_domain((0..41).map(|x| x as f32).collect());
+        for name in &["warm", "mild"] {
+            temp.create_set(name.to_string(), MembershipFactory::triangular(0.0, 20.0, 40.0))
+                .unwrap();
+        }
+        let mut fan = UniversalSet::new("fan".to_string());
+        fan.set_domain((0..101).map(|x| x as f32 / 10.0).collect());
+        fan.create_set("medium".to_string(), MembershipFactory::triangular(2.0, 4.0, 10.0))
+           .unwrap();
+        let mut universes = HashMap::new();
+        universes.insert("temp".to_string(), temp);
+        universes.insert("fan".to_string(), fan);
+        let machine = |aggregation: Box<AggregationOp>| {
+            let rules = fuzzy_rules! {
+                if temp is warm => fan is medium;
+                if temp is mild => fan is medium;
+            };
+            let mut options = InferenceOptions::mamdani();
+            options.aggregation = Some(aggregation);
+            let rules = RuleSet::new(rules).unwrap();
+            let mut machine = InferenceMachine::new(rules, universes.clone(), options);
+            machine.set_value("temp", 16.0).unwrap();
+            machine.compute_for("fan").unwrap()
+        };
+
+        // Both rules fire with 0.8, the sum is twice the maximum and isn't clamped.
+        let sum = machine(Box::new(SumAggregation {}));
+        let max = machine(Box::new(MaxAggregation {}));
+        assert!((sum.height - 1.6).abs() < 1e-6);
+        assert!((sum.mass - 2.0 * max.mass).abs() < 1e-4);
+        assert!(!sum.clamped);
+        assert!((sum.value - max.value).abs() < 1e-5);
+    }
+
+    #[test]
+    fn intersection() {
+        let ops = MinMaxOps {};
+        let with_mem = set();
+        let mut cache = HashMap::new();
+        for &(x, mem) in &[(2.0, 1.0), (5.0, 0.5), (12.0, 0.7)] {
+            cache.insert(OrderedFloat(x), mem);
+        }
+        let cached = Set::new_with_domain("Cached", RefCell::new(cache));
+        let left = ops.intersection(&with_mem, &cached);
+        let right = ops.intersection(&cached, &with_mem);
+        assert_eq!(&*left.name, "Set INTERSECT Cached");
+        assert!(left.membership.is_none());
+        assert_eq!(*left.cache.borrow(), *right.cache.borrow());
+        let result = left.cache.borrow();
+        assert_eq!(result.len(), 10);
+        assert_eq!(result[&OrderedFloat(2.0)], with_mem.check(2.0));
+        assert_eq!(result[&OrderedFloat(5.0)], 0.5);
+        assert_eq!(result[&OrderedFloat(9.0)], 0.0);
+        assert_eq!(result[&OrderedFloat(12.0)], 0.0);
+        assert_eq!(cached.cache.borrow().len(), 3);
+
+        let other = Set::new_with_mem("Other", MembershipFactory::triangular(4.0, 8.0, 12.0));
+        let both = ops.intersection(&with_mem, &other);
+        for (k, &mem) in both.cache.borrow().iter() {
+            assert_eq!(mem, with_mem.check(k.0).min(other.membership_at(k.0)));
+        }
+        assert!((both.check(6.5) - 0.625).abs() < 1e-6);
+        assert_eq!(other.cache.borrow().len(), 0);
+    }
+
+    #[test]
+    fn complement() {
+        let ops = MinMaxOps {};
+        let with_mem = set();
+        let complement = ops.complement(&with_mem);
+        assert_eq!(&*complement.name, "NOT Set");
+        // Full membership at 5 becomes 0, which stays cached and isn't calculated again.
+        assert_eq!(complement.cache.borrow()[&OrderedFloat(5.0)], 0.0);
+        assert_eq!(complement.check(5.0), 0.0);
+        assert!(complement.cache.borrow().contains_key(&OrderedFloat(5.0)));
+        assert!((complement.check(2.0) - 0.6).abs() < 1e-6);
+        // New points are complemented by the wrapped function.
+        assert!((complement.check(2.5) - 0.5).abs() < 1e-6);
+        assert_eq!(complement.check(20.0), 1.0);
+        // Points where the complement is 0 aren't cached, as for any other set.
+        let peak = Set::new_with_mem("Peak", MembershipFactory::triangular(0.0, 5.5, 10.0));
+        let complement = ops.complement(&peak);
+        assert_eq!(complement.check(5.5), 0.0);
+        assert!(!complement.cache.borrow().contains_key(&OrderedFloat(5.5)));
+
+        let mut cache = HashMap::new();
+        cache.insert(OrderedFloat(1.0), 1.0);
+        cache.insert(OrderedFloat(2.0), 0.25);
+        let cached = Set::new_with_domain("Cached", RefCell::new(cache));
+        let complement = ops.complement(&cached);
+        assert!(complement.membership.is_none());
+        assert_eq!(complement.cache.borrow().len(), 2);
+        assert_eq!(complement.check(1.0), 0.0);
+        assert_eq!(complement.check(2.0), 0.75);
+        assert_eq!(complement.check(3.0), 0.0);
+    }
 }
@@ -0,0 +1,388 @@
+//! This module contains SVG plots of universes and inference results.
+//!
+//! Plots are plain SVG documents written by hand, so the module has no dependencies.
+//! It's available with the `plot` feature.
+use inference::{InferenceError, InferenceMachine};
+use set::{Set, UniversalSet};
+use std::f32;
+use std::fmt::Write;
+
+/// Number of points membership functions are sampled at by default.
+pub const DEFAULT_RESOLUTION: usize = 200;
+
+/// Margin around the plot area in pixels.
+const MARGIN: f32 = 20.0;
+
+/// Width of the legend on the right of the plot area in pixels.
+const LEGEND_WIDTH: f32 = 100.0;
+
+/// Colors of the curves, repeated if there are more terms.
+const COLORS: [&'static str; 8] = ["#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd",
+                                    "#8c564b", "#e377c2", "#17becf"];
+
+/// Transformation from the universe's coordinates into the plot's ones.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    min: f32,
+    max: f32,
+    width: f32,
+    height: f32,
+}
+
+impl Transform {
+    /// Constructs the transformation of `[min, max]` into the plot of given size.
+    pub fn new(min: f32, max: f32, width: u32, height: u32) -> Transform {
+        Transform {
+            min: min,
+            max: max,
+            width: width as f32,
+            height: height as f32,
+        }
+    }
+
+    /// Returns the horizontal coordinate of the point of the universe.
+    pub fn x(&self, x: f32) -> f32 {
+        let span = (self.width - 2.0 * MARGIN - LEGEND_WIDTH).max(0.0);
+        if self.max > self.min {
+            MARGIN + (x - self.min) / (self.max - self.min) * span
+        } else {
+            MARGIN
+        }
+    }
+
+    /// Returns the vertical coordinate of the membership.
+    pub fn y(&self, mem: f32) -> f32 {
+        self.height - MARGIN - mem * (self.height - 2.0 * MARGIN).max(0.0)
+    }
+}
+
+impl UniversalSet {
+    /// Renders membership functions of every set with a legend, see `plot_svg_with_resolution`.
+    pub fn plot_svg(&self, width: u32, height: u32) -> String {
+        self.plot_svg_with_resolution(width, height, DEFAULT_RESOLUTION)
+    }
+
+    /// Renders membership functions of every set with a legend.
+    ///
+    /// Functions are sampled at `resolution` points over the domain,
+    /// sets without the function are drawn through their cached points.
+    /// The plot covers the domain, or the cached points if the domain is empty.
+    pub fn plot_svg_with_resolution(&self, width: u32, height: u32, resolution: usize) -> String {
+        let (min, max) = self.bounds();
+        let mut svg = Svg::new(Transform::new(min, max, width, height));
+        let mut names = self.sets.keys().collect::<Vec<_>>();
+        names.sort();
+        for (index, name) in names.into_iter().enumerate() {
+            let points = sample(&self.sets[name], min, max, resolution);
+            svg.curve("term", index, &points);
+            svg.legend(index, name);
+        }
+        svg.finish()
+    }
+
+    /// Returns bounds of the domain or, if it's empty, of the cached points.
+    fn bounds(&self) -> (f32, f32) {
+        if !self.domain().is_empty() {
+            return bounds(self.domain().iter().cloned());
+        }
+        let cached = self.sets
+                         .values()
+                         .flat_map(|set| {
+                             set.cache.borrow().keys().map(|key| key.0).collect::<Vec<_>>()
+                         })
+                         .collect::<Vec<_>>();
+        bounds(cached.into_iter())
+    }
+}
+
+impl InferenceMachine {
+    /// Computes the inference in `RuleSet::result_universe` and renders the aggregated output set
+    /// over the sets of that universe, with a marker at the defuzzificated value.
+    pub fn plot_result_svg(&mut self, width: u32, height: u32) -> Result<String, InferenceError> {
+        let output = self.rules.result_universe().to_string();
+        let value = self.compute_for(&output)?.value;
+        // `compute_for` fails if the universe doesn't exist.
+        let universe = &self.universes[&output];
+        let (min, max) = if universe.domain().is_empty() {
+            bounds(self.output().cache.borrow().keys().map(|key| key.0))
+        } else {
+            universe.bounds()
+        };
+        let mut svg = Svg::new(Transform::new(min, max, width, height));
+        let mut names = universe.sets.keys().collect::<Vec<_>>();
+        names.sort();
+        for (index, name) in names.into_iter().enumerate() {
+            let points = sample(&universe.sets[name], min, max, DEFAULT_RESOLUTION);
+            svg.curve("term", index, &points);
+            svg.legend(index, name);
+        }
+        let points = sample(self.output(), min, max, 0);
+        svg.area("output", &points);
+        svg.marker(value);
+        Ok(svg.finish())
+    }
+}
+
+/// Returns sorted points of the set's membership function.
+///
+/// The function is sampled at `resolution` points over `[min, max]`,
+/// cached points are used if there is no function.
+fn sample(set: &Set, min: f32, max: f32, resolution: usize) -> Vec<(f32, f32)> {
+    if set.membership.is_some() && resolution > 1 {
+        let step = (max - min) / (resolution - 1) as f32;
+        return (0..resolution)
+                   .map(|i| min + step * i as f32)
+                   .map(|x| (x, set.membership_at(x)))
+                   .collect();
+    }
+    let mut points = set.cache
+                        .borrow()
+                        .iter()
+                        .map(|(key, &mem)| (key.0, mem))
+                        .collect::<Vec<_>>();
+    points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    points
+}
+
+/// Returns minimum and maximum of the points, or `(0, 1)` if there are none.
+fn bounds<I>(points: I) -> (f32, f32)
+    where I: Iterator<Item = f32>
+{
+    let (min, max) = points.fold((f32::INFINITY, f32::NEG_INFINITY),
+                                 |(min, max), x| (min.min(x), max.max(x)));
+    if min > max {
+        (0.0, 1.0)
+    } else {
+        (min, max)
+    }
+}
+
+/// Escapes the text for XML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// SVG document being written.
+struct Svg {
+    transform: Transform,
+    out: String,
+}
+
+impl Svg {
+    /// Starts the document with the axes and their labels.
+    fn new(transform: Transform) -> Svg {
+        let mut out = String::new();
+        let (width, height) = (transform.width, transform.height);
+        writeln!(out,
+                 "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+                  viewBox=\"0 0 {0} {1}\">",
+                 width,
+                 height)
+            .unwrap();
+        let (left, right) = (transform.x(transform.min), transform.x(transform.max));
+        let (bottom, top) = (transform.y(0.0), transform.y(1.0));
+        writeln!(out,
+                 "<path class=\"axis\" d=\"M{:.2} {:.2} L{:.2} {:.2} L{:.2} {:.2}\" \
+                  stroke=\"black\" fill=\"none\"/>",
+                 left,
+                 top,
+                 left,
+                 bottom,
+                 right,
+                 bottom)
+            .unwrap();
+        for &(x, value) in &[(left, transform.min), (right, transform.max)] {
+            writeln!(out,
+                     "<text class=\"label\" x=\"{:.2}\" y=\"{:.2}\" font-size=\"10\" \
+                      text-anchor=\"middle\">{}</text>",
+                     x,
+                     bottom + 12.0,
+                     value)
+                .unwrap();
+        }
+        Svg {
+            transform: transform,
+            out: out,
+        }
+    }
+
+    /// Returns path data through the points.
+    fn path_data(&self, points: &[(f32, f32)]) -> String {
+        let mut data = String::new();
+        for (i, &(x, mem)) in points.iter().enumerate() {
+            write!(data,
+                   "{}{:.2} {:.2}",
+                   if i == 0 { "M" } else { " L" },
+                   self.transform.x(x),
+                   self.transform.y(mem))
+                .unwrap();
+        }
+        data
+    }
+
+    /// Writes the curve through the points.
+    fn curve(&mut self, class: &str, index: usize, points: &[(f32, f32)]) {
+        if points.is_empty() {
+            return;
+        }
+        let data = self.path_data(points);
+        writeln!(self.out,
+                 "<path class=\"{}\" d=\"{}\" stroke=\"{}\" fill=\"none\"/>",
+                 class,
+                 data,
+                 COLORS[index % COLORS.len()])
+            .unwrap();
+    }
+
+    /// Writes the area under the points.
+    fn area(&mut self, class: &str, points: &[(f32, f32)]) {
+        if points.is_empty() {
+            return;
+        }
+        let mut data = self.path_data(points);
+        write!(data,
+               " L{:.2} {:.2} L{:.2} {:.2} Z",
+               self.transform.x(points[points.len() - 1].0),
+               self.transform.y(0.0),
+               self.transform.x(points[0].0),
+               self.transform.y(0.0))
+            .unwrap();
+        writeln!(self.out,
+                 "<path class=\"{}\" d=\"{}\" stroke=\"black\" fill=\"gray\" \
+                  fill-opacity=\"0.4\"/>",
+                 class,
+                 data)
+            .unwrap();
+    }
+
+    /// Writes the legend entry of the curve.
+    fn legend(&mut self, index: usize, name: &str) {
+        let x = self.transform.width - LEGEND_WIDTH;
+        let y = MARGIN + 15.0 * index as f32;
+        let color = COLORS[index % COLORS.len()];
+        writeln!(self.out,
+                 "<line class=\"legend\" x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" \
+                  stroke=\"{}\"/>",
+                 x,
+                 y,
+                 x + 15.0,
+                 y,
+                 color)
+            .unwrap();
+        writeln!(self.out,
+                 "<text class=\"legend\" x=\"{:.2}\" y=\"{:.2}\" font-size=\"10\">{}</text>",
+                 x + 20.0,
+                 y + 4.0,
+                 escape(name))
+            .unwrap();
+    }
+
+    /// Writes the vertical marker at the point of the universe.
+    fn marker(&mut self, x: f32) {
+        let x = self.transform.x(x);
+        writeln!(self.out,
+                 "<line class=\"marker\" x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" \
+                  stroke=\"red\"/>",
+                 x,
+                 self.transform.y(0.0),
+                 x,
+                 self.transform.y(1.0))
+            .unwrap();
+    }
+
+    /// Finishes the document.
+    fn finish(mut self) -> String {
+        self.out.push_str("</svg>\n");
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Transform;
+    use functions::MembershipFactory;
+    use inference::{InferenceMachine, InferenceOptions};
+    use rules::{Is, Rule, RuleSet};
+    use set::UniversalSet;
+    use std::collections::HashMap;
+
+    /// Checks that tags are closed in order and attributes are quoted.
+    fn assert_well_formed(svg: &str) {
+        let mut open = Vec::new();
+        for part in svg.split('<').skip(1) {
+            let end = part.find('>').expect("unclosed tag");
+            let tag = &part[..end];
+            assert_eq!(tag.matches('"').count() % 2, 0, "unquoted attribute in {}", tag);
+            assert!(!part[end + 1..].contains('>'), "unescaped text after {}", tag);
+            if let Some(closed) = tag.strip_prefix('/') {
+                assert_eq!(open.pop(), Some(closed));
+            } else if !tag.ends_with('/') {
+                open.push(tag.split(' ').next().unwrap());
+            }
+        }
+        assert!(open.is_empty(), "unclosed elements {:?}", open);
+    }
+
+    fn attribute(element: &str, name: &str) -> f32 {
+        let start = element.find(&format!(" {}=\"", name)).unwrap() + name.len() + 3;
+        let end = start + element[start..].find('"').unwrap();
+        element[start..end].parse().unwrap()
+    }
+
+    fn universe() -> UniversalSet {
+        let mut universe = UniversalSet::new("fan".to_string());
+        universe.set_domain((0..101).map(|x| x as f32 / 10.0).collect());
+        universe.create_set("slow".to_string(), MembershipFactory::triangular(0.0, 2.0, 5.0))
+                .unwrap();
+        universe.create_set("fast".to_string(), MembershipFactory::triangular(5.0, 8.0, 10.0))
+                .unwrap();
+        universe.create_set("<very> & fast".to_string(),
+                            MembershipFactory::triangular(9.0, 10.0, 11.0))
+                .unwrap();
+        universe
+    }
+
+    #[test]
+    fn universe_plot() {
+        let svg = universe().plot_svg(400, 200);
+        assert_well_formed(&svg);
+        assert_eq!(svg.matches("<path class=\"term\"").count(), 3);
+        assert_eq!(svg.matches("<text class=\"legend\"").count(), 3);
+        assert!(svg.contains("&lt;very&gt; &amp; fast"));
+
+        let svg = universe().plot_svg_with_resolution(400, 200, 11);
+        let path = svg.lines().find(|line| line.contains("class=\"term\"")).unwrap();
+        assert_eq!(path.matches('L').count(), 10);
+    }
+
+    #[test]
+    fn result_plot() {
+        let mut temp = UniversalSet::new("temp".to_string());
+        temp.create_set("hot".to_string(), MembershipFactory::triangular(20.0, 40.0, 60.0))
+            .unwrap();
+        let mut universes = HashMap::new();
+        universes.insert("temp".to_string(), temp);
+        universes.insert("fan".to_string(), universe());
+        let rule = Rule::new(Box::new(Is::new("temp".to_string(), "hot".to_string())),
+                             "fan".to_string(),
+                             "fast".to_string());
+        let options = InferenceOptions::mamdani();
+        let mut machine = InferenceMachine::new(RuleSet::new(vec![rule]).unwrap(),
+                                                universes,
+                                                options);
+        machine.set_value("temp", 30.0).unwrap();
+        let svg = machine.plot_result_svg(400, 200).unwrap();
+        assert_well_formed(&svg);
+        assert_eq!(svg.matches("<path class=\"term\"").count(), 3);
+        assert_eq!(svg.matches("<path class=\"output\"").count(), 1);
+
+        let value = machine.compute_for("fan").unwrap().value;
+        let marker = svg.lines().find(|line| line.contains("class=\"marker\"")).unwrap();
+        let expected = Transform::new(0.0, 10.0, 400, 200).x(value);
+        assert!((attribute(marker, "x1") - expected).abs() < 0.01);
+        assert_eq!(attribute(marker, "x1"), attribute(marker, "x2"));
+    }
+}
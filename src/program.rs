@@ -0,0 +1,328 @@
+//! This module contains expressions compiled into a flat postfix program.
+//!
+//! Deep expression trees are evaluated with a virtual call per node.
+//! `Program` stores the same expression as a vector of instructions evaluated by a small stack machine.
+//! Memberships of all leaves are calculated first, each distinct (variable, set) pair only once.
+//!
+//! # Usage
+//!
+//! ```rust
+//! use fuzzy_logic::program::Program;
+//! use fuzzy_logic::rules::{And, Is};
+//!
+//! let condition = And::new(Is::new("temp".to_string(), "hot".to_string()),
+//!                          Is::new("humidity".to_string(), "low".to_string()));
+//! let program = Program::compile(&condition).unwrap();
+//! assert_eq!(program.ops().len(), 3);
+//! ```
+use inference::{InferenceContext, InferenceError};
+use rules::Expression;
+
+use std::cell::RefCell;
+
+/// Instruction of the compiled program.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    /// Pushes the membership of the term with given index.
+    PushMembership(usize),
+    /// Pushes the constant.
+    PushConst(f32),
+    /// Replaces two topmost values with their fuzzy 'and'.
+    And,
+    /// Replaces two topmost values with their fuzzy 'or'.
+    Or,
+    /// Replaces the topmost value with its fuzzy 'not'.
+    Not,
+    /// Replaces two topmost values with their fuzzy 'xor'.
+    Xor,
+    /// Replaces two topmost values with their fuzzy equivalence.
+    Equiv,
+    /// Raises the topmost value to given power, see `rules::Power`.
+    Power(f32),
+}
+
+/// Leaf of the compiled expression: the variable and the set to check its membership in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    /// Name of the variable.
+    pub variable: String,
+    /// Name of the set.
+    pub set: String,
+}
+
+/// Expression compiled into postfix instructions.
+///
+/// `Program` is an `Expression` itself, so it can be used as a rule condition.
+pub struct Program {
+    /// Distinct leaves of the expression.
+    terms: Vec<Term>,
+    /// Postfix instructions.
+    ops: Vec<Op>,
+    /// String representation of the compiled expression.
+    source: String,
+    /// Evaluation buffers: memberships of the terms and the stack.
+    buffers: RefCell<(Vec<f32>, Vec<f32>)>,
+}
+
+impl Program {
+    /// Constructs the empty `Program`.
+    ///
+    /// Programs are built only by `Program::compile`, which checks the instructions.
+    fn new() -> Program {
+        Program {
+            terms: Vec::new(),
+            ops: Vec::new(),
+            source: String::new(),
+            buffers: RefCell::new((Vec::new(), Vec::new())),
+        }
+    }
+
+    /// Compiles the expression.
+    ///
+    /// Fails if the expression, or any of its children, doesn't support compilation,
+    /// or if its instructions don't leave exactly one value on the stack.
+    pub fn compile(expression: &Expression) -> Result<Program, String> {
+        let mut program = Program::new();
+        expression.compile(&mut program)?;
+        program.source = expression.to_string();
+        if !program.is_balanced() {
+            return Err(format!("{} compiles into an unbalanced program", program.source));
+        }
+        Ok(program)
+    }
+
+    /// Returns `true` if every instruction finds its operands on the stack,
+    /// every term exists and exactly one value is left.
+    fn is_balanced(&self) -> bool {
+        let mut depth = 0;
+        for op in &self.ops {
+            let operands = match *op {
+                Op::PushMembership(index) if index >= self.terms.len() => return false,
+                Op::PushMembership(_) | Op::PushConst(_) => 0,
+                Op::Not | Op::Power(_) => 1,
+                Op::And | Op::Or | Op::Xor | Op::Equiv => 2,
+            };
+            if depth < operands {
+                return false;
+            }
+            depth = depth - operands + 1;
+        }
+        depth == 1
+    }
+
+    /// Appends the instruction.
+    pub fn push(&mut self, op: Op) {
+        self.ops.push(op);
+    }
+
+    /// Appends the instruction pushing the membership of `variable` in `set`.
+    pub fn push_membership(&mut self, variable: &str, set: &str) {
+        let index = match self.terms.iter().position(|t| t.variable == variable && t.set == set) {
+            Some(index) => index,
+            None => {
+                self.terms.push(Term {
+                    variable: variable.to_string(),
+                    set: set.to_string(),
+                });
+                self.terms.len() - 1
+            }
+        };
+        self.ops.push(Op::PushMembership(index));
+    }
+
+    /// Returns the instructions.
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+
+    /// Returns the distinct terms referenced by `Op::PushMembership`.
+    pub fn terms(&self) -> &[Term] {
+        &self.terms
+    }
+
+    /// Calculates the membership of every term.
+    fn fuzzify(&self,
+               context: &InferenceContext,
+               degrees: &mut Vec<f32>)
+               -> Result<(), InferenceError> {
+        degrees.clear();
+        for term in &self.terms {
+            degrees.push(context.membership(&term.variable, &term.set)?);
+        }
+        Ok(())
+    }
+
+    /// Runs the instructions.
+    fn run(&self, context: &InferenceContext, degrees: &[f32], stack: &mut Vec<f32>) -> f32 {
+        let ops = &*context.options.logic_ops;
+        stack.clear();
+        for op in &self.ops {
+            match *op {
+                Op::PushMembership(index) => stack.push(degrees[index]),
+                Op::PushConst(value) => stack.push(value),
+                Op::And => {
+                    let right = stack.pop().expect("stack underflow");
+                    let left = stack.pop().expect("stack underflow");
+                    stack.push(ops.and(left, right));
+                }
+                Op::Or => {
+                    let right = stack.pop().expect("stack underflow");
+                    let left = stack.pop().expect("stack underflow");
+                    stack.push(ops.or(left, right));
+                }
+                Op::Not => {
+                    let value = stack.pop().expect("stack underflow");
+                    stack.push(ops.not(value));
+                }
+                Op::Xor => {
+                    let right = stack.pop().expect("stack underflow");
+                    let left = stack.pop().expect("stack underflow");
+                    stack.push(ops.xor(left, right));
+                }
+                Op::Equiv => {
+                    let right = stack.pop().expect("stack underflow");
+                    let left = stack.pop().expect("stack underflow");
+                    stack.push(ops.equiv(left, right));
+                }
+                Op::Power(exponent) => {
+                    let value = stack.pop().expect("stack underflow");
+                    stack.push(value.powf(exponent));
+                }
+            }
+        }
+        stack.pop().expect("program is empty")
+    }
+}
+
+impl Expression for Program {
+    /// Evaluates the instructions with the stack machine.
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        let mut buffers = self.buffers.borrow_mut();
+        let (ref mut degrees, ref mut stack) = *buffers;
+        self.fuzzify(context, degrees)?;
+        Ok(self.run(context, degrees, stack))
+    }
+
+    /// String representation of the compiled expression.
+    fn to_string(&self) -> String {
+        self.source.clone()
+    }
+
+    /// Appends the instructions of this program.
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        for op in &self.ops {
+            match *op {
+                Op::PushMembership(index) => {
+                    let term = &self.terms[index];
+                    program.push_membership(&term.variable, &term.set);
+                }
+                op => program.push(op),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use functions::MembershipFactory;
+    use inference::InferenceOptions;
+    use rules::{And, Is, Or};
+    use set::UniversalSet;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    /// Xorshift generator, returns values in [0, 1).
+    fn random(state: &mut u32) -> f32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        (*state % 10000) as f32 / 10000.0
+    }
+
+    fn is(variable: &str, set: &str) -> Is {
+        Is::new(variable.to_string(), set.to_string())
+    }
+
+    #[test]
+    fn compiled_matches_tree() {
+        let mut universes = HashMap::new();
+        for name in &["a", "b", "c"] {
+            let mut universe = UniversalSet::new(name.to_string());
+            universe.create_set("low".to_string(),
+                                MembershipFactory::triangular(-1.0, 0.0, 0.6)).unwrap();
+            universe.create_set("high".to_string(),
+                                MembershipFactory::triangular(0.4, 1.0, 2.0)).unwrap();
+            universes.insert(name.to_string(), Rc::new(universe));
+        }
+        let options = InferenceOptions::mamdani();
+        let tree = Or::new(And::new(is("a", "low"), Or::new(is("b", "high"), is("c", "low"))),
+                           And::new(is("a", "high"), is("b", "low")));
+        let program = Program::compile(&tree).unwrap();
+        assert_eq!(program.terms().len(), 5);
+        assert_eq!(program.to_string(), tree.to_string());
+
+        let mut state = 12345;
+        for _ in 0..1000 {
+            let mut values = HashMap::new();
+            for name in &["a", "b", "c"] {
+                values.insert(name.to_string(), random(&mut state));
+            }
+            let bindings = HashMap::new();
+            let fuzzy_values = HashMap::new();
+            let context = InferenceContext {
+                values: &values,
+                universes: &universes,
+                bindings: &bindings,
+                fuzzy_values: &fuzzy_values,
+                options: &options,
+            };
+            assert_eq!(program.eval(&context).unwrap(), tree.eval(&context).unwrap());
+        }
+    }
+
+    #[test]
+    fn custom_expression_is_not_compiled() {
+        struct Custom;
+        impl Expression for Custom {
+            fn eval(&self, _: &InferenceContext) -> Result<f32, InferenceError> {
+                Ok(1.0)
+            }
+            fn to_string(&self) -> String {
+                "(custom)".to_string()
+            }
+        }
+        let result = Program::compile(&And::new(is("a", "low"), Custom));
+        assert_eq!(result.err(), Some("(custom) can't be compiled".to_string()));
+    }
+
+    #[test]
+    fn unbalanced_program_is_rejected() {
+        struct Unbalanced(Vec<Op>);
+        impl Expression for Unbalanced {
+            fn eval(&self, _: &InferenceContext) -> Result<f32, InferenceError> {
+                Ok(1.0)
+            }
+            fn to_string(&self) -> String {
+                "(unbalanced)".to_string()
+            }
+            fn compile(&self, program: &mut Program) -> Result<(), String> {
+                for &op in &self.0 {
+                    program.push(op);
+                }
+                Ok(())
+            }
+        }
+        let error = Some("(unbalanced) compiles into an unbalanced program".to_string());
+        for ops in [vec![],
+                    vec![Op::And],
+                    vec![Op::PushConst(1.0), Op::Or],
+                    vec![Op::PushConst(1.0), Op::PushConst(0.0)],
+                    vec![Op::PushMembership(0)]] {
+            assert_eq!(Program::compile(&Unbalanced(ops)).err(), error);
+        }
+        let program = Program::compile(&Unbalanced(vec![Op::PushConst(0.25), Op::Not])).unwrap();
+        assert_eq!(program.ops().len(), 2);
+    }
+}
@@ -5,19 +5,60 @@
 //! User can implement his own operations by implementing `LogicOps` or `SetOps` traits.
 extern crate ordered_float;
 
-use inference::InferenceContext;
+use arena::{ExprArena, ExprId};
+use inference::{InferenceContext, InferenceError};
+use ops::ImplicationOp;
+use program::{Op, Program};
 use set::Set;
 
-use std::fmt;
-use std::cell::RefCell;
+use self::ordered_float::OrderedFloat;
+
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
+use std::slice;
+use std::sync::Arc;
 
 /// Abstraction over rule's expression.
 pub trait Expression {
     /// Evaluates the expression with given `InferenceContext`.
-    fn eval(&self, context: &InferenceContext) -> f32;
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError>;
     /// Return the string representation of the expression.
     fn to_string(&self) -> String;
+    /// Appends the postfix instructions of the expression to `program`.
+    ///
+    /// Default implementation fails, so only the crate's expressions can be compiled.
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        let _ = program;
+        Err(format!("{} can't be compiled", self.to_string()))
+    }
+
+    /// Returns the boxed `And` of this expression and `other`.
+    ///
+    /// Boxing keeps the types of long conditions short:
+    /// `is("temp", "hot").and(is("humidity", "low")).not()` is `Box<Expression>`.
+    fn and<R: Expression + 'static>(self, other: R) -> Box<Expression>
+        where Self: Sized + 'static
+    {
+        Box::new(And::new(self, other))
+    }
+
+    /// Returns the boxed `Or` of this expression and `other`.
+    fn or<R: Expression + 'static>(self, other: R) -> Box<Expression>
+        where Self: Sized + 'static
+    {
+        Box::new(Or::new(self, other))
+    }
+
+    /// Returns the boxed `Not` of this expression.
+    fn not(self) -> Box<Expression>
+        where Self: Sized + 'static
+    {
+        Box::new(Not::new(self))
+    }
 }
 
 /// 'Is' expression calculates membership of the given variable.
@@ -40,20 +81,322 @@ impl Is {
 
 impl Expression for Is {
     /// Returns membership of given value.
-    fn eval(&self, context: &InferenceContext) -> f32 {
-        let value = context.values[&self.variable];
-        let universe = context.universes
-                                  .get(&self.variable)
-                                  .expect(&format!("{} is not exists", &self.variable));
-        let set = universe.sets
-                              .get(&self.set)
-                              .expect(&format!("{} is not exists", &self.set));
-        set.check(value)
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        context.membership(&self.variable, &self.set)
     }
     /// String representation of the current `Is` expression.
     fn to_string(&self) -> String {
         format!("(is {} {})", self.variable, self.set)
     }
+    /// Pushes the membership of the variable.
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        program.push_membership(&self.variable, &self.set);
+        Ok(())
+    }
+}
+
+/// Constructs `Is` expression.
+pub fn is(variable: &str, set: &str) -> Is {
+    Is::new(variable.to_string(), set.to_string())
+}
+
+/// 'IsAnyOf' expression calculates OR of memberships of the variable in given sets.
+///
+/// Same as `Or` of `Is` expressions, but the universe is looked up once.
+/// Memberships are folded from left to right, `IsAnyOf` of no sets is 0.
+pub struct IsAnyOf {
+    /// Variable in which membership we're interested.
+    variable: String,
+    /// Where to calculate the membership.
+    sets: Vec<String>,
+}
+
+impl IsAnyOf {
+    /// Constructs `IsAnyOf` expression.
+    pub fn new(variable: String, sets: Vec<String>) -> IsAnyOf {
+        IsAnyOf {
+            variable: variable,
+            sets: sets,
+        }
+    }
+}
+
+impl Expression for IsAnyOf {
+    /// Gets 'or' implementation from `context` and folds the memberships with it.
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        fold_memberships(context, &self.variable, &self.sets, 0.0, |l, r| {
+            (*context.options.logic_ops).or(l, r)
+        })
+    }
+
+    /// String representation of the current `IsAnyOf` expression.
+    fn to_string(&self) -> String {
+        format!("(is-any-of {} {})", self.variable, self.sets.join(" "))
+    }
+
+    /// Pushes the memberships of the variable, applying 'or' after each but the first one.
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        compile_memberships(program, &self.variable, &self.sets, 0.0, Op::Or);
+        Ok(())
+    }
+}
+
+/// 'IsAllOf' expression calculates AND of memberships of the variable in given sets.
+///
+/// Dual of `IsAnyOf`. `IsAllOf` of no sets is 1.
+pub struct IsAllOf {
+    /// Variable in which membership we're interested.
+    variable: String,
+    /// Where to calculate the membership.
+    sets: Vec<String>,
+}
+
+impl IsAllOf {
+    /// Constructs `IsAllOf` expression.
+    pub fn new(variable: String, sets: Vec<String>) -> IsAllOf {
+        IsAllOf {
+            variable: variable,
+            sets: sets,
+        }
+    }
+}
+
+impl Expression for IsAllOf {
+    /// Gets 'and' implementation from `context` and folds the memberships with it.
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        fold_memberships(context, &self.variable, &self.sets, 1.0, |l, r| {
+            (*context.options.logic_ops).and(l, r)
+        })
+    }
+
+    /// String representation of the current `IsAllOf` expression.
+    fn to_string(&self) -> String {
+        format!("(is-all-of {} {})", self.variable, self.sets.join(" "))
+    }
+
+    /// Pushes the memberships of the variable, applying 'and' after each but the first one.
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        compile_memberships(program, &self.variable, &self.sets, 1.0, Op::And);
+        Ok(())
+    }
+}
+
+/// Folds memberships of `variable` in `sets` from left to right, `empty` if there are none.
+fn fold_memberships<F>(context: &InferenceContext,
+                       variable: &str,
+                       sets: &[String],
+                       empty: f32,
+                       f: F)
+                       -> Result<f32, InferenceError>
+    where F: Fn(f32, f32) -> f32
+{
+    let (first, rest) = match sets.split_first() {
+        Some(split) => split,
+        None => return Ok(empty),
+    };
+    let universe = context.universe(variable)?;
+    let mut result = context.membership_in(universe, variable, first)?;
+    for set in rest {
+        result = f(result, context.membership_in(universe, variable, set)?);
+    }
+    Ok(result)
+}
+
+/// Pushes memberships of `variable` in `sets`, followed by `op` after each but the first one,
+/// or `empty` if there are none.
+fn compile_memberships(program: &mut Program, variable: &str, sets: &[String], empty: f32, op: Op) {
+    if sets.is_empty() {
+        program.push(Op::PushConst(empty));
+    }
+    for (i, set) in sets.iter().enumerate() {
+        program.push_membership(variable, set);
+        if i > 0 {
+            program.push(op);
+        }
+    }
+}
+
+/// 'GreaterThan' expression compares the crisp value of the variable with the threshold.
+///
+/// Returns 1 if the value is greater than the threshold and 0 otherwise.
+/// With a transition width the value changes linearly from 0 at `threshold - width / 2`
+/// to 1 at `threshold + width / 2`.
+pub struct GreaterThan {
+    /// Variable to compare.
+    variable: String,
+    /// Threshold of the comparison.
+    threshold: f32,
+    /// Width of the transition between 0 and 1.
+    width: f32,
+}
+
+impl GreaterThan {
+    /// Constructs crisp `GreaterThan` expression.
+    pub fn new(variable: String, threshold: f32) -> GreaterThan {
+        GreaterThan {
+            variable: variable,
+            threshold: threshold,
+            width: 0.0,
+        }
+    }
+
+    /// Sets the width of the transition between 0 and 1.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is negative or not finite.
+    pub fn with_width(mut self, width: f32) -> GreaterThan {
+        self.width = check_width(width);
+        self
+    }
+}
+
+impl Expression for GreaterThan {
+    /// Returns the degree to which the value is greater than the threshold.
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        let value = context.value(&self.variable)?;
+        Ok(ramp(value - self.threshold, self.width, false))
+    }
+
+    /// String representation of the current `GreaterThan` expression.
+    fn to_string(&self) -> String {
+        comparison_to_string(">", &self.variable, &[self.threshold], self.width)
+    }
+}
+
+/// 'LessThan' expression compares the crisp value of the variable with the threshold.
+///
+/// Mirror image of `GreaterThan`: returns 1 if the value is less than the threshold.
+pub struct LessThan {
+    /// Variable to compare.
+    variable: String,
+    /// Threshold of the comparison.
+    threshold: f32,
+    /// Width of the transition between 1 and 0.
+    width: f32,
+}
+
+impl LessThan {
+    /// Constructs crisp `LessThan` expression.
+    pub fn new(variable: String, threshold: f32) -> LessThan {
+        LessThan {
+            variable: variable,
+            threshold: threshold,
+            width: 0.0,
+        }
+    }
+
+    /// Sets the width of the transition between 1 and 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is negative or not finite.
+    pub fn with_width(mut self, width: f32) -> LessThan {
+        self.width = check_width(width);
+        self
+    }
+}
+
+impl Expression for LessThan {
+    /// Returns the degree to which the value is less than the threshold.
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        let value = context.value(&self.variable)?;
+        Ok(ramp(self.threshold - value, self.width, false))
+    }
+
+    /// String representation of the current `LessThan` expression.
+    fn to_string(&self) -> String {
+        comparison_to_string("<", &self.variable, &[self.threshold], self.width)
+    }
+}
+
+/// 'Between' expression checks that the crisp value of the variable is in the range.
+///
+/// Returns 1 if the value is in [low, high] and 0 otherwise. With a transition width
+/// both bounds change linearly, as in `GreaterThan` and `LessThan`.
+pub struct Between {
+    /// Variable to compare.
+    variable: String,
+    /// Lower bound of the range.
+    low: f32,
+    /// Upper bound of the range.
+    high: f32,
+    /// Width of the transitions at the bounds.
+    width: f32,
+}
+
+impl Between {
+    /// Constructs crisp `Between` expression.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `low` is greater than `high`.
+    pub fn new(variable: String, low: f32, high: f32) -> Between {
+        assert!(low <= high, "range [{}, {}] is empty", low, high);
+        Between {
+            variable: variable,
+            low: low,
+            high: high,
+            width: 0.0,
+        }
+    }
+
+    /// Sets the width of the transitions at the bounds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is negative or not finite.
+    pub fn with_width(mut self, width: f32) -> Between {
+        self.width = check_width(width);
+        self
+    }
+}
+
+impl Expression for Between {
+    /// Returns the degree to which the value is in the range.
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        let value = context.value(&self.variable)?;
+        let above = ramp(value - self.low, self.width, true);
+        let below = ramp(self.high - value, self.width, true);
+        Ok(above.min(below))
+    }
+
+    /// String representation of the current `Between` expression.
+    fn to_string(&self) -> String {
+        comparison_to_string("between", &self.variable, &[self.low, self.high], self.width)
+    }
+}
+
+/// Returns the degree to which `distance` is positive, see `GreaterThan`.
+fn ramp(distance: f32, width: f32, inclusive: bool) -> f32 {
+    if width > 0.0 {
+        (distance / width + 0.5).max(0.0).min(1.0)
+    } else if distance > 0.0 || (inclusive && distance == 0.0) {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// Returns `width` if it's a valid width of the transition, panics otherwise.
+fn check_width(width: f32) -> f32 {
+    assert!(width >= 0.0 && width.is_finite(),
+            "width must be a non-negative number, got {}",
+            width);
+    width
+}
+
+/// Returns "(`op` variable bounds...)", followed by " ~width" if the width is not zero.
+fn comparison_to_string(op: &str, variable: &str, bounds: &[f32], width: f32) -> String {
+    let mut s = format!("({} {}", op, variable);
+    for bound in bounds {
+        s.push_str(&format!(" {}", bound));
+    }
+    if width > 0.0 {
+        s.push_str(&format!(" ~{}", width));
+    }
+    s.push(')');
+    s
 }
 
 /// 'And' expression calculates AND logical operation with given implementation.
@@ -79,15 +422,22 @@ impl<L: Expression, R: Expression> And<L, R> {
 
 impl<L: Expression, R: Expression> Expression for And<L, R> {
     /// Gets 'and' implementation from `context` and returns its value.
-    fn eval(&self, context: &InferenceContext) -> f32 {
-        let left_result = self.left.eval(context);
-        let right_result = self.right.eval(context);
-        (*context.options.logic_ops).and(left_result, right_result)
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        let left_result = self.left.eval(context)?;
+        let right_result = self.right.eval(context)?;
+        Ok((*context.options.logic_ops).and(left_result, right_result))
     }
     /// String representation of the current `And` expression.
     fn to_string(&self) -> String {
         format!("(and {} {})", self.left.to_string(), self.right.to_string())
     }
+    /// Compiles both operands, then applies 'and'.
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        self.left.compile(program)?;
+        self.right.compile(program)?;
+        program.push(Op::And);
+        Ok(())
+    }
 }
 
 /// 'Or' expression calculates OR logical operation with given implementation.
@@ -113,135 +463,1942 @@ impl<L: Expression, R: Expression> Or<L, R> {
 
 impl<L: Expression, R: Expression> Expression for Or<L, R> {
     /// Gets 'or' implementation from `context` and returns its value.
-    fn eval(&self, context: &InferenceContext) -> f32 {
-        let left_result = self.left.eval(context);
-        let right_result = self.right.eval(context);
-        (*context.options.logic_ops).or(left_result, right_result)
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        let left_result = self.left.eval(context)?;
+        let right_result = self.right.eval(context)?;
+        Ok((*context.options.logic_ops).or(left_result, right_result))
     }
 
     /// String representation of the current `Or` expression.
     fn to_string(&self) -> String {
         format!("(or {} {})", self.left.to_string(), self.right.to_string())
     }
+
+    /// Compiles both operands, then applies 'or'.
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        self.left.compile(program)?;
+        self.right.compile(program)?;
+        program.push(Op::Or);
+        Ok(())
+    }
 }
 
 /// 'Not' expression calculates NOT logical operation with given implementation.
-pub struct Not {
+pub struct Not<E>
+    where E: Expression
+{
     /// Expression to calculate.
-    expression: Box<Expression>,
+    expression: E,
 }
 
-impl Not {
+impl<E: Expression> Not<E> {
     /// Constructs `Not` expression.
-    fn new(expression: Box<Expression>) -> Not {
+    pub fn new(expression: E) -> Not<E> {
         Not { expression: expression }
     }
 }
 
-impl Expression for Not {
+impl<E: Expression> Expression for Not<E> {
     /// Gets 'not' implementation from `context` and returns its value.
-    fn eval(&self, context: &InferenceContext) -> f32 {
-        let value = (*self.expression).eval(context);
-        (*context.options.logic_ops).not(value)
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        let value = self.expression.eval(context)?;
+        Ok((*context.options.logic_ops).not(value))
     }
 
     /// String representation of the current `Not` expression.
     fn to_string(&self) -> String {
-        format!("(not {})", (*self.expression).to_string())
+        format!("(not {})", self.expression.to_string())
+    }
+
+    /// Compiles the expression, then applies 'not'.
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        self.expression.compile(program)?;
+        program.push(Op::Not);
+        Ok(())
     }
 }
 
-/// Describes fuzzy inference rule.
-pub struct Rule {
-    /// Root of the evaluation tree.
-    condition: Box<Expression>,
-    /// IF ... THEN `result_set`.
-    result_set: String,
-    /// The universe of `result_set`.
-    result_universe: String,
+/// Constructs `Not` expression.
+pub fn not<E: Expression>(expression: E) -> Not<E> {
+    Not::new(expression)
 }
 
-impl Rule {
-    /// Constructs the new rule with given arguments.
-    pub fn new(condition: Box<Expression>, result_universe: String, result_set: String) -> Rule {
-        Rule {
-            condition: condition,
-            result_set: result_set,
-            result_universe: result_universe,
+/// 'Xor' expression calculates XOR logical operation with given implementation.
+pub struct Xor<L, R>
+    where L: Expression,
+          R: Expression
+{
+    /// Left operand.
+    left: L,
+    /// Right operand.
+    right: R,
+}
+
+impl<L: Expression, R: Expression> Xor<L, R> {
+    /// Constructs `Xor` expression.
+    pub fn new(left: L, right: R) -> Xor<L, R> {
+        Xor {
+            left: left,
+            right: right,
         }
     }
+}
+
+impl<L: Expression, R: Expression> Expression for Xor<L, R> {
+    /// Gets 'xor' implementation from `context` and returns its value.
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        let left_result = self.left.eval(context)?;
+        let right_result = self.right.eval(context)?;
+        Ok((*context.options.logic_ops).xor(left_result, right_result))
+    }
 
-    /// Computes the current rule. Returns the fuzzy set as the result.
-    pub fn compute(&self, context: &InferenceContext) -> Set {
-        let expression_result = (*self.condition).eval(context);
-        let universe = context.universes
-                              .get(&self.result_universe)
-                              .expect(&format!("{} is not exists", &self.result_universe));
-        let set = universe.sets
-                          .get(&self.result_set)
-                          .expect(&format!("{} is not exists", &self.result_set));
-        let result_values = set.cache.borrow()
-                               .iter()
-                               .filter_map(|(&key, &value)| {
-                                   if value <= expression_result {
-                                       Some((key, value))
-                                   } else {
-                                       None
-                                   }
-                               })
-                               .collect::<HashMap<_, f32>>();
-        Set::new_with_domain(format!("{}: {}", &self.result_universe, &self.result_set),
-                             RefCell::new(result_values))
+    /// String representation of the current `Xor` expression.
+    fn to_string(&self) -> String {
+        format!("(xor {} {})", self.left.to_string(), self.right.to_string())
     }
-}
 
-impl fmt::Display for Rule {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f,
-               "(Rule {}:{} if:{})",
-               &self.result_universe,
-               &self.result_set,
-               &(*self.condition).to_string())
+    /// Compiles both operands, then applies 'xor'.
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        self.left.compile(program)?;
+        self.right.compile(program)?;
+        program.push(Op::Xor);
+        Ok(())
     }
 }
 
-/// Contains all the rules. Evaluates them.
-pub struct RuleSet {
-    /// Vector with rules.
-    rules: Vec<Rule>,
+/// 'Equiv' expression calculates logical equivalence with given implementation.
+pub struct Equiv<L, R>
+    where L: Expression,
+          R: Expression
+{
+    /// Left operand.
+    left: L,
+    /// Right operand.
+    right: R,
 }
 
-impl RuleSet {
-    /// Constructs the `RuleSet` with given `Rule`s
-    pub fn new(rules: Vec<Rule>) -> Result<RuleSet, String> {
-        let rule_universe = rules[0].result_universe.clone();
-        for rule in &rules {
-            if rule_universe != rule.result_universe {
-                return Err(format!("Rules are in different result universes({} and {})",
-                                   &rule_universe,
-                                   &rule.result_universe));
-            }
+impl<L: Expression, R: Expression> Equiv<L, R> {
+    /// Constructs `Equiv` expression.
+    pub fn new(left: L, right: R) -> Equiv<L, R> {
+        Equiv {
+            left: left,
+            right: right,
         }
-        return Ok(RuleSet { rules: rules });
     }
+}
 
-    /// Computes all rules. Resulting fuzzy sets are then united and returned.
-    pub fn compute_all(&self, context: &InferenceContext) -> Set {
-        let mut result_set = self.rules[0].compute(context);
-        for rule in &self.rules[1..self.rules.len()] {
-            let mut result = rule.compute(context);
-            result_set = (*context.options.set_ops).union(&mut result_set, &mut result);
-        }
-        result_set
+impl<L: Expression, R: Expression> Expression for Equiv<L, R> {
+    /// Gets equivalence implementation from `context` and returns its value.
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        let left_result = self.left.eval(context)?;
+        let right_result = self.right.eval(context)?;
+        Ok((*context.options.logic_ops).equiv(left_result, right_result))
+    }
+
+    /// String representation of the current `Equiv` expression.
+    fn to_string(&self) -> String {
+        format!("(equiv {} {})", self.left.to_string(), self.right.to_string())
+    }
+
+    /// Compiles both operands, then applies equivalence.
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        self.left.compile(program)?;
+        self.right.compile(program)?;
+        program.push(Op::Equiv);
+        Ok(())
     }
 }
 
-impl fmt::Display for RuleSet {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut s = String::new();
-        for rule in &self.rules {
-            s = s + &format!("\t{}\n", rule);
+/// 'All' expression folds AND logical operation over any number of expressions.
+///
+/// Expressions are folded from left to right, so for non-associative operations
+/// `all [a, b, c]` is `(a and b) and c`. `All` of no expressions is 1.
+pub struct All {
+    /// Expressions to calculate.
+    expressions: Vec<Box<Expression>>,
+}
+
+impl All {
+    /// Constructs `All` expression.
+    pub fn new(expressions: Vec<Box<Expression>>) -> All {
+        All { expressions: expressions }
+    }
+}
+
+impl Expression for All {
+    /// Gets 'and' implementation from `context` and folds the values with it.
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        fold(&self.expressions, context, 1.0, |l, r| (*context.options.logic_ops).and(l, r))
+    }
+
+    /// String representation of the current `All` expression.
+    fn to_string(&self) -> String {
+        list_to_string("all", &self.expressions)
+    }
+
+    /// Compiles the expressions, applying 'and' after each but the first one.
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        compile_fold(&self.expressions, program, 1.0, Op::And)
+    }
+}
+
+/// 'Any' expression folds OR logical operation over any number of expressions.
+///
+/// Expressions are folded from left to right, see `All`. `Any` of no expressions is 0.
+pub struct Any {
+    /// Expressions to calculate.
+    expressions: Vec<Box<Expression>>,
+}
+
+impl Any {
+    /// Constructs `Any` expression.
+    pub fn new(expressions: Vec<Box<Expression>>) -> Any {
+        Any { expressions: expressions }
+    }
+}
+
+impl Expression for Any {
+    /// Gets 'or' implementation from `context` and folds the values with it.
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        fold(&self.expressions, context, 0.0, |l, r| (*context.options.logic_ops).or(l, r))
+    }
+
+    /// String representation of the current `Any` expression.
+    fn to_string(&self) -> String {
+        list_to_string("any", &self.expressions)
+    }
+
+    /// Compiles the expressions, applying 'or' after each but the first one.
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        compile_fold(&self.expressions, program, 0.0, Op::Or)
+    }
+}
+
+/// Folds values of `expressions` from left to right, `empty` if there are none.
+fn fold<F>(expressions: &[Box<Expression>],
+           context: &InferenceContext,
+           empty: f32,
+           f: F)
+           -> Result<f32, InferenceError>
+    where F: Fn(f32, f32) -> f32
+{
+    let (first, rest) = match expressions.split_first() {
+        Some(split) => split,
+        None => return Ok(empty),
+    };
+    let mut result = first.eval(context)?;
+    for expression in rest {
+        result = f(result, expression.eval(context)?);
+    }
+    Ok(result)
+}
+
+/// Compiles `expressions`, pushing `op` after each but the first one, or `empty` if there are none.
+fn compile_fold(expressions: &[Box<Expression>],
+                program: &mut Program,
+                empty: f32,
+                op: Op)
+                -> Result<(), String> {
+    if expressions.is_empty() {
+        program.push(Op::PushConst(empty));
+        return Ok(());
+    }
+    for (i, expression) in expressions.iter().enumerate() {
+        expression.compile(program)?;
+        if i > 0 {
+            program.push(op);
         }
-        write!(f, "(RuleSet\n{})", s)
+    }
+    Ok(())
+}
+
+/// Returns "(`name` e1 e2 ...)".
+fn list_to_string(name: &str, expressions: &[Box<Expression>]) -> String {
+    let mut s = format!("({}", name);
+    for expression in expressions {
+        s.push(' ');
+        s.push_str(&expression.to_string());
+    }
+    s.push(')');
+    s
+}
+
+/// 'Very' hedge concentrates the expression: squares its value.
+pub struct Very<E>
+    where E: Expression
+{
+    /// Expression to calculate.
+    expression: E,
+}
+
+impl<E: Expression> Very<E> {
+    /// Constructs `Very` hedge.
+    pub fn new(expression: E) -> Very<E> {
+        Very { expression: expression }
+    }
+}
+
+impl<E: Expression> Expression for Very<E> {
+    /// Returns the square of the value of the expression.
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        Ok(self.expression.eval(context)?.powi(2))
+    }
+
+    /// String representation of the current `Very` hedge.
+    fn to_string(&self) -> String {
+        format!("(very {})", self.expression.to_string())
+    }
+
+    /// Compiles the expression, then raises it to the power of 2.
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        self.expression.compile(program)?;
+        program.push(Op::Power(2.0));
+        Ok(())
+    }
+}
+
+/// Constructs `Very` hedge.
+pub fn very<E: Expression>(expression: E) -> Very<E> {
+    Very::new(expression)
+}
+
+/// 'Extremely' hedge concentrates the expression more than `Very`: cubes its value.
+pub struct Extremely<E>
+    where E: Expression
+{
+    /// Expression to calculate.
+    expression: E,
+}
+
+impl<E: Expression> Extremely<E> {
+    /// Constructs `Extremely` hedge.
+    pub fn new(expression: E) -> Extremely<E> {
+        Extremely { expression: expression }
+    }
+}
+
+impl<E: Expression> Expression for Extremely<E> {
+    /// Returns the cube of the value of the expression.
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        Ok(self.expression.eval(context)?.powi(3))
+    }
+
+    /// String representation of the current `Extremely` hedge.
+    fn to_string(&self) -> String {
+        format!("(extremely {})", self.expression.to_string())
+    }
+
+    /// Compiles the expression, then raises it to the power of 3.
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        self.expression.compile(program)?;
+        program.push(Op::Power(3.0));
+        Ok(())
+    }
+}
+
+/// Constructs `Extremely` hedge.
+pub fn extremely<E: Expression>(expression: E) -> Extremely<E> {
+    Extremely::new(expression)
+}
+
+/// 'Somewhat' hedge dilates the expression: takes the square root of its value.
+pub struct Somewhat<E>
+    where E: Expression
+{
+    /// Expression to calculate.
+    expression: E,
+}
+
+impl<E: Expression> Somewhat<E> {
+    /// Constructs `Somewhat` hedge.
+    pub fn new(expression: E) -> Somewhat<E> {
+        Somewhat { expression: expression }
+    }
+}
+
+impl<E: Expression> Expression for Somewhat<E> {
+    /// Returns the square root of the value of the expression.
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        Ok(self.expression.eval(context)?.sqrt())
+    }
+
+    /// String representation of the current `Somewhat` hedge.
+    fn to_string(&self) -> String {
+        format!("(somewhat {})", self.expression.to_string())
+    }
+
+    /// Compiles the expression, then raises it to the power of 0.5.
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        self.expression.compile(program)?;
+        program.push(Op::Power(0.5));
+        Ok(())
+    }
+}
+
+/// Constructs `Somewhat` hedge.
+pub fn somewhat<E: Expression>(expression: E) -> Somewhat<E> {
+    Somewhat::new(expression)
+}
+
+/// 'Power' hedge raises the value of the expression to the arbitrary exponent.
+///
+/// Exponents greater than 1 concentrate the expression, less than 1 dilate it.
+pub struct Power<E>
+    where E: Expression
+{
+    /// Expression to calculate.
+    expression: E,
+    /// Exponent of the value.
+    exponent: f32,
+}
+
+impl<E: Expression> Power<E> {
+    /// Constructs `Power` hedge.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `exponent` is not positive or not finite.
+    pub fn new(expression: E, exponent: f32) -> Power<E> {
+        assert!(exponent > 0.0 && exponent.is_finite(),
+                "exponent must be a positive number, got {}",
+                exponent);
+        Power {
+            expression: expression,
+            exponent: exponent,
+        }
+    }
+}
+
+impl<E: Expression> Expression for Power<E> {
+    /// Returns the value of the expression raised to the exponent.
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        Ok(self.expression.eval(context)?.powf(self.exponent))
+    }
+
+    /// String representation of the current `Power` hedge.
+    fn to_string(&self) -> String {
+        format!("(power {} {})", self.exponent, self.expression.to_string())
+    }
+
+    /// Compiles the expression, then raises it to the exponent.
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        self.expression.compile(program)?;
+        program.push(Op::Power(self.exponent));
+        Ok(())
+    }
+}
+
+/// Constructs `Power` hedge. See `Power::new`.
+pub fn power<E: Expression>(expression: E, exponent: f32) -> Power<E> {
+    Power::new(expression, exponent)
+}
+
+/// Boxed expressions are expressions too, so they can be children of `And`, `Or` and `Not`.
+impl Expression for Box<Expression> {
+    fn eval(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        (**self).eval(context)
+    }
+
+    fn to_string(&self) -> String {
+        (**self).to_string()
+    }
+
+    fn compile(&self, program: &mut Program) -> Result<(), String> {
+        (**self).compile(program)
+    }
+}
+
+/// Consequent of the rule: THEN `universe` IS `set`.
+struct Consequent {
+    /// The universe of `set`.
+    universe: String,
+    /// The set of the result.
+    set: String,
+    /// Name of the computed set, precomputed as "universe: set".
+    name: Arc<str>,
+}
+
+impl Consequent {
+    /// Constructs the consequent and precomputes its name.
+    fn new(universe: String, set: String) -> Consequent {
+        let name = Arc::from(format!("{}: {}", &universe, &set));
+        Consequent {
+            universe: universe,
+            set: set,
+            name: name,
+        }
+    }
+}
+
+/// Describes fuzzy inference rule.
+pub struct Rule {
+    /// Identifier of the rule, see `named`.
+    id: Option<String>,
+    /// Root of the evaluation tree.
+    condition: Box<Expression>,
+    /// IF ... THEN all of the consequents. Never empty.
+    consequents: Vec<Consequent>,
+    /// Experts' certainty in the rule, see `with_certainty`.
+    certainty: Option<f32>,
+    /// Weight of the rule in (0, 1], which multiplies the value of the condition.
+    weight: f32,
+    /// Implication of the rule, overrides the one of `InferenceOptions` if present.
+    implication: Option<Box<ImplicationOp>>,
+}
+
+impl Rule {
+    /// Constructs the new rule with given arguments.
+    pub fn new(condition: Box<Expression>, result_universe: String, result_set: String) -> Rule {
+        Rule {
+            id: None,
+            condition: condition,
+            consequents: vec![Consequent::new(result_universe, result_set)],
+            certainty: None,
+            weight: 1.0,
+            implication: None,
+        }
+    }
+
+    /// Constructs the new rule with given identifier.
+    ///
+    /// Identifiers are shown by `Display` and must be unique within a `RuleSet`.
+    pub fn named(id: String,
+                 condition: Box<Expression>,
+                 result_universe: String,
+                 result_set: String)
+                 -> Rule {
+        let mut rule = Rule::new(condition, result_universe, result_set);
+        rule.id = Some(id);
+        rule
+    }
+
+    /// Sets the identifier of the rule, e.g. of one constructed with `with_weight`.
+    ///
+    /// Same as constructing the rule with `named`.
+    pub fn with_id(mut self, id: String) -> Rule {
+        self.id = Some(id);
+        self
+    }
+
+    /// Returns the identifier of the rule, if it was set with `named` or `with_id`.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Adds one more consequent to the rule, e.g. in another output universe.
+    ///
+    /// The condition is evaluated once for all consequents.
+    pub fn with_consequent(mut self, result_universe: String, result_set: String) -> Rule {
+        self.consequents.push(Consequent::new(result_universe, result_set));
+        self
+    }
+
+    /// Constructs the new rule with given weight in (0, 1].
+    ///
+    /// The value of the condition is multiplied by the weight before the implication,
+    /// so less trusted rules contribute less to the result.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight` is not in (0, 1].
+    pub fn with_weight(condition: Box<Expression>,
+                       result_universe: String,
+                       result_set: String,
+                       weight: f32)
+                       -> Rule {
+        assert!(weight > 0.0 && weight <= 1.0, "weight must be in (0, 1], got {}", weight);
+        let mut rule = Rule::new(condition, result_universe, result_set);
+        rule.weight = weight;
+        rule
+    }
+
+    /// Returns the weight of the rule, 1 unless constructed with `with_weight`.
+    pub fn weight(&self) -> f32 {
+        self.weight
+    }
+
+    /// Returns the firing strength of the rule: the value of the condition multiplied
+    /// by the weight.
+    pub fn firing_strength(&self, context: &InferenceContext) -> Result<f32, InferenceError> {
+        Ok((*self.condition).eval(context)? * self.weight)
+    }
+
+    /// Sets the certainty factor of the rule in (0, 1].
+    ///
+    /// Certainty doesn't change the rule's result, it only affects the confidence
+    /// reported in `InferenceResult`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `certainty` is not in (0, 1].
+    pub fn with_certainty(mut self, certainty: f32) -> Rule {
+        assert!(certainty > 0.0 && certainty <= 1.0,
+                "certainty factor must be in (0, 1], got {}",
+                certainty);
+        self.certainty = Some(certainty);
+        self
+    }
+
+    /// Returns the certainty factor of the rule, if it was set.
+    pub fn certainty(&self) -> Option<f32> {
+        self.certainty
+    }
+
+    /// Sets the implication of the rule, e.g. `ProductImplication` to scale the consequents
+    /// instead of clipping them. Other rules keep using the implication of `InferenceOptions`.
+    pub fn with_implication(mut self, implication: Box<ImplicationOp>) -> Rule {
+        self.implication = Some(implication);
+        self
+    }
+
+    /// Returns the implication set by `with_implication`, if any.
+    pub fn implication(&self) -> Option<&ImplicationOp> {
+        self.implication.as_deref()
+    }
+
+    /// Constructs the new rule with the condition stored in the arena.
+    pub fn new_with_arena(arena: Rc<ExprArena>,
+                          root: ExprId,
+                          result_universe: String,
+                          result_set: String)
+                          -> Rule {
+        Rule::new(Box::new(ExprArena::expression(arena, root)), result_universe, result_set)
+    }
+
+    /// Returns the condition of the rule.
+    pub fn condition(&self) -> &Expression {
+        &*self.condition
+    }
+
+    /// Returns the universe of the rule's first result.
+    pub fn result_universe(&self) -> &str {
+        &self.consequents[0].universe
+    }
+
+    /// Returns the set of the rule's first result.
+    pub fn result_set(&self) -> &str {
+        &self.consequents[0].set
+    }
+
+    /// Returns the universes and the sets of all the rule's results.
+    pub fn consequents(&self) -> Vec<(&str, &str)> {
+        self.consequents
+            .iter()
+            .map(|consequent| (&*consequent.universe, &*consequent.set))
+            .collect()
+    }
+
+    /// Returns the set of the rule's result in `universe`, if there is one.
+    pub fn result_set_in(&self, universe: &str) -> Option<&str> {
+        self.consequents
+            .iter()
+            .find(|consequent| consequent.universe == universe)
+            .map(|consequent| &*consequent.set)
+    }
+
+    /// Computes the current rule. Returns the fuzzy set of every consequent as the result.
+    pub fn compute(&self, context: &InferenceContext) -> Result<Vec<Set>, InferenceError> {
+        let strength = self.firing_strength(context)?;
+        let mut results = Vec::with_capacity(self.consequents.len());
+        for index in 0..self.consequents.len() {
+            let mut result = Set::new_empty();
+            self.compute_consequent_into(context, index, strength, &mut result)?;
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    /// Computes the first consequent of the current rule into `result`, reusing its cache.
+    ///
+    /// Returns the firing strength of the rule.
+    pub fn compute_into(&self,
+                        context: &InferenceContext,
+                        result: &mut Set)
+                        -> Result<f32, InferenceError> {
+        let strength = self.firing_strength(context)?;
+        self.compute_consequent_into(context, 0, strength, result)?;
+        Ok(strength)
+    }
+
+    /// Computes the consequent with given index into `result` for given firing strength.
+    ///
+    /// Memberships of the result set over the domain of the result universe are combined
+    /// with the firing strength by the implication of the rule or, if it has none,
+    /// of the options.
+    /// If the domain is empty, cached memberships are used.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no consequent with given index.
+    pub fn compute_consequent_into(&self,
+                                   context: &InferenceContext,
+                                   index: usize,
+                                   strength: f32,
+                                   result: &mut Set)
+                                   -> Result<(), InferenceError> {
+        let consequent = &self.consequents[index];
+        let universe = context.universes
+                              .get(&consequent.universe)
+                              .ok_or_else(|| {
+                                  InferenceError::UnknownUniverse(consequent.universe.clone())
+                              })?;
+        let set = universe.sets
+                          .get(&consequent.set)
+                          .ok_or_else(|| {
+                              InferenceError::UnknownSet {
+                                  universe: consequent.universe.clone(),
+                                  set: consequent.set.clone(),
+                              }
+                          })?;
+        let implication = self.implication().unwrap_or(&*context.options.implication);
+        result.name = consequent.name.clone();
+        result.membership = None;
+        let mut result_values = result.cache.borrow_mut();
+        result_values.clear();
+        if universe.domain().is_empty() {
+            for (&key, &value) in set.cache.borrow().iter() {
+                result_values.insert(key, implication.apply(strength, value));
+            }
+        } else {
+            for &x in universe.domain() {
+                let value = implication.apply(strength, set.check(x));
+                result_values.insert(OrderedFloat(x), value);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(Rule")?;
+        if let Some(ref id) = self.id {
+            write!(f, " #{}", id)?;
+        }
+        for consequent in &self.consequents {
+            write!(f, " {}:{}", &consequent.universe, &consequent.set)?;
+        }
+        write!(f, " if:{}", &(*self.condition).to_string())?;
+        if self.weight != 1.0 {
+            write!(f, " weight:{}", self.weight)?;
+        }
+        if let Some(ref implication) = self.implication {
+            write!(f, " implication:{}", implication.name())?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Activation of a rule by a computation, see `RuleSet::compute_with_trace`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleActivation {
+    /// Index of the rule in the rule set.
+    pub index: usize,
+    /// Identifier of the rule, if it has one.
+    pub id: Option<String>,
+    /// String representation of the rule.
+    pub rule: String,
+    /// Value of the rule's condition multiplied by the rule's weight.
+    pub strength: f32,
+    /// Name of the rule's result set in the rule set's `result_universe`,
+    /// `None` if the rule doesn't conclude in it.
+    pub result_set: Option<String>,
+}
+
+/// Error of modifying a `RuleSet`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleSetError {
+    /// The last rule can't be removed, since a rule set is never empty.
+    LastRule,
+    /// Another rule of the set has the same identifier.
+    DuplicateId(String),
+    /// There is no rule with given index.
+    InvalidIndex {
+        /// Index of the missing rule.
+        index: usize,
+        /// Number of the rules.
+        len: usize,
+    },
+}
+
+impl fmt::Display for RuleSetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RuleSetError::LastRule => write!(f, "the last rule of the rule set can't be removed"),
+            RuleSetError::DuplicateId(ref id) => write!(f, "rule id {} is not unique", id),
+            RuleSetError::InvalidIndex { index, len } => {
+                write!(f, "no rule with index {} in the rule set of {} rules", index, len)
+            }
+        }
+    }
+}
+
+impl Error for RuleSetError {
+    fn description(&self) -> &str {
+        match *self {
+            RuleSetError::LastRule => "the last rule can't be removed",
+            RuleSetError::DuplicateId(_) => "rule id is not unique",
+            RuleSetError::InvalidIndex { .. } => "no rule with given index",
+        }
+    }
+}
+
+/// Contains all the rules. Evaluates them.
+pub struct RuleSet {
+    /// Vector with rules.
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    /// Constructs the `RuleSet` with given `Rule`s
+    ///
+    /// Fails if there are no rules or if identifiers of some rules are the same.
+    /// Rules may conclude in different universes, see `result_universes`.
+    pub fn new(rules: Vec<Rule>) -> Result<RuleSet, String> {
+        if rules.is_empty() {
+            return Err("rule set must contain at least one rule".to_string());
+        }
+        let mut rule_set = RuleSet { rules: Vec::with_capacity(rules.len()) };
+        for rule in rules {
+            rule_set.add_rule(rule).map_err(|error| error.to_string())?;
+        }
+        Ok(rule_set)
+    }
+
+    /// Returns the number of rules.
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Returns `true` if there are no rules. Never the case for a constructed `RuleSet`.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Returns the rules.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Returns an iterator over the rules.
+    pub fn iter<'a>(&'a self) -> slice::Iter<'a, Rule> {
+        self.rules.iter()
+    }
+
+    /// Returns the index of the rule with given identifier.
+    pub fn index_of(&self, id: &str) -> Option<usize> {
+        self.rules.iter().position(|rule| rule.id() == Some(id))
+    }
+
+    /// Appends the rule to the set. Rules may conclude in different universes.
+    ///
+    /// Fails with `RuleSetError::DuplicateId` if another rule has the same identifier.
+    pub fn add_rule(&mut self, rule: Rule) -> Result<(), RuleSetError> {
+        self.check_id(&rule, None)?;
+        self.rules.push(rule);
+        Ok(())
+    }
+
+    /// Removes the rule with given index and returns it.
+    ///
+    /// Fails with `RuleSetError::LastRule` if it's the only rule, the set is kept intact then.
+    pub fn remove_rule(&mut self, index: usize) -> Result<Rule, RuleSetError> {
+        self.check_index(index)?;
+        if self.rules.len() == 1 {
+            return Err(RuleSetError::LastRule);
+        }
+        Ok(self.rules.remove(index))
+    }
+
+    /// Replaces the rule with given index and returns the old one.
+    ///
+    /// Fails with `RuleSetError::DuplicateId` if another rule has the same identifier.
+    pub fn replace_rule(&mut self, index: usize, rule: Rule) -> Result<Rule, RuleSetError> {
+        self.check_index(index)?;
+        self.check_id(&rule, Some(index))?;
+        Ok(mem::replace(&mut self.rules[index], rule))
+    }
+
+    /// Checks that no rule but the one with index `except` has the identifier of `rule`.
+    fn check_id(&self, rule: &Rule, except: Option<usize>) -> Result<(), RuleSetError> {
+        if let Some(id) = rule.id() {
+            match self.index_of(id) {
+                Some(index) if Some(index) != except => {
+                    return Err(RuleSetError::DuplicateId(id.to_string()))
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn check_index(&self, index: usize) -> Result<(), RuleSetError> {
+        if index >= self.rules.len() {
+            return Err(RuleSetError::InvalidIndex {
+                index: index,
+                len: self.rules.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the universe of the first rule's first result set.
+    ///
+    /// It's the output universe of `compute_all`, `compute_each` and `compute_all_into`.
+    pub fn result_universe(&self) -> &str {
+        self.rules[0].result_universe()
+    }
+
+    /// Returns all universes of the rules' results in order of their first appearance.
+    pub fn result_universes(&self) -> Vec<&str> {
+        let mut universes = Vec::new();
+        for rule in &self.rules {
+            for consequent in &rule.consequents {
+                if !universes.contains(&&*consequent.universe) {
+                    universes.push(&*consequent.universe);
+                }
+            }
+        }
+        universes
+    }
+
+    /// Computes all rules. Resulting fuzzy sets in `result_universe` are then aggregated
+    /// and returned.
+    ///
+    /// Sets are united with `SetOps::union` unless the options contain an aggregation.
+    pub fn compute_all(&self, context: &InferenceContext) -> Result<Set, InferenceError> {
+        Ok(context.options.aggregate(self.compute_each(context)?))
+    }
+
+    /// Computes all rules and aggregates their resulting fuzzy sets in each of
+    /// `result_universes` separately, like `compute_all` does in `result_universe`.
+    ///
+    /// Conditions of the rules are evaluated once. Returns the aggregated sets
+    /// by the universes' names.
+    pub fn compute_outputs(&self,
+                           context: &InferenceContext)
+                           -> Result<HashMap<String, Set>, InferenceError> {
+        let mut strengths = Vec::with_capacity(self.rules.len());
+        self.firing_strengths_into(context, &mut strengths)?;
+        let mut output = Set::new_empty();
+        let mut outputs = HashMap::new();
+        for universe in self.result_universes() {
+            let mut result = Set::new_empty();
+            self.aggregate_into(context, universe, &strengths, &mut output, &mut result)?;
+            outputs.insert(universe.to_string(), result);
+        }
+        Ok(outputs)
+    }
+
+    /// Computes all rules like `compute_all` and describes how strongly each of them fired.
+    ///
+    /// Activations are sorted by descending strength, see `activations`.
+    pub fn compute_with_trace(&self,
+                              context: &InferenceContext)
+                              -> Result<(Set, Vec<RuleActivation>), InferenceError> {
+        let mut strengths = Vec::with_capacity(self.rules.len());
+        let mut output = Set::new_empty();
+        let mut result = Set::new_empty();
+        self.compute_all_into(context, &mut strengths, &mut output, &mut result)?;
+        Ok((result, self.activations(&strengths)))
+    }
+
+    /// Describes activations of the rules with given firing strengths, e.g. written
+    /// by `compute_all_into`. Sorted by descending strength, ties keep the rules' order.
+    pub fn activations(&self, strengths: &[f32]) -> Vec<RuleActivation> {
+        let universe = self.result_universe();
+        let mut activations = self.rules
+                                  .iter()
+                                  .zip(strengths)
+                                  .enumerate()
+                                  .map(|(index, (rule, &strength))| {
+                                      RuleActivation {
+                                          index: index,
+                                          id: rule.id.clone(),
+                                          rule: rule.to_string(),
+                                          strength: strength,
+                                          result_set: rule.result_set_in(universe)
+                                                          .map(str::to_string),
+                                      }
+                                  })
+                                  .collect::<Vec<_>>();
+        activations.sort_by(|a, b| {
+            b.strength.partial_cmp(&a.strength).unwrap_or(Ordering::Equal)
+        });
+        activations
+    }
+
+    /// Computes all rules. Returns their resulting fuzzy sets in `result_universe`
+    /// without uniting them.
+    pub fn compute_each(&self, context: &InferenceContext) -> Result<Vec<Set>, InferenceError> {
+        let mut strengths = Vec::with_capacity(self.rules.len());
+        self.firing_strengths_into(context, &mut strengths)?;
+        self.results_in(context, self.result_universe(), &strengths)
+    }
+
+    /// Computes the rules' results in `universe` for given firing `strengths`
+    /// without uniting them.
+    pub fn results_in(&self,
+                      context: &InferenceContext,
+                      universe: &str,
+                      strengths: &[f32])
+                      -> Result<Vec<Set>, InferenceError> {
+        let mut sets = Vec::with_capacity(self.rules.len());
+        for (rule, &strength) in self.rules.iter().zip(strengths) {
+            for (index, consequent) in rule.consequents.iter().enumerate() {
+                if consequent.universe == universe {
+                    let mut result = Set::new_empty();
+                    rule.compute_consequent_into(context, index, strength, &mut result)?;
+                    sets.push(result);
+                }
+            }
+        }
+        Ok(sets)
+    }
+
+    /// Evaluates conditions of all rules without computing their results.
+    ///
+    /// Returns the names of the rules' result sets in `result_universe` and the firing strengths.
+    /// Rules without a consequent in `result_universe` are skipped.
+    pub fn firing_strengths(&self,
+                            context: &InferenceContext)
+                            -> Result<Vec<(String, f32)>, InferenceError> {
+        let universe = self.result_universe();
+        self.rules
+            .iter()
+            .filter_map(|rule| rule.result_set_in(universe).map(|set| (rule, set)))
+            .map(|(rule, set)| Ok((set.to_string(), rule.firing_strength(context)?)))
+            .collect()
+    }
+
+    /// Computes all rules into `result` without allocating new sets.
+    ///
+    /// `output` is the buffer for every rule's result in `result_universe`, which is then
+    /// aggregated into `result` with `AggregationOp::aggregate_into` of the options
+    /// or `SetOps::union_into`. Firing strengths of the rules are written to `strengths`.
+    pub fn compute_all_into(&self,
+                            context: &InferenceContext,
+                            strengths: &mut Vec<f32>,
+                            output: &mut Set,
+                            result: &mut Set)
+                            -> Result<(), InferenceError> {
+        self.firing_strengths_into(context, strengths)?;
+        self.aggregate_into(context, self.result_universe(), strengths, output, result)
+    }
+
+    /// Evaluates conditions of all rules into `strengths`.
+    pub fn firing_strengths_into(&self,
+                                 context: &InferenceContext,
+                                 strengths: &mut Vec<f32>)
+                                 -> Result<(), InferenceError> {
+        strengths.clear();
+        for rule in &self.rules {
+            strengths.push(rule.firing_strength(context)?);
+        }
+        Ok(())
+    }
+
+    /// Computes the rules' results in `universe` for given firing `strengths`
+    /// and aggregates them into `result`, using `output` as the buffer.
+    ///
+    /// `result` is left empty if no rule concludes in `universe`.
+    pub fn aggregate_into(&self,
+                          context: &InferenceContext,
+                          universe: &str,
+                          strengths: &[f32],
+                          output: &mut Set,
+                          result: &mut Set)
+                          -> Result<(), InferenceError> {
+        let mut first = true;
+        for (rule, &strength) in self.rules.iter().zip(strengths) {
+            for (index, consequent) in rule.consequents.iter().enumerate() {
+                if consequent.universe != universe {
+                    continue;
+                }
+                if first {
+                    rule.compute_consequent_into(context, index, strength, result)?;
+                    first = false;
+                    continue;
+                }
+                rule.compute_consequent_into(context, index, strength, output)?;
+                match context.options.aggregation {
+                    Some(ref aggregation) => aggregation.aggregate_into(result, output),
+                    None => (*context.options.set_ops).union_into(result, output),
+                }
+            }
+        }
+        if first {
+            *result = Set::new_empty();
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for RuleSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = String::new();
+        for rule in &self.rules {
+            s = s + &format!("\t{}\n", rule);
+        }
+        write!(f, "(RuleSet\n{})", s)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use set::UniversalSet;
+    use ops::{AlgebraicSetOps, LogicOps, MinImplication, ProductImplication};
+    use functions::{DefuzzFactory, MembershipFactory};
+    use inference::InferenceOptions;
+    use std::rc::Rc;
+    use std::cell::RefCell;
+
+    fn universes() -> HashMap<String, Rc<UniversalSet>> {
+        let mut temp = UniversalSet::new("temp".to_string());
+        temp.set_domain((0..41).map(|x| x as f32).collect());
+        temp.create_set("hot".to_string(),
+                        MembershipFactory::triangular(20.0, 40.0, 60.0)).unwrap();
+        // The domain is set after the set is created, so the consequent's cache is empty.
+        let mut fan = UniversalSet::new("fan".to_string());
+        fan.create_set("fast".to_string(), MembershipFactory::triangular(5.0, 10.0, 15.0)).unwrap();
+        fan.set_domain((0..11).map(|x| x as f32).collect());
+        let mut universes = HashMap::new();
+        universes.insert("temp".to_string(), Rc::new(temp));
+        universes.insert("fan".to_string(), Rc::new(fan));
+        universes
+    }
+
+    fn options() -> InferenceOptions {
+        InferenceOptions::mamdani()
+    }
+
+    #[test]
+    fn rule_result_name() {
+        let universes = universes();
+        let options = options();
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), 30.0);
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        let context = InferenceContext {
+            values: &values,
+            universes: &universes,
+            bindings: &bindings,
+            fuzzy_values: &fuzzy_values,
+            options: &options,
+        };
+        let rule = Rule::new(Box::new(Is::new("temp".to_string(), "hot".to_string())),
+                             "fan".to_string(),
+                             "fast".to_string());
+        let first = rule.compute(&context).unwrap().remove(0);
+        let second = rule.compute(&context).unwrap().remove(0);
+        assert_eq!(&*first.name, "fan: fast");
+        assert!(Arc::ptr_eq(&first.name, &second.name));
+    }
+
+    #[test]
+    fn multiple_consequents() {
+        let universes = universes();
+        let options = options();
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), 30.0);
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        let context = InferenceContext {
+            values: &values,
+            universes: &universes,
+            bindings: &bindings,
+            fuzzy_values: &fuzzy_values,
+            options: &options,
+        };
+        let rule = Rule::new(Box::new(Is::new("temp".to_string(), "hot".to_string())),
+                             "fan".to_string(),
+                             "fast".to_string())
+                       .with_consequent("temp".to_string(), "hot".to_string());
+        assert_eq!(rule.consequents(), vec![("fan", "fast"), ("temp", "hot")]);
+        assert_eq!(rule.result_set_in("temp"), Some("hot"));
+        assert_eq!(rule.result_set_in("valve"), None);
+        assert_eq!(rule.to_string(), "(Rule fan:fast temp:hot if:(is temp hot))");
+        let results = rule.compute(&context).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(&*results[0].name, "fan: fast");
+        assert_eq!(&*results[1].name, "temp: hot");
+        assert_eq!(results[1].membership_at(40.0), 0.5);
+
+        let rules = RuleSet::new(vec![rule]).unwrap();
+        assert_eq!(rules.result_universes(), vec!["fan", "temp"]);
+        // Only the sets in the first result universe are aggregated by `compute_each`.
+        let sets = rules.compute_each(&context).unwrap();
+        assert_eq!(sets.len(), 1);
+        assert_eq!(&*sets[0].name, "fan: fast");
+
+        // Rules are described by their consequents in the first result universe.
+        let hot = || Box::new(Is::new("temp".to_string(), "hot".to_string()));
+        let rules = RuleSet::new(vec![Rule::new(hot(), "fan".to_string(), "fast".to_string()),
+                                      Rule::new(hot(), "temp".to_string(), "hot".to_string())
+                                          .with_consequent("fan".to_string(), "fast".to_string()),
+                                      Rule::new(hot(), "temp".to_string(), "hot".to_string())])
+                        .unwrap();
+        assert_eq!(rules.firing_strengths(&context),
+                   Ok(vec![("fast".to_string(), 0.5), ("fast".to_string(), 0.5)]));
+        let result_sets = rules.activations(&[0.5, 0.5, 0.5])
+                               .into_iter()
+                               .map(|activation| activation.result_set)
+                               .collect::<Vec<_>>();
+        assert_eq!(result_sets,
+                   vec![Some("fast".to_string()), Some("fast".to_string()), None]);
+    }
+
+    #[test]
+    fn consequent_over_domain() {
+        let universes = universes();
+        assert!(universes["fan"].sets["fast"].cache.borrow().is_empty());
+        let options = options();
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), 30.0);
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        let context = InferenceContext {
+            values: &values,
+            universes: &universes,
+            bindings: &bindings,
+            fuzzy_values: &fuzzy_values,
+            options: &options,
+        };
+        let rule = Rule::new(Box::new(Is::new("temp".to_string(), "hot".to_string())),
+                             "fan".to_string(),
+                             "fast".to_string());
+        let result = rule.compute(&context).unwrap().remove(0);
+        let cache = result.cache.borrow();
+        assert_eq!(cache.len(), 11);
+        assert_eq!(cache[&OrderedFloat(5.0)], 0.0);
+        assert!((cache[&OrderedFloat(6.0)] - 0.2).abs() < 1e-6);
+        assert_eq!(cache[&OrderedFloat(10.0)], 0.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "certainty factor must be in (0, 1]")]
+    fn zero_certainty() {
+        Rule::new(Box::new(is("temp", "hot")), "fan".to_string(), "fast".to_string())
+            .with_certainty(0.0);
+    }
+
+    #[test]
+    fn empty_rule_set() {
+        assert_eq!(RuleSet::new(vec![]).err(),
+                   Some("rule set must contain at least one rule".to_string()));
+    }
+
+    #[test]
+    fn named_rules() {
+        let hot = || Box::new(Is::new("temp".to_string(), "hot".to_string()));
+        let named = |id: &str, set: &str| {
+            Rule::named(id.to_string(), hot(), "fan".to_string(), set.to_string())
+        };
+        let rule = named("cool-down", "fast");
+        assert_eq!(rule.id(), Some("cool-down"));
+        assert_eq!(rule.to_string(), "(Rule #cool-down fan:fast if:(is temp hot))");
+        let mut rules = RuleSet::new(vec![rule,
+                                          Rule::new(hot(), "fan".to_string(), "slow".to_string()),
+                                          named("quiet", "slow")])
+                            .unwrap();
+        assert_eq!(rules.to_string(),
+                   "(RuleSet\n\t(Rule #cool-down fan:fast if:(is temp hot))\n\t(Rule fan:slow \
+                    if:(is temp hot))\n\t(Rule #quiet fan:slow if:(is temp hot))\n)");
+        assert_eq!(rules.index_of("quiet"), Some(2));
+        assert_eq!(rules.index_of("loud"), None);
+
+        assert_eq!(rules.add_rule(named("quiet", "fast")).err(),
+                   Some(RuleSetError::DuplicateId("quiet".to_string())));
+        assert_eq!(rules.replace_rule(1, named("quiet", "fast")).err(),
+                   Some(RuleSetError::DuplicateId("quiet".to_string())));
+        rules.replace_rule(2, named("quiet", "fast")).unwrap();
+        assert_eq!(rules.len(), 3);
+        assert_eq!(RuleSet::new(vec![named("quiet", "fast"), named("quiet", "slow")]).err(),
+                   Some("rule id quiet is not unique".to_string()));
+
+        let weighted = Rule::with_weight(hot(), "fan".to_string(), "fast".to_string(), 0.5)
+                           .with_id("boost".to_string());
+        assert_eq!(weighted.id(), Some("boost"));
+        assert_eq!(weighted.weight(), 0.5);
+        rules.add_rule(weighted).unwrap();
+        assert_eq!(rules.index_of("boost"), Some(3));
+    }
+
+    #[test]
+    fn single_rule_set() {
+        let universes = universes();
+        let options = options();
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), 30.0);
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        let context = InferenceContext {
+            values: &values,
+            universes: &universes,
+            bindings: &bindings,
+            fuzzy_values: &fuzzy_values,
+            options: &options,
+        };
+        let rule = Rule::new(Box::new(Is::new("temp".to_string(), "hot".to_string())),
+                             "fan".to_string(),
+                             "fast".to_string());
+        let expected = rule.compute(&context).unwrap().remove(0);
+        let rules = RuleSet::new(vec![rule]).unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(*rules.compute_all(&context).unwrap().cache.borrow(), *expected.cache.borrow());
+        let mut strengths = Vec::new();
+        let mut output = Set::new_with_domain("", RefCell::new(HashMap::new()));
+        let mut result = Set::new_with_domain("", RefCell::new(HashMap::new()));
+        rules.compute_all_into(&context, &mut strengths, &mut output, &mut result).unwrap();
+        assert_eq!(strengths, vec![0.5]);
+        assert_eq!(*result.cache.borrow(), *expected.cache.borrow());
+    }
+
+    #[test]
+    fn same_universe() {
+        let universes = universes();
+        let options = options();
+        let mut values = HashMap::new();
+        values.insert("fan".to_string(), 7.0);
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        let context = InferenceContext {
+            values: &values,
+            universes: &universes,
+            bindings: &bindings,
+            fuzzy_values: &fuzzy_values,
+            options: &options,
+        };
+        let rule = Rule::new(Box::new(Is::new("fan".to_string(), "fast".to_string())),
+                             "fan".to_string(),
+                             "fast".to_string());
+        let result = rule.compute(&context).unwrap().remove(0);
+        let fast = &universes["fan"].sets["fast"];
+        let strength = fast.check(7.0);
+        assert!(strength > 0.0);
+        for (key, &value) in result.cache.borrow().iter() {
+            assert_eq!(value, fast.check(key.0).min(strength));
+        }
+        assert_eq!(universes["fan"].memberships(7.0)["fast"], strength);
+    }
+
+    #[test]
+    fn min_and_product_implication() {
+        let universes = universes();
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), 30.0);
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        let rule = Rule::new(Box::new(Is::new("temp".to_string(), "hot".to_string())),
+                             "fan".to_string(),
+                             "fast".to_string());
+        let mut results = Vec::new();
+        for &product in &[false, true] {
+            let mut options = options();
+            if product {
+                options.implication = Box::new(ProductImplication {});
+            }
+            let context = InferenceContext {
+                values: &values,
+                universes: &universes,
+                bindings: &bindings,
+                fuzzy_values: &fuzzy_values,
+                options: &options,
+            };
+            results.push(rule.compute(&context).unwrap().remove(0));
+        }
+        let fast = &universes["fan"].sets["fast"];
+        let (clipped, scaled) = (results[0].cache.borrow(), results[1].cache.borrow());
+        assert_eq!(clipped.len(), 11);
+        for (key, &value) in clipped.iter() {
+            let membership = fast.check(key.0);
+            assert_eq!(value, membership.min(0.5));
+            assert_eq!(scaled[key], membership * 0.5);
+            assert!(scaled[key] <= value);
+        }
+        // Clipping flattens the top of the set, while scaling preserves its shape.
+        assert_eq!((clipped[&OrderedFloat(8.0)], clipped[&OrderedFloat(10.0)]), (0.5, 0.5));
+        assert!((scaled[&OrderedFloat(8.0)] - 0.3).abs() < 1e-6);
+        assert_eq!(scaled[&OrderedFloat(10.0)], 0.5);
+    }
+
+    /// Universe "out" over [0, 10] with asymmetric triangular sets "low" and "high".
+    fn out_universes() -> HashMap<String, Rc<UniversalSet>> {
+        let mut out = UniversalSet::new("out".to_string());
+        out.set_domain((0..1001).map(|x| x as f32 / 100.0).collect());
+        out.create_set("low".to_string(), MembershipFactory::triangular(0.0, 2.0, 10.0)).unwrap();
+        out.create_set("high".to_string(), MembershipFactory::triangular(4.0, 8.0, 10.0)).unwrap();
+        let mut universes = HashMap::new();
+        universes.insert("out".to_string(), Rc::new(out));
+        universes
+    }
+
+    #[test]
+    fn clipped_centroids() {
+        let universes = out_universes();
+        let options = options();
+        let values = HashMap::new();
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        let context = InferenceContext {
+            values: &values,
+            universes: &universes,
+            bindings: &bindings,
+            fuzzy_values: &fuzzy_values,
+            options: &options,
+        };
+        let centroid = DefuzzFactory::center_of_mass();
+        // Clipping the triangle at `h` gives a trapezoid with the top in [2h, 10 - 8h],
+        // the expected centroids are the trapezoids' moments divided by their areas.
+        for &(strength, expected) in &[(0.3, 11.67 / 2.55), (0.7, 18.83 / 4.55), (1.0, 4.0)] {
+            let rule = Rule::new(Box::new(Value(strength)), "out".to_string(), "low".to_string());
+            let result = rule.compute(&context).unwrap().remove(0);
+            assert_eq!(result.cache.borrow().len(), 1001);
+            assert!((result.height() - strength).abs() < 1e-6);
+            assert!((centroid(&result).unwrap() - expected).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn scaled_consequents() {
+        let universes = out_universes();
+        let values = HashMap::new();
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        let mut options = options();
+        options.implication = Box::new(ProductImplication {});
+        let context = InferenceContext {
+            values: &values,
+            universes: &universes,
+            bindings: &bindings,
+            fuzzy_values: &fuzzy_values,
+            options: &options,
+        };
+        let centroid = DefuzzFactory::center_of_mass();
+        let low = |strength| Rule::new(Box::new(Value(strength)), "out".into(), "low".into());
+        // Scaling keeps the centroid of the triangle at (0 + 2 + 10) / 3, clipping shifts it.
+        for &strength in &[0.3, 0.7, 1.0] {
+            let scaled = low(strength).compute(&context).unwrap().remove(0);
+            assert!((scaled.height() - strength).abs() < 1e-6);
+            assert!((centroid(&scaled).unwrap() - 4.0).abs() < 1e-3);
+            let clipped = low(strength).with_implication(Box::new(MinImplication {}));
+            let clipped = clipped.compute(&context).unwrap().remove(0);
+            assert_eq!(clipped.cache.borrow().len(), scaled.cache.borrow().len());
+            if strength < 1.0 {
+                assert!(centroid(&clipped).unwrap() > 4.1);
+            }
+        }
+        let rule = low(0.3).with_implication(Box::new(MinImplication {}));
+        assert_eq!(rule.implication().map(|implication| implication.name()), Some("min"));
+        assert_eq!(rule.to_string(), "(Rule out:low if:0.3 implication:min)");
+
+        // Scaled sets are aggregated pointwise by the union of the options.
+        let high = Rule::new(Box::new(Value(0.6)), "out".into(), "high".into());
+        let rules = RuleSet::new(vec![low(0.3), high]).unwrap();
+        let union = rules.compute_all(&context).unwrap();
+        let (low, high) = (&universes["out"].sets["low"], &universes["out"].sets["high"]);
+        for (key, &value) in union.cache.borrow().iter() {
+            let expected = (low.check(key.0) * 0.3).max(high.check(key.0) * 0.6);
+            assert!((value - expected).abs() < 1e-6);
+        }
+        assert!((union.height() - 0.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fold_from_empty_set() {
+        let mut universes = universes();
+        {
+            let fan = Rc::get_mut(universes.get_mut("fan").unwrap()).unwrap();
+            fan.create_set("slow".to_string(), MembershipFactory::triangular(-5.0, 0.0, 5.0))
+               .unwrap();
+        }
+        let options = options();
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), 30.0);
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        let context = InferenceContext {
+            values: &values,
+            universes: &universes,
+            bindings: &bindings,
+            fuzzy_values: &fuzzy_values,
+            options: &options,
+        };
+        let rules = vec![Rule::new(Box::new(Is::new("temp".to_string(), "hot".to_string())),
+                                   "fan".to_string(),
+                                   "fast".to_string()),
+                         Rule::new(Box::new(Is::new("temp".to_string(), "hot".to_string())),
+                                   "fan".to_string(),
+                                   "slow".to_string())];
+        let mut folded = Set::new_empty();
+        for rule in &rules {
+            let mut result = rule.compute(&context).unwrap().remove(0);
+            folded = options.set_ops.union(&mut folded, &mut result);
+        }
+        let expected = RuleSet::new(rules).unwrap().compute_all(&context).unwrap();
+        assert_eq!(&*folded.name, "fan: fast UNION fan: slow");
+        assert_eq!(folded.name, expected.name);
+        assert_eq!(*folded.cache.borrow(), *expected.cache.borrow());
+    }
+
+    #[test]
+    fn minmax_and_algebraic_aggregation() {
+        let mut universes = universes();
+        {
+            let fan = Rc::get_mut(universes.get_mut("fan").unwrap()).unwrap();
+            fan.create_set("slow".to_string(), MembershipFactory::triangular(0.0, 4.0, 8.0))
+               .unwrap();
+        }
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), 30.0);
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        let rules = vec![Rule::new(Box::new(Is::new("temp".to_string(), "hot".to_string())),
+                                   "fan".to_string(),
+                                   "fast".to_string()),
+                         Rule::new(Box::new(Is::new("temp".to_string(), "hot".to_string())),
+                                   "fan".to_string(),
+                                   "slow".to_string())];
+        let rules = RuleSet::new(rules).unwrap();
+        let mut results = Vec::new();
+        for &algebraic in &[false, true] {
+            let mut options = options();
+            if algebraic {
+                options.set_ops = Box::new(AlgebraicSetOps {});
+            }
+            let context = InferenceContext {
+                values: &values,
+                universes: &universes,
+                bindings: &bindings,
+                fuzzy_values: &fuzzy_values,
+                options: &options,
+            };
+            results.push(rules.compute_all(&context).unwrap());
+        }
+        let (fast, slow) = (&universes["fan"].sets["fast"], &universes["fan"].sets["slow"]);
+        let (minmax, algebraic) = (results[0].cache.borrow(), results[1].cache.borrow());
+        assert_eq!(minmax.len(), algebraic.len());
+        for (key, &value) in minmax.iter() {
+            let (a, b) = (fast.check(key.0).min(0.5), slow.check(key.0).min(0.5));
+            assert_eq!(value, a.max(b));
+            assert!((algebraic[key] - (a + b - a * b)).abs() < 1e-6);
+            assert!(algebraic[key] >= value);
+        }
+        // Both rules fire with 0.5 where the consequents overlap.
+        assert_eq!(minmax[&OrderedFloat(6.0)], 0.5);
+        assert!((algebraic[&OrderedFloat(6.0)] - 0.6).abs() < 1e-6);
+        assert_eq!(algebraic[&OrderedFloat(10.0)], 0.5);
+    }
+
+    #[test]
+    fn negation() {
+        let universes = universes();
+        let options = options();
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), 30.0);
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        let context = InferenceContext {
+            values: &values,
+            universes: &universes,
+            bindings: &bindings,
+            fuzzy_values: &fuzzy_values,
+            options: &options,
+        };
+        let hot = || Is::new("temp".to_string(), "hot".to_string());
+        let negation = not(hot());
+        assert_eq!(negation.to_string(), "(not (is temp hot))");
+        assert_eq!(negation.eval(&context), Ok(0.5));
+
+        let nested = And::new(Not::new(hot()), Or::new(hot(), not(not(hot()))));
+        assert_eq!(nested.to_string(),
+                   "(and (not (is temp hot)) (or (is temp hot) (not (not (is temp hot)))))");
+        assert_eq!(nested.eval(&context), Ok(0.5));
+
+        let boxed: Box<Expression> = Box::new(hot());
+        let negation = Not::new(boxed);
+        assert_eq!(negation.to_string(), "(not (is temp hot))");
+        assert_eq!(Program::compile(&negation).unwrap().ops(),
+                   &[Op::PushMembership(0), Op::Not]);
+    }
+
+    #[test]
+    fn hedges() {
+        let universes = universes();
+        let options = options();
+        let hot = || Is::new("temp".to_string(), "hot".to_string());
+        for &temp in &[20.0, 25.0, 30.0, 36.0, 40.0] {
+            let mut values = HashMap::new();
+            values.insert("temp".to_string(), temp);
+            let bindings = HashMap::new();
+            let fuzzy_values = HashMap::new();
+            let context = InferenceContext {
+                values: &values,
+                universes: &universes,
+                bindings: &bindings,
+                fuzzy_values: &fuzzy_values,
+                options: &options,
+            };
+            let is = hot().eval(&context).unwrap();
+            let close = |expression: &Expression, expected: f32| {
+                let value = expression.eval(&context).unwrap();
+                assert!((value - expected).abs() < 1e-6, "{}: {}", expression.to_string(), value);
+                let program = Program::compile(expression).unwrap();
+                assert!((program.eval(&context).unwrap() - expected).abs() < 1e-6);
+            };
+            close(&very(hot()), is * is);
+            close(&extremely(hot()), is * is * is);
+            close(&somewhat(hot()), is.sqrt());
+            close(&power(hot(), 1.5), is.powf(1.5));
+            close(&very(very(hot())), is.powi(4));
+            close(&And::new(very(hot()), not(somewhat(hot()))), (is * is).min(1.0 - is.sqrt()));
+        }
+        assert_eq!(very(very(hot())).to_string(), "(very (very (is temp hot)))");
+        assert_eq!(And::new(somewhat(hot()), power(hot(), 1.5)).to_string(),
+                   "(and (somewhat (is temp hot)) (power 1.5 (is temp hot)))");
+        assert_eq!(Program::compile(&extremely(hot())).unwrap().ops(),
+                   &[Op::PushMembership(0), Op::Power(3.0)]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_power() {
+        power(is("temp", "hot"), 0.0);
+    }
+
+    #[test]
+    fn boxed_combinators() {
+        let universes = universes();
+        let options = options();
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), 36.0);
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        let context = InferenceContext {
+            values: &values,
+            universes: &universes,
+            bindings: &bindings,
+            fuzzy_values: &fuzzy_values,
+            options: &options,
+        };
+        let boxed = is("temp", "hot")
+                        .and(very(is("temp", "hot")).or(is("temp", "hot").not()))
+                        .not();
+        let generic = Not::new(And::new(is("temp", "hot"),
+                                        Or::new(very(is("temp", "hot")),
+                                                Not::new(is("temp", "hot")))));
+        assert_eq!(boxed.to_string(), generic.to_string());
+        assert_eq!(boxed.to_string(),
+                   "(not (and (is temp hot) (or (very (is temp hot)) (not (is temp hot)))))");
+        assert_eq!(boxed.eval(&context), generic.eval(&context));
+        assert!((boxed.eval(&context).unwrap() - 0.36).abs() < 1e-6);
+        assert_eq!(Program::compile(&boxed).unwrap().ops(),
+                   Program::compile(&generic).unwrap().ops());
+    }
+
+    /// Expression with the constant value.
+    struct Value(f32);
+
+    impl Expression for Value {
+        fn eval(&self, _: &InferenceContext) -> Result<f32, InferenceError> {
+            Ok(self.0)
+        }
+
+        fn to_string(&self) -> String {
+            format!("{}", self.0)
+        }
+    }
+
+    /// Non-associative operations: 'and' and 'or' are arithmetic means unless
+    /// one of the arguments is 0 or 1.
+    struct MeanOps;
+
+    impl LogicOps for MeanOps {
+        fn and(&self, left: f32, right: f32) -> f32 {
+            if left == 1.0 || right == 1.0 || left * right == 0.0 {
+                left.min(right)
+            } else {
+                (left + right) / 2.0
+            }
+        }
+
+        fn or(&self, left: f32, right: f32) -> f32 {
+            1.0 - self.and(1.0 - left, 1.0 - right)
+        }
+
+        fn not(&self, value: f32) -> f32 {
+            1.0 - value
+        }
+    }
+
+    #[test]
+    fn n_ary_expressions() {
+        let universes = universes();
+        let values = HashMap::new();
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        let mut options = options();
+        let all = || {
+            All::new(vec![Box::new(Value(0.2)), Box::new(Value(0.6)), Box::new(Value(0.4))])
+        };
+        let any = || {
+            Any::new(vec![Box::new(Value(0.2)), Box::new(Value(0.6)), Box::new(Value(0.4))])
+        };
+        assert_eq!(all().to_string(), "(all 0.2 0.6 0.4)");
+        assert_eq!(any().to_string(), "(any 0.2 0.6 0.4)");
+        assert_eq!(All::new(vec![]).to_string(), "(all)");
+        {
+            let context = InferenceContext {
+                values: &values,
+                universes: &universes,
+                bindings: &bindings,
+                fuzzy_values: &fuzzy_values,
+                options: &options,
+            };
+            let left = And::new(And::new(Value(0.2), Value(0.6)), Value(0.4));
+            let right = And::new(Value(0.2), And::new(Value(0.6), Value(0.4)));
+            assert_eq!(all().eval(&context), left.eval(&context));
+            assert_eq!(all().eval(&context), right.eval(&context));
+            assert_eq!(all().eval(&context), Ok(0.2));
+            assert_eq!(any().eval(&context), Ok(0.6));
+            assert_eq!(All::new(vec![]).eval(&context), Ok(1.0));
+            assert_eq!(Any::new(vec![]).eval(&context), Ok(0.0));
+            let nested = All::new(vec![Box::new(any()), Box::new(Value(0.5))]);
+            assert_eq!(nested.eval(&context), Ok(0.5));
+        }
+        // With non-associative operations the values are folded from left to right.
+        options.logic_ops = Box::new(MeanOps {});
+        let context = InferenceContext {
+            values: &values,
+            universes: &universes,
+            bindings: &bindings,
+            fuzzy_values: &fuzzy_values,
+            options: &options,
+        };
+        let left = And::new(And::new(Value(0.2), Value(0.6)), Value(0.4));
+        let right = And::new(Value(0.2), And::new(Value(0.6), Value(0.4)));
+        assert!((all().eval(&context).unwrap() - 0.4).abs() < 1e-6);
+        assert_eq!(all().eval(&context), left.eval(&context));
+        assert!(all().eval(&context) != right.eval(&context));
+    }
+
+    #[test]
+    fn compiled_n_ary_expressions() {
+        let hot = || -> Box<Expression> { Box::new(is("temp", "hot")) };
+        let all = All::new(vec![hot(), Box::new(Any::new(vec![hot(), hot()])), hot()]);
+        assert_eq!(Program::compile(&all).unwrap().ops(),
+                   &[Op::PushMembership(0),
+                     Op::PushMembership(0),
+                     Op::PushMembership(0),
+                     Op::Or,
+                     Op::And,
+                     Op::PushMembership(0),
+                     Op::And]);
+        assert_eq!(Program::compile(&Any::new(vec![])).unwrap().ops(), &[Op::PushConst(0.0)]);
+    }
+
+    #[test]
+    fn xor_and_equiv() {
+        let universes = universes();
+        let values = HashMap::new();
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        let options = options();
+        let context = InferenceContext {
+            values: &values,
+            universes: &universes,
+            bindings: &bindings,
+            fuzzy_values: &fuzzy_values,
+            options: &options,
+        };
+        for i in 0..11 {
+            for j in 0..11 {
+                let (a, b) = (i as f32 / 10.0, j as f32 / 10.0);
+                let composed = Or::new(And::new(Value(a), not(Value(b))),
+                                       And::new(not(Value(a)), Value(b)));
+                let xor = Xor::new(Value(a), Value(b)).eval(&context).unwrap();
+                assert_eq!(xor, composed.eval(&context).unwrap());
+                let equiv = Equiv::new(Value(a), Value(b)).eval(&context).unwrap();
+                assert_eq!(equiv, not(composed).eval(&context).unwrap());
+            }
+        }
+        assert_eq!(Xor::new(Value(1.0), Value(0.0)).to_string(), "(xor 1 0)");
+        assert_eq!(Equiv::new(Value(1.0), Value(0.0)).to_string(), "(equiv 1 0)");
+    }
+
+    #[test]
+    fn xor_rule() {
+        let universes = universes();
+        let options = options();
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), 36.0);
+        values.insert("fan".to_string(), 7.0);
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        let context = InferenceContext {
+            values: &values,
+            universes: &universes,
+            bindings: &bindings,
+            fuzzy_values: &fuzzy_values,
+            options: &options,
+        };
+        // "hot" is 0.8 and "fast" is 0.4, so exactly one of them is true with 0.6.
+        let rule = Rule::new(Box::new(Xor::new(is("temp", "hot"), is("fan", "fast"))),
+                             "fan".to_string(),
+                             "fast".to_string());
+        let result = rule.compute(&context).unwrap().remove(0);
+        let fast = &universes["fan"].sets["fast"];
+        for (key, &value) in result.cache.borrow().iter() {
+            assert!((value - fast.check(key.0).min(0.6)).abs() < 1e-6);
+        }
+        let program = Program::compile(rule.condition()).unwrap();
+        assert_eq!(program.ops(), &[Op::PushMembership(0), Op::PushMembership(1), Op::Xor]);
+        assert!((program.eval(&context).unwrap() - 0.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn crisp_comparisons() {
+        let universes = universes();
+        let options = options();
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), 36.0);
+        values.insert("rpm".to_string(), 4200.0);
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        let context = InferenceContext {
+            values: &values,
+            universes: &universes,
+            bindings: &bindings,
+            fuzzy_values: &fuzzy_values,
+            options: &options,
+        };
+        let rpm = || "rpm".to_string();
+        assert_eq!(GreaterThan::new(rpm(), 4000.0).eval(&context), Ok(1.0));
+        assert_eq!(GreaterThan::new(rpm(), 4200.0).eval(&context), Ok(0.0));
+        assert_eq!(LessThan::new(rpm(), 4000.0).eval(&context), Ok(0.0));
+        assert_eq!(Between::new(rpm(), 4000.0, 4200.0).eval(&context), Ok(1.0));
+        assert_eq!(Between::new(rpm(), 4300.0, 5000.0).eval(&context), Ok(0.0));
+        // Soft versions change linearly around the thresholds.
+        assert_eq!(GreaterThan::new(rpm(), 4100.0).with_width(400.0).eval(&context), Ok(0.75));
+        assert_eq!(LessThan::new(rpm(), 4100.0).with_width(400.0).eval(&context), Ok(0.25));
+        let between = Between::new(rpm(), 3000.0, 4200.0).with_width(200.0);
+        assert_eq!(between.eval(&context), Ok(0.5));
+        assert_eq!(between.to_string(), "(between rpm 3000 4200 ~200)");
+        assert_eq!(GreaterThan::new(rpm(), 4000.0).to_string(), "(> rpm 4000)");
+
+        // "hot" is 0.8, so the crisp clause passes it through or blocks it.
+        let rule = And::new(is("temp", "hot"), GreaterThan::new(rpm(), 4000.0));
+        assert_eq!(rule.eval(&context), Ok(0.8));
+        let rule = And::new(is("temp", "hot"), LessThan::new(rpm(), 4000.0));
+        assert_eq!(rule.eval(&context), Ok(0.0));
+        let rule = Or::new(is("temp", "hot"), LessThan::new(rpm(), 4000.0).with_width(1000.0));
+        assert_eq!(rule.eval(&context), Ok(0.8));
+
+        let missing = GreaterThan::new("speed".to_string(), 10.0);
+        assert_eq!(missing.eval(&context),
+                   Err(InferenceError::MissingValue("speed".to_string())));
+    }
+
+    #[test]
+    fn is_any_of() {
+        let mut universes = universes();
+        {
+            let temp = Rc::get_mut(universes.get_mut("temp").unwrap()).unwrap();
+            temp.create_set("warm".to_string(), MembershipFactory::triangular(10.0, 25.0, 40.0))
+                .unwrap();
+        }
+        let options = options();
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), 30.0);
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        let context = InferenceContext {
+            values: &values,
+            universes: &universes,
+            bindings: &bindings,
+            fuzzy_values: &fuzzy_values,
+            options: &options,
+        };
+        let sets = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        let any = IsAnyOf::new("temp".to_string(), sets(&["hot", "warm"]));
+        let all = IsAllOf::new("temp".to_string(), sets(&["hot", "warm"]));
+        assert_eq!(any.to_string(), "(is-any-of temp hot warm)");
+        assert_eq!(all.to_string(), "(is-all-of temp hot warm)");
+        // "hot" is 0.5 and "warm" is 2/3.
+        let or = Or::new(is("temp", "hot"), is("temp", "warm"));
+        let and = And::new(is("temp", "hot"), is("temp", "warm"));
+        assert_eq!(any.eval(&context), or.eval(&context));
+        assert_eq!(all.eval(&context), and.eval(&context));
+        assert_eq!(all.eval(&context), Ok(0.5));
+        assert_eq!(Program::compile(&any).unwrap().ops(),
+                   Program::compile(&or).unwrap().ops());
+        assert_eq!(IsAnyOf::new("temp".to_string(), vec![]).eval(&context), Ok(0.0));
+
+        let typo = IsAnyOf::new("temp".to_string(), sets(&["hot", "wram", "warm"]));
+        let error = typo.eval(&context).unwrap_err();
+        assert_eq!(error,
+                   InferenceError::UnknownSet {
+                       universe: "temp".to_string(),
+                       set: "wram".to_string(),
+                   });
+        assert_eq!(error.to_string(), "set wram doesn't exist in universe temp");
+    }
+
+    #[test]
+    fn weighted_rules() {
+        let universes = universes();
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), 36.0);
+        let bindings = HashMap::new();
+        let fuzzy_values = HashMap::new();
+        let rule = |weight| {
+            Rule::with_weight(Box::new(is("temp", "hot")),
+                              "fan".to_string(),
+                              "fast".to_string(),
+                              weight)
+        };
+        assert_eq!(rule(0.5).to_string(), "(Rule fan:fast if:(is temp hot) weight:0.5)");
+        assert_eq!(rule(1.0).to_string(), "(Rule fan:fast if:(is temp hot))");
+        let fast = &universes["fan"].sets["fast"];
+        for &product in &[false, true] {
+            let mut options = options();
+            if product {
+                options.implication = Box::new(ProductImplication {});
+            }
+            let context = InferenceContext {
+                values: &values,
+                universes: &universes,
+                bindings: &bindings,
+                fuzzy_values: &fuzzy_values,
+                options: &options,
+            };
+            // "hot" is 0.8, so the halved rule fires with 0.4.
+            let full = rule(1.0).compute(&context).unwrap().remove(0);
+            let half = rule(0.5).compute(&context).unwrap().remove(0);
+            let unweighted = Rule::new(Box::new(is("temp", "hot")),
+                                       "fan".to_string(),
+                                       "fast".to_string());
+            let unweighted = unweighted.compute(&context).unwrap().remove(0);
+            assert_eq!(*full.cache.borrow(), *unweighted.cache.borrow());
+            for (key, &value) in half.cache.borrow().iter() {
+                let full_value = full.cache.borrow()[key];
+                if product {
+                    assert!((value - full_value / 2.0).abs() < 1e-6);
+                } else {
+                    assert_eq!(value, fast.check(key.0).min(0.4));
+                    assert!(value <= full_value);
+                }
+            }
+            let strengths = RuleSet::new(vec![rule(0.5)]).unwrap().firing_strengths(&context);
+            assert_eq!(strengths, Ok(vec![("fast".to_string(), 0.4)]));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_weight() {
+        Rule::with_weight(Box::new(is("temp", "hot")), "fan".to_string(), "fast".to_string(), 0.0);
     }
 }
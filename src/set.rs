@@ -4,79 +4,630 @@
 //! Given as a part of the universal set with the membership function.
 extern crate ordered_float;
 
+use std::error::Error;
 use std::fmt;
 use std::f32;
-use std::collections::HashMap;
-use std::cell::RefCell;
-use functions::MembershipFunction;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::Arc;
+use functions::{MAXIMA_EPSILON, MembershipFunction};
 
 use self::ordered_float::OrderedFloat;
 
+/// Counters of the membership cache usage.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CacheStats {
+    /// Memberships returned from the cache.
+    pub hits: usize,
+    /// Memberships calculated from the membership function.
+    pub misses: usize,
+    /// Entries evicted to keep the cache within its capacity.
+    pub evictions: usize,
+}
+
+/// Tolerance of full membership in `Set::core` and `Set::boundary`.
+pub const CORE_EPSILON: f32 = 1e-6;
+
+/// Tolerance of membership changes in `Set::is_convex`.
+pub const CONVEXITY_EPSILON: f32 = 1e-6;
+
+/// Tolerance of memberships in `PartialEq` of `Set`.
+pub const SET_EQ_EPSILON: f32 = 1e-6;
+
+/// Error of the fuzzy set operations.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SetError {
+    /// Membership of NaN was requested.
+    NotANumber,
+    /// The fuzzy set with given name already exists in the universal set.
+    DuplicateSet(String),
+    /// Membership function returned NaN for given item.
+    InvalidMembership(f32),
+    /// Alpha of the cut is not in [0, 1].
+    InvalidAlpha(f32),
+}
+
+impl fmt::Display for SetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SetError::NotANumber => write!(f, "membership of NaN is undefined"),
+            SetError::DuplicateSet(ref name) => write!(f, "set {} already exists", name),
+            SetError::InvalidMembership(x) => write!(f, "membership of {} is NaN", x),
+            SetError::InvalidAlpha(alpha) => write!(f, "alpha {} is not in [0, 1]", alpha),
+        }
+    }
+}
+
+impl Error for SetError {
+    fn description(&self) -> &str {
+        match *self {
+            SetError::NotANumber => "membership of NaN is undefined",
+            SetError::DuplicateSet(_) => "set already exists",
+            SetError::InvalidMembership(_) => "membership is NaN",
+            SetError::InvalidAlpha(_) => "alpha is not in [0, 1]",
+        }
+    }
+}
+
+/// What `Set::check` does with memberships which are not greater than zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZeroPolicy {
+    /// Zero memberships are cached like any other.
+    Keep,
+    /// Zero memberships are not cached and are calculated again on every `check`.
+    Prune,
+}
+
+impl Default for ZeroPolicy {
+    /// `Prune`, the behavior of the sets before the policy was introduced.
+    fn default() -> ZeroPolicy {
+        ZeroPolicy::Prune
+    }
+}
+
+/// Recency bookkeeping of a bounded cache.
+#[derive(Clone, Default)]
+struct Lru {
+    /// Incremented on every access.
+    tick: u64,
+    /// Last access tick of every evictable key.
+    last_used: HashMap<OrderedFloat<f32>, u64>,
+    /// Evictable keys ordered by their last access.
+    order: BTreeMap<u64, OrderedFloat<f32>>,
+    /// Keys which are never evicted.
+    pinned: HashSet<OrderedFloat<f32>>,
+}
+
+impl Lru {
+    /// Marks the key as the most recently used one.
+    fn touch(&mut self, key: OrderedFloat<f32>) {
+        if self.pinned.contains(&key) {
+            return;
+        }
+        self.tick += 1;
+        if let Some(old) = self.last_used.insert(key, self.tick) {
+            self.order.remove(&old);
+        }
+        self.order.insert(self.tick, key);
+    }
+
+    /// Excludes the key from the eviction.
+    fn pin(&mut self, key: OrderedFloat<f32>) {
+        if let Some(old) = self.last_used.remove(&key) {
+            self.order.remove(&old);
+        }
+        self.pinned.insert(key);
+    }
+
+    /// Removes and returns the least recently used key.
+    fn pop(&mut self) -> Option<OrderedFloat<f32>> {
+        let oldest = match self.order.keys().next() {
+            Some(&tick) => tick,
+            None => return None,
+        };
+        let key = self.order.remove(&oldest).unwrap();
+        self.last_used.remove(&key);
+        Some(key)
+    }
+}
+
 /// Fuzzy set itself.
+///
+/// Clones share the membership function and copy the cache.
+///
+/// The cache is behind a `RefCell`, so memberships are computed through a shared reference,
+/// e.g. from an `InferenceContext`. The cache is never borrowed while the membership function runs,
+/// so evaluation of the same set may be nested. Holding a borrow of `cache` while calling `check`
+/// panics.
+#[derive(Clone)]
 pub struct Set {
     /// Name of the fuzzy set.
-    pub name: String,
+    ///
+    /// Shared, so cloning the name of a result set doesn't allocate.
+    pub name: Arc<str>,
     /// Membership function.
-    pub membership: Option<Box<MembershipFunction>>,
+    pub membership: Option<Rc<MembershipFunction>>,
     /// Cache with calculated memberships.
     pub cache: RefCell<HashMap<OrderedFloat<f32>, f32>>,
+    /// Maximum number of cached memberships. Unbounded if `None`.
+    capacity: Option<usize>,
+    /// Recency of cached memberships, used when `capacity` is set.
+    lru: RefCell<Lru>,
+    /// Whether zero memberships are cached.
+    zero_policy: ZeroPolicy,
+    /// Items are snapped to the nearest multiple of it. Exact if zero.
+    resolution: f32,
+    /// Cache usage counters.
+    stats: Cell<CacheStats>,
 }
 
 impl Set {
     /// Constructs the new `Set` with given membership function.
     /// Don't create sets with this method. Use `UniversalSet`.
-    pub fn new_with_mem(name: String, membership: Box<MembershipFunction>) -> Set {
+    pub fn new_with_mem<S: Into<Arc<str>>>(name: S, membership: Box<MembershipFunction>) -> Set {
         Set {
-            name: name,
-            membership: Some(membership),
+            name: name.into(),
+            membership: Some(Rc::from(membership)),
             cache: RefCell::new(HashMap::new()),
+            capacity: None,
+            lru: RefCell::new(Lru::default()),
+            zero_policy: ZeroPolicy::default(),
+            resolution: 0.0,
+            stats: Cell::new(CacheStats::default()),
         }
     }
 
     /// Constructs the new `Set` with given cache function.
     /// This cover the cases, where membership function is not available. E.g. result of an operation.
-    pub fn new_with_domain(name: String, cache: RefCell<HashMap<OrderedFloat<f32>, f32>>) -> Set {
+    pub fn new_with_domain<S: Into<Arc<str>>>(name: S,
+                                              cache: RefCell<HashMap<OrderedFloat<f32>, f32>>)
+                                              -> Set {
         Set {
-            name: name,
+            name: name.into(),
             membership: None,
             cache: cache,
+            capacity: None,
+            lru: RefCell::new(Lru::default()),
+            zero_policy: ZeroPolicy::default(),
+            resolution: 0.0,
+            stats: Cell::new(CacheStats::default()),
         }
     }
 
+    /// Constructs the empty `Set`, which has no membership function and no cached memberships.
+    ///
+    /// It's the identity of the union: union of the empty set and `X` is equal to `X`,
+    /// including the name, no matter on which side the empty set is.
+    pub fn new_empty() -> Set {
+        Set::new_with_domain("", RefCell::new(HashMap::new()))
+    }
+
+    /// Returns `true` if the set has no membership function and no cached memberships.
+    pub fn is_empty(&self) -> bool {
+        self.membership.is_none() && self.cache.borrow().is_empty()
+    }
+
+    /// Limits the cache to `capacity` memberships.
+    ///
+    /// Once exceeded, the least recently used memberships are evicted
+    /// and calculated again on the next `check`. Pinned memberships are never evicted.
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Set {
+        self.set_cache_capacity(Some(capacity));
+        self
+    }
+
+    /// Sets the cache capacity. `None` makes the cache unbounded.
+    ///
+    /// Memberships cached before the capacity was set are treated as the least recently used.
+    pub fn set_cache_capacity(&mut self, capacity: Option<usize>) {
+        self.capacity = capacity;
+        if capacity.is_some() {
+            {
+                let cache = self.cache.borrow();
+                let mut lru = self.lru.borrow_mut();
+                for key in cache.keys() {
+                    if !lru.pinned.contains(key) && !lru.last_used.contains_key(key) {
+                        lru.touch(*key);
+                    }
+                }
+            }
+            self.evict();
+        }
+    }
+
+    /// Sets whether zero memberships are cached. See `ZeroPolicy`.
+    pub fn with_zero_policy(mut self, policy: ZeroPolicy) -> Set {
+        self.zero_policy = policy;
+        self
+    }
+
+    /// Returns the policy of caching zero memberships.
+    pub fn zero_policy(&self) -> ZeroPolicy {
+        self.zero_policy
+    }
+
+    /// Snaps items to the nearest multiple of `resolution` before their memberships
+    /// are calculated and cached, so nearly equal items share the cache entry.
+    ///
+    /// Zero resolution, the default, keeps items exact.
+    pub fn with_resolution(mut self, resolution: f32) -> Set {
+        self.resolution = resolution;
+        self
+    }
+
+    /// Returns the resolution of the items.
+    pub fn resolution(&self) -> f32 {
+        self.resolution
+    }
+
+    /// Returns the cache capacity. `None` if the cache is unbounded.
+    pub fn cache_capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// Returns the cache usage counters.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.stats.get()
+    }
+
     /// Returns the membership of item.
     /// If already computed -- returns from cache.
     /// Elsewise -- calculates from function, and if value>0 then caches it.
+    /// Zero memberships are cached too with `ZeroPolicy::Keep`.
+    ///
+    /// Memberships calculated by the function are clamped into [0, 1].
+    /// NaN membership is treated as zero and is never cached.
+    ///
+    /// Membership of NaN item is NaN. Use `try_check` to get errors instead.
     pub fn check(&self, x: f32) -> f32 {
+        match self.try_check(x) {
+            Ok(mem) => mem,
+            Err(SetError::InvalidMembership(_)) => 0.0,
+            Err(_) => f32::NAN,
+        }
+    }
+
+    /// Same as `check`, but fails with `SetError::NotANumber` if `x` is NaN
+    /// and with `SetError::InvalidMembership` if the function returned NaN.
+    pub fn try_check(&self, x: f32) -> Result<f32, SetError> {
+        if x.is_nan() {
+            return Err(SetError::NotANumber);
+        }
+        let x = self.quantize(x);
         let ordered = OrderedFloat(x);
-        let func = self.membership.as_ref();
-        let mut cache = self.cache.borrow_mut();
-        let mut mem = 0.0;
-        {
-            let value = cache.entry(ordered).or_insert(match func {
-                Some(f) => f(x),
-                None => 0.0,
-            });
-            mem = *value;
+        let mut stats = self.stats.get();
+        let cached = self.cache.borrow().get(&ordered).cloned();
+        if let Some(mem) = cached {
+            stats.hits += 1;
+            self.stats.set(stats);
+            if self.capacity.is_some() {
+                self.lru.borrow_mut().touch(ordered);
+            }
+            return Ok(mem);
+        }
+        stats.misses += 1;
+        self.stats.set(stats);
+        let mem = self.calculate(x)?;
+        if mem > 0.0 || self.zero_policy == ZeroPolicy::Keep {
+            self.cache.borrow_mut().insert(ordered, mem);
+            if self.capacity.is_some() {
+                self.lru.borrow_mut().touch(ordered);
+                self.evict();
+            }
+        }
+        Ok(mem)
+    }
+
+    /// Returns the membership of item without modifying the cache.
+    ///
+    /// Cached membership is returned if present, elsewise it's calculated from function.
+    /// Cache usage counters and the recency of cached memberships aren't changed either.
+    pub fn membership_at(&self, x: f32) -> f32 {
+        if x.is_nan() {
+            return f32::NAN;
+        }
+        let x = self.quantize(x);
+        if let Some(&mem) = self.cache.borrow().get(&OrderedFloat(x)) {
+            return mem;
         }
-        if mem <= 0.0 {
-            cache.remove(&ordered);
+        self.calculate(x).unwrap_or(0.0)
+    }
+
+    /// Returns the representative point of the set: the mean of the points of `domain`
+    /// with the highest membership, or of the cached items if `domain` is empty.
+    ///
+    /// Memberships are taken from `membership_at`, so the cache is not modified.
+    /// Returns `None` if memberships of all points are zero.
+    pub fn peak(&self, domain: &[f32]) -> Option<f32> {
+        let cached;
+        let points = if domain.is_empty() {
+            cached = self.cache.borrow().keys().map(|x| x.0).collect::<Vec<_>>();
+            &cached
+        } else {
+            domain
+        };
+        let memberships = points.iter().map(|&x| self.membership_at(x)).collect::<Vec<_>>();
+        let height = memberships.iter().fold(0.0, |height: f32, &mem| height.max(mem));
+        if height <= 0.0 {
+            return None;
+        }
+        let maxima = points.iter()
+                           .zip(&memberships)
+                           .filter(|&(_, &mem)| mem >= height - MAXIMA_EPSILON)
+                           .map(|(&x, _)| x)
+                           .collect::<Vec<_>>();
+        Some(maxima.iter().sum::<f32>() / maxima.len() as f32)
+    }
+
+    /// Returns the Jaccard similarity of the sets: |A ∩ B| / |A ∪ B|.
+    ///
+    /// Cardinalities are sums of minimums and maximums of memberships over cached items of both sets.
+    /// Membership missing in the cache of one set is taken from `membership_at`.
+    /// Similarity of two empty sets is 1.
+    pub fn similarity(&self, other: &Set) -> f32 {
+        let mut intersection = 0.0;
+        let mut union = 0.0;
+        let own = self.cache.borrow();
+        for (key, &mem) in own.iter() {
+            let other_mem = other.membership_at(key.0);
+            intersection += mem.min(other_mem);
+            union += mem.max(other_mem);
+        }
+        for (key, &other_mem) in other.cache.borrow().iter() {
+            if own.contains_key(key) {
+                continue;
+            }
+            let mem = self.membership_at(key.0);
+            intersection += mem.min(other_mem);
+            union += mem.max(other_mem);
+        }
+        if union > 0.0 {
+            intersection / union
+        } else {
+            1.0
+        }
+    }
+
+    /// Returns cached items with positive membership in ascending order.
+    pub fn support(&self) -> Vec<f32> {
+        self.sorted_items(|mem| mem > 0.0)
+    }
+
+    /// Returns cached items with full membership in ascending order.
+    ///
+    /// Memberships closer to 1 than `CORE_EPSILON` are considered full.
+    pub fn core(&self) -> Vec<f32> {
+        self.sorted_items(|mem| mem >= 1.0 - CORE_EPSILON)
+    }
+
+    /// Returns cached items with partial membership in ascending order:
+    /// the support without the core.
+    pub fn boundary(&self) -> Vec<f32> {
+        self.sorted_items(|mem| mem > 0.0 && mem < 1.0 - CORE_EPSILON)
+    }
+
+    /// Returns cached items with membership matching `predicate` in ascending order.
+    fn sorted_items<P: Fn(f32) -> bool>(&self, predicate: P) -> Vec<f32> {
+        let mut items = self.cache
+                            .borrow()
+                            .iter()
+                            .filter(|&(_, &mem)| predicate(mem))
+                            .map(|(x, _)| *x)
+                            .collect::<Vec<_>>();
+        items.sort();
+        items.into_iter().map(|x| x.0).collect()
+    }
+
+    /// Returns `true` if the height of the set is 1 within `CORE_EPSILON`.
+    pub fn is_normal(&self) -> bool {
+        self.height() >= 1.0 - CORE_EPSILON
+    }
+
+    /// Returns `true` if cached memberships in ascending order of the items
+    /// don't decrease and then don't increase.
+    ///
+    /// Changes within `CONVEXITY_EPSILON` are ignored. The empty set is convex.
+    pub fn is_convex(&self) -> bool {
+        let cache = self.cache.borrow();
+        let mut items = cache.iter().collect::<Vec<_>>();
+        items.sort_by_key(|&(k, _)| *k);
+        let mut decreasing = false;
+        for pair in items.windows(2) {
+            let (previous, next) = (*pair[0].1, *pair[1].1);
+            if next < previous - CONVEXITY_EPSILON {
+                decreasing = true;
+            } else if next > previous + CONVEXITY_EPSILON && decreasing {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Returns the highest cached membership, 0 if the cache is empty.
+    pub fn height(&self) -> f32 {
+        self.cache.borrow().values().fold(0.0, |height: f32, &mem| height.max(mem))
+    }
+
+    /// Divides memberships by the height, so the highest one becomes 1.
+    ///
+    /// The membership function, if any, is scaled the same way.
+    /// Does nothing if the height is 0, e.g. for the empty set.
+    pub fn normalize(&mut self) {
+        let height = self.height();
+        if height <= 0.0 || height == 1.0 {
+            return;
+        }
+        for mem in self.cache.borrow_mut().values_mut() {
+            *mem /= height;
+        }
+        if let Some(f) = self.membership.take() {
+            self.membership = Some(Rc::new(move |x| f(x) / height));
+        }
+    }
+
+    /// Returns the normalized copy of the set. See `normalize`.
+    pub fn normalized(&self) -> Set {
+        let mut set = self.clone();
+        set.normalize();
+        set
+    }
+
+    /// Returns the alpha-cut of the set: cached items with membership not less than `alpha`.
+    ///
+    /// Memberships are preserved, the result has no membership function.
+    /// Fails with `SetError::InvalidAlpha` if `alpha` is not in [0, 1].
+    pub fn alpha_cut(&self, alpha: f32) -> Result<Set, SetError> {
+        self.cut(alpha, false)
+    }
+
+    /// Returns the strong alpha-cut of the set: cached items with membership greater than `alpha`.
+    ///
+    /// Same as `alpha_cut` otherwise.
+    pub fn strong_alpha_cut(&self, alpha: f32) -> Result<Set, SetError> {
+        self.cut(alpha, true)
+    }
+
+    /// Returns the lowest and the highest items of `alpha_cut`, `None` if the cut is empty.
+    pub fn alpha_cut_interval(&self, alpha: f32) -> Result<Option<(f32, f32)>, SetError> {
+        let cut = self.alpha_cut(alpha)?;
+        let cache = cut.cache.borrow();
+        let mut keys = cache.keys();
+        Ok(keys.next().map(|&first| {
+            let (min, max) = keys.fold((first, first), |(min, max), &x| (min.min(x), max.max(x)));
+            (min.0, max.0)
+        }))
+    }
+
+    /// Collects cached items with membership not less than `alpha`, or greater if `strict`.
+    fn cut(&self, alpha: f32, strict: bool) -> Result<Set, SetError> {
+        if !(alpha >= 0.0 && alpha <= 1.0) {
+            return Err(SetError::InvalidAlpha(alpha));
+        }
+        let cut = self.cache
+                      .borrow()
+                      .iter()
+                      .filter(|&(_, &mem)| if strict { mem > alpha } else { mem >= alpha })
+                      .map(|(&x, &mem)| (x, mem))
+                      .collect();
+        Ok(Set::new_with_domain(self.name.clone(), RefCell::new(cut)))
+    }
+
+    /// Returns Kosko's subsethood: the degree to which the set is contained in `other`,
+    /// |A ∩ B| / |A|.
+    ///
+    /// Cardinalities are sums of memberships over cached items of this set, memberships
+    /// of `other` are taken from `membership_at`. The set with zero cardinality,
+    /// e.g. the empty set, is contained in any set, so its subsethood is 1.
+    pub fn subsethood(&self, other: &Set) -> f32 {
+        let mut intersection = 0.0;
+        let mut cardinality = 0.0;
+        for (key, &mem) in self.cache.borrow().iter() {
+            intersection += mem.min(other.membership_at(key.0));
+            cardinality += mem;
+        }
+        if cardinality > 0.0 {
+            intersection / cardinality
+        } else {
+            1.0
+        }
+    }
+
+    /// Returns `true` if cached memberships of the sets differ by at most `epsilon`.
+    ///
+    /// Memberships are compared at the keys of both caches, memberships missing
+    /// from a cache are 0. Names and membership functions are not compared.
+    pub fn approx_eq(&self, other: &Set, epsilon: f32) -> bool {
+        let (own, other) = (self.cache.borrow(), other.cache.borrow());
+        let close = |a: f32, b: f32| (a - b).abs() <= epsilon;
+        own.iter().all(|(key, &mem)| close(mem, other.get(key).cloned().unwrap_or(0.0))) &&
+        other.iter()
+             .filter(|&(key, _)| !own.contains_key(key))
+             .all(|(_, &mem)| close(mem, 0.0))
+    }
+
+    /// Calculates the membership from function, clamped into [0, 1].
+    fn calculate(&self, x: f32) -> Result<f32, SetError> {
+        let mem = match self.membership {
+            Some(ref f) => f(x),
+            None => 0.0,
+        };
+        if mem.is_nan() {
+            return Err(SetError::InvalidMembership(x));
+        }
+        Ok(mem.max(0.0).min(1.0))
+    }
+
+    /// Same as `check`, but the cached membership is never evicted.
+    ///
+    /// Only memberships which are actually cached are pinned, e.g. zeros dropped
+    /// by `ZeroPolicy::Prune` are not. Does nothing more than `check` if the cache is unbounded.
+    pub fn pin(&self, x: f32) -> f32 {
+        let mem = self.check(x);
+        if x.is_nan() || self.capacity.is_none() {
+            return mem;
+        }
+        let key = OrderedFloat(self.quantize(x));
+        if self.cache.borrow().contains_key(&key) {
+            self.lru.borrow_mut().pin(key);
         }
         mem
     }
+
+    /// Snaps `x` to the nearest multiple of the resolution.
+    fn quantize(&self, x: f32) -> f32 {
+        if self.resolution > 0.0 {
+            (x / self.resolution).round() * self.resolution
+        } else {
+            x
+        }
+    }
+
+    /// Evicts the least recently used memberships until the cache fits the capacity.
+    fn evict(&self) {
+        let capacity = match self.capacity {
+            Some(capacity) => capacity,
+            None => return,
+        };
+        let mut cache = self.cache.borrow_mut();
+        let mut lru = self.lru.borrow_mut();
+        let mut stats = self.stats.get();
+        while cache.len() > capacity {
+            match lru.pop() {
+                Some(key) => {
+                    cache.remove(&key);
+                    stats.evictions += 1;
+                }
+                None => break,
+            }
+        }
+        self.stats.set(stats);
+    }
 }
 
 impl fmt::Debug for Set {
+    /// Cached memberships are written in ascending order of the keys.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cache = self.cache.borrow();
+        let mut items = cache.iter().collect::<Vec<_>>();
+        items.sort_by_key(|&(k, _)| *k);
         let mut s = String::new();
-        for (k, v) in self.cache.borrow().iter() {
+        for (k, v) in items {
             s = s + &format!("k:{} v:{}\n", k, v);
         }
         write!(f, "Set {{ name: {}\ncache: {} }}", self.name, s)
     }
 }
 
+impl PartialEq for Set {
+    /// Same as `approx_eq` with `SET_EQ_EPSILON`, so names are not compared.
+    fn eq(&self, other: &Set) -> bool {
+        self.approx_eq(other, SET_EQ_EPSILON)
+    }
+}
+
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 /// Universal set for fuzzy sets.
 pub struct UniversalSet {
     /// Name of the universal set.
@@ -85,6 +636,12 @@ pub struct UniversalSet {
     domain: Vec<f32>,
     /// Children fuzzy sets.
     pub sets: HashMap<String, Set>, // TODO
+    /// Cache capacity of children fuzzy sets.
+    cache_capacity: Option<usize>,
+    /// Zero policy of children fuzzy sets.
+    zero_policy: ZeroPolicy,
+    /// Resolution of children fuzzy sets.
+    resolution: f32,
 }
 
 impl UniversalSet {
@@ -94,33 +651,126 @@ impl UniversalSet {
             name: name,
             domain: Vec::new(),
             sets: HashMap::new(),
+            cache_capacity: None,
+            zero_policy: ZeroPolicy::default(),
+            resolution: 0.0,
         }
     }
 
+    /// Returns the name of the universal set.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the domain of the universal set.
+    pub fn domain(&self) -> &[f32] {
+        &self.domain
+    }
+
     /// Sets the domain of the universal set.
     pub fn set_domain(&mut self, domain: Vec<f32>) {
         self.domain = domain;
     }
 
-    /// Constructs the child fuzzy set with given membership.
-    pub fn create_set(&mut self, name: String, membership: Box<MembershipFunction>) {
-        if !self.sets.contains_key(&name) {
-            let set = Set {
-                name: name.clone(),
-                membership: Some(membership),
-                cache: RefCell::new(HashMap::new()),
-            };
+    /// Sets the cache capacity of all children fuzzy sets, including the ones created later.
+    ///
+    /// Memberships precomputed over the domain are pinned and never evicted.
+    pub fn set_cache_capacity(&mut self, capacity: Option<usize>) {
+        self.cache_capacity = capacity;
+        for set in self.sets.values_mut() {
+            set.set_cache_capacity(capacity);
+            // Sets don't pin anything while their caches are unbounded.
             for i in &self.domain {
-                set.check(*i);
+                set.pin(*i);
             }
-            self.sets.insert(name, set);
         }
     }
 
+    /// Sets the zero policy of all children fuzzy sets, including the ones created later.
+    ///
+    /// With `ZeroPolicy::Keep` every domain point has a cached membership.
+    pub fn set_zero_policy(&mut self, policy: ZeroPolicy) {
+        self.zero_policy = policy;
+        for set in self.sets.values_mut() {
+            set.zero_policy = policy;
+            for i in &self.domain {
+                set.pin(*i);
+            }
+        }
+    }
+
+    /// Sets the resolution of all children fuzzy sets, including the ones created later.
+    ///
+    /// Memberships cached by the existing sets are dropped and precomputed again over the domain.
+    /// See `Set::with_resolution`.
+    pub fn set_resolution(&mut self, resolution: f32) {
+        self.resolution = resolution;
+        for set in self.sets.values_mut() {
+            set.resolution = resolution;
+            set.cache.borrow_mut().clear();
+            *set.lru.borrow_mut() = Lru::default();
+            for i in &self.domain {
+                set.pin(*i);
+            }
+        }
+    }
+
+    /// Constructs the child fuzzy set with given membership.
+    ///
+    /// Fails with `SetError::DuplicateSet` if the set with given name already exists,
+    /// which is kept unchanged. Use `replace_set` to change it.
+    pub fn create_set(&mut self,
+                      name: String,
+                      membership: Box<MembershipFunction>)
+                      -> Result<(), SetError> {
+        if self.sets.contains_key(&name) {
+            return Err(SetError::DuplicateSet(name));
+        }
+        let set = self.child_set(&name, membership);
+        self.sets.insert(name, set);
+        Ok(())
+    }
+
+    /// Constructs the child fuzzy set with given membership, replacing the existing one.
+    ///
+    /// Returns the replaced set, if any. Memberships cached by it are not reused.
+    pub fn replace_set(&mut self,
+                       name: String,
+                       membership: Box<MembershipFunction>)
+                       -> Option<Set> {
+        let set = self.child_set(&name, membership);
+        self.sets.insert(name, set)
+    }
+
+    /// Constructs the fuzzy set with the settings of the universal set,
+    /// memberships over the domain are precomputed.
+    fn child_set(&self, name: &str, membership: Box<MembershipFunction>) -> Set {
+        let mut set = Set::new_with_mem(name, membership)
+                          .with_zero_policy(self.zero_policy)
+                          .with_resolution(self.resolution);
+        set.set_cache_capacity(self.cache_capacity);
+        for i in &self.domain {
+            set.pin(*i);
+        }
+        set
+    }
+
+    /// Computes memberships from all children fuzzy sets without modifying their caches.
+    ///
+    /// See `Set::membership_at`.
+    pub fn memberships_ro(&self, x: f32) -> HashMap<String, f32> {
+        self.sets
+            .iter()
+            .map(|(name, set)| (name.clone(), set.membership_at(x)))
+            .collect()
+    }
+
     /// Computes memberships from all children fuzzy sets.
-    pub fn memberships(&mut self, x: f32) -> HashMap<String, f32> {
+    ///
+    /// Like `Set::check`, needs only a shared reference.
+    pub fn memberships(&self, x: f32) -> HashMap<String, f32> {
         self.sets
-            .iter_mut()
+            .iter()
             .map(|(name, set)| (name.clone(), set.check(x)))
             .collect()
     }
@@ -129,4 +779,359 @@ impl UniversalSet {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use functions::MembershipFactory;
+    use std::rc::Rc;
+
+    #[test]
+    fn bounded_cache() {
+        let set = Set::new_with_mem("Test", MembershipFactory::sigmoidal(0.001, 0.0))
+                      .with_cache_capacity(100);
+        for i in 0..10000 {
+            let x = i as f32 / 10.0;
+            assert_eq!(set.check(x), 1.0 / (1.0 + (-0.001 * x).exp()));
+            assert!(set.cache.borrow().len() <= 100);
+        }
+        // Evicted memberships are calculated again.
+        assert_eq!(set.check(0.0), 0.5);
+        let stats = set.cache_stats();
+        assert_eq!(stats.misses, 10001);
+        assert_eq!(stats.evictions, 9901);
+        assert_eq!(set.check(0.0), 0.5);
+        assert_eq!(set.cache_stats().hits, 1);
+    }
+
+    #[test]
+    fn pinned_domain() {
+        let mut universe = UniversalSet::new("Test".to_string());
+        universe.set_domain((0..10).map(|x| x as f32).collect());
+        universe.set_cache_capacity(Some(15));
+        universe.create_set("Set".to_string(), MembershipFactory::sigmoidal(1.0, 0.0)).unwrap();
+        let set = &universe.sets["Set"];
+        for i in 0..100 {
+            set.check(100.0 + i as f32);
+        }
+        let cache = set.cache.borrow();
+        assert_eq!(cache.len(), 15);
+        for i in 0..10 {
+            assert!(cache.contains_key(&OrderedFloat(i as f32)));
+        }
+    }
+
+    #[test]
+    fn pin_only_cached() {
+        let mut universe = UniversalSet::new("Test".to_string());
+        universe.set_domain((0..10).map(|x| x as f32).collect());
+        universe.create_set("Set".to_string(), MembershipFactory::triangular(-1.0, 0.0, 5.0))
+                .unwrap();
+        // Unbounded caches don't track pinned keys.
+        assert!(universe.sets["Set"].lru.borrow().pinned.is_empty());
+
+        // Domain points are pinned once the capacity is set, except the dropped zeros.
+        universe.set_cache_capacity(Some(8));
+        let set = &universe.sets["Set"];
+        assert_eq!(set.lru.borrow().pinned.len(), 5);
+        for i in 0..100 {
+            set.check(0.005 + i as f32 / 100.0);
+        }
+        let cache = set.cache.borrow();
+        assert_eq!(cache.len(), 8);
+        for i in 0..5 {
+            assert!(cache.contains_key(&OrderedFloat(i as f32)));
+        }
+    }
+
+    #[test]
+    fn keep_zeros() {
+        let mut universe = UniversalSet::new("Test".to_string());
+        universe.set_domain((0..50).map(|x| x as f32).collect());
+        universe.set_zero_policy(ZeroPolicy::Keep);
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        let triangular = MembershipFactory::triangular(10.0, 20.0, 30.0);
+        universe.create_set("Set".to_string(),
+                            Box::new(move |x| {
+                                counter.set(counter.get() + 1);
+                                triangular(x)
+                            })).unwrap();
+        let set = &universe.sets["Set"];
+        assert_eq!(set.cache.borrow().len(), 50);
+        assert_eq!(calls.get(), 50);
+        for _ in 0..10 {
+            assert_eq!(set.check(0.0), 0.0);
+        }
+        assert_eq!(calls.get(), 50);
+    }
+
+    #[test]
+    fn prune_zeros() {
+        let mut universe = UniversalSet::new("Test".to_string());
+        universe.set_domain((0..50).map(|x| x as f32).collect());
+        universe.create_set("Set".to_string(),
+                            MembershipFactory::triangular(10.0, 20.0, 30.0)).unwrap();
+        assert_eq!(universe.sets["Set"].cache.borrow().len(), 19);
+    }
+
+    #[test]
+    fn nan_is_not_cached() {
+        let set = Set::new_with_mem("Test", MembershipFactory::triangular(0.0, 1.0, 2.0));
+        assert_eq!(set.try_check(f32::NAN), Err(SetError::NotANumber));
+        assert!(set.check(f32::NAN).is_nan());
+        assert!(set.cache.borrow().is_empty());
+        assert_eq!(set.check(1.0), 1.0);
+        assert_eq!(set.cache.borrow().len(), 1);
+    }
+
+    #[test]
+    fn duplicate_set() {
+        let mut universe = UniversalSet::new("Test".to_string());
+        universe.set_domain((0..10).map(|x| x as f32).collect());
+        universe.create_set("Set".to_string(),
+                            MembershipFactory::triangular(0.0, 2.0, 4.0)).unwrap();
+        assert_eq!(universe.create_set("Set".to_string(),
+                                       MembershipFactory::triangular(4.0, 6.0, 8.0)),
+                   Err(SetError::DuplicateSet("Set".to_string())));
+        assert_eq!(universe.sets["Set"].check(2.0), 1.0);
+
+        let old = universe.replace_set("Set".to_string(),
+                                       MembershipFactory::triangular(4.0, 6.0, 8.0));
+        assert_eq!(old.unwrap().check(2.0), 1.0);
+        let set = &universe.sets["Set"];
+        assert!(!set.cache.borrow().contains_key(&OrderedFloat(2.0)));
+        assert_eq!(set.check(2.0), 0.0);
+        assert_eq!(set.check(6.0), 1.0);
+        assert!(universe.replace_set("Other".to_string(),
+                                     MembershipFactory::triangular(0.0, 1.0, 2.0))
+                        .is_none());
+    }
+
+    #[test]
+    fn resolution() {
+        let exact = Set::new_with_mem("Exact", MembershipFactory::triangular(10.0, 20.0, 30.0));
+        let set = Set::new_with_mem("Test", MembershipFactory::triangular(10.0, 20.0, 30.0))
+                      .with_resolution(0.01);
+        let mut jitter = 1u32;
+        for i in 0..10000 {
+            jitter = jitter.wrapping_mul(1664525).wrapping_add(1013904223);
+            let x = 20.0 + (i % 10) as f32 * 0.1 + (jitter >> 8) as f32 / (1 << 24) as f32 * 0.001;
+            // The slope of the membership is 0.1, the item moves by half of the quantum at most.
+            assert!((set.check(x) - exact.check(x)).abs() <= 0.1 * 0.005 + 1e-5);
+        }
+        assert!(set.cache.borrow().len() <= 20);
+        assert!(exact.cache.borrow().len() > 1000);
+    }
+
+    #[test]
+    fn universe_resolution() {
+        let mut universe = UniversalSet::new("Test".to_string());
+        universe.set_domain((0..101).map(|x| x as f32 / 100.0).collect());
+        universe.create_set("Early".to_string(), MembershipFactory::triangular(0.0, 0.5, 1.0))
+                .unwrap();
+        assert_eq!(universe.sets["Early"].cache.borrow().len(), 99);
+        universe.set_resolution(0.1);
+        universe.create_set("Late".to_string(), MembershipFactory::triangular(0.0, 0.5, 1.0))
+                .unwrap();
+        for name in &["Early", "Late"] {
+            let set = &universe.sets[*name];
+            assert_eq!(set.resolution(), 0.1);
+            // The domain snaps to 11 points, the zeros at both ends are dropped.
+            assert_eq!(set.cache.borrow().len(), 9);
+            assert_eq!(set.check(0.52), 1.0);
+        }
+        universe.replace_set("Late".to_string(), MembershipFactory::triangular(0.0, 0.5, 1.0));
+        assert_eq!(universe.sets["Late"].resolution(), 0.1);
+    }
+
+    #[test]
+    fn read_only_memberships() {
+        let mut universe = UniversalSet::new("Test".to_string());
+        universe.set_domain((0..10).map(|x| x as f32).collect());
+        universe.create_set("Low".to_string(),
+                            MembershipFactory::triangular(-5.0, 0.0, 5.0)).unwrap();
+        universe.create_set("High".to_string(),
+                            MembershipFactory::triangular(5.0, 10.0, 15.0)).unwrap();
+        let sizes = |universe: &UniversalSet| {
+            universe.sets.values().map(|set| set.cache.borrow().len()).collect::<Vec<_>>()
+        };
+        let before = sizes(&universe);
+        let mutated = universe.clone();
+        for i in 0..100 {
+            let x = i as f32 / 7.0;
+            let degrees = universe.memberships_ro(x);
+            assert_eq!(sizes(&universe), before);
+            assert_eq!(degrees, mutated.memberships(x));
+        }
+        assert!(sizes(&mutated) != before);
+    }
+
+    #[test]
+    fn clamped_memberships() {
+        let set = Set::new_with_mem("Test",
+                                    Box::new(|x: f32| if x < 1.0 {
+                                        2.0
+                                    } else if x < 2.0 {
+                                        -0.5
+                                    } else {
+                                        f32::NAN
+                                    }))
+                      .with_zero_policy(ZeroPolicy::Keep);
+        assert_eq!(set.check(0.0), 1.0);
+        assert_eq!(set.check(1.0), 0.0);
+        assert_eq!(set.try_check(2.0), Err(SetError::InvalidMembership(2.0)));
+        assert_eq!(set.check(2.0), 0.0);
+        assert_eq!(set.membership_at(2.0), 0.0);
+        let cache = set.cache.borrow();
+        assert_eq!(cache.len(), 2);
+        assert!(cache.values().all(|&mem| mem >= 0.0 && mem <= 1.0));
+    }
+
+    #[test]
+    fn similarity() {
+        let set = |a, b, c| {
+            let set = Set::new_with_mem("Set", MembershipFactory::triangular(a, b, c));
+            for i in 0..21 {
+                set.check(i as f32);
+            }
+            set
+        };
+        assert_eq!(set(0.0, 5.0, 10.0).similarity(&set(0.0, 5.0, 10.0)), 1.0);
+        assert_eq!(set(0.0, 5.0, 10.0).similarity(&set(10.0, 15.0, 20.0)), 0.0);
+        assert_eq!(Set::new_empty().similarity(&Set::new_empty()), 1.0);
+
+        let cached = |items: &[(f32, f32)]| {
+            let cache = items.iter().map(|&(x, mem)| (OrderedFloat(x), mem)).collect();
+            Set::new_with_domain("Cached", RefCell::new(cache))
+        };
+        let left = cached(&[(1.0, 1.0), (2.0, 0.5)]);
+        let right = cached(&[(2.0, 1.0), (3.0, 0.5)]);
+        // Intersection is 0.5 at 2, union is 1 at 1, 1 at 2 and 0.5 at 3.
+        assert_eq!(left.similarity(&right), 0.2);
+        assert_eq!(right.similarity(&left), 0.2);
+        assert_eq!(left.similarity(&Set::new_empty()), 0.0);
+    }
+
+    #[test]
+    fn alpha_cut() {
+        let mut universe = UniversalSet::new("Test".to_string());
+        universe.set_domain((0..101).map(|x| x as f32).collect());
+        universe.create_set("Set".to_string(), MembershipFactory::triangular(0.0, 50.0, 100.0))
+                .unwrap();
+        let set = &universe.sets["Set"];
+        let cut = set.alpha_cut(0.8).unwrap();
+        assert_eq!(cut.cache.borrow().len(), 21);
+        assert!(cut.cache.borrow().iter().all(|(x, &mem)| mem == set.check(x.0) && mem >= 0.8));
+        assert_eq!(set.alpha_cut_interval(0.8), Ok(Some((40.0, 60.0))));
+        assert_eq!(set.alpha_cut_interval(1.0), Ok(Some((50.0, 50.0))));
+        assert_eq!(set.strong_alpha_cut(0.8).unwrap().cache.borrow().len(), 19);
+        assert!(set.strong_alpha_cut(1.0).unwrap().cache.borrow().is_empty());
+        assert_eq!(Set::new_empty().alpha_cut_interval(0.5), Ok(None));
+        assert_eq!(set.alpha_cut(1.5).unwrap_err(), SetError::InvalidAlpha(1.5));
+        assert!(set.alpha_cut(-0.1).is_err() && set.alpha_cut(f32::NAN).is_err());
+    }
+
+    #[test]
+    fn normalization() {
+        let mut cache = HashMap::new();
+        for &(x, mem) in &[(1.0, 0.1), (2.0, 0.3), (3.0, 0.6), (4.0, 0.2)] {
+            cache.insert(OrderedFloat(x), mem);
+        }
+        let set = Set::new_with_domain("Set", RefCell::new(cache));
+        assert_eq!(set.height(), 0.6);
+        let normalized = set.normalized();
+        assert_eq!(normalized.height(), 1.0);
+        assert_eq!(set.height(), 0.6);
+        for (x, &mem) in set.cache.borrow().iter() {
+            assert!((normalized.check(x.0) - mem / 0.6).abs() < 1e-6);
+        }
+        assert!((normalized.check(2.0) / normalized.check(4.0) - 1.5).abs() < 1e-5);
+
+        let mut scaled = Set::new_with_mem("Scaled", Box::new(|x: f32| 0.5 * (-x * x).exp()));
+        scaled.check(0.0);
+        scaled.normalize();
+        assert_eq!(scaled.check(0.0), 1.0);
+        assert!((scaled.check(1.0) - (-1.0f32).exp()).abs() < 1e-6);
+
+        let mut empty = Set::new_empty();
+        empty.normalize();
+        assert!(empty.is_empty());
+        assert_eq!(empty.height(), 0.0);
+    }
+
+    #[test]
+    fn support_core_boundary() {
+        let mut universe = UniversalSet::new("Test".to_string());
+        universe.set_domain((0..21).rev().map(|x| x as f32).collect());
+        universe.set_zero_policy(ZeroPolicy::Keep);
+        universe.create_set("Set".to_string(),
+                            MembershipFactory::trapezoidal(2.0, 5.0, 10.0, 15.0))
+                .unwrap();
+        let set = &universe.sets["Set"];
+        let range = |from: u8, to: u8| (from..to + 1).map(f32::from).collect::<Vec<_>>();
+        assert_eq!(set.support(), range(3, 14));
+        assert_eq!(set.core(), range(5, 10));
+        assert_eq!(set.boundary(), vec![3.0, 4.0, 11.0, 12.0, 13.0, 14.0]);
+        assert!(Set::new_empty().support().is_empty());
+    }
+
+    #[test]
+    fn subsethood() {
+        let set = Set::new_with_mem("Set", MembershipFactory::triangular(0.0, 5.0, 10.0));
+        for i in 0..11 {
+            set.check(i as f32);
+        }
+        assert_eq!(set.subsethood(&set), 1.0);
+        let clipped = set.cache
+                         .borrow()
+                         .iter()
+                         .map(|(&x, &mem)| (x, mem.min(0.5)))
+                         .collect();
+        let clipped = Set::new_with_domain("Clipped", RefCell::new(clipped));
+        assert_eq!(clipped.subsethood(&set), 1.0);
+        // Cardinality of the set is 5, cardinality of the clipped set and the intersection is 3.7.
+        assert!((set.subsethood(&clipped) - 0.74).abs() < 1e-6);
+        assert_eq!(Set::new_empty().subsethood(&set), 1.0);
+        assert_eq!(set.subsethood(&Set::new_empty()), 0.0);
+    }
+
+    #[test]
+    fn approximate_equality() {
+        let cached = |name: &str, items: &[(f32, f32)]| {
+            let cache = items.iter().map(|&(x, mem)| (OrderedFloat(x), mem)).collect();
+            Set::new_with_domain(name, RefCell::new(cache))
+        };
+        let set = cached("Set", &[(1.0, 0.5), (2.0, 1.0)]);
+        assert_eq!(set, cached("Other", &[(0.0, 0.0), (1.0, 0.5 + 1e-7), (2.0, 1.0)]));
+        assert_ne!(set, cached("Set", &[(1.0, 0.5), (2.0, 1.0), (3.0, 0.1)]));
+        assert_ne!(set, cached("Set", &[(1.0, 0.5)]));
+        assert!(set.approx_eq(&cached("Set", &[(1.0, 0.45), (2.0, 1.0)]), 0.1));
+        assert!(!set.approx_eq(&cached("Set", &[(1.0, 0.45), (2.0, 1.0)]), 0.01));
+        assert_eq!(Set::new_empty(), cached("Empty", &[(5.0, 0.0)]));
+
+        let unordered = cached("Set", &[(3.0, 0.1), (-1.0, 0.2), (2.0, 0.3)]);
+        assert_eq!(format!("{:?}", unordered),
+                   "Set { name: Set\ncache: k:-1 v:0.2\nk:2 v:0.3\nk:3 v:0.1\n }");
+    }
+
+    #[test]
+    fn convexity_and_normality() {
+        use ops::{MinMaxOps, SetOps};
+
+        let sampled = |a, b, c| {
+            let set = Set::new_with_mem("Set", MembershipFactory::triangular(a, b, c));
+            for i in 0..201 {
+                set.check(i as f32 / 10.0);
+            }
+            set
+        };
+        let triangle = sampled(0.0, 5.0, 10.0);
+        assert!(triangle.is_convex() && triangle.is_normal());
+        let mut other = sampled(12.0, 16.0, 20.0);
+        let union = MinMaxOps {}.union(&mut triangle.clone(), &mut other);
+        assert!(union.is_normal() && !union.is_convex());
+        let clipped = triangle.alpha_cut(0.0).unwrap();
+        for mem in clipped.cache.borrow_mut().values_mut() {
+            *mem = mem.min(0.5);
+        }
+        assert!(clipped.is_convex() && !clipped.is_normal());
+        assert!(Set::new_empty().is_convex() && !Set::new_empty().is_normal());
+    }
 }
@@ -0,0 +1,209 @@
+//! This module contains the closed-loop simulation of controllers against plant models.
+//!
+//! Controllers are closures from the setpoint and the measurement to the control,
+//! plants are closures from the state, the control and the time step to the next state.
+//! `fuzzy_controller` and `machine_controller` adapt fuzzy systems into controllers.
+use controllers::FuzzyController;
+use inference::InferenceMachine;
+use std::f32;
+
+/// Time series recorded by `simulate`, one entry per step.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SimulationTrace {
+    /// Time of the step.
+    pub time: Vec<f32>,
+    /// Setpoint at the step.
+    pub setpoint: Vec<f32>,
+    /// Measured state at the step.
+    pub measurement: Vec<f32>,
+    /// Control computed at the step.
+    pub control: Vec<f32>,
+}
+
+impl SimulationTrace {
+    /// Returns the largest excess of the measurement past the final setpoint,
+    /// relative to the distance from the initial measurement to the final setpoint.
+    ///
+    /// Returns 0 for an empty trace or if the measurement starts at the final setpoint.
+    pub fn overshoot(&self) -> f32 {
+        let (initial, target) = match (self.measurement.first(), self.setpoint.last()) {
+            (Some(&initial), Some(&target)) => (initial, target),
+            _ => return 0.0,
+        };
+        let step = target - initial;
+        if step == 0.0 {
+            return 0.0;
+        }
+        self.measurement
+            .iter()
+            .map(|&y| (y - target) * step.signum() / step.abs())
+            .fold(0.0, f32::max)
+    }
+
+    /// Returns the time, after which the measurement stays within the band around
+    /// the final setpoint, or `None` if the last measurement is outside the band.
+    ///
+    /// Half-width of the band is `tolerance` of the distance from the initial measurement
+    /// to the final setpoint, or `tolerance` itself if the distance is zero.
+    pub fn settling_time(&self, tolerance: f32) -> Option<f32> {
+        let (initial, target) = match (self.measurement.first(), self.setpoint.last()) {
+            (Some(&initial), Some(&target)) => (initial, target),
+            _ => return None,
+        };
+        let step = (target - initial).abs();
+        let band = if step > 0.0 { tolerance * step } else { tolerance };
+        let outside = self.measurement.iter().rposition(|&y| (y - target).abs() > band);
+        match outside {
+            None => self.time.first().cloned(),
+            Some(index) => self.time.get(index + 1).cloned(),
+        }
+    }
+
+    /// Returns the integrated absolute error between the setpoint and the measurement.
+    pub fn iae(&self) -> f32 {
+        let dt = if self.time.len() > 1 { self.time[1] - self.time[0] } else { 0.0 };
+        self.setpoint
+            .iter()
+            .zip(&self.measurement)
+            .fold(0.0, |sum, (&setpoint, &y)| sum + (setpoint - y).abs() * dt)
+    }
+}
+
+/// Simulates the closed loop for `steps` steps of `dt`, the plant's state starts at 0.
+///
+/// At each step the controller gets the setpoint at the current time and the state,
+/// then the plant advances the state with the control.
+pub fn simulate<C, P, S>(controller: &mut C,
+                         plant: &mut P,
+                         setpoint: S,
+                         dt: f32,
+                         steps: usize)
+                         -> SimulationTrace
+    where C: FnMut(f32, f32) -> f32,
+          P: FnMut(f32, f32, f32) -> f32,
+          S: Fn(f32) -> f32
+{
+    let mut trace = SimulationTrace {
+        time: Vec::with_capacity(steps),
+        setpoint: Vec::with_capacity(steps),
+        measurement: Vec::with_capacity(steps),
+        control: Vec::with_capacity(steps),
+    };
+    let mut state = 0.0;
+    for step in 0..steps {
+        let time = step as f32 * dt;
+        let target = setpoint(time);
+        let control = controller(target, state);
+        trace.time.push(time);
+        trace.setpoint.push(target);
+        trace.measurement.push(state);
+        trace.control.push(control);
+        state = plant(state, control, dt);
+    }
+    trace
+}
+
+/// Adapts the fuzzy controller, stepping it by `dt`.
+pub fn fuzzy_controller<'a>(controller: &'a mut FuzzyController,
+                            dt: f32)
+                            -> Box<FnMut(f32, f32) -> f32 + 'a> {
+    Box::new(move |setpoint, measurement| controller.step(setpoint, measurement, dt))
+}
+
+/// Adapts the machine, whose input `error` is the setpoint minus the measurement
+/// and whose output in `RuleSet::result_universe` is the control.
+/// Gives 0 if the inference fails.
+pub fn machine_controller<'a>(machine: &'a mut InferenceMachine,
+                              error: &str)
+                              -> Box<FnMut(f32, f32) -> f32 + 'a> {
+    let error = error.to_string();
+    let output = machine.rules.result_universe().to_string();
+    Box::new(move |setpoint, measurement| {
+        if machine.set_value(&error, setpoint - measurement).is_err() {
+            return 0.0;
+        }
+        machine.compute_for(&output).map(|result| result.value).unwrap_or(0.0)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use builder::SystemBuilder;
+    use controllers::ControllerConfig;
+    use functions::MembershipFactory;
+    use rules::is;
+
+    /// First-order lag `dy/dt = (u - y) / tau` with `tau` of 1.
+    fn lag(state: f32, control: f32, dt: f32) -> f32 {
+        state + dt * (control - state)
+    }
+
+    #[test]
+    fn fuzzy_controller_settles() {
+        let mut controller = FuzzyController::new(ControllerConfig {
+                                 error_range: 2.0,
+                                 delta_range: 4.0,
+                                 control_range: 3.0,
+                                 incremental: true,
+                                 ..ControllerConfig::default()
+                             })
+                                 .unwrap();
+        let dt = 0.05;
+        let trace = simulate(&mut fuzzy_controller(&mut controller, dt),
+                             &mut lag,
+                             |_| 1.0,
+                             dt,
+                             400);
+        assert_eq!(trace.time.len(), 400);
+        assert_eq!(trace.measurement[0], 0.0);
+        let settling = trace.settling_time(0.02).unwrap();
+        assert!(settling < 15.0, "settled at {}", settling);
+        assert!(trace.overshoot() < 0.2);
+        assert!(trace.iae() < 5.0);
+    }
+
+    #[test]
+    fn machine_controller_follows_error() {
+        let mut machine = SystemBuilder::new()
+                              .input("error", |u| {
+                                  u.range(-2.0, 2.0, 41)
+                                   .term("negative", MembershipFactory::triangular(-4.0, -2.0, 0.0))
+                                   .term("zero", MembershipFactory::triangular(-2.0, 0.0, 2.0))
+                                   .term("positive", MembershipFactory::triangular(0.0, 2.0, 4.0))
+                              })
+                              .output("control", |u| {
+                                  u.range(-5.0, 5.0, 101)
+                                   .term("down", MembershipFactory::triangular(-10.0, -5.0, 0.0))
+                                   .term("hold", MembershipFactory::triangular(-1.0, 0.0, 1.0))
+                                   .term("up", MembershipFactory::triangular(0.0, 5.0, 10.0))
+                              })
+                              .rule(|r| r.when(is("error", "negative")).then("control", "down"))
+                              .rule(|r| r.when(is("error", "zero")).then("control", "hold"))
+                              .rule(|r| r.when(is("error", "positive")).then("control", "up"))
+                              .build()
+                              .unwrap();
+        let trace = simulate(&mut machine_controller(&mut machine, "error"),
+                             &mut |state, control, dt| state + dt * control,
+                             |time| if time < 1.0 { 0.0 } else { 1.0 },
+                             0.1,
+                             100);
+        assert!(trace.control[0].abs() < 1e-5);
+        assert!((trace.measurement[99] - 1.0).abs() < 0.05);
+        assert!(trace.settling_time(0.05).is_some());
+    }
+
+    #[test]
+    fn trace_metrics() {
+        let trace = SimulationTrace {
+            time: vec![0.0, 1.0, 2.0, 3.0],
+            setpoint: vec![2.0; 4],
+            measurement: vec![0.0, 2.5, 1.9, 2.0],
+            control: vec![0.0; 4],
+        };
+        assert!((trace.overshoot() - 0.25).abs() < 1e-6);
+        assert_eq!(trace.settling_time(0.1), Some(2.0));
+        assert_eq!(trace.settling_time(0.5), Some(1.0));
+        assert!((trace.iae() - 2.6).abs() < 1e-5);
+    }
+}
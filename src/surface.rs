@@ -0,0 +1,210 @@
+//! This module contains control surfaces of the inference.
+//!
+//! `Surface` holds outputs of the inference over a grid of one or two swept input variables,
+//! while values of other variables are fixed.
+use inference::{InferenceError, InferenceMachine};
+use std::collections::HashMap;
+use std::f32;
+use std::io;
+
+/// Outputs of the inference over the grid of swept input variables.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Surface {
+    /// Name of the variable swept along the rows.
+    pub x_variable: String,
+    /// Values of the `x_variable`.
+    pub x: Vec<f32>,
+    /// Name of the variable swept along the columns, if any.
+    pub y_variable: Option<String>,
+    /// Values of the `y_variable`, empty if there is no such variable.
+    pub y: Vec<f32>,
+    /// Row-major grid of outputs, `x.len()` outputs per row of `y`.
+    ///
+    /// Output is NaN where no rule fired.
+    pub values: Vec<f32>,
+}
+
+impl Surface {
+    /// Returns the output at `x[i]` and `y[j]`, `j` is 0 for the surface of one variable.
+    pub fn get(&self, i: usize, j: usize) -> f32 {
+        self.values[j * self.x.len() + i]
+    }
+
+    /// Writes the surface as CSV with a header and a line per grid point.
+    pub fn write_csv<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        match self.y_variable {
+            Some(ref y_variable) => {
+                writeln!(out, "{},{},output", self.x_variable, y_variable)?;
+                for (j, y) in self.y.iter().enumerate() {
+                    for (i, x) in self.x.iter().enumerate() {
+                        writeln!(out, "{},{},{}", x, y, self.get(i, j))?;
+                    }
+                }
+            }
+            None => {
+                writeln!(out, "{},output", self.x_variable)?;
+                for (i, x) in self.x.iter().enumerate() {
+                    writeln!(out, "{},{}", x, self.get(i, 0))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl InferenceMachine {
+    /// Computes the control surface over one or two input variables.
+    ///
+    /// Each variable is swept at `resolution` points from the minimum to the maximum of
+    /// its universe's domain, values of other variables are taken from `fixed`.
+    /// Values of the machine are restored afterwards.
+    /// Errors other than `InferenceError::NoRuleFired` stop the computation.
+    pub fn surface(&mut self,
+                   x_var: &str,
+                   y_var: Option<&str>,
+                   resolution: usize,
+                   fixed: &HashMap<String, f32>)
+                   -> Result<Surface, InferenceError> {
+        let x = self.sweep(x_var, resolution)?;
+        let y = match y_var {
+            Some(y_var) => self.sweep(y_var, resolution)?,
+            None => Vec::new(),
+        };
+        let mut variables = vec![x_var];
+        let mut rows = Vec::with_capacity(x.len() * y.len().max(1));
+        match y_var {
+            Some(y_var) => {
+                variables.push(y_var);
+                for &y in &y {
+                    rows.extend(x.iter().map(|&x| vec![x, y]));
+                }
+            }
+            None => rows.extend(x.iter().map(|&x| vec![x])),
+        }
+
+        let saved = self.values.clone();
+        let saved_fuzzy = self.fuzzy_values.clone();
+        for (variable, &value) in fixed {
+            if let Err(error) = self.set_value(variable, value) {
+                self.values = saved;
+                self.fuzzy_values = saved_fuzzy;
+                return Err(error);
+            }
+        }
+        let results = self.compute_batch(&variables, &rows);
+        self.values = saved;
+        self.fuzzy_values = saved_fuzzy;
+        let mut values = Vec::with_capacity(results.len());
+        for result in results {
+            match result {
+                Ok(result) => values.push(result.value),
+                Err(InferenceError::NoRuleFired) => values.push(f32::NAN),
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(Surface {
+            x_variable: x_var.to_string(),
+            x: x,
+            y_variable: y_var.map(|y_var| y_var.to_string()),
+            y: y,
+            values: values,
+        })
+    }
+
+    /// Returns `resolution` evenly spaced points over the domain of the variable's universe.
+    fn sweep(&self, variable: &str, resolution: usize) -> Result<Vec<f32>, InferenceError> {
+        let universe = match self.bindings.get(variable) {
+            Some(name) => {
+                self.universes
+                    .get(name)
+                    .ok_or_else(|| InferenceError::UnknownUniverse(name.clone()))?
+            }
+            None => {
+                self.universes
+                    .get(variable)
+                    .ok_or_else(|| InferenceError::UnboundVariable(variable.to_string()))?
+            }
+        };
+        let domain = universe.domain();
+        if domain.is_empty() {
+            return Err(InferenceError::EmptyDomain(universe.name().to_string()));
+        }
+        let min = domain.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = domain.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        if resolution < 2 {
+            return Ok(vec![min; resolution]);
+        }
+        let step = (max - min) / (resolution - 1) as f32;
+        Ok((0..resolution).map(|i| min + step * i as f32).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use functions::MembershipFactory;
+    use inference::{InferenceError, InferenceMachine, InferenceOptions};
+    use rules::RuleSet;
+    use set::UniversalSet;
+    use std::collections::HashMap;
+
+    /// Fan speeds up with the temperature and slows down with the humidity.
+    fn machine() -> InferenceMachine {
+        let mut universes = HashMap::new();
+        for &(name, low, high, max) in &[("temp", "cold", "hot", 40.0),
+                                         ("humidity", "dry", "wet", 100.0),
+                                         ("fan", "slow", "fast", 10.0)] {
+            let mut universe = UniversalSet::new(name.to_string());
+            universe.set_domain((0..101).map(|x| x as f32 * max / 100.0).collect());
+            universe.create_set(low.to_string(),
+                                MembershipFactory::triangular(-max, 0.0, max))
+                    .unwrap();
+            universe.create_set(high.to_string(),
+                                MembershipFactory::triangular(0.0, max, 2.0 * max))
+                    .unwrap();
+            universes.insert(name.to_string(), universe);
+        }
+        let rules = fuzzy_rules! {
+            if temp is cold || humidity is wet => fan is slow;
+            if temp is hot && humidity is dry => fan is fast;
+        };
+        let options = InferenceOptions::mamdani();
+        InferenceMachine::new(RuleSet::new(rules).unwrap(), universes, options)
+    }
+
+    #[test]
+    fn monotone_surface() {
+        let mut machine = machine();
+        machine.set_value("temp", 5.0).unwrap();
+        let mut fixed = HashMap::new();
+        fixed.insert("humidity".to_string(), 20.0);
+        let surface = machine.surface("temp", None, 21, &fixed).unwrap();
+        assert_eq!(surface.x.len(), 21);
+        assert_eq!((surface.x[0], surface.x[20]), (0.0, 40.0));
+        assert!(surface.values.windows(2).all(|pair| pair[0] <= pair[1] + 1e-5));
+        assert!(surface.values[20] > surface.values[0]);
+        assert_eq!(machine.values["temp"], 5.0);
+        assert!(!machine.values.contains_key("humidity"));
+
+        let surface = machine.surface("temp", Some("humidity"), 11, &HashMap::new()).unwrap();
+        assert_eq!(surface.values.len(), 11 * 11);
+        for j in 0..11 {
+            for i in 1..11 {
+                assert!(surface.get(i - 1, j) <= surface.get(i, j) + 1e-5);
+                assert!(surface.get(j, i - 1) + 1e-5 >= surface.get(j, i));
+            }
+        }
+
+        let mut csv = Vec::new();
+        surface.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 1 + 11 * 11);
+        assert!(csv.starts_with("temp,humidity,output\n0,0,"));
+    }
+
+    #[test]
+    fn unknown_variable() {
+        let mut machine = machine();
+        assert_eq!(machine.surface("pressure", None, 10, &HashMap::new()),
+                   Err(InferenceError::UnboundVariable("pressure".to_string())));
+    }
+}
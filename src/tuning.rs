@@ -0,0 +1,642 @@
+//! This module contains tuning of membership functions against data.
+//!
+//! Membership functions are closures, so their parameters can't be read back.
+//! Tuned sets are described by `GaussianTerm`s instead, which recreate the sets
+//! in the machine's universes whenever their parameters change.
+//!
+//! `fit` tunes gaussian terms by gradient descent. `random_search` and `evolution_strategy`
+//! tune any `Tunable` components against a user-supplied objective without derivatives.
+use functions::MembershipFactory;
+use inference::{InferenceError, InferenceMachine};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error::Error;
+use std::f32;
+use std::fmt;
+
+/// Error of `Tunable::set_params`, `random_search` and `evolution_strategy`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TuningError {
+    /// Number of the parameters or bounds differs from the number of the components' parameters.
+    ParamCount {
+        /// Number of the components' parameters.
+        expected: usize,
+        /// Number of the given parameters or bounds.
+        actual: usize,
+    },
+    /// Components can't be installed into the machine.
+    Inference(InferenceError),
+}
+
+impl fmt::Display for TuningError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TuningError::ParamCount { expected, actual } => {
+                write!(f, "expected {} parameters, got {}", expected, actual)
+            }
+            TuningError::Inference(ref error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for TuningError {
+    fn description(&self) -> &str {
+        match *self {
+            TuningError::ParamCount { .. } => "wrong number of parameters",
+            TuningError::Inference(_) => "components can't be installed",
+        }
+    }
+}
+
+impl From<InferenceError> for TuningError {
+    fn from(error: InferenceError) -> TuningError {
+        TuningError::Inference(error)
+    }
+}
+
+/// Gaussian set of the machine's universe with tuned center and width.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GaussianTerm {
+    /// Name of the universe.
+    pub universe: String,
+    /// Name of the set.
+    pub set: String,
+    /// Center of the curve.
+    pub center: f32,
+    /// Width of the curve.
+    pub sigma: f32,
+}
+
+impl GaussianTerm {
+    /// Constructs the term.
+    pub fn new(universe: &str, set: &str, center: f32, sigma: f32) -> GaussianTerm {
+        GaussianTerm {
+            universe: universe.to_string(),
+            set: set.to_string(),
+            center: center,
+            sigma: sigma,
+        }
+    }
+
+    /// Replaces the set in the machine's universe with the gaussian of the term's parameters.
+    ///
+    /// Fails with `InferenceError::UnknownUniverse` if there is no such universe.
+    pub fn install(&self, machine: &mut InferenceMachine) -> Result<(), InferenceError> {
+        let name = &self.universe;
+        let universe = machine.universe_mut(name)
+                              .ok_or_else(|| InferenceError::UnknownUniverse(name.clone()))?;
+        universe.replace_set(self.set.clone(),
+                             MembershipFactory::gaussian(1.0, self.center, self.sigma));
+        Ok(())
+    }
+}
+
+/// Components of the machine with tunable parameters.
+pub trait Tunable {
+    /// Returns the parameters.
+    fn params(&self) -> Vec<f32>;
+    /// Sets the parameters, in the order of `params`.
+    ///
+    /// Fails with `TuningError::ParamCount` if there are more or fewer of them than `params`.
+    fn set_params(&mut self, params: &[f32]) -> Result<(), TuningError>;
+    /// Returns the bounds of every parameter as `(min, max)`.
+    fn bounds(&self) -> Vec<(f32, f32)>;
+    /// Installs the components with the current parameters into the machine.
+    fn install(&self, machine: &mut InferenceMachine) -> Result<(), InferenceError>;
+}
+
+impl Tunable for GaussianTerm {
+    /// Returns the center and the width.
+    fn params(&self) -> Vec<f32> {
+        vec![self.center, self.sigma]
+    }
+
+    /// Sets the center and the width, the width is kept positive.
+    fn set_params(&mut self, params: &[f32]) -> Result<(), TuningError> {
+        if params.len() != 2 {
+            return Err(TuningError::ParamCount {
+                expected: 2,
+                actual: params.len(),
+            });
+        }
+        self.center = params[0];
+        self.sigma = params[1].abs().max(f32::EPSILON);
+        Ok(())
+    }
+
+    fn bounds(&self) -> Vec<(f32, f32)> {
+        vec![(f32::NEG_INFINITY, f32::INFINITY), (f32::EPSILON, f32::INFINITY)]
+    }
+
+    fn install(&self, machine: &mut InferenceMachine) -> Result<(), InferenceError> {
+        GaussianTerm::install(self, machine)
+    }
+}
+
+/// Parameters of the components are concatenated.
+impl<T: Tunable> Tunable for Vec<T> {
+    fn params(&self) -> Vec<f32> {
+        self.iter().flat_map(|component| component.params()).collect()
+    }
+
+    fn set_params(&mut self, params: &[f32]) -> Result<(), TuningError> {
+        let counts = self.iter().map(|component| component.params().len()).collect::<Vec<_>>();
+        let expected = counts.iter().sum();
+        if params.len() != expected {
+            return Err(TuningError::ParamCount {
+                expected: expected,
+                actual: params.len(),
+            });
+        }
+        let mut offset = 0;
+        for (component, count) in self.iter_mut().zip(counts) {
+            component.set_params(&params[offset..offset + count])?;
+            offset += count;
+        }
+        Ok(())
+    }
+
+    fn bounds(&self) -> Vec<(f32, f32)> {
+        self.iter().flat_map(|component| component.bounds()).collect()
+    }
+
+    fn install(&self, machine: &mut InferenceMachine) -> Result<(), InferenceError> {
+        for component in self {
+            component.install(machine)?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of `random_search` and `evolution_strategy`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchReport {
+    /// Parameters with the lowest objective.
+    pub best: Vec<f32>,
+    /// Lowest objective.
+    pub value: f32,
+    /// Lowest objective after each iteration or generation.
+    pub history: Vec<f32>,
+}
+
+/// Settings of `evolution_strategy`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EsConfig {
+    /// Number of parents kept between generations.
+    pub mu: usize,
+    /// Number of offspring in each generation.
+    pub lambda: usize,
+    /// Number of generations.
+    pub generations: usize,
+    /// Standard deviation of mutations relative to the width of the bounds.
+    pub sigma: f32,
+    /// Seed of the random numbers.
+    pub seed: u64,
+}
+
+impl Default for EsConfig {
+    fn default() -> EsConfig {
+        EsConfig {
+            mu: 5,
+            lambda: 20,
+            generations: 50,
+            sigma: 0.1,
+            seed: 1,
+        }
+    }
+}
+
+/// Minimizes the objective by sampling parameters uniformly within the bounds.
+///
+/// The parameters with the lowest objective are set to the components and installed.
+/// Bounds must be finite, one per parameter of the components,
+/// otherwise fails with `TuningError::ParamCount`.
+pub fn random_search<T, F>(objective: F,
+                           machine: &mut InferenceMachine,
+                           components: &mut T,
+                           bounds: &[(f32, f32)],
+                           iterations: usize,
+                           seed: u64)
+                           -> Result<SearchReport, TuningError>
+    where T: Tunable,
+          F: Fn(&mut InferenceMachine) -> f32
+{
+    check_bounds(components, bounds)?;
+    let mut random = Random::new(seed);
+    let mut best = (components.params(), f32::INFINITY);
+    let mut history = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let params = bounds.iter()
+                           .map(|&(min, max)| random.uniform(min, max))
+                           .collect::<Vec<_>>();
+        let value = evaluate(&objective, machine, components, &params)?;
+        if value < best.1 {
+            best = (params, value);
+        }
+        history.push(best.1);
+    }
+    finish(machine, components, best, history)
+}
+
+/// Minimizes the objective with the (μ+λ) evolution strategy.
+///
+/// Parents are sampled uniformly within the bounds. Each generation every parent in turn
+/// produces an offspring with gaussian mutations clamped to the bounds, then the best `mu`
+/// of parents and offspring become the next parents.
+/// The parameters with the lowest objective are set to the components and installed.
+/// Bounds must be finite, one per parameter of the components,
+/// otherwise fails with `TuningError::ParamCount`.
+pub fn evolution_strategy<T, F>(objective: F,
+                                machine: &mut InferenceMachine,
+                                components: &mut T,
+                                bounds: &[(f32, f32)],
+                                config: EsConfig)
+                                -> Result<SearchReport, TuningError>
+    where T: Tunable,
+          F: Fn(&mut InferenceMachine) -> f32
+{
+    check_bounds(components, bounds)?;
+    let mut random = Random::new(config.seed);
+    let mut parents = Vec::with_capacity(config.mu + config.lambda);
+    for _ in 0..config.mu.max(1) {
+        let params = bounds.iter()
+                           .map(|&(min, max)| random.uniform(min, max))
+                           .collect::<Vec<_>>();
+        let value = evaluate(&objective, machine, components, &params)?;
+        parents.push((params, value));
+    }
+    let mut history = Vec::with_capacity(config.generations);
+    for _ in 0..config.generations {
+        let mu = parents.len();
+        for i in 0..config.lambda {
+            let params = parents[i % mu]
+                             .0
+                             .iter()
+                             .zip(bounds)
+                             .map(|(&x, &(min, max))| {
+                                 let x = x + random.normal() * config.sigma * (max - min);
+                                 x.max(min).min(max)
+                             })
+                             .collect::<Vec<_>>();
+            let value = evaluate(&objective, machine, components, &params)?;
+            parents.push((params, value));
+        }
+        parents.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+        parents.truncate(mu);
+        history.push(parents[0].1);
+    }
+    let best = parents.swap_remove(0);
+    finish(machine, components, best, history)
+}
+
+/// Checks that there is one bound per parameter of the components.
+fn check_bounds<T: Tunable>(components: &T, bounds: &[(f32, f32)]) -> Result<(), TuningError> {
+    let expected = components.params().len();
+    if bounds.len() != expected {
+        return Err(TuningError::ParamCount {
+            expected: expected,
+            actual: bounds.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Returns the objective of the parameters, NaN is replaced with infinity.
+fn evaluate<T, F>(objective: &F,
+                  machine: &mut InferenceMachine,
+                  components: &mut T,
+                  params: &[f32])
+                  -> Result<f32, TuningError>
+    where T: Tunable,
+          F: Fn(&mut InferenceMachine) -> f32
+{
+    components.set_params(params)?;
+    components.install(machine)?;
+    let value = objective(machine);
+    Ok(if value.is_nan() { f32::INFINITY } else { value })
+}
+
+/// Installs the best parameters and makes the report.
+fn finish<T: Tunable>(machine: &mut InferenceMachine,
+                      components: &mut T,
+                      best: (Vec<f32>, f32),
+                      history: Vec<f32>)
+                      -> Result<SearchReport, TuningError> {
+    components.set_params(&best.0)?;
+    components.install(machine)?;
+    Ok(SearchReport {
+        best: best.0,
+        value: best.1,
+        history: history,
+    })
+}
+
+/// Xorshift generator of random numbers.
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    fn new(seed: u64) -> Random {
+        Random { state: seed.max(1) }
+    }
+
+    /// Returns a number uniformly distributed in [0, 1).
+    fn next(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Returns a number uniformly distributed in [min, max).
+    fn uniform(&mut self, min: f32, max: f32) -> f32 {
+        min + self.next() * (max - min)
+    }
+
+    /// Returns a standard normally distributed number (Box-Muller transform).
+    fn normal(&mut self) -> f32 {
+        let u = 1.0 - self.next();
+        let v = self.next();
+        (-2.0 * u.ln()).sqrt() * (2.0 * f32::consts::PI * v).cos()
+    }
+}
+
+/// Settings of `fit`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FitConfig {
+    /// Maximum step along the negative gradient.
+    ///
+    /// The step is halved while it doesn't decrease the loss and doubled after it does.
+    pub learning_rate: f32,
+    /// Maximum number of epochs.
+    pub epochs: usize,
+    /// Fitting stops once an epoch decreases the loss by less than this.
+    pub min_improvement: f32,
+    /// Step of the finite differences approximating the gradient.
+    pub delta: f32,
+}
+
+impl Default for FitConfig {
+    fn default() -> FitConfig {
+        FitConfig {
+            learning_rate: 0.1,
+            epochs: 100,
+            min_improvement: 1e-6,
+            delta: 1e-2,
+        }
+    }
+}
+
+/// Result of `fit`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FitReport {
+    /// Mean squared error before fitting and after each epoch.
+    pub losses: Vec<f32>,
+    /// Root mean squared error after fitting.
+    pub rmse: f32,
+}
+
+/// Tunes centers and widths of the gaussian terms to fit the machine's outputs to the targets.
+///
+/// Batch gradient descent on the mean squared error is used, the gradient is approximated with
+/// central differences. A step that doesn't decrease the loss is halved until it does,
+/// so the loss never grows. Fitting stops early if no step decreases the loss.
+/// Terms are updated in place and installed into the machine.
+/// Values of the machine are restored afterwards.
+pub fn fit(machine: &mut InferenceMachine,
+           terms: &mut [GaussianTerm],
+           data: &[(HashMap<String, f32>, f32)],
+           config: FitConfig)
+           -> Result<FitReport, InferenceError> {
+    let saved = machine.values.clone();
+    let saved_fuzzy = machine.fuzzy_values.clone();
+    let report = fit_values(machine, terms, data, &config);
+    machine.values = saved;
+    machine.fuzzy_values = saved_fuzzy;
+    report
+}
+
+fn fit_values(machine: &mut InferenceMachine,
+              terms: &mut [GaussianTerm],
+              data: &[(HashMap<String, f32>, f32)],
+              config: &FitConfig)
+              -> Result<FitReport, InferenceError> {
+    for term in terms.iter() {
+        term.install(machine)?;
+    }
+    let mut loss = mse(machine, data)?;
+    let mut losses = vec![loss];
+    let mut rate = config.learning_rate;
+    for _ in 0..config.epochs {
+        let mut gradient = Vec::with_capacity(2 * terms.len());
+        for i in 0..2 * terms.len() {
+            let original = param(&terms[i / 2], i);
+            *param_mut(&mut terms[i / 2], i) = original + config.delta;
+            terms[i / 2].install(machine)?;
+            let forward = mse(machine, data)?;
+            *param_mut(&mut terms[i / 2], i) = original - config.delta;
+            terms[i / 2].install(machine)?;
+            let backward = mse(machine, data)?;
+            *param_mut(&mut terms[i / 2], i) = original;
+            terms[i / 2].install(machine)?;
+            gradient.push((forward - backward) / (2.0 * config.delta));
+        }
+
+        let start = terms.to_vec();
+        let mut next = loss;
+        while rate > f32::EPSILON {
+            for (i, slope) in gradient.iter().enumerate() {
+                *param_mut(&mut terms[i / 2], i) = param(&start[i / 2], i) - rate * slope;
+            }
+            for term in terms.iter_mut() {
+                term.sigma = term.sigma.abs().max(f32::EPSILON);
+                term.install(machine)?;
+            }
+            next = mse(machine, data)?;
+            if next < loss {
+                break;
+            }
+            rate /= 2.0;
+        }
+        if next >= loss {
+            terms.clone_from_slice(&start);
+            for term in terms.iter() {
+                term.install(machine)?;
+            }
+            break;
+        }
+        let improvement = loss - next;
+        loss = next;
+        rate = (rate * 2.0).min(config.learning_rate);
+        losses.push(loss);
+        if improvement < config.min_improvement {
+            break;
+        }
+    }
+    Ok(FitReport {
+        losses: losses,
+        rmse: loss.sqrt(),
+    })
+}
+
+/// Returns the `i`-th parameter of the term pair, even ones are centers and odd ones are widths.
+fn param(term: &GaussianTerm, i: usize) -> f32 {
+    if i.is_multiple_of(2) { term.center } else { term.sigma }
+}
+
+fn param_mut(term: &mut GaussianTerm, i: usize) -> &mut f32 {
+    if i.is_multiple_of(2) {
+        &mut term.center
+    } else {
+        &mut term.sigma
+    }
+}
+
+/// Returns the mean squared error of the machine's outputs in `RuleSet::result_universe`.
+fn mse(machine: &mut InferenceMachine,
+       data: &[(HashMap<String, f32>, f32)])
+       -> Result<f32, InferenceError> {
+    let output = machine.rules.result_universe().to_string();
+    let mut sum = 0.0;
+    for &(ref inputs, target) in data {
+        machine.update(inputs)?;
+        sum += (machine.compute_for(&output)?.value - target).powi(2);
+    }
+    Ok(sum / data.len().max(1) as f32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use inference::InferenceOptions;
+    use rules::RuleSet;
+    use set::UniversalSet;
+
+    fn machine(cold: &GaussianTerm, hot: &GaussianTerm) -> InferenceMachine {
+        let mut temp = UniversalSet::new("temp".to_string());
+        temp.set_domain((0..41).map(|x| x as f32).collect());
+        let mut fan = UniversalSet::new("fan".to_string());
+        fan.set_domain((0..101).map(|x| x as f32 / 10.0).collect());
+        fan.create_set("slow".to_string(), MembershipFactory::triangular(-5.0, 0.0, 6.0))
+           .unwrap();
+        fan.create_set("fast".to_string(), MembershipFactory::triangular(4.0, 10.0, 15.0))
+           .unwrap();
+        let mut universes = HashMap::new();
+        universes.insert("temp".to_string(), temp);
+        universes.insert("fan".to_string(), fan);
+        let rules = fuzzy_rules! {
+            if temp is cold => fan is slow;
+            if temp is hot => fan is fast;
+        };
+        let options = InferenceOptions::mamdani();
+        let mut machine = InferenceMachine::new(RuleSet::new(rules).unwrap(), universes, options);
+        cold.install(&mut machine).unwrap();
+        hot.install(&mut machine).unwrap();
+        machine
+    }
+
+    #[test]
+    fn fit_perturbed_terms() {
+        let cold = GaussianTerm::new("temp", "cold", 5.0, 8.0);
+        let hot = GaussianTerm::new("temp", "hot", 35.0, 8.0);
+        let mut reference = machine(&cold, &hot);
+        let data = (0..11)
+                       .map(|i| {
+                           let mut values = HashMap::new();
+                           values.insert("temp".to_string(), i as f32 * 4.0);
+                           reference.update(&values).unwrap();
+                           (values, reference.compute_for("fan").unwrap().value)
+                       })
+                       .collect::<Vec<_>>();
+
+        let mut terms = vec![GaussianTerm::new("temp", "cold", 10.0, 12.0),
+                             GaussianTerm::new("temp", "hot", 28.0, 5.0)];
+        let mut machine = machine(&terms[0], &terms[1]);
+        let config = FitConfig {
+            learning_rate: 10.0,
+            epochs: 50,
+            ..FitConfig::default()
+        };
+        let report = fit(&mut machine, &mut terms, &data, config).unwrap();
+        assert!(report.losses.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(report.rmse < 0.1, "{:?}", report);
+        assert!(report.rmse < report.losses[0].sqrt() / 10.0);
+        assert!(machine.values.is_empty());
+    }
+
+    /// Machine with the center of "hot" at 30 and 11 examples of it.
+    fn hot_at_30() -> (InferenceMachine, Vec<(HashMap<String, f32>, f32)>) {
+        let cold = GaussianTerm::new("temp", "cold", 5.0, 8.0);
+        let mut machine = machine(&cold, &GaussianTerm::new("temp", "hot", 30.0, 8.0));
+        let data = (0..11)
+                       .map(|i| {
+                           let mut values = HashMap::new();
+                           values.insert("temp".to_string(), i as f32 * 4.0);
+                           machine.update(&values).unwrap();
+                           (values, machine.compute_for("fan").unwrap().value)
+                       })
+                       .collect::<Vec<_>>();
+        (machine, data)
+    }
+
+    #[test]
+    fn derivative_free_search() {
+        let (mut machine, data) = hot_at_30();
+        let objective = |machine: &mut InferenceMachine| mse(machine, &data).unwrap();
+        // Width of "hot" is fixed, so the only parameter is its center.
+        let bounds = [(10.0, 40.0), (8.0, 8.0)];
+
+        let mut hot = GaussianTerm::new("temp", "hot", 20.0, 8.0);
+        let report = random_search(objective, &mut machine, &mut hot, &bounds, 200, 7).unwrap();
+        assert!(report.history.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!((hot.center - 30.0).abs() < 0.5, "{:?}", report);
+        assert_eq!(hot.params(), report.best);
+
+        let mut hot = vec![GaussianTerm::new("temp", "hot", 20.0, 8.0)];
+        let report = evolution_strategy(objective,
+                                        &mut machine,
+                                        &mut hot,
+                                        &bounds,
+                                        EsConfig {
+                                            lambda: 10,
+                                            generations: 20,
+                                            ..EsConfig::default()
+                                        })
+                         .unwrap();
+        assert!((hot[0].center - 30.0).abs() < 0.05, "{:?}", report);
+        assert!(report.value < 1e-4);
+    }
+
+    #[test]
+    fn param_count() {
+        let mut terms = vec![GaussianTerm::new("temp", "cold", 5.0, 8.0),
+                             GaussianTerm::new("temp", "hot", 30.0, 8.0)];
+        assert_eq!(terms[0].set_params(&[1.0]),
+                   Err(TuningError::ParamCount {
+                       expected: 2,
+                       actual: 1,
+                   }));
+        assert_eq!(terms.set_params(&[1.0, 2.0, 3.0]),
+                   Err(TuningError::ParamCount {
+                       expected: 4,
+                       actual: 3,
+                   }));
+        assert_eq!(terms.params(), vec![5.0, 8.0, 30.0, 8.0]);
+        terms.set_params(&[1.0, 2.0, 3.0, 4.0]).unwrap();
+        assert_eq!(terms.params(), vec![1.0, 2.0, 3.0, 4.0]);
+
+        let (mut machine, _) = hot_at_30();
+        let error = Some(TuningError::ParamCount {
+            expected: 4,
+            actual: 1,
+        });
+        let bounds = [(0.0, 40.0)];
+        let objective = |_: &mut InferenceMachine| 0.0;
+        let report = random_search(objective, &mut machine, &mut terms, &bounds, 10, 1);
+        assert_eq!(report.err(), error);
+        let report =
+            evolution_strategy(objective, &mut machine, &mut terms, &bounds, EsConfig::default());
+        assert_eq!(report.err(), error);
+    }
+}
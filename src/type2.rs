@@ -0,0 +1,484 @@
+//! This module contains interval type-2 fuzzy sets and their inference.
+//!
+//! Membership in `IntervalType2Set` is an interval between the lower and the upper membership
+//! functions, the region between them is the footprint of uncertainty.
+//! Expressions give interval firing strengths, `Type2Machine` aggregates the rules' results
+//! over the domain of the output universe, reduces them to the interval of centroids with
+//! the Karnik-Mendel algorithm and returns its midpoint as the crisp value.
+//! The module doesn't share the type-1 pipeline of `inference`.
+use functions::{MembershipFactory, MembershipFunction};
+use inference::InferenceError;
+use std::collections::HashMap;
+use std::f32;
+use std::rc::Rc;
+
+/// Fuzzy set with interval memberships.
+pub struct IntervalType2Set {
+    /// Name of the set.
+    pub name: String,
+    lower: Box<MembershipFunction>,
+    upper: Box<MembershipFunction>,
+}
+
+impl IntervalType2Set {
+    /// Constructs the set bounded by the lower and upper membership functions.
+    pub fn new(name: &str,
+               lower: Box<MembershipFunction>,
+               upper: Box<MembershipFunction>)
+               -> IntervalType2Set {
+        IntervalType2Set {
+            name: name.to_string(),
+            lower: lower,
+            upper: upper,
+        }
+    }
+
+    /// Constructs the set with zero footprint of uncertainty, equal to the type-1 set.
+    pub fn from_type1(name: &str, membership: Box<MembershipFunction>) -> IntervalType2Set {
+        let membership: Rc<MembershipFunction> = membership.into();
+        let upper = membership.clone();
+        IntervalType2Set::new(name,
+                              Box::new(move |x| membership(x)),
+                              Box::new(move |x| upper(x)))
+    }
+
+    /// Returns the interval of memberships of `x`, clamped into [0, 1].
+    pub fn membership(&self, x: f32) -> (f32, f32) {
+        let lower = clamp((*self.lower)(x));
+        let upper = clamp((*self.upper)(x));
+        (lower.min(upper), lower.max(upper))
+    }
+}
+
+/// Clamps the membership into [0, 1], NaN gives 0.
+fn clamp(mem: f32) -> f32 {
+    if mem.is_nan() { 0.0 } else { mem.max(0.0).min(1.0) }
+}
+
+/// Defines methods to create interval type-2 sets from type-1 shapes blurred by a parameter.
+pub struct Type2Factory;
+
+impl Type2Factory {
+    /// Creates triangular set, whose feet are uncertain by `blur`.
+    ///
+    /// The upper function's feet are `blur` further from the peak, the lower one's are nearer.
+    pub fn triangular(name: &str, a: f32, b: f32, c: f32, blur: f32) -> IntervalType2Set {
+        let lower = MembershipFactory::triangular((a + blur).min(b), b, (c - blur).max(b));
+        IntervalType2Set::new(name,
+                              lower,
+                              MembershipFactory::triangular(a - blur, b, c + blur))
+    }
+
+    /// Creates gaussian set, whose width is uncertain by `blur`.
+    pub fn gaussian(name: &str, center: f32, sigma: f32, blur: f32) -> IntervalType2Set {
+        IntervalType2Set::new(name,
+                              MembershipFactory::gaussian(1.0,
+                                                          center,
+                                                          (sigma - blur).max(f32::EPSILON)),
+                              MembershipFactory::gaussian(1.0, center, sigma + blur))
+    }
+}
+
+/// Universe of interval type-2 sets.
+pub struct Type2Universe {
+    /// Name of the universe.
+    pub name: String,
+    /// Points of the universe, the output is reduced over them.
+    pub domain: Vec<f32>,
+    /// Sets of the universe.
+    pub sets: HashMap<String, IntervalType2Set>,
+}
+
+impl Type2Universe {
+    /// Constructs the empty universe.
+    pub fn new(name: &str, domain: Vec<f32>) -> Type2Universe {
+        Type2Universe {
+            name: name.to_string(),
+            domain: domain,
+            sets: HashMap::new(),
+        }
+    }
+
+    /// Adds the set, replacing the one with the same name.
+    pub fn add_set(&mut self, set: IntervalType2Set) {
+        self.sets.insert(set.name.clone(), set);
+    }
+}
+
+/// Values of the variables and universes, in which expressions are evaluated.
+pub struct Type2Context<'a> {
+    /// Values of the input variables.
+    pub values: &'a HashMap<String, f32>,
+    /// Universes, variables are in the universes named after them.
+    pub universes: &'a HashMap<String, Type2Universe>,
+}
+
+/// Expression with the interval firing strength.
+pub trait Type2Expression {
+    /// Returns the lower and upper firing strengths.
+    fn eval(&self, context: &Type2Context) -> Result<(f32, f32), InferenceError>;
+}
+
+/// Membership of the variable in the set.
+pub struct Is {
+    variable: String,
+    set: String,
+}
+
+impl Is {
+    /// Constructs the expression "`variable` is `set`".
+    pub fn new(variable: &str, set: &str) -> Is {
+        Is {
+            variable: variable.to_string(),
+            set: set.to_string(),
+        }
+    }
+}
+
+impl Type2Expression for Is {
+    fn eval(&self, context: &Type2Context) -> Result<(f32, f32), InferenceError> {
+        let value = *context.values
+                            .get(&self.variable)
+                            .ok_or_else(|| InferenceError::MissingValue(self.variable.clone()))?;
+        let universe = context.universes
+                              .get(&self.variable)
+                              .ok_or_else(|| {
+                                  InferenceError::UnboundVariable(self.variable.clone())
+                              })?;
+        let set = universe.sets
+                          .get(&self.set)
+                          .ok_or_else(|| {
+                              InferenceError::UnknownSet {
+                                  universe: universe.name.clone(),
+                                  set: self.set.clone(),
+                              }
+                          })?;
+        Ok(set.membership(value))
+    }
+}
+
+/// Minimum of both bounds.
+pub struct And<L: Type2Expression, R: Type2Expression> {
+    left: L,
+    right: R,
+}
+
+impl<L: Type2Expression, R: Type2Expression> And<L, R> {
+    /// Constructs the conjunction.
+    pub fn new(left: L, right: R) -> And<L, R> {
+        And {
+            left: left,
+            right: right,
+        }
+    }
+}
+
+impl<L: Type2Expression, R: Type2Expression> Type2Expression for And<L, R> {
+    fn eval(&self, context: &Type2Context) -> Result<(f32, f32), InferenceError> {
+        let (left, right) = (self.left.eval(context)?, self.right.eval(context)?);
+        Ok((left.0.min(right.0), left.1.min(right.1)))
+    }
+}
+
+/// Maximum of both bounds.
+pub struct Or<L: Type2Expression, R: Type2Expression> {
+    left: L,
+    right: R,
+}
+
+impl<L: Type2Expression, R: Type2Expression> Or<L, R> {
+    /// Constructs the disjunction.
+    pub fn new(left: L, right: R) -> Or<L, R> {
+        Or {
+            left: left,
+            right: right,
+        }
+    }
+}
+
+impl<L: Type2Expression, R: Type2Expression> Type2Expression for Or<L, R> {
+    fn eval(&self, context: &Type2Context) -> Result<(f32, f32), InferenceError> {
+        let (left, right) = (self.left.eval(context)?, self.right.eval(context)?);
+        Ok((left.0.max(right.0), left.1.max(right.1)))
+    }
+}
+
+/// Complement of the interval: `[1 - upper, 1 - lower]`.
+pub struct Not<E: Type2Expression> {
+    expression: E,
+}
+
+impl<E: Type2Expression> Not<E> {
+    /// Constructs the negation.
+    pub fn new(expression: E) -> Not<E> {
+        Not { expression: expression }
+    }
+}
+
+impl<E: Type2Expression> Type2Expression for Not<E> {
+    fn eval(&self, context: &Type2Context) -> Result<(f32, f32), InferenceError> {
+        let (lower, upper) = self.expression.eval(context)?;
+        Ok((1.0 - upper, 1.0 - lower))
+    }
+}
+
+/// Rule "if `condition` then output is `set`".
+pub struct Type2Rule {
+    condition: Box<Type2Expression>,
+    set: String,
+}
+
+impl Type2Rule {
+    /// Constructs the rule.
+    pub fn new<E: 'static + Type2Expression>(condition: E, set: &str) -> Type2Rule {
+        Type2Rule {
+            condition: Box::new(condition),
+            set: set.to_string(),
+        }
+    }
+}
+
+/// Result of the type-2 inference.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Type2Result {
+    /// Interval of centroids of the output's footprint of uncertainty.
+    pub interval: (f32, f32),
+    /// Midpoint of the interval.
+    pub value: f32,
+}
+
+/// Inference over interval type-2 sets.
+pub struct Type2Machine {
+    /// Rules, their results are in the output universe.
+    pub rules: Vec<Type2Rule>,
+    /// Universes by name.
+    pub universes: HashMap<String, Type2Universe>,
+    /// Name of the output universe.
+    pub output: String,
+    /// Values of the input variables.
+    pub values: HashMap<String, f32>,
+}
+
+impl Type2Machine {
+    /// Constructs the machine.
+    pub fn new(rules: Vec<Type2Rule>,
+               universes: HashMap<String, Type2Universe>,
+               output: &str)
+               -> Type2Machine {
+        Type2Machine {
+            rules: rules,
+            universes: universes,
+            output: output.to_string(),
+            values: HashMap::new(),
+        }
+    }
+
+    /// Sets the value of the input variable.
+    ///
+    /// Fails with `InferenceError::InvalidValue` if the value is NaN or infinite.
+    pub fn set_value(&mut self, variable: &str, value: f32) -> Result<(), InferenceError> {
+        if !value.is_finite() {
+            return Err(InferenceError::InvalidValue(variable.to_string()));
+        }
+        self.values.insert(variable.to_string(), value);
+        Ok(())
+    }
+
+    /// Computes the inference.
+    ///
+    /// Every rule clips both bounds of its set with its firing strengths (minimum),
+    /// results are united (maximum) at each point of the output's domain.
+    /// Points of the domain that are NaN or infinite are skipped.
+    /// Fails with `InferenceError::NoRuleFired` if the upper bound of the result is zero.
+    pub fn compute(&self) -> Result<Type2Result, InferenceError> {
+        let context = Type2Context {
+            values: &self.values,
+            universes: &self.universes,
+        };
+        let output = self.universes
+                         .get(&self.output)
+                         .ok_or_else(|| InferenceError::UnknownUniverse(self.output.clone()))?;
+        let mut points: Vec<f32> = output.domain
+                                         .iter()
+                                         .cloned()
+                                         .filter(|x| x.is_finite())
+                                         .collect();
+        points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mut lower = vec![0.0; points.len()];
+        let mut upper = vec![0.0; points.len()];
+        for rule in &self.rules {
+            let (fire_lower, fire_upper) = rule.condition.eval(&context)?;
+            let set = output.sets
+                            .get(&rule.set)
+                            .ok_or_else(|| {
+                                InferenceError::UnknownSet {
+                                    universe: output.name.clone(),
+                                    set: rule.set.clone(),
+                                }
+                            })?;
+            for (i, &x) in points.iter().enumerate() {
+                let (mem_lower, mem_upper) = set.membership(x);
+                lower[i] = f32::max(lower[i], mem_lower.min(fire_lower));
+                upper[i] = f32::max(upper[i], mem_upper.min(fire_upper));
+            }
+        }
+        let interval = karnik_mendel(&points, &lower, &upper).ok_or(InferenceError::NoRuleFired)?;
+        Ok(Type2Result {
+            interval: interval,
+            value: (interval.0 + interval.1) / 2.0,
+        })
+    }
+}
+
+/// Reduces the footprint of uncertainty to the interval of centroids `(left, right)`.
+///
+/// `points` must be sorted ascending, `lower` and `upper` are memberships at them.
+/// Returns `None` if the upper memberships sum to zero.
+pub fn karnik_mendel(points: &[f32], lower: &[f32], upper: &[f32]) -> Option<(f32, f32)> {
+    if upper.iter().fold(0.0, |sum, &mem| sum + mem) <= 0.0 {
+        return None;
+    }
+    Some((km_endpoint(points, lower, upper, true), km_endpoint(points, lower, upper, false)))
+}
+
+/// Computes the left or the right endpoint of the centroid interval.
+///
+/// The left endpoint takes upper memberships up to the switch point and lower ones after it,
+/// the right endpoint does the opposite. The switch point is moved until the centroid settles.
+fn km_endpoint(points: &[f32], lower: &[f32], upper: &[f32], left: bool) -> f32 {
+    let centroid = |weights: &mut Iterator<Item = f32>| {
+        let (weighted, total) = points.iter()
+                                      .zip(weights)
+                                      .fold((0.0, 0.0), |(weighted, total), (&x, w)| {
+                                          (weighted + x * w, total + w)
+                                      });
+        if total > 0.0 { Some(weighted / total) } else { None }
+    };
+    let mut current = centroid(&mut lower.iter().zip(upper).map(|(l, u)| (l + u) / 2.0))
+                          .unwrap_or(points[0]);
+    for _ in 0..points.len() + 1 {
+        let switch = points.iter().position(|&x| x > current).unwrap_or(points.len());
+        let next = centroid(&mut (0..points.len()).map(|i| {
+                                if (i < switch) == left { upper[i] } else { lower[i] }
+                            }))
+                       .unwrap_or(current);
+        if (next - current).abs() <= f32::EPSILON * current.abs().max(1.0) {
+            return next;
+        }
+        current = next;
+    }
+    current
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use inference::{InferenceMachine, InferenceOptions};
+    use rules::RuleSet;
+    use set::UniversalSet;
+
+    fn type2_machine(blur: f32) -> Type2Machine {
+        let mut temp = Type2Universe::new("temp", Vec::new());
+        temp.add_set(Type2Factory::triangular("cold", -20.0, 0.0, 25.0, blur));
+        temp.add_set(Type2Factory::triangular("hot", 15.0, 40.0, 60.0, blur));
+        let mut fan = Type2Universe::new("fan", (0..101).map(|x| x as f32 / 10.0).collect());
+        fan.add_set(Type2Factory::triangular("slow", -5.0, 0.0, 6.0, blur));
+        fan.add_set(Type2Factory::triangular("fast", 4.0, 10.0, 15.0, blur));
+        let mut universes = HashMap::new();
+        universes.insert("temp".to_string(), temp);
+        universes.insert("fan".to_string(), fan);
+        let rules = vec![Type2Rule::new(Is::new("temp", "cold"), "slow"),
+                         Type2Rule::new(Is::new("temp", "hot"), "fast")];
+        Type2Machine::new(rules, universes, "fan")
+    }
+
+    fn type1_machine() -> InferenceMachine {
+        let mut temp = UniversalSet::new("temp".to_string());
+        temp.create_set("cold".to_string(), MembershipFactory::triangular(-20.0, 0.0, 25.0))
+            .unwrap();
+        temp.create_set("hot".to_string(), MembershipFactory::triangular(15.0, 40.0, 60.0))
+            .unwrap();
+        let mut fan = UniversalSet::new("fan".to_string());
+        fan.set_domain((0..101).map(|x| x as f32 / 10.0).collect());
+        fan.create_set("slow".to_string(), MembershipFactory::triangular(-5.0, 0.0, 6.0))
+           .unwrap();
+        fan.create_set("fast".to_string(), MembershipFactory::triangular(4.0, 10.0, 15.0))
+           .unwrap();
+        let mut universes = HashMap::new();
+        universes.insert("temp".to_string(), temp);
+        universes.insert("fan".to_string(), fan);
+        let rules = fuzzy_rules! {
+            if temp is cold => fan is slow;
+            if temp is hot => fan is fast;
+        };
+        let options = InferenceOptions::mamdani();
+        InferenceMachine::new(RuleSet::new(rules).unwrap(), universes, options)
+    }
+
+    #[test]
+    fn zero_footprint() {
+        let mut type1 = type1_machine();
+        let mut type2 = type2_machine(0.0);
+        for i in 0..9 {
+            let temp = i as f32 * 5.0;
+            type1.set_value("temp", temp).unwrap();
+            type2.set_value("temp", temp).unwrap();
+            let expected = type1.compute_for("fan").unwrap().value;
+            let result = type2.compute().unwrap();
+            assert!((result.interval.0 - expected).abs() < 1e-3, "{:?} {}", result, expected);
+            assert!((result.interval.1 - expected).abs() < 1e-3, "{:?} {}", result, expected);
+        }
+    }
+
+    #[test]
+    fn interval_brackets_centroid() {
+        let mut type1 = type1_machine();
+        let mut type2 = type2_machine(2.0);
+        for i in 0..9 {
+            let temp = i as f32 * 5.0;
+            type1.set_value("temp", temp).unwrap();
+            type2.set_value("temp", temp).unwrap();
+            let expected = type1.compute_for("fan").unwrap().value;
+            let (left, right) = type2.compute().unwrap().interval;
+            assert!(left <= expected + 1e-4 && expected <= right + 1e-4,
+                    "{} not in [{}, {}]",
+                    expected,
+                    left,
+                    right);
+            assert!(left < right);
+        }
+    }
+
+    #[test]
+    fn non_finite_points_are_skipped() {
+        let mut type2 = type2_machine(2.0);
+        type2.set_value("temp", 20.0).unwrap();
+        let expected = type2.compute().unwrap().interval;
+        type2.universes.get_mut("fan").unwrap().domain.extend(&[f32::NAN, f32::INFINITY]);
+        assert_eq!(type2.compute().unwrap().interval, expected);
+    }
+
+    #[test]
+    fn interval_operations() {
+        let mut universes = HashMap::new();
+        let mut temp = Type2Universe::new("temp", Vec::new());
+        temp.add_set(Type2Factory::triangular("warm", 0.0, 10.0, 20.0, 2.0));
+        temp.add_set(Type2Factory::gaussian("hot", 20.0, 5.0, 1.0));
+        universes.insert("temp".to_string(), temp);
+        let mut values = HashMap::new();
+        values.insert("temp".to_string(), 15.0);
+        let context = Type2Context {
+            values: &values,
+            universes: &universes,
+        };
+        let warm = Is::new("temp", "warm").eval(&context).unwrap();
+        let hot = Is::new("temp", "hot").eval(&context).unwrap();
+        assert!(warm.0 < warm.1 && hot.0 < hot.1);
+        let and = And::new(Is::new("temp", "warm"), Is::new("temp", "hot")).eval(&context);
+        assert_eq!(and.unwrap(), (warm.0.min(hot.0), warm.1.min(hot.1)));
+        let or = Or::new(Is::new("temp", "warm"), Is::new("temp", "hot")).eval(&context);
+        assert_eq!(or.unwrap(), (warm.0.max(hot.0), warm.1.max(hot.1)));
+        let not = Not::new(Is::new("temp", "warm")).eval(&context).unwrap();
+        assert_eq!(not, (1.0 - warm.1, 1.0 - warm.0));
+    }
+}
@@ -0,0 +1,94 @@
+//! This module contains linguistic variables.
+//!
+//! `LinguisticVariable` is the universal set along with its role in the system,
+//! the name of the variable bound to it, the unit of measurement and the default value.
+use set::UniversalSet;
+
+/// Role of the linguistic variable in the inference system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Value of the variable is given to the system.
+    Input,
+    /// Rules' results in the universe are aggregated and defuzzificated.
+    Output,
+}
+
+/// Universal set with the metadata of the variable bound to it.
+#[derive(Debug, Clone)]
+pub struct LinguisticVariable {
+    /// Name of the variable.
+    name: String,
+    /// Role of the variable.
+    role: Role,
+    /// Universe of the variable.
+    universe: UniversalSet,
+    /// Unit of measurement.
+    unit: Option<String>,
+    /// Value of the input variable used until another value is set.
+    default: Option<f32>,
+}
+
+impl LinguisticVariable {
+    /// Constructs the input variable with given name in the universe.
+    pub fn input(name: &str, universe: UniversalSet) -> LinguisticVariable {
+        LinguisticVariable::new(name, Role::Input, universe)
+    }
+
+    /// Constructs the output variable with given name in the universe.
+    pub fn output(name: &str, universe: UniversalSet) -> LinguisticVariable {
+        LinguisticVariable::new(name, Role::Output, universe)
+    }
+
+    /// Constructs the variable.
+    fn new(name: &str, role: Role, universe: UniversalSet) -> LinguisticVariable {
+        LinguisticVariable {
+            name: name.to_string(),
+            role: role,
+            universe: universe,
+            unit: None,
+            default: None,
+        }
+    }
+
+    /// Sets the unit of measurement.
+    pub fn with_unit(mut self, unit: &str) -> LinguisticVariable {
+        self.unit = Some(unit.to_string());
+        self
+    }
+
+    /// Sets the default value. Used only by input variables.
+    pub fn with_default(mut self, default: f32) -> LinguisticVariable {
+        self.default = Some(default);
+        self
+    }
+
+    /// Returns the name of the variable.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the role of the variable.
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// Returns the universe of the variable.
+    pub fn universe(&self) -> &UniversalSet {
+        &self.universe
+    }
+
+    /// Returns the unit of measurement.
+    pub fn unit(&self) -> Option<&str> {
+        self.unit.as_deref()
+    }
+
+    /// Returns the default value.
+    pub fn default(&self) -> Option<f32> {
+        self.default
+    }
+
+    /// Returns the universe, consuming the variable.
+    pub fn into_universe(self) -> UniversalSet {
+        self.universe
+    }
+}
@@ -0,0 +1,108 @@
+//! Counts heap allocations made by the inference hot path.
+extern crate fuzzy_logic;
+
+use fuzzy_logic::functions::MembershipFactory;
+use fuzzy_logic::inference::{InferenceContext, InferenceMachine, InferenceOptions};
+use fuzzy_logic::rules::{Is, Rule, RuleSet};
+use fuzzy_logic::set::UniversalSet;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Allocator which counts allocations made by the current thread.
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Returns the number of allocations made by `f`.
+fn allocations<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.with(|count| count.get());
+    f();
+    ALLOCATIONS.with(|count| count.get()) - before
+}
+
+fn universes() -> HashMap<String, Rc<UniversalSet>> {
+    let mut temp = UniversalSet::new("temp".to_string());
+    temp.set_domain((0..41).map(|x| x as f32).collect());
+    temp.create_set("cold".to_string(), MembershipFactory::triangular(-20.0, 0.0, 20.0)).unwrap();
+    temp.create_set("hot".to_string(), MembershipFactory::triangular(10.0, 40.0, 60.0)).unwrap();
+    let mut fan = UniversalSet::new("fan".to_string());
+    fan.set_domain((0..11).map(|x| x as f32).collect());
+    fan.create_set("slow".to_string(), MembershipFactory::triangular(-5.0, 0.0, 5.0)).unwrap();
+    fan.create_set("fast".to_string(), MembershipFactory::triangular(5.0, 10.0, 15.0)).unwrap();
+    let mut universes = HashMap::new();
+    universes.insert("temp".to_string(), Rc::new(temp));
+    universes.insert("fan".to_string(), Rc::new(fan));
+    universes
+}
+
+fn options() -> InferenceOptions {
+    InferenceOptions::mamdani()
+}
+
+#[test]
+fn rule_compute_does_not_allocate_name() {
+    let universes = universes();
+    let options = options();
+    let mut values = HashMap::new();
+    values.insert("temp".to_string(), 20.0);
+    let bindings = HashMap::new();
+    let fuzzy_values = HashMap::new();
+    let context = InferenceContext {
+        values: &values,
+        universes: &universes,
+        bindings: &bindings,
+        fuzzy_values: &fuzzy_values,
+        options: &options,
+    };
+    let rule = Rule::new(Box::new(Is::new("temp".to_string(), "cold".to_string())),
+                         "fan".to_string(),
+                         "slow".to_string());
+    let mut result = rule.compute(&context).unwrap().remove(0);
+
+    // The result set already holds every point of the domain, so recomputing it allocates nothing.
+    let count = allocations(|| {
+        rule.compute_into(&context, &mut result).unwrap();
+    });
+    assert_eq!(count, 0);
+    assert_eq!(&*result.name, "fan: slow");
+}
+
+#[test]
+fn compute_after_warm_up() {
+    let rules = vec![Rule::new(Box::new(Is::new("temp".to_string(), "cold".to_string())),
+                               "fan".to_string(),
+                               "slow".to_string()),
+                     Rule::new(Box::new(Is::new("temp".to_string(), "hot".to_string())),
+                               "fan".to_string(),
+                               "fast".to_string())];
+    let mut machine = InferenceMachine::new(RuleSet::new(rules).unwrap(), universes(), options());
+    let mut values = HashMap::new();
+    values.insert("temp".to_string(), 15.0);
+    machine.update(&values).unwrap();
+    machine.preallocate();
+    machine.compute_for("fan").unwrap();
+
+    // Only the name of the united set and the name of the output universe are allocated.
+    assert!(allocations(|| {
+        machine.compute_for("fan").unwrap();
+    }) <= 3);
+}